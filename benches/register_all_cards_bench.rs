@@ -3,7 +3,7 @@ use std::sync::Arc;
 
 use criterion::{Criterion, criterion_group, criterion_main};
 use repeater::crud::DB;
-use repeater::utils::register_all_cards;
+use repeater::utils::{ScanOptions, register_all_cards};
 use std::hint::black_box;
 use tokio::runtime::Runtime;
 
@@ -17,7 +17,7 @@ fn bench_register_all_cards(c: &mut Criterion) {
             let db = Arc::clone(&db);
             let paths = paths.clone();
             async move {
-                let cards = register_all_cards(db.as_ref(), paths)
+                let cards = register_all_cards(db.as_ref(), &ScanOptions::default(), paths)
                     .await
                     .expect("failed to register cards");
                 black_box(cards);
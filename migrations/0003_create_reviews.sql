@@ -0,0 +1,15 @@
+-- Create the review log table.
+PRAGMA foreign_keys = ON;
+
+CREATE TABLE IF NOT EXISTS reviews (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    card_hash TEXT NOT NULL,
+    reviewed_at TEXT NOT NULL,
+    rating INTEGER NOT NULL,
+    elapsed_days REAL NOT NULL,
+    previous_stability REAL,
+    new_stability REAL NOT NULL,
+    interval_days INTEGER NOT NULL
+) STRICT;
+
+CREATE INDEX IF NOT EXISTS idx_reviews_card_hash ON reviews(card_hash, reviewed_at);
@@ -0,0 +1,2 @@
+-- Let users hide cards from drills without losing their schedule.
+ALTER TABLE cards ADD COLUMN suspended INTEGER NOT NULL DEFAULT 0;
@@ -0,0 +1,3 @@
+-- Count lapses (Again on an already-reviewed card) and flag leeches.
+ALTER TABLE cards ADD COLUMN lapses INTEGER NOT NULL DEFAULT 0;
+ALTER TABLE cards ADD COLUMN leech INTEGER NOT NULL DEFAULT 0;
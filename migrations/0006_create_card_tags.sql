@@ -0,0 +1,8 @@
+-- Tags from each deck's frontmatter, refreshed whenever cards are registered.
+CREATE TABLE IF NOT EXISTS card_tags (
+    card_hash TEXT NOT NULL,
+    tag TEXT NOT NULL,
+    PRIMARY KEY (card_hash, tag)
+) STRICT;
+
+CREATE INDEX IF NOT EXISTS idx_card_tags_tag ON card_tags(tag);
@@ -0,0 +1,2 @@
+-- Milliseconds between showing a card and grading it; NULL for reviews logged before it was tracked.
+ALTER TABLE reviews ADD COLUMN duration_ms INTEGER;
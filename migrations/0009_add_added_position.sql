@@ -0,0 +1,2 @@
+-- Where a card sat among the cards added with it, so new cards keep the order they were written in.
+ALTER TABLE cards ADD COLUMN added_position INTEGER NOT NULL DEFAULT 0;
@@ -0,0 +1,57 @@
+-- Point the review log and tags at their card, so deleting a card removes them and
+-- renaming its hash carries them along. SQLite can't add a foreign key to an existing
+-- table, so both are rebuilt; rows left behind by cards deleted earlier are dropped.
+CREATE TABLE reviews_new (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    card_hash TEXT NOT NULL REFERENCES cards(card_hash) ON DELETE CASCADE ON UPDATE CASCADE,
+    reviewed_at TEXT NOT NULL,
+    rating INTEGER NOT NULL,
+    elapsed_days REAL NOT NULL,
+    previous_stability REAL,
+    new_stability REAL NOT NULL,
+    interval_days INTEGER NOT NULL,
+    duration_ms INTEGER
+) STRICT;
+
+INSERT INTO reviews_new (
+    id,
+    card_hash,
+    reviewed_at,
+    rating,
+    elapsed_days,
+    previous_stability,
+    new_stability,
+    interval_days,
+    duration_ms
+)
+SELECT
+    id,
+    card_hash,
+    reviewed_at,
+    rating,
+    elapsed_days,
+    previous_stability,
+    new_stability,
+    interval_days,
+    duration_ms
+FROM reviews
+WHERE card_hash IN (SELECT card_hash FROM cards);
+
+DROP TABLE reviews;
+ALTER TABLE reviews_new RENAME TO reviews;
+CREATE INDEX IF NOT EXISTS idx_reviews_card_hash ON reviews(card_hash, reviewed_at);
+
+CREATE TABLE card_tags_new (
+    card_hash TEXT NOT NULL REFERENCES cards(card_hash) ON DELETE CASCADE ON UPDATE CASCADE,
+    tag TEXT NOT NULL,
+    PRIMARY KEY (card_hash, tag)
+) STRICT;
+
+INSERT INTO card_tags_new (card_hash, tag)
+SELECT card_hash, tag
+FROM card_tags
+WHERE card_hash IN (SELECT card_hash FROM cards);
+
+DROP TABLE card_tags;
+ALTER TABLE card_tags_new RENAME TO card_tags;
+CREATE INDEX IF NOT EXISTS idx_card_tags_tag ON card_tags(tag);
@@ -0,0 +1,3 @@
+-- When `--prune` found a card missing from its files. Archived cards keep their schedule
+-- and review log but are left out of drills and stats until `repeater unarchive`.
+ALTER TABLE cards ADD COLUMN archived_at TEXT;
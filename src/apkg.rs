@@ -0,0 +1,641 @@
+use std::collections::{BTreeMap, HashMap};
+use std::fs::File;
+use std::io::Write;
+use std::path::{Component, Path, PathBuf};
+
+use anyhow::{Context, Result};
+use html_escape::encode_text;
+use once_cell::sync::Lazy;
+use regex::Regex;
+use serde_json::{Value, json};
+use sqlx::SqlitePool;
+use sqlx::sqlite::{SqliteConnectOptions, SqliteJournalMode};
+use zip::ZipWriter;
+use zip::write::FileOptions;
+
+use crate::card::{Card, CardContent};
+use crate::media::{MediaKind, media_kind_from_path, resolve_media_path};
+use crate::utils::reversed_hash;
+
+static MEDIA_LINK_RE: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r"!?\[[^\]\n]*\]\(([^)\s]+)\)").unwrap());
+
+const DEFAULT_DECK_ID: i64 = 1;
+const CSS: &str = ".card { font-family: arial; font-size: 20px; text-align: center; color: black; background-color: white; }\n.cloze { font-weight: bold; color: blue; }\n";
+const LATEX_PRE: &str = "\\documentclass[12pt]{article}\n\\special{papersize=3in,5in}\n\\usepackage[utf8]{inputenc}\n\\usepackage{amssymb,amsmath}\n\\pagestyle{empty}\n\\setlength{\\parindent}{0in}\n\\begin{document}\n";
+const LATEX_POST: &str = "\\end{document}";
+
+const SCHEMA: &str = r#"
+CREATE TABLE col (
+    id integer primary key, crt integer not null, mod integer not null, scm integer not null,
+    ver integer not null, dty integer not null, usn integer not null, ls integer not null,
+    conf text not null, models text not null, decks text not null, dconf text not null, tags text not null
+);
+CREATE TABLE notes (
+    id integer primary key, guid text not null, mid integer not null, mod integer not null,
+    usn integer not null, tags text not null, flds text not null, sfld integer not null,
+    csum integer not null, flags integer not null, data text not null
+);
+CREATE TABLE cards (
+    id integer primary key, nid integer not null, did integer not null, ord integer not null,
+    mod integer not null, usn integer not null, type integer not null, queue integer not null,
+    due integer not null, ivl integer not null, factor integer not null, reps integer not null,
+    lapses integer not null, left integer not null, odue integer not null, odid integer not null,
+    flags integer not null, data text not null
+);
+CREATE TABLE revlog (
+    id integer primary key, cid integer not null, usn integer not null, ease integer not null,
+    ivl integer not null, lastIvl integer not null, factor integer not null, time integer not null,
+    type integer not null
+);
+CREATE TABLE graves (usn integer not null, oid integer not null, type integer not null);
+CREATE INDEX ix_notes_usn on notes (usn);
+CREATE INDEX ix_cards_usn on cards (usn);
+CREATE INDEX ix_revlog_usn on revlog (usn);
+CREATE INDEX ix_cards_nid on cards (nid);
+CREATE INDEX ix_cards_sched on cards (did, queue, due);
+CREATE INDEX ix_revlog_cid on revlog (cid);
+CREATE INDEX ix_notes_csum on notes (csum);
+"#;
+
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct ApkgSummary {
+    pub notes: usize,
+    pub cards: usize,
+    pub media: usize,
+    /// Bracket clozes without a deletion, which Anki cannot show.
+    pub skipped: usize,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum NoteType {
+    Basic,
+    Cloze,
+}
+
+struct Note<'a> {
+    first: &'a Card,
+    kind: NoteType,
+    fields: Vec<String>,
+    /// Template ordinals; one per cluster for numbered clozes.
+    ords: Vec<i64>,
+}
+
+/// Files referenced by the exported cards, named the way they are stored in the package.
+#[derive(Default)]
+struct MediaFiles {
+    names: HashMap<PathBuf, String>,
+    files: Vec<(PathBuf, String)>,
+}
+
+impl MediaFiles {
+    fn add(&mut self, path: &Path) -> String {
+        if let Some(name) = self.names.get(path) {
+            return name.clone();
+        }
+        let file_name = path
+            .file_name()
+            .and_then(|name| name.to_str())
+            .unwrap_or("media")
+            .to_string();
+        let mut name = file_name.clone();
+        let mut n = 1;
+        while self.files.iter().any(|(_, taken)| *taken == name) {
+            name = format!("{n}-{file_name}");
+            n += 1;
+        }
+        self.names.insert(path.to_path_buf(), name.clone());
+        self.files.push((path.to_path_buf(), name.clone()));
+        name
+    }
+}
+
+/// Builds an Anki package with one note per card block. Cards come out new;
+/// only the text, tags, and media are carried over.
+pub async fn write_apkg(cards: &HashMap<String, Card>, output: &Path) -> Result<ApkgSummary> {
+    let mut summary = ApkgSummary::default();
+    let mut media = MediaFiles::default();
+    let notes = build_notes(cards, &mut media, &mut summary);
+
+    let dir = tempfile::tempdir().context("failed to create a temporary directory")?;
+    let collection_path = dir.path().join("collection.anki2");
+    let options = SqliteConnectOptions::new()
+        .filename(&collection_path)
+        .journal_mode(SqliteJournalMode::Delete)
+        .create_if_missing(true);
+    let pool = SqlitePool::connect_with(options)
+        .await
+        .context("failed to create Anki collection")?;
+    write_collection(&pool, &notes, &mut summary).await?;
+    pool.close().await;
+
+    let collection = std::fs::read(&collection_path)?;
+    write_package(output, &collection, &media)?;
+    summary.media = media.files.len();
+    Ok(summary)
+}
+
+fn build_notes<'a>(
+    cards: &'a HashMap<String, Card>,
+    media: &mut MediaFiles,
+    summary: &mut ApkgSummary,
+) -> Vec<Note<'a>> {
+    // Reversed twins and numbered-cloze siblings share a block.
+    let mut blocks: BTreeMap<(&Path, usize, usize), Vec<&Card>> = BTreeMap::new();
+    for card in cards.values() {
+        let (start, end) = card.file_card_range;
+        blocks
+            .entry((card.file_path.as_path(), start, end))
+            .or_default()
+            .push(card);
+    }
+
+    let mut notes = Vec::new();
+    for mut block in blocks.into_values() {
+        block.sort_by_key(|card| match &card.content {
+            CardContent::Cloze { cloze_index, .. } => cloze_index.unwrap_or(0),
+            CardContent::Basic { .. } | CardContent::TypeIn { .. } => 0,
+        });
+        let base_dir = block[0].file_path.parent();
+        let first = match &block[0].content {
+            CardContent::Basic { .. } | CardContent::TypeIn { .. } => block
+                .iter()
+                .find(|card| {
+                    !block
+                        .iter()
+                        .any(|other| reversed_hash(&other.card_hash) == card.card_hash)
+                })
+                .copied()
+                .unwrap_or(block[0]),
+            CardContent::Cloze { .. } => block[0],
+        };
+
+        let note = match &first.content {
+            CardContent::Basic { question, answer } | CardContent::TypeIn { question, answer } => {
+                Note {
+                    first,
+                    kind: NoteType::Basic,
+                    fields: vec![
+                        to_html(question, base_dir, media),
+                        to_html(answer, base_dir, media),
+                    ],
+                    ords: vec![0],
+                }
+            }
+            CardContent::Cloze {
+                text,
+                cloze_index: Some(_),
+                ..
+            } => Note {
+                first,
+                kind: NoteType::Cloze,
+                fields: vec![to_html(text, base_dir, media), String::new()],
+                ords: block
+                    .iter()
+                    .filter_map(|card| match &card.content {
+                        CardContent::Cloze {
+                            cloze_index: Some(index),
+                            ..
+                        } => Some(i64::from(*index) - 1),
+                        _ => None,
+                    })
+                    .collect(),
+            },
+            CardContent::Cloze {
+                text,
+                cloze_range: Some(range),
+                ..
+            } => {
+                let inner = &text[range.start + 1..range.end - 1];
+                let text = format!(
+                    "{}{{{{c1::{}}}}}{}",
+                    &text[..range.start],
+                    inner,
+                    &text[range.end..]
+                );
+                Note {
+                    first,
+                    kind: NoteType::Cloze,
+                    fields: vec![to_html(&text, base_dir, media), String::new()],
+                    ords: vec![0],
+                }
+            }
+            CardContent::Cloze { .. } => {
+                summary.skipped += 1;
+                continue;
+            }
+        };
+        notes.push(note);
+    }
+    notes
+}
+
+/// Escapes card text for an Anki field, turning media links into `<img>` and `[sound:]` references.
+fn to_html(text: &str, base_dir: Option<&Path>, media: &mut MediaFiles) -> String {
+    let mut out = String::new();
+    let mut last = 0;
+    for caps in MEDIA_LINK_RE.captures_iter(text) {
+        let whole = caps.get(0).expect("match");
+        let path = resolve_media_path(PathBuf::from(&caps[1]), base_dir);
+        let Some(kind) = media_kind_from_path(&path).filter(|_| path.is_file()) else {
+            continue;
+        };
+        out.push_str(&encode_text(&text[last..whole.start()]));
+        let name = media.add(&path);
+        match kind {
+            MediaKind::Image => out.push_str(&format!("<img src=\"{}\">", encode_text(&name))),
+            MediaKind::Audio | MediaKind::Video => out.push_str(&format!("[sound:{name}]")),
+        }
+        last = whole.end();
+    }
+    out.push_str(&encode_text(&text[last..]));
+    out.replace('\n', "<br>")
+}
+
+async fn write_collection(
+    pool: &SqlitePool,
+    notes: &[Note<'_>],
+    summary: &mut ApkgSummary,
+) -> Result<()> {
+    let now = chrono::Utc::now();
+    let now_ms = now.timestamp_millis();
+    let now_s = now.timestamp();
+    let basic_model_id = now_ms;
+    let cloze_model_id = now_ms + 1;
+
+    let mut deck_ids: BTreeMap<String, i64> = BTreeMap::new();
+    for note in notes {
+        let next = now_ms + 2 + deck_ids.len() as i64;
+        deck_ids
+            .entry(deck_name(&note.first.file_path))
+            .or_insert(next);
+    }
+
+    for statement in SCHEMA.split(';').filter(|s| !s.trim().is_empty()) {
+        sqlx::query(statement).execute(pool).await?;
+    }
+    let models = json!({
+        basic_model_id.to_string(): model(
+            basic_model_id,
+            "Basic",
+            0,
+            &["Front", "Back"],
+            "{{Front}}",
+            "{{FrontSide}}\n\n<hr id=answer>\n\n{{Back}}",
+            now_s,
+        ),
+        cloze_model_id.to_string(): model(
+            cloze_model_id,
+            "Cloze",
+            1,
+            &["Text", "Back Extra"],
+            "{{cloze:Text}}",
+            "{{cloze:Text}}<br>\n{{Back Extra}}",
+            now_s,
+        ),
+    });
+    let mut decks = serde_json::Map::new();
+    decks.insert(
+        DEFAULT_DECK_ID.to_string(),
+        deck(DEFAULT_DECK_ID, "Default", now_s),
+    );
+    for (name, id) in &deck_ids {
+        decks.insert(id.to_string(), deck(*id, name, now_s));
+    }
+    let conf = json!({
+        "activeDecks": [DEFAULT_DECK_ID],
+        "curDeck": DEFAULT_DECK_ID,
+        "newSpread": 0,
+        "collapseTime": 1200,
+        "timeLim": 0,
+        "estTimes": true,
+        "dueCounts": true,
+        "curModel": basic_model_id,
+        "nextPos": notes.len() + 1,
+        "sortType": "noteFld",
+        "sortBackwards": false,
+        "addToCur": true,
+    });
+    sqlx::query("INSERT INTO col VALUES (1, ?, ?, ?, 11, 0, 0, 0, ?, ?, ?, ?, '{}')")
+        .bind(now_s)
+        .bind(now_ms)
+        .bind(now_ms)
+        .bind(conf.to_string())
+        .bind(models.to_string())
+        .bind(Value::Object(decks).to_string())
+        .bind(json!({ "1": deck_config(now_s) }).to_string())
+        .execute(pool)
+        .await?;
+
+    let mut next_card_id = now_ms;
+    for (position, note) in notes.iter().enumerate() {
+        let note_id = now_ms + position as i64;
+        let model_id = match note.kind {
+            NoteType::Basic => basic_model_id,
+            NoteType::Cloze => cloze_model_id,
+        };
+        let sort_field = &note.fields[0];
+        let tags = if note.first.tags.is_empty() {
+            String::new()
+        } else {
+            format!(" {} ", note.first.tags.join(" "))
+        };
+        sqlx::query("INSERT INTO notes VALUES (?, ?, ?, ?, -1, ?, ?, ?, ?, 0, '')")
+            .bind(note_id)
+            .bind(&note.first.card_hash[..10])
+            .bind(model_id)
+            .bind(now_s)
+            .bind(tags)
+            .bind(note.fields.join("\x1f"))
+            .bind(sort_field)
+            .bind(checksum(sort_field))
+            .execute(pool)
+            .await?;
+
+        let deck_id = deck_ids[&deck_name(&note.first.file_path)];
+        for ord in &note.ords {
+            sqlx::query(
+                "INSERT INTO cards VALUES (?, ?, ?, ?, ?, -1, 0, 0, ?, 0, 0, 0, 0, 0, 0, 0, 0, '')",
+            )
+            .bind(next_card_id)
+            .bind(note_id)
+            .bind(deck_id)
+            .bind(*ord)
+            .bind(now_s)
+            .bind(position as i64 + 1)
+            .execute(pool)
+            .await?;
+            next_card_id += 1;
+            summary.cards += 1;
+        }
+        summary.notes += 1;
+    }
+    Ok(())
+}
+
+fn write_package(output: &Path, collection: &[u8], media: &MediaFiles) -> Result<()> {
+    let file =
+        File::create(output).with_context(|| format!("Failed to create {}", output.display()))?;
+    let mut zip = ZipWriter::new(file);
+    let options = FileOptions::default();
+
+    zip.start_file("collection.anki2", options)?;
+    zip.write_all(collection)?;
+    // Newer Anki versions and `repeater import` read this name.
+    zip.start_file("collection.anki21", options)?;
+    zip.write_all(collection)?;
+
+    let mut map = serde_json::Map::new();
+    for (index, (path, name)) in media.files.iter().enumerate() {
+        let bytes = std::fs::read(path)
+            .with_context(|| format!("Failed to read media file {}", path.display()))?;
+        zip.start_file(index.to_string(), options)?;
+        zip.write_all(&bytes)?;
+        map.insert(index.to_string(), Value::String(name.clone()));
+    }
+    zip.start_file("media", options)?;
+    zip.write_all(Value::Object(map).to_string().as_bytes())?;
+    zip.finish()?;
+    Ok(())
+}
+
+/// `decks/spanish/verbs.md` becomes `decks::spanish::verbs`.
+fn deck_name(file_path: &Path) -> String {
+    let parts: Vec<String> = file_path
+        .with_extension("")
+        .components()
+        .filter_map(|component| match component {
+            Component::Normal(part) => Some(part.to_string_lossy().into_owned()),
+            _ => None,
+        })
+        .collect();
+    if parts.is_empty() {
+        "repeater".to_string()
+    } else {
+        parts.join("::")
+    }
+}
+
+/// Anki uses this for duplicate detection and recomputes it with "Check Database".
+fn checksum(sort_field: &str) -> i64 {
+    let hash = blake3::hash(sort_field.as_bytes());
+    let bytes: [u8; 4] = hash.as_bytes()[..4].try_into().expect("4 bytes");
+    i64::from(u32::from_be_bytes(bytes))
+}
+
+fn model(
+    id: i64,
+    name: &str,
+    kind: i64,
+    fields: &[&str],
+    qfmt: &str,
+    afmt: &str,
+    modified: i64,
+) -> Value {
+    let flds: Vec<Value> = fields
+        .iter()
+        .enumerate()
+        .map(|(ord, name)| {
+            json!({
+                "name": name,
+                "ord": ord,
+                "sticky": false,
+                "rtl": false,
+                "font": "Arial",
+                "size": 20,
+                "media": [],
+            })
+        })
+        .collect();
+    json!({
+        "id": id,
+        "name": name,
+        "type": kind,
+        "mod": modified,
+        "usn": -1,
+        "sortf": 0,
+        "did": DEFAULT_DECK_ID,
+        "tmpls": [{
+            "name": if kind == 1 { "Cloze" } else { "Card 1" },
+            "ord": 0,
+            "qfmt": qfmt,
+            "afmt": afmt,
+            "bqfmt": "",
+            "bafmt": "",
+            "did": null,
+        }],
+        "flds": flds,
+        "css": CSS,
+        "latexPre": LATEX_PRE,
+        "latexPost": LATEX_POST,
+        "tags": [],
+        "vers": [],
+        "req": [[0, "any", [0]]],
+    })
+}
+
+fn deck(id: i64, name: &str, modified: i64) -> Value {
+    json!({
+        "id": id,
+        "name": name,
+        "mod": modified,
+        "usn": -1,
+        "desc": "",
+        "dyn": 0,
+        "conf": 1,
+        "collapsed": false,
+        "extendNew": 10,
+        "extendRev": 50,
+        "newToday": [0, 0],
+        "revToday": [0, 0],
+        "lrnToday": [0, 0],
+        "timeToday": [0, 0],
+    })
+}
+
+fn deck_config(modified: i64) -> Value {
+    json!({
+        "id": 1,
+        "name": "Default",
+        "mod": modified,
+        "usn": -1,
+        "maxTaken": 60,
+        "autoplay": true,
+        "timer": 0,
+        "replayq": true,
+        "dyn": false,
+        "new": {
+            "delays": [1.0, 10.0],
+            "ints": [1, 4, 7],
+            "initialFactor": 2500,
+            "order": 1,
+            "perDay": 20,
+            "bury": true,
+        },
+        "rev": {
+            "perDay": 200,
+            "ease4": 1.3,
+            "fuzz": 0.05,
+            "maxIvl": 36500,
+            "ivlFct": 1.0,
+            "bury": true,
+            "hardFactor": 1.2,
+        },
+        "lapse": {
+            "delays": [10.0],
+            "mult": 0.0,
+            "minInt": 1,
+            "leechFails": 8,
+            "leechAction": 1,
+        },
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Read;
+
+    use sqlx::Row;
+    use zip::ZipArchive;
+
+    use super::*;
+    use crate::utils::content_to_cards;
+
+    fn cards_from(path: &Path, blocks: &[&str]) -> HashMap<String, Card> {
+        let mut cards = HashMap::new();
+        for (i, block) in blocks.iter().enumerate() {
+            for mut card in content_to_cards(path, block, "---", i * 10, i * 10 + 2).unwrap() {
+                card.tags = vec!["maths".into()];
+                cards.insert(card.card_hash.clone(), card);
+            }
+        }
+        cards
+    }
+
+    #[test]
+    fn fields_are_escaped_and_link_media() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("dog.png"), b"png").unwrap();
+        let mut media = MediaFiles::default();
+        let html = to_html(
+            "a < b\n![dog](dog.png) [missing](cat.png)",
+            Some(dir.path()),
+            &mut media,
+        );
+        assert_eq!(html, "a &lt; b<br><img src=\"dog.png\"> [missing](cat.png)");
+        assert_eq!(media.files.len(), 1);
+    }
+
+    #[test]
+    fn deck_names_follow_the_file_path() {
+        assert_eq!(
+            deck_name(Path::new("decks/spanish/verbs.md")),
+            "decks::spanish::verbs"
+        );
+        assert_eq!(deck_name(Path::new("./cards.md")), "cards");
+    }
+
+    #[tokio::test]
+    async fn writes_a_package_anki_can_read() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("maths.md");
+        let cards = cards_from(
+            &path,
+            &[
+                "R: 2+2?\nA: 4",
+                "C: The answer is [42].",
+                "C: {{c1::Paris}} is in {{c2::France}}.",
+                "C: No deletion here.",
+            ],
+        );
+        let output = dir.path().join("out.apkg");
+        let summary = write_apkg(&cards, &output).await.unwrap();
+        assert_eq!(
+            summary,
+            ApkgSummary {
+                notes: 3,
+                cards: 4,
+                media: 0,
+                skipped: 1,
+            }
+        );
+
+        let mut zip = ZipArchive::new(File::open(&output).unwrap()).unwrap();
+        let mut media = String::new();
+        zip.by_name("media")
+            .unwrap()
+            .read_to_string(&mut media)
+            .unwrap();
+        assert_eq!(media, "{}");
+        let collection = dir.path().join("collection.anki2");
+        std::io::copy(
+            &mut zip.by_name("collection.anki2").unwrap(),
+            &mut File::create(&collection).unwrap(),
+        )
+        .unwrap();
+
+        let pool = SqlitePool::connect_with(SqliteConnectOptions::new().filename(&collection))
+            .await
+            .unwrap();
+        let rows = sqlx::query("SELECT flds, tags FROM notes ORDER BY id")
+            .fetch_all(&pool)
+            .await
+            .unwrap();
+        let fields: Vec<String> = rows.iter().map(|row| row.get("flds")).collect();
+        assert_eq!(
+            fields,
+            vec![
+                "2+2?\x1f4",
+                "The answer is {{c1::42}}.\x1f",
+                "{{c1::Paris}} is in {{c2::France}}.\x1f",
+            ]
+        );
+        let tags: String = rows[0].get("tags");
+        assert_eq!(tags, " maths ");
+        let ords: Vec<i64> = sqlx::query_scalar("SELECT ord FROM cards ORDER BY id")
+            .fetch_all(&pool)
+            .await
+            .unwrap();
+        assert_eq!(ords, vec![0, 0, 0, 1]);
+    }
+}
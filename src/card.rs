@@ -1,29 +1,42 @@
 use std::path::PathBuf;
 
 use anyhow::{Result, anyhow};
+use serde::{Deserialize, Serialize};
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct Card {
     pub file_path: PathBuf,
     #[allow(dead_code)]
     pub file_card_range: (usize, usize),
     pub content: CardContent,
     pub card_hash: String,
+    /// Hash of the block the card was parsed from, shared by its siblings: both sides
+    /// of an `R:` card and every cluster of a numbered cloze.
+    pub origin: String,
+    /// Tags from the `tags:` list in the file's frontmatter.
+    pub tags: Vec<String>,
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub enum CardContent {
     Basic {
         question: String,
         answer: String,
     },
+    /// A `T:` card whose answer is typed in during the drill and checked.
+    TypeIn {
+        question: String,
+        answer: String,
+    },
     Cloze {
         text: String,
         cloze_range: Option<ClozeRange>,
+        /// Cluster tested by this card for `{{cN::...}}` clozes.
+        cloze_index: Option<u32>,
     },
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct ClozeRange {
     pub start: usize,
     pub end: usize,
@@ -45,8 +58,31 @@ impl ClozeRange {
     }
 }
 
+/// One `{{cN::answer}}` or `{{cN::answer::hint}}` deletion; `start..end` spans the braces.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct NumberedCloze {
+    pub start: usize,
+    pub end: usize,
+    pub index: u32,
+    pub answer: String,
+    pub hint: Option<String>,
+}
+
 #[derive(Debug, PartialEq, Eq)]
 pub enum CardType {
     Basic,
+    BasicReversed,
+    TypeIn,
     Cloze,
 }
+
+impl CardType {
+    pub fn name(&self) -> &'static str {
+        match self {
+            CardType::Basic => "basic",
+            CardType::BasicReversed => "reversed",
+            CardType::TypeIn => "type-in",
+            CardType::Cloze => "cloze",
+        }
+    }
+}
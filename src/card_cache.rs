@@ -0,0 +1,173 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+
+use crate::card::Card;
+use crate::crud::DB;
+
+const CACHE_FILE: &str = "card_cache.json";
+/// Files modified this recently may still change within the same mtime tick, so they aren't cached.
+const RACY_WINDOW: Duration = Duration::from_secs(2);
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Fingerprint {
+    size: u64,
+    modified_secs: u64,
+    modified_nanos: u32,
+}
+
+impl Fingerprint {
+    pub fn of(path: &Path) -> Option<Self> {
+        let metadata = fs::metadata(path).ok()?;
+        let modified = metadata.modified().ok()?.duration_since(UNIX_EPOCH).ok()?;
+        Some(Self {
+            size: metadata.len(),
+            modified_secs: modified.as_secs(),
+            modified_nanos: modified.subsec_nanos(),
+        })
+    }
+
+    fn modified(&self) -> SystemTime {
+        UNIX_EPOCH + Duration::new(self.modified_secs, self.modified_nanos)
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+struct CachedFile {
+    fingerprint: Fingerprint,
+    cards: Vec<Card>,
+}
+
+/// Cards parsed from each markdown file, keyed by absolute path.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct CardCache {
+    /// Release that wrote the cache; parsing may differ between releases.
+    version: String,
+    files: HashMap<PathBuf, CachedFile>,
+}
+
+impl CardCache {
+    pub fn path() -> Result<PathBuf> {
+        Ok(DB::data_dir()?.join(CACHE_FILE))
+    }
+
+    /// A missing, unreadable, or outdated cache is treated as empty.
+    pub fn load_from(path: &Path) -> Self {
+        fs::read_to_string(path)
+            .ok()
+            .and_then(|contents| serde_json::from_str::<Self>(&contents).ok())
+            .filter(|cache| cache.version == env!("CARGO_PKG_VERSION"))
+            .unwrap_or_default()
+    }
+
+    pub fn save_to(&mut self, path: &Path) -> Result<()> {
+        self.version = env!("CARGO_PKG_VERSION").to_string();
+        self.files.retain(|file, _| file.is_file());
+        let contents = serde_json::to_string(self)?;
+        fs::write(path, contents).with_context(|| format!("Failed to write {}", path.display()))
+    }
+
+    /// Cached cards for the file at `path` if it still has `fingerprint`.
+    pub fn get(&self, path: &Path, fingerprint: &Fingerprint) -> Option<Vec<Card>> {
+        let cached = self.files.get(&absolute(path))?;
+        if cached.fingerprint != *fingerprint {
+            return None;
+        }
+        Some(
+            cached
+                .cards
+                .iter()
+                .cloned()
+                .map(|mut card| {
+                    card.file_path = path.to_path_buf();
+                    card
+                })
+                .collect(),
+        )
+    }
+
+    pub fn insert(&mut self, path: &Path, fingerprint: Fingerprint, cards: Vec<Card>) {
+        let absolute = absolute(path);
+        let settled = SystemTime::now()
+            .duration_since(fingerprint.modified())
+            .is_ok_and(|age| age >= RACY_WINDOW);
+        if !settled {
+            self.files.remove(&absolute);
+            return;
+        }
+        self.files
+            .insert(absolute, CachedFile { fingerprint, cards });
+    }
+}
+
+fn absolute(path: &Path) -> PathBuf {
+    fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs::File;
+
+    use super::*;
+    use crate::utils::cards_from_md;
+
+    fn write_old(path: &Path, contents: &str) {
+        fs::write(path, contents).unwrap();
+        let an_hour_ago = SystemTime::now() - Duration::from_secs(3600);
+        File::options()
+            .write(true)
+            .open(path)
+            .unwrap()
+            .set_modified(an_hour_ago)
+            .unwrap();
+    }
+
+    #[test]
+    fn reuses_cards_until_the_file_changes() {
+        let dir = tempfile::tempdir().unwrap();
+        let deck = dir.path().join("deck.md");
+        write_old(&deck, "Q: one?\nA: 1\n");
+        let fingerprint = Fingerprint::of(&deck).unwrap();
+
+        let mut cache = CardCache::default();
+        cache.insert(&deck, fingerprint, cards_from_md(&deck, "---").unwrap());
+        let cache_path = dir.path().join(CACHE_FILE);
+        cache.save_to(&cache_path).unwrap();
+
+        let cache = CardCache::load_from(&cache_path);
+        let cards = cache.get(&deck, &fingerprint).unwrap();
+        assert_eq!(cards.len(), 1);
+        assert_eq!(cards[0].file_path, deck);
+
+        write_old(&deck, "Q: two?\nA: 2\n");
+        let changed = Fingerprint::of(&deck).unwrap();
+        assert_ne!(changed, fingerprint);
+        assert!(cache.get(&deck, &changed).is_none());
+    }
+
+    #[test]
+    fn skips_files_modified_just_now() {
+        let dir = tempfile::tempdir().unwrap();
+        let deck = dir.path().join("deck.md");
+        fs::write(&deck, "Q: one?\nA: 1\n").unwrap();
+        let fingerprint = Fingerprint::of(&deck).unwrap();
+
+        let mut cache = CardCache::default();
+        cache.insert(&deck, fingerprint, cards_from_md(&deck, "---").unwrap());
+        assert!(cache.get(&deck, &fingerprint).is_none());
+    }
+
+    #[test]
+    fn ignores_a_cache_from_another_release() {
+        let dir = tempfile::tempdir().unwrap();
+        let cache_path = dir.path().join(CACHE_FILE);
+        fs::write(&cache_path, r#"{"version":"0.0.1","files":{}}"#).unwrap();
+        assert!(CardCache::load_from(&cache_path).files.is_empty());
+        fs::write(&cache_path, "not json").unwrap();
+        assert!(CardCache::load_from(&cache_path).files.is_empty());
+    }
+}
@@ -0,0 +1,228 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::PathBuf;
+
+use anyhow::{Context, Result, bail};
+
+use crate::card::{Card, CardContent};
+use crate::crud::DB;
+use crate::crud::cards::RehashOutcome;
+use crate::utils::{content_to_cards, parse_cards_from_reader, write_file_atomically};
+
+/// The lines of a card file one card was parsed from, as they were when loaded.
+pub struct CardSource {
+    pub path: PathBuf,
+    start: usize,
+    /// The block without its trailing blank lines, which saving keeps as they were.
+    pub text: String,
+    trailing_blank: usize,
+}
+
+impl CardSource {
+    pub fn load(card: &Card) -> Result<Self> {
+        let contents = fs::read_to_string(&card.file_path)
+            .with_context(|| format!("Failed to read {}", card.file_path.display()))?;
+        let lines: Vec<&str> = contents.lines().collect();
+        let (start, end) = card.file_card_range;
+        if start >= end || end > lines.len() {
+            bail!(
+                "{} changed since the card was read; restart the drill to edit it",
+                card.file_path.display()
+            );
+        }
+        let block = &lines[start..end];
+        let kept = block
+            .iter()
+            .rposition(|line| !line.trim().is_empty())
+            .map_or(0, |last| last + 1);
+        Ok(Self {
+            path: card.file_path.clone(),
+            start,
+            text: block[..kept].join("\n"),
+            trailing_blank: block.len() - kept,
+        })
+    }
+
+    /// 1-based line where the block starts.
+    pub fn line(&self) -> usize {
+        self.start + 1
+    }
+
+    fn end(&self) -> usize {
+        self.start + self.text.lines().count() + self.trailing_blank
+    }
+}
+
+/// What saving an edited card changed.
+pub struct SavedEdit {
+    /// Every card now in the edited file.
+    pub cards: Vec<Card>,
+    /// New hash of each card from the block whose text changed, by old hash.
+    pub renamed: HashMap<String, String>,
+    pub outcome: RehashOutcome,
+}
+
+/// Writes `text` over the block `source` was loaded from, then moves the schedule and
+/// review history of the block's cards to the hashes their new text gives them.
+pub async fn save(db: &DB, source: &CardSource, text: &str, separator: &str) -> Result<SavedEdit> {
+    let contents = fs::read_to_string(&source.path)
+        .with_context(|| format!("Failed to read {}", source.path.display()))?;
+    let lines: Vec<&str> = contents.lines().collect();
+    let end = source.end();
+    if end > lines.len() || lines[source.start..end].join("\n").trim_end() != source.text {
+        bail!(
+            "{} changed on disk while editing; nothing was saved",
+            source.path.display()
+        );
+    }
+    let text = text.trim_end();
+    let new_end = source.start + text.lines().count();
+    content_to_cards(&source.path, text, separator, source.start, new_end)
+        .context("Invalid card")?;
+
+    let (before, _) = parse_cards_from_reader(&source.path, contents.as_bytes(), separator)?;
+    let old: Vec<Card> = before
+        .into_iter()
+        .filter(|card| card.file_card_range.0 == source.start)
+        .collect();
+
+    let mut updated: Vec<&str> = lines[..source.start].to_vec();
+    updated.extend(text.lines());
+    updated.extend(&lines[source.start + source.text.lines().count()..]);
+    let mut updated = updated.join("\n");
+    if contents.ends_with('\n') {
+        updated.push('\n');
+    }
+    let (cards, _) = parse_cards_from_reader(&source.path, updated.as_bytes(), separator)?;
+    let new: Vec<Card> = cards
+        .iter()
+        .filter(|card| (source.start..new_end).contains(&card.file_card_range.0))
+        .cloned()
+        .collect();
+    write_file_atomically(&source.path, updated.as_bytes())?;
+
+    let pairs = schedule_pairs(&old, &new);
+    db.add_cards_batch(&new).await?;
+    let outcome = db.rehash_cards(&pairs).await?;
+    Ok(SavedEdit {
+        cards,
+        renamed: pairs.into_iter().collect(),
+        outcome,
+    })
+}
+
+/// Pairs each card of the old block with the one it became: numbered clozes by their
+/// number, anything else by position, like the two sides of an `R:` card.
+fn schedule_pairs(old: &[Card], new: &[Card]) -> Vec<(String, String)> {
+    fn key(index: usize, card: &Card) -> (bool, usize) {
+        match &card.content {
+            CardContent::Cloze {
+                cloze_index: Some(number),
+                ..
+            } => (true, *number as usize),
+            _ => (false, index),
+        }
+    }
+    old.iter()
+        .enumerate()
+        .filter_map(|(i, before)| {
+            new.iter()
+                .enumerate()
+                .find(|(j, after)| key(*j, after) == key(i, before))
+                .map(|(_, after)| (before.card_hash.clone(), after.card_hash.clone()))
+        })
+        .filter(|(old, new)| old != new)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use super::{CardSource, save, schedule_pairs};
+    use crate::crud::DB;
+    use crate::fsrs::{Performance, ReviewStatus, SchedulerParams};
+    use crate::utils::{cards_from_md, content_to_cards};
+
+    #[test]
+    fn pairs_cloze_clusters_by_number_and_sides_by_position() {
+        let path = Path::new("deck.md");
+        let old = content_to_cards(path, "C: {{c1::Paris}} and {{c2::Rome}}", "---", 0, 1).unwrap();
+        let new =
+            content_to_cards(path, "C: {{c2::Rome}} and {{c1::Paris}}!", "---", 0, 1).unwrap();
+        let pairs = schedule_pairs(&old, &new);
+        assert_eq!(pairs.len(), 2);
+        for (before, after) in &pairs {
+            let index = |hash: &String, cards: &[crate::card::Card]| {
+                cards.iter().position(|card| &card.card_hash == hash)
+            };
+            assert_eq!(index(before, &old), index(after, &new));
+        }
+
+        let basic = content_to_cards(path, "Q: a?\nA: b", "---", 0, 2).unwrap();
+        let reversed = content_to_cards(path, "R: a?\nA: b", "---", 0, 2).unwrap();
+        let pairs = schedule_pairs(&basic, &reversed);
+        assert_eq!(
+            pairs,
+            [(basic[0].card_hash.clone(), reversed[0].card_hash.clone())]
+        );
+        assert!(schedule_pairs(&basic, &basic).is_empty());
+    }
+
+    #[tokio::test]
+    async fn saving_an_edit_keeps_the_schedule() {
+        let db = DB::new_in_memory().await.unwrap();
+        let dir = tempfile::tempdir().unwrap();
+        let deck = dir.path().join("deck.md");
+        std::fs::write(
+            &deck,
+            "Q: capitol of France?\nA: Paris\n\n---\nQ: two?\nA: 2\n",
+        )
+        .unwrap();
+        let cards = cards_from_md(&deck, "---").unwrap();
+        db.add_cards_batch(&cards).await.unwrap();
+        db.update_card_performance(
+            &cards[0],
+            ReviewStatus::Good,
+            None,
+            &SchedulerParams::default(),
+            None,
+        )
+        .await
+        .unwrap();
+
+        let source = CardSource::load(&cards[0]).unwrap();
+        assert_eq!(source.text, "Q: capitol of France?\nA: Paris");
+        let saved = save(
+            &db,
+            &source,
+            "Q: capital of France?\nA: Paris\nThe largest city, too.",
+            "---",
+        )
+        .await
+        .unwrap();
+        assert_eq!(
+            std::fs::read_to_string(&deck).unwrap(),
+            "Q: capital of France?\nA: Paris\nThe largest city, too.\n\n---\nQ: two?\nA: 2\n"
+        );
+        assert_eq!(saved.outcome.moved, 1);
+        let new_hash = &saved.renamed[&cards[0].card_hash];
+        let edited = saved
+            .cards
+            .iter()
+            .find(|card| &card.card_hash == new_hash)
+            .unwrap();
+        assert!(matches!(
+            db.get_card_performance(edited).await.unwrap(),
+            Performance::Reviewed(_)
+        ));
+        assert_eq!(saved.cards[1].card_hash, cards[1].card_hash);
+        assert_eq!(saved.cards[1].file_card_range.0, 5);
+
+        assert!(
+            save(&db, &source, "Q: stale?\nA: yes", "---")
+                .await
+                .is_err()
+        );
+    }
+}
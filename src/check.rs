@@ -1,9 +1,9 @@
 use crate::{
     check_version::{check_version, prompt_for_new_version},
     crud::DB,
-    stats::{CardLifeCycle, CardStats, Histogram},
+    stats::{CardLifeCycle, CardStats, DEFAULT_FORECAST_DAYS, Histogram, LONG_OVERDUE_DAYS, Prune},
     tui::Theme,
-    utils::register_all_cards,
+    utils::{ScanOptions, register_all_cards},
 };
 
 use std::{
@@ -14,7 +14,6 @@ use std::{
 };
 
 use anyhow::Result;
-use chrono::NaiveDate;
 use crossterm::{
     event::{self, Event, KeyCode, KeyEventKind, KeyModifiers},
     execute,
@@ -28,17 +27,36 @@ use ratatui::{
     widgets::{Bar, BarChart, BarGroup, Paragraph, Wrap},
 };
 
-pub async fn run(db: &DB, paths: Vec<PathBuf>) -> Result<usize> {
+pub async fn run(
+    db: &DB,
+    scan: &ScanOptions,
+    paths: Vec<PathBuf>,
+    prune: Option<Prune>,
+    day_cutoff_hour: u32,
+) -> Result<usize> {
     let version_check = tokio::spawn(check_version(db.clone()));
 
-    let card_hashes = register_all_cards(db, paths).await?;
+    let card_hashes = register_all_cards(db, scan, paths).await?;
     let count = card_hashes.len();
-    let stats = db.collection_stats(&card_hashes).await?;
+    let pruned = match prune {
+        Some(prune) => Some(
+            prune
+                .run(db, &card_hashes.keys().cloned().collect())
+                .await?,
+        ),
+        None => None,
+    };
+    let stats = db
+        .collection_stats(&card_hashes, DEFAULT_FORECAST_DAYS, day_cutoff_hour)
+        .await?;
     if let Some(notification) = version_check.await.ok().flatten() {
         prompt_for_new_version(db, &notification).await;
     }
 
     render_dashboard(&stats)?;
+    if let Some(message) = pruned {
+        println!("{message}");
+    }
     Ok(count)
 }
 
@@ -125,30 +143,15 @@ fn collection_panel(stats: &CardStats) -> Paragraph<'static> {
         Line::from(vec![
             Theme::span("New"),
             Theme::bullet(),
-            Theme::label_span(format!(
-                "{}",
-                *stats.card_lifecycles.get(&CardLifeCycle::New).unwrap_or(&0)
-            )),
+            Theme::label_span(format!("{}", stats.new_cards())),
             Theme::bullet(),
             Theme::span("Young"),
             Theme::bullet(),
-            Theme::label_span(format!(
-                "{}",
-                *stats
-                    .card_lifecycles
-                    .get(&CardLifeCycle::Young)
-                    .unwrap_or(&0)
-            )),
+            Theme::label_span(format!("{}", stats.lifecycle_count(CardLifeCycle::Young))),
             Theme::bullet(),
             Theme::span("Mature"),
             Theme::bullet(),
-            Theme::label_span(format!(
-                "{}",
-                *stats
-                    .card_lifecycles
-                    .get(&CardLifeCycle::Mature)
-                    .unwrap_or(&0)
-            )),
+            Theme::label_span(format!("{}", stats.lifecycle_count(CardLifeCycle::Mature))),
         ]),
         Line::from(vec![
             Theme::span("Files in Collection"),
@@ -177,7 +180,7 @@ fn due_panel(stats: &CardStats) -> Paragraph<'static> {
     } else {
         Theme::emphasis()
     };
-    let upcoming_week_total: usize = stats.upcoming_week.values().sum();
+    let upcoming_week_total = stats.upcoming_week_total();
     let lines = vec![
         Line::from(vec![Span::styled("Focus", emphasis)]),
         Line::from(vec![
@@ -188,6 +191,13 @@ fn due_panel(stats: &CardStats) -> Paragraph<'static> {
             Theme::span("Due now"),
             Theme::bullet(),
             Theme::label_span(format!("{}", stats.due_cards)),
+            Theme::bullet(),
+            Theme::span("Overdue"),
+            Theme::bullet(),
+            Theme::label_span(format!(
+                "{} ({} >{LONG_OVERDUE_DAYS}d)",
+                stats.overdue_cards, stats.long_overdue_cards
+            )),
             Theme::span("  "),
         ]),
         Line::from(vec![
@@ -232,14 +242,13 @@ fn render_upcoming_histogram(frame: &mut Frame<'_>, area: Rect, stats: &CardStat
     }
 
     let bars: Vec<Bar<'static>> = stats
-        .upcoming_week
-        .iter()
-        .map(|(day, count)| {
-            let label = format_upcoming_label(day);
+        .upcoming_week_buckets()
+        .into_iter()
+        .map(|bucket| {
             Bar::default()
-                .value(*count as u64)
-                .text_value(count.to_string())
-                .label(Line::from(vec![Theme::span(label)]))
+                .value(bucket.count as u64)
+                .text_value(bucket.count.to_string())
+                .label(Line::from(vec![Theme::span(bucket.label())]))
                 .style(Theme::label())
         })
         .collect();
@@ -265,12 +274,6 @@ fn render_upcoming_histogram(frame: &mut Frame<'_>, area: Rect, stats: &CardStat
     frame.render_widget(chart, chart_area);
 }
 
-fn format_upcoming_label(day: &str) -> String {
-    NaiveDate::parse_from_str(day, "%Y-%m-%d")
-        .map(|date| date.format("%a %d").to_string())
-        .unwrap_or_else(|_| day.to_string())
-}
-
 fn render_fsrs_histogram(
     frame: &mut Frame<'_>,
     chart_area: Rect,
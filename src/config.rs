@@ -0,0 +1,573 @@
+use std::env;
+use std::ffi::OsString;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use anyhow::{Context, Result, bail};
+use serde::Deserialize;
+
+use crate::drill::DrillSettings;
+use crate::fsrs::{
+    DEFAULT_DESIRED_RETENTION, DEFAULT_LEARNING_STEPS, DEFAULT_MAX_INTERVAL_DAYS, DEFAULT_WEIGHTS,
+    IntervalRounding, Scheduler, SchedulerParams, WEIGHT_COUNT, parse_learning_step,
+    validate_desired_retention,
+};
+use crate::profile;
+use crate::utils::{DEFAULT_CARD_EXTENSIONS, DEFAULT_CARD_SEPARATOR, DEFAULT_DAY_CUTOFF_HOUR};
+
+const CONFIG_FILE: &str = "config.toml";
+pub const COLLECTION_ENV: &str = "REPEATER_COLLECTION";
+pub const DEFAULT_LEECH_THRESHOLD: u32 = 8;
+pub const DEFAULT_MAX_ANSWER_SECS: u64 = 60;
+pub const DEFAULT_RELEARN_GAP: usize = 3;
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    pub scheduler: Scheduler,
+    pub weights: Vec<f64>,
+    pub desired_retention: f64,
+    pub enable_fuzz: bool,
+    pub max_interval_days: usize,
+    /// Whether FSRS intervals are floored, rounded, or rounded up to whole days.
+    pub interval_rounding: IntervalRounding,
+    /// Steps like `"10m"` a new card goes through before its first day-long interval.
+    pub learning_steps: Vec<String>,
+    pub leech_threshold: u32,
+    pub leech_auto_suspend: bool,
+    /// Default for `drill --new-card-limit`.
+    pub new_cards_per_day: Option<usize>,
+    /// Default for `drill --card-limit`.
+    pub max_reviews_per_day: Option<usize>,
+    /// Longer answer times are logged as this many seconds.
+    pub max_answer_secs: u64,
+    /// How many other cards come before one graded Again is shown again.
+    pub relearn_gap: usize,
+    /// Local hour when a new study day starts; reviews before it count toward the day before.
+    pub day_cutoff_hour: u32,
+    /// Whether reviewing a card defers its siblings to the next day.
+    pub bury_siblings: bool,
+    /// Whether typed answers to `T:` cards are checked ignoring case.
+    pub type_in_ignore_case: bool,
+    /// Lines starting with this split a file into cards.
+    pub card_separator: String,
+    /// Extensions, without the dot, of the files scanned for cards.
+    pub card_extensions: Vec<String>,
+    /// Directory `drill`, `stats`, `list`, and `search` read when given no paths.
+    pub collection: Option<PathBuf>,
+}
+
+/// When a card that keeps lapsing gets flagged as a leech.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct LeechPolicy {
+    pub threshold: u32,
+    pub auto_suspend: bool,
+}
+
+impl Default for LeechPolicy {
+    fn default() -> Self {
+        Self {
+            threshold: DEFAULT_LEECH_THRESHOLD,
+            auto_suspend: false,
+        }
+    }
+}
+
+impl LeechPolicy {
+    pub fn is_leech(&self, lapses: i64) -> bool {
+        lapses >= i64::from(self.threshold)
+    }
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            scheduler: Scheduler::Fsrs,
+            weights: DEFAULT_WEIGHTS.to_vec(),
+            desired_retention: DEFAULT_DESIRED_RETENTION,
+            enable_fuzz: true,
+            max_interval_days: DEFAULT_MAX_INTERVAL_DAYS,
+            interval_rounding: IntervalRounding::Round,
+            learning_steps: DEFAULT_LEARNING_STEPS
+                .iter()
+                .map(|step| format!("{}m", step.num_minutes()))
+                .collect(),
+            leech_threshold: DEFAULT_LEECH_THRESHOLD,
+            leech_auto_suspend: false,
+            new_cards_per_day: None,
+            max_reviews_per_day: None,
+            max_answer_secs: DEFAULT_MAX_ANSWER_SECS,
+            relearn_gap: DEFAULT_RELEARN_GAP,
+            day_cutoff_hour: DEFAULT_DAY_CUTOFF_HOUR,
+            bury_siblings: false,
+            type_in_ignore_case: true,
+            card_separator: DEFAULT_CARD_SEPARATOR.to_string(),
+            card_extensions: DEFAULT_CARD_EXTENSIONS
+                .iter()
+                .map(|ext| ext.to_string())
+                .collect(),
+            collection: None,
+        }
+    }
+}
+
+impl Config {
+    pub fn path() -> Result<PathBuf> {
+        let proj_dirs = profile::project_dirs()?;
+        Ok(proj_dirs.config_dir().join(CONFIG_FILE))
+    }
+
+    pub fn load() -> Result<Self> {
+        Self::load_from(&Self::path()?)
+    }
+
+    pub fn load_from(path: &Path) -> Result<Self> {
+        if !path.is_file() {
+            return Ok(Self::default());
+        }
+        let contents = fs::read_to_string(path)
+            .with_context(|| format!("Failed to read config file {}", path.display()))?;
+        Self::parse(&contents).with_context(|| format!("Invalid config file {}", path.display()))
+    }
+
+    pub fn parse(contents: &str) -> Result<Self> {
+        let config: Self = toml::from_str(contents)?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    fn validate(&self) -> Result<()> {
+        if self.weights.len() != WEIGHT_COUNT {
+            bail!(
+                "`weights` must contain exactly {} FSRS weights, found {}",
+                WEIGHT_COUNT,
+                self.weights.len()
+            );
+        }
+        validate_desired_retention(self.desired_retention)
+            .context("Invalid `desired_retention`")?;
+        if self.max_interval_days == 0 {
+            bail!("`max_interval_days` must be at least 1");
+        }
+        self.learning_steps().context("Invalid `learning_steps`")?;
+        if self.leech_threshold == 0 {
+            bail!("`leech_threshold` must be at least 1");
+        }
+        if self.max_answer_secs == 0 {
+            bail!("`max_answer_secs` must be at least 1");
+        }
+        if self.day_cutoff_hour > 23 {
+            bail!("`day_cutoff_hour` must be an hour from 0 to 23");
+        }
+        let separator = &self.card_separator;
+        if separator.trim().is_empty() || separator.contains('\n') {
+            bail!("`card_separator` must be a non-blank single line");
+        }
+        if ["Q:", "A:", "R:", "T:", "C:"]
+            .iter()
+            .any(|prefix| separator.starts_with(prefix))
+        {
+            bail!("`card_separator` must not start with a card prefix like `Q:`");
+        }
+        if self.card_extensions.is_empty() {
+            bail!("`card_extensions` must list at least one extension");
+        }
+        if let Some(ext) = self
+            .card_extensions
+            .iter()
+            .find(|ext| ext.is_empty() || !ext.chars().all(|c| c.is_ascii_alphanumeric()))
+        {
+            bail!(
+                "Invalid `card_extensions` entry {ext:?}: use letters and digits without the dot"
+            );
+        }
+        Ok(())
+    }
+
+    /// Rewrites `weights` in the config file at `path`, keeping every other setting.
+    pub fn save_weights(path: &Path, weights: &[f64; WEIGHT_COUNT]) -> Result<()> {
+        let mut table = if path.is_file() {
+            let contents = fs::read_to_string(path)
+                .with_context(|| format!("Failed to read config file {}", path.display()))?;
+            contents
+                .parse::<toml::Table>()
+                .with_context(|| format!("Invalid config file {}", path.display()))?
+        } else {
+            toml::Table::new()
+        };
+        table.insert(
+            "weights".to_string(),
+            toml::Value::Array(weights.iter().map(|&w| toml::Value::Float(w)).collect()),
+        );
+        let contents = toml::to_string(&table)?;
+        Self::parse(&contents)?;
+
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)
+                .with_context(|| format!("Failed to create {}", parent.display()))?;
+        }
+        fs::write(path, contents)
+            .with_context(|| format!("Failed to write config file {}", path.display()))
+    }
+
+    pub fn scheduler_params(&self) -> SchedulerParams {
+        let mut weights = DEFAULT_WEIGHTS;
+        weights.copy_from_slice(&self.weights);
+        SchedulerParams {
+            scheduler: self.scheduler,
+            weights,
+            desired_retention: self.desired_retention,
+            enable_fuzz: self.enable_fuzz,
+            max_interval_days: self.max_interval_days,
+            learning_steps: self
+                .learning_steps()
+                .expect("learning steps are validated when the config is parsed"),
+            interval_rounding: self.interval_rounding,
+            day_cutoff_hour: self.day_cutoff_hour,
+        }
+    }
+
+    fn learning_steps(&self) -> Result<Vec<chrono::Duration>> {
+        self.learning_steps
+            .iter()
+            .map(|step| parse_learning_step(step))
+            .collect()
+    }
+
+    /// `(card_limit, new_card_limit)` for a drill: a flag wins over the config, and
+    /// neither means no limit. An explicit `0` is kept as zero.
+    pub fn drill_limits(
+        &self,
+        card_limit: Option<usize>,
+        new_card_limit: Option<usize>,
+    ) -> (Option<usize>, Option<usize>) {
+        (
+            card_limit.or(self.max_reviews_per_day),
+            new_card_limit.or(self.new_cards_per_day),
+        )
+    }
+
+    pub fn max_answer_time(&self) -> Duration {
+        Duration::from_secs(self.max_answer_secs)
+    }
+
+    pub fn leech_policy(&self) -> LeechPolicy {
+        LeechPolicy {
+            threshold: self.leech_threshold,
+            auto_suspend: self.leech_auto_suspend,
+        }
+    }
+
+    /// `paths` when any were given, else the collection from `--collection`,
+    /// `REPEATER_COLLECTION`, or the config file, in that order, else the current directory.
+    pub fn collection_paths(
+        &self,
+        paths: Vec<PathBuf>,
+        flag: Option<PathBuf>,
+    ) -> Result<Vec<PathBuf>> {
+        resolve_collection(
+            paths,
+            flag,
+            env::var_os(COLLECTION_ENV),
+            self.collection.clone(),
+        )
+    }
+
+    /// Drill settings around `params`, which callers may have adjusted for the session.
+    pub fn drill_settings(&self, params: SchedulerParams) -> DrillSettings {
+        DrillSettings {
+            params,
+            leech_policy: self.leech_policy(),
+            max_answer_time: self.max_answer_time(),
+            relearn_gap: self.relearn_gap,
+            bury_siblings: self.bury_siblings,
+            type_in_ignore_case: self.type_in_ignore_case,
+            card_separator: self.card_separator.clone(),
+        }
+    }
+}
+
+fn resolve_collection(
+    paths: Vec<PathBuf>,
+    flag: Option<PathBuf>,
+    env_dir: Option<OsString>,
+    config_dir: Option<PathBuf>,
+) -> Result<Vec<PathBuf>> {
+    if !paths.is_empty() {
+        return Ok(paths);
+    }
+    let chosen = flag
+        .map(|dir| (dir, "--collection"))
+        .or_else(|| {
+            env_dir
+                .filter(|dir| !dir.is_empty())
+                .map(|dir| (expand_home(PathBuf::from(dir)), COLLECTION_ENV))
+        })
+        .or_else(|| config_dir.map(|dir| (expand_home(dir), "the config file")));
+    let Some((dir, source)) = chosen else {
+        crate::verbose!("No collection set; using the current directory");
+        return Ok(vec![PathBuf::from(".")]);
+    };
+    if !dir.is_dir() {
+        bail!(
+            "Collection {} (from {source}) is not a directory",
+            dir.display()
+        );
+    }
+    crate::verbose!("Using collection {} (from {source})", dir.display());
+    Ok(vec![dir])
+}
+
+/// `path` with a leading `~` replaced by the home directory, since the config file and
+/// environment variables don't go through the shell's expansion.
+fn expand_home(path: PathBuf) -> PathBuf {
+    let Ok(rest) = path.strip_prefix("~") else {
+        return path;
+    };
+    match directories::BaseDirs::new() {
+        Some(dirs) => dirs.home_dir().join(rest),
+        None => path,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::{Config, LeechPolicy, resolve_collection};
+    use crate::fsrs::{
+        DEFAULT_LEARNING_STEPS, DEFAULT_MAX_INTERVAL_DAYS, DEFAULT_WEIGHTS, IntervalRounding,
+        Scheduler, SchedulerParams,
+    };
+
+    #[test]
+    fn missing_file_falls_back_to_defaults() {
+        let config = Config::load_from(std::path::Path::new("does/not/exist.toml")).unwrap();
+        assert_eq!(config.scheduler_params(), SchedulerParams::default());
+    }
+
+    #[test]
+    fn parses_custom_weights() {
+        let weights: Vec<String> = (0..19).map(|i| format!("{}.5", i)).collect();
+        let contents = format!("weights = [{}]", weights.join(", "));
+        let params = Config::parse(&contents).unwrap().scheduler_params();
+        assert_eq!(params.weights[0], 0.5);
+        assert_eq!(params.weights[18], 18.5);
+        assert_ne!(params.weights, DEFAULT_WEIGHTS);
+    }
+
+    #[test]
+    fn rejects_wrong_number_of_weights() {
+        let err = Config::parse("weights = [1.0, 2.0]").unwrap_err();
+        assert!(err.to_string().contains("exactly 19"));
+    }
+
+    #[test]
+    fn parses_desired_retention() {
+        let config = Config::parse("desired_retention = 0.85").unwrap();
+        assert_eq!(config.scheduler_params().desired_retention, 0.85);
+        assert!(Config::parse("desired_retention = 0.5").is_err());
+    }
+
+    #[test]
+    fn fuzz_can_be_disabled() {
+        let config = Config::parse("enable_fuzz = false").unwrap();
+        assert!(!config.scheduler_params().enable_fuzz);
+        assert!(Config::default().scheduler_params().enable_fuzz);
+    }
+
+    #[test]
+    fn parses_max_interval() {
+        let config = Config::parse("max_interval_days = 365\n").unwrap();
+        assert_eq!(config.scheduler_params().max_interval_days, 365);
+        assert_eq!(
+            Config::default().scheduler_params().max_interval_days,
+            DEFAULT_MAX_INTERVAL_DAYS
+        );
+        assert!(Config::parse("max_interval_days = 0\n").is_err());
+    }
+
+    #[test]
+    fn parses_learning_steps() {
+        let config = Config::parse("learning_steps = [\"30s\", \"5m\", \"2h\"]\n").unwrap();
+        assert_eq!(
+            config.scheduler_params().learning_steps,
+            vec![
+                chrono::Duration::seconds(30),
+                chrono::Duration::minutes(5),
+                chrono::Duration::hours(2),
+            ]
+        );
+        assert_eq!(
+            Config::default().scheduler_params().learning_steps,
+            DEFAULT_LEARNING_STEPS.to_vec()
+        );
+        assert!(Config::parse("learning_steps = []\n").is_ok());
+        let err = Config::parse("learning_steps = [\"2d\"]\n").unwrap_err();
+        assert!(format!("{err:#}").contains("learning_steps"));
+    }
+
+    #[test]
+    fn parses_scheduler() {
+        let config = Config::parse("scheduler = \"sm2\"").unwrap();
+        assert_eq!(config.scheduler_params().scheduler, Scheduler::Sm2);
+        assert_eq!(Config::default().scheduler, Scheduler::Fsrs);
+        assert!(Config::parse("scheduler = \"leitner\"").is_err());
+    }
+
+    #[test]
+    fn parses_interval_rounding() {
+        let config = Config::parse("interval_rounding = \"ceil\"").unwrap();
+        assert_eq!(
+            config.scheduler_params().interval_rounding,
+            IntervalRounding::Ceil
+        );
+        assert_eq!(Config::default().interval_rounding, IntervalRounding::Round);
+        assert!(Config::parse("interval_rounding = \"up\"").is_err());
+    }
+
+    #[test]
+    fn collection_paths_prefer_arguments_then_flag_then_env_then_config() {
+        let dir = tempfile::tempdir().unwrap();
+        let flag = dir.path().join("flag");
+        let env = dir.path().join("env");
+        let config = dir.path().join("config");
+        for path in [&flag, &env, &config] {
+            std::fs::create_dir(path).unwrap();
+        }
+        let given = vec![PathBuf::from("spanish.md")];
+
+        let resolve = |paths: &[PathBuf], flag: Option<&PathBuf>, env: Option<&PathBuf>| {
+            resolve_collection(
+                paths.to_vec(),
+                flag.cloned(),
+                env.map(|env| env.clone().into_os_string()),
+                Some(config.clone()),
+            )
+            .unwrap()
+        };
+        assert_eq!(resolve(&given, Some(&flag), Some(&env)), given);
+        assert_eq!(resolve(&[], Some(&flag), Some(&env)), vec![flag.clone()]);
+        assert_eq!(resolve(&[], None, Some(&env)), vec![env.clone()]);
+        assert_eq!(resolve(&[], None, None), vec![config.clone()]);
+        assert_eq!(
+            resolve_collection(Vec::new(), None, Some("".into()), None).unwrap(),
+            vec![PathBuf::from(".")]
+        );
+
+        let missing = dir.path().join("missing");
+        let err =
+            resolve_collection(Vec::new(), None, Some(missing.into_os_string()), None).unwrap_err();
+        assert!(err.to_string().contains("REPEATER_COLLECTION"), "{err}");
+    }
+
+    #[test]
+    fn empty_file_uses_defaults() {
+        let config = Config::parse("").unwrap();
+        assert_eq!(config.weights, DEFAULT_WEIGHTS.to_vec());
+    }
+
+    #[test]
+    fn parses_leech_policy() {
+        let config = Config::parse("leech_threshold = 4\nleech_auto_suspend = true").unwrap();
+        let policy = config.leech_policy();
+        assert_eq!(
+            policy,
+            LeechPolicy {
+                threshold: 4,
+                auto_suspend: true
+            }
+        );
+        assert!(!policy.is_leech(3));
+        assert!(policy.is_leech(4));
+        assert_eq!(Config::default().leech_policy(), LeechPolicy::default());
+        assert!(Config::parse("leech_threshold = 0").is_err());
+    }
+
+    #[test]
+    fn drill_limit_flags_override_config() {
+        let config = Config::parse("new_cards_per_day = 15\nmax_reviews_per_day = 100").unwrap();
+        assert_eq!(config.drill_limits(None, None), (Some(100), Some(15)));
+        assert_eq!(config.drill_limits(Some(20), Some(0)), (Some(20), Some(0)));
+        assert_eq!(Config::default().drill_limits(None, None), (None, None));
+    }
+
+    #[test]
+    fn parses_max_answer_time() {
+        let config = Config::parse("max_answer_secs = 90").unwrap();
+        assert_eq!(config.max_answer_time().as_secs(), 90);
+        assert_eq!(
+            Config::default().max_answer_time().as_secs(),
+            super::DEFAULT_MAX_ANSWER_SECS
+        );
+        assert!(Config::parse("max_answer_secs = 0").is_err());
+    }
+
+    #[test]
+    fn drill_settings_carry_the_relearn_gap() {
+        let config = Config::parse("relearn_gap = 0").unwrap();
+        let settings = config.drill_settings(config.scheduler_params());
+        assert_eq!(settings.relearn_gap, 0);
+        assert_eq!(settings.leech_policy, config.leech_policy());
+        assert_eq!(Config::default().relearn_gap, super::DEFAULT_RELEARN_GAP);
+        assert!(settings.type_in_ignore_case);
+        assert!(!settings.bury_siblings);
+        assert!(
+            Config::parse("bury_siblings = true")
+                .unwrap()
+                .drill_settings(config.scheduler_params())
+                .bury_siblings
+        );
+    }
+
+    #[test]
+    fn validates_day_cutoff_hour() {
+        assert_eq!(
+            Config::parse("day_cutoff_hour = 4")
+                .unwrap()
+                .day_cutoff_hour,
+            4
+        );
+        assert_eq!(Config::default().day_cutoff_hour, 0);
+        assert!(Config::parse("day_cutoff_hour = 24").is_err());
+    }
+
+    #[test]
+    fn validates_card_separator() {
+        let config = Config::parse("card_separator = \"%%\"").unwrap();
+        assert_eq!(config.card_separator, "%%");
+        assert_eq!(Config::default().card_separator, "---");
+        assert!(Config::parse("card_separator = \" \"").is_err());
+        assert!(Config::parse("card_separator = \"a\\nb\"").is_err());
+        assert!(Config::parse("card_separator = \"Q:\"").is_err());
+    }
+
+    #[test]
+    fn validates_card_extensions() {
+        let config = Config::parse("card_extensions = [\"md\", \"mdown\"]").unwrap();
+        assert_eq!(config.card_extensions, ["md", "mdown"]);
+        assert_eq!(Config::default().card_extensions, ["md", "markdown"]);
+        assert!(Config::parse("card_extensions = []").is_err());
+        assert!(Config::parse("card_extensions = [\".md\"]").is_err());
+        assert!(Config::parse("card_extensions = [\"*\"]").is_err());
+    }
+
+    #[test]
+    fn save_weights_keeps_other_settings() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("repeater").join("config.toml");
+        let mut weights = DEFAULT_WEIGHTS;
+        weights[0] = 0.25;
+
+        Config::save_weights(&path, &weights).unwrap();
+        assert_eq!(Config::load_from(&path).unwrap().weights[0], 0.25);
+
+        std::fs::write(&path, "desired_retention = 0.85\nenable_fuzz = false\n").unwrap();
+        Config::save_weights(&path, &weights).unwrap();
+        let config = Config::load_from(&path).unwrap();
+        assert_eq!(config.weights, weights.to_vec());
+        assert_eq!(config.desired_retention, 0.85);
+        assert!(!config.enable_fuzz);
+    }
+}
@@ -1,9 +1,15 @@
 use crate::{
     card::CardType,
     crud::DB,
+    markdown::render_markdown,
     tui::Editor,
+    tui::MouseCapture,
     tui::Theme,
-    utils::{cards_from_md, content_to_card, is_markdown},
+    tui::clipboard,
+    utils::{
+        ScanOptions, cards_from_md, cards_from_reader, describe_card_extensions, is_card_separator,
+        is_markdown, parse_card_lines, parse_cards_from_reader, write_file_atomically,
+    },
 };
 
 use std::{
@@ -17,8 +23,9 @@ use std::{
 use anyhow::{Context, Result, anyhow};
 use crossterm::{
     event::{
-        self, Event, KeyCode, KeyEventKind, KeyModifiers, KeyboardEnhancementFlags,
-        PopKeyboardEnhancementFlags, PushKeyboardEnhancementFlags,
+        self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers, KeyboardEnhancementFlags,
+        MouseButton, MouseEvent, MouseEventKind, PopKeyboardEnhancementFlags,
+        PushKeyboardEnhancementFlags,
     },
     execute,
     terminal::{EnterAlternateScreen, LeaveAlternateScreen, disable_raw_mode, enable_raw_mode},
@@ -26,28 +33,36 @@ use crossterm::{
 use ratatui::{
     Terminal,
     backend::CrosstermBackend,
-    layout::{Constraint, Direction, Layout},
+    layout::{Constraint, Direction, Layout, Rect},
     text::{Line, Span},
     widgets::{Paragraph, Wrap},
 };
 
 const FLASH_SECS: f64 = 1.5;
+const WHEEL_LINES: isize = 3;
 
-pub async fn run(db: &DB, card_path: PathBuf) -> Result<()> {
-    if !is_markdown(&card_path) {
+pub async fn run(db: &DB, scan: &ScanOptions, card_path: PathBuf, edit: bool) -> Result<()> {
+    if !is_markdown(&card_path, &scan.extensions) {
         return Err(anyhow!(
-            "Card path must be a markdown file: {}",
+            "Card path must be a markdown file ({}): {}",
+            describe_card_extensions(&scan.extensions),
             card_path.display()
         ));
     }
 
     let file_exists = card_path.is_file();
+    if edit && !file_exists {
+        return Err(anyhow!(
+            "Cannot edit {}: file does not exist",
+            card_path.display()
+        ));
+    }
     if !file_exists && !prompt_create(&card_path)? {
         println!("Aborting; card not created.");
         return Ok(());
     }
 
-    capture_cards(db, &card_path).await?;
+    capture_cards(db, &scan.separator, &card_path, edit).await?;
     Ok(())
 }
 
@@ -63,15 +78,51 @@ fn prompt_create(path: &Path) -> io::Result<bool> {
     Ok(trimmed == "y" || trimmed == "yes")
 }
 
-async fn create_card_append_file(db: &DB, path: &Path, contents: &str) -> Result<()> {
-    let existing_len = fs::metadata(path).map(|m| m.len()).unwrap_or(0);
-    let start_idx = existing_len as usize;
-    let end_idx = start_idx + contents.len();
+/// Appends every card in `contents`, which may hold several split by the card
+/// separator, once all of them parse and none is already in the database.
+async fn create_card_append_file(
+    db: &DB,
+    path: &Path,
+    contents: &str,
+    card_type: &CardType,
+    card_separator: &str,
+) -> Result<usize> {
+    let existing = fs::read_to_string(path).unwrap_or_default();
+    let separator = separator_before_append(&existing, card_separator);
+    let first_line = format!("{existing}{separator}").lines().count();
 
-    let card = content_to_card(path, contents, start_idx, end_idx).context("Invalid card")?;
-    let card_exists = db.card_exists(&card).await?;
-    if card_exists {
-        return Err(anyhow!("This card already exists in the database."));
+    let (mut cards, issues) = parse_cards_from_reader(path, contents.as_bytes(), card_separator)?;
+    let batch = cards.len() + issues.len() > 1;
+    if let Some(issue) = issues.first() {
+        let block = card_block(contents, issue.line - 1, card_separator);
+        let problem = missing_card_parts(card_type, block, card_separator);
+        if batch {
+            let problem = problem.unwrap_or_else(|| issue.message.clone());
+            return Err(anyhow!("Card on line {}: {problem}", issue.line));
+        }
+        return Err(match problem {
+            Some(problem) => anyhow!(problem),
+            None => anyhow!(issue.message.clone()).context("Invalid card"),
+        });
+    }
+    if cards.is_empty() {
+        return Err(anyhow!(
+            missing_card_parts(card_type, contents, card_separator)
+                .unwrap_or_else(|| String::from("There is no card to save"))
+        ));
+    }
+    for card in &mut cards {
+        if db.card_exists(card).await? {
+            if batch {
+                return Err(anyhow!(
+                    "Card on line {} already exists in the database.",
+                    card.file_card_range.0 + 1
+                ));
+            }
+            return Err(anyhow!("This card already exists in the database."));
+        }
+        card.file_card_range.0 += first_line;
+        card.file_card_range.1 += first_line;
     }
     if let Some(parent) = path.parent()
         && !parent.as_os_str().is_empty()
@@ -80,17 +131,79 @@ async fn create_card_append_file(db: &DB, path: &Path, contents: &str) -> Result
     }
     let mut file = OpenOptions::new().create(true).append(true).open(path)?;
 
-    if start_idx > 0 {
-        writeln!(file)?;
+    // One write, synced before reporting success, so "Card saved" survives a crash.
+    file.write_all(format!("{separator}{contents}\n").as_bytes())?;
+    file.sync_all()?;
+
+    db.add_cards_batch(&cards).await?;
+
+    Ok(cards.len())
+}
+
+/// The lines of `contents` from the card starting at `start_line` (0-based) up to
+/// the next card or separator.
+fn card_block<'a>(contents: &'a str, start_line: usize, separator: &str) -> &'a str {
+    let mut offset = 0;
+    let mut block_start = None;
+    for (idx, line) in contents.split_inclusive('\n').enumerate() {
+        match block_start {
+            None if idx == start_line => block_start = Some(offset),
+            Some(begin)
+                if is_card_separator(line, separator)
+                    || ["Q:", "R:", "T:", "C:"]
+                        .iter()
+                        .any(|prefix| line.starts_with(prefix)) =>
+            {
+                return &contents[begin..offset];
+            }
+            _ => {}
+        }
+        offset += line.len();
+    }
+    block_start.map_or("", |begin| &contents[begin..])
+}
+
+/// A line holding just the card separator, starting a line of its own, so
+/// `Ctrl+N` can split the buffer into several cards.
+fn separator_insertion(cursor_col: usize, separator: &str) -> String {
+    let lead = if cursor_col > 0 { "\n" } else { "" };
+    format!("{lead}{separator}\n")
+}
+
+/// What goes between a file's existing cards and an appended one, so the new card
+/// never runs into the last one.
+fn separator_before_append(existing: &str, separator: &str) -> String {
+    let Some(last_line) = existing.lines().rev().find(|line| !line.trim().is_empty()) else {
+        return String::new();
+    };
+    let newline = if existing.ends_with('\n') { "" } else { "\n" };
+    if is_card_separator(last_line, separator) {
+        format!("{newline}\n")
+    } else {
+        format!("{newline}\n{separator}\n\n")
     }
-    writeln!(file, "{}", contents)?;
+}
 
-    db.add_card(&card).await?;
+/// Replaces the whole file with `contents` once every card in it parses.
+async fn overwrite_card_file(
+    db: &DB,
+    path: &Path,
+    contents: &str,
+    separator: &str,
+) -> Result<usize> {
+    let cards = cards_from_reader(path, contents.as_bytes(), separator).context("Invalid card")?;
+    let mut contents = contents.to_string();
+    if !contents.ends_with('\n') {
+        contents.push('\n');
+    }
+    write_file_atomically(path, contents.as_bytes())?;
+    db.add_cards_batch(&cards).await?;
 
-    Ok(())
+    let unique_hashes: HashSet<_> = cards.into_iter().map(|c| c.card_hash).collect();
+    Ok(unique_hashes.len())
 }
 
-async fn capture_cards(db: &DB, card_path: &Path) -> Result<()> {
+async fn capture_cards(db: &DB, separator: &str, card_path: &Path, edit: bool) -> Result<()> {
     enable_raw_mode()?;
     let mut stdout = io::stdout();
     execute!(
@@ -104,16 +217,28 @@ async fn capture_cards(db: &DB, card_path: &Path) -> Result<()> {
     let backend = CrosstermBackend::new(stdout);
     let mut terminal = Terminal::new(backend)?;
     terminal.show_cursor()?;
+    let mut mouse = MouseCapture::enable()?;
     let editor_result: Result<()> = async {
-        let mut editor = Editor::new();
+        let mut editor = if edit {
+            Editor::from_content(&fs::read_to_string(card_path)?)
+        } else {
+            Editor::new()
+        };
         let mut status: Option<String> = None;
-        let existing_cards = cards_from_md(card_path)?;
+        let existing_cards = cards_from_md(card_path, separator)?;
         let unique_hashes: HashSet<_> = existing_cards.into_iter().map(|c| c.card_hash).collect();
 
         let mut num_cards_in_collection = unique_hashes.len();
         let mut card_created_count = 0;
         let mut card_last_save_attempt: Option<std::time::Instant> = None;
+        // A failed save stays on screen until the text it complained about changes.
+        let mut failed_save: Option<String> = None;
         let mut view_height = 0usize;
+        let mut editor_area = Rect::default();
+        let mut show_preview = true;
+        let mut show_line_numbers = true;
+        let mut saved_content = editor.content();
+        let mut search: Option<Search> = None;
         loop {
             terminal.draw(|frame| {
                 let area = frame.area();
@@ -122,43 +247,116 @@ async fn capture_cards(db: &DB, card_path: &Path) -> Result<()> {
                     .direction(Direction::Vertical)
                     .constraints([Constraint::Min(5), Constraint::Length(5)])
                     .split(area);
+                let panes = if show_preview {
+                    Layout::default()
+                        .direction(Direction::Horizontal)
+                        .constraints([Constraint::Percentage(50), Constraint::Percentage(50)])
+                        .split(chunks[0])
+                } else {
+                    Layout::default()
+                        .constraints([Constraint::Min(0)])
+                        .split(chunks[0])
+                };
+                editor_area = panes[0];
 
-                view_height = chunks[0].height.saturating_sub(2) as usize;
+                view_height = editor_area.height.saturating_sub(2) as usize;
                 editor.ensure_cursor_visible(view_height.max(1));
 
-                let editor_block = Theme::panel(card_path.display().to_string());
-                let editor_widget = Paragraph::new(editor.content())
-                    .block(editor_block)
-                    .wrap(Wrap { trim: false })
-                    .scroll((editor.scroll_top() as u16, 0));
-                frame.render_widget(editor_widget, chunks[0]);
+                let gutter = gutter_width(&editor, show_line_numbers);
+                let modified = if editor.content() != saved_content {
+                    "*"
+                } else {
+                    ""
+                };
+                let editor_block = Theme::panel(format!("{}{modified}", card_path.display()));
+                let matches = search
+                    .as_ref()
+                    .map(|search| editor.find_all(&search.query, search.case_sensitive))
+                    .unwrap_or_default();
+                let match_len = search
+                    .as_ref()
+                    .map_or(0, |search| search.query.chars().count());
+                let editor_widget =
+                    Paragraph::new(editor_lines(&editor, gutter, &matches, match_len))
+                        .block(editor_block)
+                        .wrap(Wrap { trim: false })
+                        .scroll((editor.scroll_top() as u16, 0));
+                frame.render_widget(editor_widget, editor_area);
 
-                let mut help_lines = vec![Line::from(vec![
-                    Theme::key_chip("Ctrl+B"),
-                    Theme::span(" basic"),
+                if show_preview {
+                    let preview = Paragraph::new(render_markdown(&editor.content()))
+                        .block(Theme::panel("Preview"))
+                        .wrap(Wrap { trim: false })
+                        .scroll((editor.scroll_top() as u16, 0));
+                    frame.render_widget(preview, panes[1]);
+                }
+
+                let mut controls = Vec::new();
+                if !edit {
+                    controls.extend([
+                        Theme::key_chip("Ctrl+B"),
+                        Theme::span(" basic"),
+                        Theme::bullet(),
+                        Theme::key_chip("Ctrl+R"),
+                        Theme::span(" reversed"),
+                        Theme::bullet(),
+                        Theme::key_chip("Ctrl+E"),
+                        Theme::span(" type-in"),
+                        Theme::bullet(),
+                        Theme::key_chip("Ctrl+K"),
+                        Theme::span(" cloze"),
+                        Theme::bullet(),
+                    ]);
+                }
+                controls.extend([
+                    Theme::key_chip("Ctrl+S"),
+                    Theme::span(if edit { " save file" } else { " save" }),
                     Theme::bullet(),
-                    Theme::key_chip("Ctrl+K"),
-                    Theme::span(" cloze"),
+                    Theme::key_chip("Ctrl+Z"),
+                    Theme::span("/"),
+                    Theme::key_chip("Ctrl+Y"),
+                    Theme::span(" undo/redo"),
                     Theme::bullet(),
-                    Theme::key_chip("Ctrl+S"),
-                    Theme::span(" save"),
+                    Theme::key_chip("Ctrl+T"),
+                    Theme::span(if mouse.is_enabled() {
+                        " mouse on"
+                    } else {
+                        " mouse off"
+                    }),
                     Theme::bullet(),
                     Theme::key_chip("Esc"),
                     Theme::span(" / "),
                     Theme::key_chip("Ctrl+C"),
                     Theme::span(" exit"),
-                ])];
-                help_lines.push(Line::from(vec![
-                    Theme::span("Cards in collection:"),
-                    Theme::label_span(format!(" {}", num_cards_in_collection)),
-                    Theme::bullet(),
-                    Theme::span("Created this session:"),
-                    Theme::label_span(format!(" {}", card_created_count)),
-                ]));
-                if let Some(time) = card_last_save_attempt
-                    && time.elapsed().as_secs_f64() < FLASH_SECS
-                    && status.is_some()
-                {
+                ]);
+                let mut help_lines = vec![Line::from(controls)];
+                if let Some(search) = &search {
+                    help_lines.push(search.prompt(matches.len()));
+                } else {
+                    let mut counts = vec![
+                        Theme::span("Cards in collection:"),
+                        Theme::label_span(format!(" {}", num_cards_in_collection)),
+                        Theme::bullet(),
+                        Theme::span("Created this session:"),
+                        Theme::label_span(format!(" {}", card_created_count)),
+                        Theme::bullet(),
+                    ];
+                    if !edit {
+                        counts.extend([
+                            Theme::span("Type:"),
+                            Theme::label_span(format!(" {}", editor.card_type.name())),
+                            Theme::bullet(),
+                        ]);
+                    }
+                    counts.push(Theme::span(cursor_summary(&editor)));
+                    help_lines.push(Line::from(counts));
+                }
+                let flashing = card_last_save_attempt
+                    .is_some_and(|time| time.elapsed().as_secs_f64() < FLASH_SECS);
+                let failure_pending = failed_save
+                    .as_ref()
+                    .is_some_and(|failed| *failed == editor.content());
+                if (flashing || failure_pending) && status.is_some() {
                     let message = status.clone().unwrap();
                     let style = if message.starts_with("Unable") {
                         Theme::danger()
@@ -175,69 +373,214 @@ async fn capture_cards(db: &DB, card_path: &Path) -> Result<()> {
 
                 let (cursor_row, cursor_col) = editor.cursor();
                 let visible_row = cursor_row.saturating_sub(editor.scroll_top());
-                let cursor_x =
-                    chunks[0].x + 1 + (cursor_col as u16).min(chunks[0].width.saturating_sub(2));
-                let cursor_y =
-                    chunks[0].y + 1 + (visible_row as u16).min(chunks[0].height.saturating_sub(2));
+                let cursor_x = editor_area.x
+                    + 1
+                    + gutter
+                    + (cursor_col as u16).min(editor_area.width.saturating_sub(2 + gutter));
+                let cursor_y = editor_area.y
+                    + 1
+                    + (visible_row as u16).min(editor_area.height.saturating_sub(2));
                 frame.set_cursor_position((cursor_x, cursor_y));
             })?;
 
-            if event::poll(Duration::from_millis(16))?
-                && let Event::Key(key) = event::read()?
-            {
+            if event::poll(Duration::from_millis(16))? {
+                let key = match event::read()? {
+                    Event::Key(key) => key,
+                    Event::Mouse(mouse_event) => {
+                        let gutter = gutter_width(&editor, show_line_numbers);
+                        handle_mouse(&mut editor, mouse_event, editor_area, gutter, view_height);
+                        continue;
+                    }
+                    _ => continue,
+                };
                 if key.kind != KeyEventKind::Press {
                     continue;
                 }
-                if key.code == KeyCode::Esc
-                    || (key.code == KeyCode::Char('c')
-                        && key.modifiers.contains(KeyModifiers::CONTROL))
+                let ctrl = key.modifiers.contains(KeyModifiers::CONTROL);
+                if ctrl && key.code == KeyCode::Char('f') {
+                    search.get_or_insert_with(Search::default).typing = true;
+                    continue;
+                }
+                if let Some(active) = &mut search {
+                    match active.handle_key(&mut editor, key) {
+                        SearchKey::Handled => continue,
+                        SearchKey::Closed => {
+                            search = None;
+                            continue;
+                        }
+                        SearchKey::PassThrough => search = None,
+                    }
+                }
+                if ctrl
+                    && matches!(key.code, KeyCode::Char('c') | KeyCode::Char('x'))
+                    && let Some(selected) = editor.selected_text()
                 {
+                    match clipboard::write_text(&selected) {
+                        Ok(()) if key.code == KeyCode::Char('x') => {
+                            editor.delete_selection();
+                        }
+                        Ok(()) => {}
+                        Err(e) => {
+                            card_last_save_attempt = Some(std::time::Instant::now());
+                            status = Some(format!("Unable to copy: {}", flatten_error(&e)));
+                        }
+                    }
+                    continue;
+                }
+                if key.code == KeyCode::Esc || (ctrl && key.code == KeyCode::Char('c')) {
                     break;
                 }
-                if key.code == KeyCode::Char('b') && key.modifiers.contains(KeyModifiers::CONTROL) {
+                if ctrl
+                    && key.modifiers.contains(KeyModifiers::SHIFT)
+                    && matches!(key.code, KeyCode::Char('k') | KeyCode::Char('K'))
+                {
+                    editor.delete_line();
+                    continue;
+                }
+                if key.code == KeyCode::Char('d') && key.modifiers.contains(KeyModifiers::CONTROL) {
+                    editor.duplicate_line();
+                    continue;
+                }
+                if !edit
+                    && key.code == KeyCode::Char('b')
+                    && key.modifiers.contains(KeyModifiers::CONTROL)
+                {
                     editor.card_type = CardType::Basic;
                     editor.clear();
                     continue;
                 }
-                if key.code == KeyCode::Char('k') && key.modifiers.contains(KeyModifiers::CONTROL) {
+                if !edit
+                    && key.code == KeyCode::Char('r')
+                    && key.modifiers.contains(KeyModifiers::CONTROL)
+                {
+                    editor.card_type = CardType::BasicReversed;
+                    editor.clear();
+                    continue;
+                }
+                if !edit
+                    && key.code == KeyCode::Char('e')
+                    && key.modifiers.contains(KeyModifiers::CONTROL)
+                {
+                    editor.card_type = CardType::TypeIn;
+                    editor.clear();
+                    continue;
+                }
+                if !edit
+                    && key.code == KeyCode::Char('k')
+                    && key.modifiers.contains(KeyModifiers::CONTROL)
+                {
                     editor.card_type = CardType::Cloze;
                     editor.clear();
                     continue;
                 }
 
+                if key.code == KeyCode::Char('v') && key.modifiers.contains(KeyModifiers::CONTROL) {
+                    match clipboard::read_text() {
+                        Ok(text) => editor.insert_text(&text),
+                        Err(e) => {
+                            card_last_save_attempt = Some(std::time::Instant::now());
+                            status = Some(format!("Unable to paste: {}", flatten_error(&e)));
+                        }
+                    }
+                    continue;
+                }
+                if key.code == KeyCode::Char('n') && key.modifiers.contains(KeyModifiers::CONTROL) {
+                    let (_, col) = editor.cursor();
+                    editor.insert_text(&separator_insertion(col, separator));
+                    continue;
+                }
+                if key.code == KeyCode::Char('l') && key.modifiers.contains(KeyModifiers::CONTROL) {
+                    show_line_numbers = !show_line_numbers;
+                    continue;
+                }
+                if key.code == KeyCode::Char('p') && key.modifiers.contains(KeyModifiers::CONTROL) {
+                    show_preview = !show_preview;
+                    continue;
+                }
+                if key.code == KeyCode::Char('t') && key.modifiers.contains(KeyModifiers::CONTROL) {
+                    mouse.toggle()?;
+                    continue;
+                }
+                if key.code == KeyCode::Char('z') && key.modifiers.contains(KeyModifiers::CONTROL) {
+                    editor.undo();
+                    continue;
+                }
+                if key.code == KeyCode::Char('y') && key.modifiers.contains(KeyModifiers::CONTROL) {
+                    editor.redo();
+                    continue;
+                }
+
                 if key.code == KeyCode::Char('s') && key.modifiers.contains(KeyModifiers::CONTROL) {
                     let contents = editor.content();
-                    let save_status = create_card_append_file(db, card_path, &contents).await;
+                    failed_save = None;
+                    if edit {
+                        card_last_save_attempt = Some(std::time::Instant::now());
+                        match overwrite_card_file(db, card_path, &contents, separator).await {
+                            Ok(count) => {
+                                num_cards_in_collection = count;
+                                saved_content = contents;
+                                status = Some(String::from("File saved."));
+                            }
+                            Err(e) => {
+                                status =
+                                    Some(format!("Unable to save file: {}", flatten_error(&e)));
+                                failed_save = Some(contents);
+                            }
+                        }
+                        continue;
+                    }
+                    let save_status = create_card_append_file(
+                        db,
+                        card_path,
+                        &contents,
+                        &editor.card_type,
+                        separator,
+                    )
+                    .await;
                     match save_status {
-                        Ok(_) => {
+                        Ok(added) => {
                             editor.clear();
-                            card_created_count += 1;
-                            num_cards_in_collection += 1;
+                            saved_content = editor.content();
+                            card_created_count += added;
+                            num_cards_in_collection += added;
                             card_last_save_attempt = Some(std::time::Instant::now());
                             status = Some(String::from("Card saved."))
                         }
                         Err(e) => {
                             card_last_save_attempt = Some(std::time::Instant::now());
-                            let flat_error = e
-                                .chain()
-                                .map(|cause| cause.to_string().replace('\n', " "))
-                                .collect::<Vec<_>>()
-                                .join(": ");
-                            status = Some(format!("Unable to save card: {}", flat_error));
+                            status = Some(format!("Unable to save card: {}", flatten_error(&e)));
+                            failed_save = Some(contents);
                         }
                     }
                     continue;
                 }
 
+                if matches!(
+                    key.code,
+                    KeyCode::Left
+                        | KeyCode::Right
+                        | KeyCode::Up
+                        | KeyCode::Down
+                        | KeyCode::Home
+                        | KeyCode::End
+                        | KeyCode::PageUp
+                        | KeyCode::PageDown
+                ) {
+                    editor.set_selecting(key.modifiers.contains(KeyModifiers::SHIFT));
+                }
                 match key.code {
                     KeyCode::Char(c) if !key.modifiers.contains(KeyModifiers::CONTROL) => {
                         editor.insert_char(c);
                     }
                     KeyCode::Enter => editor.insert_newline(),
                     KeyCode::Tab => editor.insert_tab(),
+                    KeyCode::Backspace if ctrl => editor.delete_word_back(),
                     KeyCode::Backspace => editor.backspace(),
+                    KeyCode::Delete if ctrl => editor.delete_word_forward(),
                     KeyCode::Delete => editor.delete(),
+                    KeyCode::Left if ctrl => editor.move_word_left(),
                     KeyCode::Left => editor.move_left(),
+                    KeyCode::Right if ctrl => editor.move_word_right(),
                     KeyCode::Right => editor.move_right(),
                     KeyCode::Up => editor.move_up(),
                     KeyCode::Down => editor.move_down(),
@@ -261,6 +604,7 @@ async fn capture_cards(db: &DB, card_path: &Path) -> Result<()> {
     }
     .await;
 
+    mouse.release()?;
     disable_raw_mode()?;
     execute!(
         terminal.backend_mut(),
@@ -271,3 +615,322 @@ async fn capture_cards(db: &DB, card_path: &Path) -> Result<()> {
 
     editor_result
 }
+
+#[derive(Default)]
+struct Search {
+    query: String,
+    case_sensitive: bool,
+    /// Keys go to the query until Enter; afterwards `n`/`N` step through matches.
+    typing: bool,
+}
+
+enum SearchKey {
+    Handled,
+    Closed,
+    /// Any other key closes the search and is handled by the editor as usual.
+    PassThrough,
+}
+
+impl Search {
+    fn handle_key(&mut self, editor: &mut Editor, key: KeyEvent) -> SearchKey {
+        let shift = key.modifiers.contains(KeyModifiers::SHIFT);
+        match key.code {
+            KeyCode::Esc => return SearchKey::Closed,
+            KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::ALT) => {
+                self.case_sensitive = !self.case_sensitive;
+            }
+            KeyCode::Enter => {
+                self.typing = false;
+                editor.find_next(&self.query, self.case_sensitive, !shift);
+            }
+            _ if key.modifiers.contains(KeyModifiers::CONTROL) => return SearchKey::PassThrough,
+            KeyCode::Char(c) if self.typing => self.query.push(c),
+            KeyCode::Backspace if self.typing => {
+                self.query.pop();
+            }
+            _ if self.typing => {}
+            KeyCode::Char('n') if !shift => {
+                editor.find_next(&self.query, self.case_sensitive, true);
+            }
+            KeyCode::Char('N') | KeyCode::Char('n') => {
+                editor.find_next(&self.query, self.case_sensitive, false);
+            }
+            _ => return SearchKey::PassThrough,
+        }
+        SearchKey::Handled
+    }
+
+    fn prompt(&self, match_count: usize) -> Line<'static> {
+        let plural = if match_count == 1 { "" } else { "es" };
+        let mut spans = vec![
+            Theme::span("Find:"),
+            Theme::label_span(format!(" {}", self.query)),
+            Theme::bullet(),
+            Theme::span(format!("{match_count} match{plural}")),
+            Theme::bullet(),
+        ];
+        if self.typing {
+            spans.extend([
+                Theme::key_chip("Enter"),
+                Theme::span("/"),
+                Theme::key_chip("Shift+Enter"),
+            ]);
+        } else {
+            spans.extend([Theme::key_chip("n"), Theme::span("/"), Theme::key_chip("N")]);
+        }
+        spans.extend([
+            Theme::span(" next/prev"),
+            Theme::bullet(),
+            Theme::key_chip("Alt+C"),
+            Theme::span(if self.case_sensitive {
+                " match case"
+            } else {
+                " ignore case"
+            }),
+            Theme::bullet(),
+            Theme::key_chip("Esc"),
+            Theme::span(" close"),
+        ]);
+        Line::from(spans)
+    }
+}
+
+/// Clicks place the cursor, drags extend the selection and the wheel scrolls. Positions map
+/// to buffer lines the same way the cursor is drawn: inside the border, right of the line number
+/// gutter and below the scroll offset.
+fn handle_mouse(
+    editor: &mut Editor,
+    event: MouseEvent,
+    area: Rect,
+    gutter: u16,
+    view_height: usize,
+) {
+    let (left, top) = (area.x + 1, area.y + 1);
+    let right = area.right().saturating_sub(2).max(left);
+    let bottom = area.bottom().saturating_sub(2).max(top);
+    let inside = (left..=right).contains(&event.column) && (top..=bottom).contains(&event.row);
+    let position = |editor: &Editor| {
+        let x = event.column.clamp(left, right);
+        let y = event.row.clamp(top, bottom);
+        (
+            editor.scroll_top() + usize::from(y - top),
+            usize::from((x - left).saturating_sub(gutter)),
+        )
+    };
+    match event.kind {
+        MouseEventKind::Down(MouseButton::Left) if inside => {
+            let (row, col) = position(editor);
+            editor.set_selecting(false);
+            editor.move_to(row, col);
+        }
+        MouseEventKind::Drag(MouseButton::Left) => {
+            let (row, col) = position(editor);
+            editor.set_selecting(true);
+            editor.move_to(row, col);
+        }
+        MouseEventKind::ScrollUp => editor.scroll(-WHEEL_LINES, view_height),
+        MouseEventKind::ScrollDown => editor.scroll(WHEEL_LINES, view_height),
+        _ => {}
+    }
+}
+
+/// Says which part of a `card_type` card is missing, so a failed save can tell
+/// the writer what to add instead of only reporting "Invalid card".
+fn missing_card_parts(card_type: &CardType, contents: &str, separator: &str) -> Option<String> {
+    let card_type = card_type_of(contents).unwrap_or(card_type);
+    let (question, answer, cloze) = parse_card_lines(contents, separator);
+    let kind = card_type.name();
+    let prefix = match card_type {
+        CardType::Basic => "Q:",
+        CardType::BasicReversed => "R:",
+        CardType::TypeIn => "T:",
+        CardType::Cloze => {
+            return cloze
+                .is_none()
+                .then(|| String::from("A cloze card needs text after `C:`"));
+        }
+    };
+    match (question, answer) {
+        (None, None) => Some(format!(
+            "A {kind} card needs a front after `{prefix}` and a back after `A:`"
+        )),
+        (None, Some(_)) => Some(format!(
+            "A {kind} card needs a front: write it after `{prefix}`"
+        )),
+        (Some(_), None) => Some(format!(
+            "A {kind} card needs a back: write it on a line starting with `A:`"
+        )),
+        (Some(_), Some(_)) => None,
+    }
+}
+
+/// The type a card's opening prefix declares, if it starts with one.
+fn card_type_of(contents: &str) -> Option<&'static CardType> {
+    let first = contents.lines().find(|line| !line.trim().is_empty())?;
+    [
+        ("Q:", &CardType::Basic),
+        ("R:", &CardType::BasicReversed),
+        ("T:", &CardType::TypeIn),
+        ("C:", &CardType::Cloze),
+    ]
+    .into_iter()
+    .find(|(prefix, _)| first.starts_with(prefix))
+    .map(|(_, card_type)| card_type)
+}
+
+fn flatten_error(error: &anyhow::Error) -> String {
+    error
+        .chain()
+        .map(|cause| cause.to_string().replace('\n', " "))
+        .collect::<Vec<_>>()
+        .join(": ")
+}
+
+/// 1-based line and column of the cursor plus the buffer's length in characters.
+fn cursor_summary(editor: &Editor) -> String {
+    let (row, col) = editor.cursor();
+    let chars = editor.content().chars().count();
+    format!("Ln {}, Col {} ({chars} chars)", row + 1, col + 1)
+}
+
+/// Columns taken by line numbers: enough digits for the last line plus a space.
+fn gutter_width(editor: &Editor, show_line_numbers: bool) -> u16 {
+    if !show_line_numbers {
+        return 0;
+    }
+    editor.lines().len().to_string().len() as u16 + 1
+}
+
+/// Editor buffer as styled lines, with search matches and the selection highlighted.
+pub(crate) fn editor_lines(
+    editor: &Editor,
+    gutter: u16,
+    matches: &[(usize, usize)],
+    match_len: usize,
+) -> Vec<Line<'static>> {
+    editor
+        .lines()
+        .iter()
+        .enumerate()
+        .map(|(row, line)| {
+            let mut spans = Vec::new();
+            if gutter > 0 {
+                let width = usize::from(gutter) - 1;
+                spans.push(Span::styled(
+                    format!("{:>width$} ", row + 1),
+                    Theme::gutter(),
+                ));
+            }
+            let selection = editor.selection_in_line(row);
+            let style_at = |col: usize| {
+                if selection.is_some_and(|(start, end)| (start..end).contains(&col)) {
+                    Theme::selection()
+                } else if matches
+                    .iter()
+                    .any(|&(r, start)| r == row && (start..start + match_len).contains(&col))
+                {
+                    Theme::search_match()
+                } else {
+                    Theme::default_style()
+                }
+            };
+            let mut run = String::new();
+            let mut run_style = Theme::default_style();
+            for (col, ch) in line.chars().enumerate() {
+                let style = style_at(col);
+                if style != run_style && !run.is_empty() {
+                    spans.push(Span::styled(std::mem::take(&mut run), run_style));
+                }
+                run_style = style;
+                run.push(ch);
+            }
+            spans.push(Span::styled(run, run_style));
+            Line::from(spans)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        card_block, cursor_summary, missing_card_parts, separator_before_append,
+        separator_insertion,
+    };
+    use crate::{card::CardType, tui::Editor};
+
+    #[test]
+    fn card_block_stops_at_the_next_card() {
+        let contents = "Q: one?\nA: 1\n---\n\nQ: two?\nA:\nC: [three]\n";
+        assert_eq!(card_block(contents, 0, "---"), "Q: one?\nA: 1\n");
+        assert_eq!(card_block(contents, 4, "---"), "Q: two?\nA:\n");
+        assert_eq!(card_block(contents, 6, "---"), "C: [three]\n");
+        assert_eq!(card_block(contents, 9, "---"), "");
+        assert_eq!(
+            missing_card_parts(&CardType::Cloze, card_block(contents, 4, "---"), "---"),
+            Some(String::from(
+                "A basic card needs a back: write it on a line starting with `A:`"
+            ))
+        );
+    }
+
+    #[test]
+    fn separator_goes_on_its_own_line() {
+        assert_eq!(separator_insertion(0, "---"), "---\n");
+        assert_eq!(separator_insertion(5, "---"), "\n---\n");
+    }
+
+    #[test]
+    fn cursor_summary_counts_from_one() {
+        let editor = Editor::new();
+        assert_eq!(cursor_summary(&editor), "Ln 1, Col 4 (7 chars)");
+
+        let mut editor = Editor::from_content("Q: café?\nA: yes");
+        editor.move_to(1, 6);
+        assert_eq!(cursor_summary(&editor), "Ln 2, Col 7 (15 chars)");
+    }
+
+    #[test]
+    fn missing_card_parts_name_what_to_add() {
+        assert_eq!(
+            missing_card_parts(&CardType::Basic, "Q: \nA: ", "---"),
+            Some(String::from(
+                "A basic card needs a front after `Q:` and a back after `A:`"
+            ))
+        );
+        assert_eq!(
+            missing_card_parts(&CardType::Basic, "Q: What is 2 + 2?\nA: ", "---"),
+            Some(String::from(
+                "A basic card needs a back: write it on a line starting with `A:`"
+            ))
+        );
+        assert_eq!(
+            missing_card_parts(&CardType::Basic, "Q: What is 2 + 2?", "---"),
+            Some(String::from(
+                "A basic card needs a back: write it on a line starting with `A:`"
+            ))
+        );
+        assert_eq!(
+            missing_card_parts(&CardType::TypeIn, "T: \nA: 4", "---"),
+            Some(String::from(
+                "A type-in card needs a front: write it after `T:`"
+            ))
+        );
+        assert_eq!(
+            missing_card_parts(&CardType::Cloze, "C: ", "---"),
+            Some(String::from("A cloze card needs text after `C:`"))
+        );
+        assert_eq!(
+            missing_card_parts(&CardType::Basic, "Q: What is 2 + 2?\nA: 4", "---"),
+            None
+        );
+    }
+
+    #[test]
+    fn appended_cards_get_a_separator() {
+        assert_eq!(separator_before_append("", "---"), "");
+        assert_eq!(separator_before_append("\n\n", "---"), "");
+        assert_eq!(separator_before_append("Q: a?\nA: b\n", "---"), "\n---\n\n");
+        assert_eq!(separator_before_append("Q: a?\nA: b", "%%"), "\n\n%%\n\n");
+        assert_eq!(separator_before_append("Q: a?\nA: b\n---\n\n", "---"), "\n");
+    }
+}
@@ -1,20 +1,208 @@
 use anyhow::Result;
+use clap::ValueEnum;
 use futures::TryStreamExt;
+use serde::{Deserialize, Serialize};
 
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 
 use anyhow::anyhow;
 
 use crate::card::Card;
+use crate::utils::{study_day_end, today};
 
 use crate::fsrs::ReviewStatus;
 use crate::fsrs::ReviewedPerformance;
-use crate::fsrs::update_performance;
+use crate::fsrs::SchedulerParams;
+use crate::fsrs::elapsed_days;
+use crate::fsrs::schedule_review;
 use crate::fsrs::{LEARN_AHEAD_THRESHOLD_MINS, Performance};
 
 use super::DB;
+use super::reviews::{ReviewLogEntry, insert_review};
+
+/// Scheduling columns shown by `repeater list`.
+pub struct CardRow {
+    pub card_hash: String,
+    pub review_count: i64,
+    pub due_date: Option<chrono::DateTime<chrono::Utc>>,
+    pub interval_raw: Option<f64>,
+    pub interval_days: Option<i64>,
+    pub suspended: bool,
+    pub lapses: i64,
+    pub leech: bool,
+    pub last_reviewed_at: Option<chrono::DateTime<chrono::Utc>>,
+    pub stability: Option<f64>,
+}
+
+/// Order of the cards `due_by` returns, after the limits picked them. `repeater drill`
+/// then moves the reviews ahead of the new cards (or behind them with `--new-first`),
+/// keeping this order within each group.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum QueueOrder {
+    /// Most overdue first, then new cards.
+    #[default]
+    Due,
+    /// Shuffled with a seed that changes once a day; a drill shuffles the reviews and the
+    /// new cards separately rather than mixing them.
+    Random,
+    /// Oldest cards in the collection first; cards added together in the order they
+    /// appear in their file. A drill still puts the reviews first, so new cards come after
+    /// them even when they are older.
+    Added,
+    /// Reviews furthest past their due date relative to their interval first, so a card
+    /// a week late on a three-day interval beats one a week late on a year; then new cards.
+    Overdue,
+}
+
+/// Scheduling state of one card, as written by `repeater export`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct CardState {
+    pub card_hash: String,
+    pub added_at: chrono::DateTime<chrono::Utc>,
+    pub last_reviewed_at: Option<chrono::DateTime<chrono::Utc>>,
+    pub stability: Option<f64>,
+    pub difficulty: Option<f64>,
+    pub interval_days: Option<i64>,
+    pub due_date: Option<chrono::DateTime<chrono::Utc>>,
+    pub review_count: i64,
+}
+
+/// What [`DB::rehash_cards`] did.
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct RehashOutcome {
+    /// Cards whose history now lives under their new hash.
+    pub moved: usize,
+    /// Cards left alone because their new hash was already reviewed.
+    pub conflicts: usize,
+}
+
+/// Result of recording one answer.
+pub struct ReviewOutcome {
+    pub scheduled_days: f64,
+    pub lapses: i64,
+    /// Row of the logged review, `None` when nothing was logged.
+    pub review_id: Option<i64>,
+}
+
+/// Every column of a card that answering it can change, so [`DB::undo_review`] can put
+/// them back exactly.
+#[derive(Clone, Debug, PartialEq)]
+pub struct CardSchedule {
+    pub card_hash: String,
+    pub last_reviewed_at: Option<chrono::DateTime<chrono::Utc>>,
+    pub stability: Option<f64>,
+    pub difficulty: Option<f64>,
+    pub interval_raw: Option<f64>,
+    pub interval_days: Option<i64>,
+    pub due_date: Option<chrono::DateTime<chrono::Utc>>,
+    pub review_count: i64,
+    pub lapses: i64,
+    pub leech: bool,
+    pub suspended: bool,
+}
 
 impl DB {
+    pub async fn card_rows(&self) -> Result<Vec<CardRow>> {
+        let rows = sqlx::query_as!(
+            CardRow,
+            r#"
+            SELECT
+                card_hash,
+                review_count as "review_count!: i64",
+                due_date as "due_date?: chrono::DateTime<chrono::Utc>",
+                interval_raw as "interval_raw?: f64",
+                interval_days as "interval_days?: i64",
+                suspended as "suspended!: bool",
+                lapses as "lapses!: i64",
+                leech as "leech!: bool",
+                last_reviewed_at as "last_reviewed_at?: chrono::DateTime<chrono::Utc>",
+                stability as "stability?: f64"
+            FROM cards
+            WHERE archived_at IS NULL
+            "#
+        )
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(rows)
+    }
+
+    pub async fn card_states(&self) -> Result<Vec<CardState>> {
+        let rows = sqlx::query_as!(
+            CardState,
+            r#"
+            SELECT
+                card_hash,
+                added_at as "added_at!: chrono::DateTime<chrono::Utc>",
+                last_reviewed_at as "last_reviewed_at?: chrono::DateTime<chrono::Utc>",
+                stability as "stability?: f64",
+                difficulty as "difficulty?: f64",
+                interval_days as "interval_days?: i64",
+                due_date as "due_date?: chrono::DateTime<chrono::Utc>",
+                review_count as "review_count!: i64"
+            FROM cards
+            ORDER BY card_hash
+            "#
+        )
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(rows)
+    }
+
+    /// Upserts imported states and returns how many rows were written. Unless
+    /// `overwrite` is set, cards that already have reviews are left alone.
+    pub async fn import_card_states(&self, states: &[CardState], overwrite: bool) -> Result<usize> {
+        let mut tx = self.pool.begin().await?;
+        let mut written = 0;
+        for state in states {
+            let interval_raw = match (state.last_reviewed_at, state.due_date) {
+                (Some(reviewed), Some(due)) => Some(elapsed_days(reviewed, due)),
+                _ => None,
+            };
+            let result = sqlx::query!(
+                r#"
+                INSERT INTO cards (
+                    card_hash,
+                    added_at,
+                    last_reviewed_at,
+                    stability,
+                    difficulty,
+                    interval_raw,
+                    interval_days,
+                    due_date,
+                    review_count
+                )
+                VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?)
+                ON CONFLICT(card_hash) DO UPDATE SET
+                    added_at = excluded.added_at,
+                    last_reviewed_at = excluded.last_reviewed_at,
+                    stability = excluded.stability,
+                    difficulty = excluded.difficulty,
+                    interval_raw = excluded.interval_raw,
+                    interval_days = excluded.interval_days,
+                    due_date = excluded.due_date,
+                    review_count = excluded.review_count
+                WHERE ? OR cards.review_count = 0
+                "#,
+                state.card_hash,
+                state.added_at,
+                state.last_reviewed_at,
+                state.stability,
+                state.difficulty,
+                interval_raw,
+                state.interval_days,
+                state.due_date,
+                state.review_count,
+                overwrite,
+            )
+            .execute(&mut *tx)
+            .await?;
+            written += result.rows_affected() as usize;
+        }
+        tx.commit().await?;
+        Ok(written)
+    }
+
     pub async fn add_card(&self, card: &Card) -> Result<()> {
         let now = chrono::Utc::now().to_rfc3339();
 
@@ -47,13 +235,17 @@ impl DB {
 
         let now = chrono::Utc::now().to_rfc3339();
 
-        for card in cards {
+        // Cards come in the order they were parsed, so their index keeps cards added
+        // together in the order they appear in their file.
+        for (position, card) in cards.iter().enumerate() {
             let added_at = now.clone();
+            let position = position as i64;
             sqlx::query!(
                 r#"
             INSERT or ignore INTO cards (
                 card_hash,
                 added_at,
+                added_position,
                 last_reviewed_at,
                 stability,
                 difficulty,
@@ -62,19 +254,212 @@ impl DB {
                 due_date,
                 review_count
             )
-            VALUES (?, ?, NULL, NULL, NULL, NULL, 0, NULL, 0)
+            VALUES (?, ?, ?, NULL, NULL, NULL, NULL, 0, NULL, 0)
             "#,
                 card.card_hash,
-                added_at
+                added_at,
+                position
             )
             .execute(&mut *tx)
             .await?;
+
+            sqlx::query!("DELETE FROM card_tags WHERE card_hash = ?", card.card_hash)
+                .execute(&mut *tx)
+                .await?;
+            for tag in &card.tags {
+                sqlx::query!(
+                    "INSERT or ignore INTO card_tags (card_hash, tag) VALUES (?, ?)",
+                    card.card_hash,
+                    tag
+                )
+                .execute(&mut *tx)
+                .await?;
+            }
         }
 
         tx.commit().await?;
         Ok(())
     }
 
+    /// Hashes of cards carrying any of `tags`.
+    pub async fn tagged_hashes(&self, tags: &[String]) -> Result<HashSet<String>> {
+        let mut hashes = HashSet::new();
+        for tag in tags {
+            let rows: Vec<String> =
+                sqlx::query_scalar!("SELECT card_hash FROM card_tags WHERE tag = ?", tag)
+                    .fetch_all(&self.pool)
+                    .await?;
+            hashes.extend(rows);
+        }
+        Ok(hashes)
+    }
+
+    /// Stored cards, archived ones aside, whose hash isn't in `current_hashes`.
+    pub async fn missing_hashes(&self, current_hashes: &HashSet<String>) -> Result<Vec<String>> {
+        let stored: Vec<String> =
+            sqlx::query_scalar!(r#"SELECT card_hash FROM cards WHERE archived_at IS NULL"#)
+                .fetch_all(&self.pool)
+                .await?;
+        Ok(stored
+            .into_iter()
+            .filter(|hash| !current_hashes.contains(hash))
+            .collect())
+    }
+
+    /// Archives cards whose hash is not in `current_hashes`, keeping their schedule,
+    /// review log, and tags. Returns how many were newly archived.
+    pub async fn archive_missing(&self, current_hashes: &HashSet<String>) -> Result<usize> {
+        let missing = self.missing_hashes(current_hashes).await?;
+        let now = chrono::Utc::now();
+
+        let mut tx = self.pool.begin().await?;
+        for card_hash in &missing {
+            sqlx::query!(
+                "UPDATE cards SET archived_at = ? WHERE card_hash = ?",
+                now,
+                card_hash
+            )
+            .execute(&mut *tx)
+            .await?;
+        }
+        tx.commit().await?;
+
+        Ok(missing.len())
+    }
+
+    pub async fn archived_hashes(&self) -> Result<HashSet<String>> {
+        let hashes: Vec<String> =
+            sqlx::query_scalar!(r#"SELECT card_hash FROM cards WHERE archived_at IS NOT NULL"#)
+                .fetch_all(&self.pool)
+                .await?;
+        Ok(hashes.into_iter().collect())
+    }
+
+    /// Brings archived cards back into drills and stats with the schedule they had.
+    pub async fn unarchive_cards(&self, card_hashes: &[String]) -> Result<()> {
+        let mut tx = self.pool.begin().await?;
+        for card_hash in card_hashes {
+            sqlx::query!(
+                "UPDATE cards SET archived_at = NULL WHERE card_hash = ?",
+                card_hash
+            )
+            .execute(&mut *tx)
+            .await?;
+        }
+        tx.commit().await?;
+        Ok(())
+    }
+
+    /// Deletes cards whose hash is not in `current_hashes`, archived ones included; their
+    /// review log and tags go with them through the foreign keys.
+    pub async fn prune_missing(&self, current_hashes: &HashSet<String>) -> Result<usize> {
+        let stored: Vec<String> = sqlx::query_scalar!(r#"SELECT card_hash FROM cards"#)
+            .fetch_all(&self.pool)
+            .await?;
+        let missing: Vec<String> = stored
+            .into_iter()
+            .filter(|hash| !current_hashes.contains(hash))
+            .collect();
+
+        let mut tx = self.pool.begin().await?;
+        let mut removed = 0;
+        for card_hash in &missing {
+            sqlx::query!("DELETE FROM cards WHERE card_hash = ?", card_hash)
+                .execute(&mut *tx)
+                .await?;
+            removed += 1;
+        }
+        tx.commit().await?;
+
+        Ok(removed)
+    }
+
+    pub async fn set_suspended(&self, card: &Card, suspended: bool) -> Result<()> {
+        sqlx::query!(
+            "UPDATE cards SET suspended = ? WHERE card_hash = ?",
+            suspended,
+            card.card_hash
+        )
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    /// Puts cards back in the state `add_card` creates them in. Their review log is kept.
+    pub async fn reset_cards(&self, card_hashes: &[String]) -> Result<()> {
+        let mut tx = self.pool.begin().await?;
+        for card_hash in card_hashes {
+            sqlx::query!(
+                r#"
+            UPDATE cards SET
+                last_reviewed_at = NULL,
+                stability = NULL,
+                difficulty = NULL,
+                interval_raw = NULL,
+                interval_days = 0,
+                due_date = NULL,
+                review_count = 0,
+                lapses = 0,
+                leech = 0
+            WHERE card_hash = ?
+            "#,
+                card_hash
+            )
+            .execute(&mut *tx)
+            .await?;
+        }
+        tx.commit().await?;
+        Ok(())
+    }
+
+    /// Moves each card's schedule and review log from the old hash of a pair to the new one.
+    /// A row already registered under the new hash is replaced if it was never reviewed.
+    pub async fn rehash_cards(&self, pairs: &[(String, String)]) -> Result<RehashOutcome> {
+        let mut outcome = RehashOutcome::default();
+        let mut tx = self.pool.begin().await?;
+        for (old, new) in pairs {
+            let old_exists =
+                sqlx::query_scalar!("SELECT review_count FROM cards WHERE card_hash = ?", old)
+                    .fetch_optional(&mut *tx)
+                    .await?
+                    .is_some();
+            if !old_exists {
+                continue;
+            }
+            let new_reviews =
+                sqlx::query_scalar!("SELECT review_count FROM cards WHERE card_hash = ?", new)
+                    .fetch_optional(&mut *tx)
+                    .await?;
+            if new_reviews.is_some_and(|count| count > 0) {
+                outcome.conflicts += 1;
+                continue;
+            }
+            sqlx::query!("DELETE FROM cards WHERE card_hash = ?", new)
+                .execute(&mut *tx)
+                .await?;
+            // The review log and tags follow the hash through the foreign keys. The tags
+            // are the old card's until the next registration refreshes them from the file.
+            sqlx::query!(
+                "UPDATE cards SET card_hash = ? WHERE card_hash = ?",
+                new,
+                old
+            )
+            .execute(&mut *tx)
+            .await?;
+            outcome.moved += 1;
+        }
+        tx.commit().await?;
+        Ok(outcome)
+    }
+
+    pub async fn suspended_hashes(&self) -> Result<HashSet<String>> {
+        let hashes: Vec<String> =
+            sqlx::query_scalar!(r#"SELECT card_hash FROM cards WHERE suspended = 1"#)
+                .fetch_all(&self.pool)
+                .await?;
+        Ok(hashes.into_iter().collect())
+    }
+
     pub async fn card_exists(&self, card: &Card) -> Result<bool> {
         let count: i64 = sqlx::query_scalar!(
             r#"SELECT COUNT(1) as "count!: i64" FROM cards WHERE card_hash = ?"#,
@@ -90,19 +475,51 @@ impl DB {
         card: &Card,
         review_status: ReviewStatus,
         optional_now: Option<chrono::DateTime<chrono::Utc>>,
-    ) -> Result<f64> {
+        params: &SchedulerParams,
+        duration_ms: Option<i64>,
+    ) -> Result<ReviewOutcome> {
         let current_performance = self.get_card_performance(card).await?;
         let now = match optional_now {
             Some(now) => now,
             None => chrono::Utc::now(),
         };
 
-        let new_performance = update_performance(current_performance, review_status, now);
+        let new_performance = schedule_review(
+            &card.card_hash,
+            current_performance,
+            review_status,
+            now,
+            params,
+        );
 
         let interval_days = new_performance.interval_days as i64;
         let review_count = new_performance.review_count as i64;
 
-        sqlx::query!(
+        let lapsed = i64::from(
+            review_status == ReviewStatus::Again
+                && matches!(current_performance, Performance::Reviewed(_)),
+        );
+        let (elapsed, previous_stability) = match current_performance {
+            Performance::New => (0.0, None),
+            Performance::Reviewed(reviewed) => (
+                elapsed_days(reviewed.last_reviewed_at, now),
+                Some(reviewed.stability),
+            ),
+        };
+        let review = ReviewLogEntry {
+            card_hash: card.card_hash.clone(),
+            reviewed_at: now,
+            rating: review_status,
+            elapsed_days: elapsed,
+            previous_stability,
+            new_stability: new_performance.stability,
+            interval_days,
+            duration_ms,
+        };
+
+        let mut tx = self.pool.begin().await?;
+
+        let lapses = sqlx::query_scalar!(
             r#"
             UPDATE cards
             SET
@@ -112,8 +529,10 @@ impl DB {
                 interval_raw = ?,
                 interval_days = ?,
                 due_date = ?,
-                review_count = ?
+                review_count = ?,
+                lapses = lapses + ?
             WHERE card_hash = ?
+            RETURNING lapses as "lapses!: i64"
             "#,
             new_performance.last_reviewed_at,
             new_performance.stability,
@@ -122,12 +541,107 @@ impl DB {
             interval_days,
             new_performance.due_date,
             review_count,
+            lapsed,
             card.card_hash,
         )
-        .execute(&self.pool)
+        .fetch_one(&mut *tx)
         .await?;
 
-        Ok(new_performance.interval_raw)
+        let review_id = insert_review(&mut tx, &review).await?;
+
+        tx.commit().await?;
+
+        Ok(ReviewOutcome {
+            scheduled_days: new_performance.scheduled_days(),
+            lapses,
+            review_id: Some(review_id),
+        })
+    }
+
+    pub async fn card_schedule(&self, card: &Card) -> Result<CardSchedule> {
+        let schedule = sqlx::query_as!(
+            CardSchedule,
+            r#"
+            SELECT
+                card_hash,
+                last_reviewed_at as "last_reviewed_at?: chrono::DateTime<chrono::Utc>",
+                stability as "stability?: f64",
+                difficulty as "difficulty?: f64",
+                interval_raw as "interval_raw?: f64",
+                interval_days as "interval_days?: i64",
+                due_date as "due_date?: chrono::DateTime<chrono::Utc>",
+                review_count as "review_count!: i64",
+                lapses as "lapses!: i64",
+                leech as "leech!: bool",
+                suspended as "suspended!: bool"
+            FROM cards
+            WHERE card_hash = ?
+            "#,
+            card.card_hash
+        )
+        .fetch_one(&self.pool)
+        .await?;
+        Ok(schedule)
+    }
+
+    /// Takes back an answer: restores the card to `previous`, captured with
+    /// [`DB::card_schedule`] before the review, and drops the review logged as `review_id`.
+    pub async fn undo_review(&self, previous: &CardSchedule, review_id: i64) -> Result<()> {
+        let mut tx = self.pool.begin().await?;
+        sqlx::query!(
+            r#"
+            UPDATE cards
+            SET
+                last_reviewed_at = ?,
+                stability = ?,
+                difficulty = ?,
+                interval_raw = ?,
+                interval_days = ?,
+                due_date = ?,
+                review_count = ?,
+                lapses = ?,
+                leech = ?,
+                suspended = ?
+            WHERE card_hash = ?
+            "#,
+            previous.last_reviewed_at,
+            previous.stability,
+            previous.difficulty,
+            previous.interval_raw,
+            previous.interval_days,
+            previous.due_date,
+            previous.review_count,
+            previous.lapses,
+            previous.leech,
+            previous.suspended,
+            previous.card_hash,
+        )
+        .execute(&mut *tx)
+        .await?;
+        sqlx::query!(
+            "DELETE FROM reviews WHERE id = ? AND card_hash = ?",
+            review_id,
+            previous.card_hash
+        )
+        .execute(&mut *tx)
+        .await?;
+        tx.commit().await?;
+        Ok(())
+    }
+
+    pub async fn mark_leech(&self, card: &Card, suspend: bool) -> Result<()> {
+        sqlx::query!(
+            r#"
+            UPDATE cards
+            SET leech = 1, suspended = CASE WHEN ? THEN 1 ELSE suspended END
+            WHERE card_hash = ?
+            "#,
+            suspend,
+            card.card_hash,
+        )
+        .execute(&self.pool)
+        .await?;
+        Ok(())
     }
 
     pub async fn get_card_performance(&self, card: &Card) -> Result<Performance> {
@@ -179,77 +693,315 @@ impl DB {
         Ok(Performance::Reviewed(reviewed))
     }
 
+    /// Scheduler state of every reviewed card, by hash. Cards missing part of their state
+    /// are left out.
+    pub async fn reviewed_performances(&self) -> Result<HashMap<String, ReviewedPerformance>> {
+        let rows = sqlx::query!(
+            r#"
+            SELECT
+                card_hash,
+                last_reviewed_at as "last_reviewed_at?: chrono::DateTime<chrono::Utc>",
+                stability as "stability?: f64",
+                difficulty as "difficulty?: f64",
+                interval_raw as "interval_raw?: f64",
+                interval_days as "interval_days?: i64",
+                due_date as "due_date?: chrono::DateTime<chrono::Utc>",
+                review_count as "review_count!: i64"
+            FROM cards
+            WHERE review_count > 0 AND archived_at IS NULL
+            "#
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(rows
+            .into_iter()
+            .filter_map(|row| {
+                let reviewed = ReviewedPerformance {
+                    last_reviewed_at: row.last_reviewed_at?,
+                    stability: row.stability?,
+                    difficulty: row.difficulty?,
+                    interval_raw: row.interval_raw?,
+                    interval_days: row.interval_days? as usize,
+                    due_date: row.due_date?,
+                    review_count: row.review_count as usize,
+                };
+                Some((row.card_hash, reviewed))
+            })
+            .collect())
+    }
+
+    /// Cards to drill now: those in the learn-ahead window and, for cards on intervals of
+    /// a day or more, everything due before the study day ends at `day_cutoff_hour`.
+    /// `card_limit` caps the already-reviewed cards and `new_card_limit` the never-reviewed
+    /// ones, independently: `Some(0)` leaves that kind out entirely and `None` doesn't
+    /// limit it.
     pub async fn due_today(
         &self,
         card_hashes: &HashMap<String, Card>,
         card_limit: Option<usize>,
         new_card_limit: Option<usize>,
+        order: QueueOrder,
+        day_cutoff_hour: u32,
+    ) -> Result<Vec<Card>> {
+        let cutoff = chrono::Utc::now() + LEARN_AHEAD_THRESHOLD_MINS;
+        self.due_by(
+            card_hashes,
+            card_limit,
+            new_card_limit,
+            cutoff,
+            order,
+            day_cutoff_hour,
+        )
+        .await
+    }
+
+    /// Like [`DB::due_today`], but with cards due up to `cutoff`.
+    pub async fn due_by(
+        &self,
+        card_hashes: &HashMap<String, Card>,
+        card_limit: Option<usize>,
+        new_card_limit: Option<usize>,
+        cutoff: chrono::DateTime<chrono::Utc>,
+        order: QueueOrder,
+        day_cutoff_hour: u32,
     ) -> Result<Vec<Card>> {
-        let now = (chrono::Utc::now() + LEARN_AHEAD_THRESHOLD_MINS).to_rfc3339();
+        let now = cutoff.to_rfc3339();
+        // Cards on day-long intervals are due for the whole study day the cutoff falls on.
+        let day_end =
+            study_day_end(cutoff - LEARN_AHEAD_THRESHOLD_MINS, day_cutoff_hour).to_rfc3339();
 
         // most overdue cards first
         // then cards due today
         // then new cards
         let mut rows = sqlx::query!(
             r#"
-        SELECT card_hash, review_count as "review_count!: i64"
+        SELECT
+            card_hash,
+            review_count as "review_count!: i64",
+            added_at as "added_at!: chrono::DateTime<chrono::Utc>",
+            added_position as "added_position!: i64",
+            due_date as "due_date?: chrono::DateTime<chrono::Utc>",
+            interval_days
         FROM cards
-        WHERE due_date <= ? OR due_date IS NULL
+        WHERE (due_date <= ? OR due_date IS NULL OR (interval_raw >= 1.0 AND due_date < ?))
+            AND suspended = 0
+            AND archived_at IS NULL
         ORDER BY
             CASE WHEN due_date IS NULL THEN 1 ELSE 0 END,
-            due_date ASC
+            due_date ASC,
+            added_at ASC,
+            added_position ASC
         "#,
-            now
+            now,
+            day_end
         )
         .fetch(&self.pool);
 
-        let mut cards: Vec<Card> = Vec::new();
-        let mut num_new_cards = 0;
+        // Each card with when it was added, its place among the cards added with it, and
+        // how overdue it is.
+        type Queued = ((chrono::DateTime<chrono::Utc>, i64), f64, Card);
+        let mut reviews: Vec<Queued> = Vec::new();
+        let mut new_cards: Vec<Queued> = Vec::new();
+        let queued_at = chrono::Utc::now();
+        let review_limit = card_limit.unwrap_or(usize::MAX);
+        let new_limit = new_card_limit.unwrap_or(usize::MAX);
 
         while let Some(row) = rows.try_next().await? {
-            if !card_hashes.contains_key(&row.card_hash) {
-                continue;
+            if reviews.len() >= review_limit && new_cards.len() >= new_limit {
+                break;
             }
-
-            let is_new = row.review_count == 0;
-
-            if is_new
-                && let Some(limit) = new_card_limit
-                && num_new_cards >= limit
-            {
+            let Some(card) = card_hashes.get(&row.card_hash) else {
                 continue;
+            };
+            if row.review_count > 0 {
+                if reviews.len() < review_limit {
+                    let overdue = row.due_date.map_or(0.0, |due_date| {
+                        overdue_ratio(due_date, row.interval_days.unwrap_or(0), queued_at)
+                    });
+                    reviews.push(((row.added_at, row.added_position), overdue, card.clone()));
+                }
+            } else if new_cards.len() < new_limit {
+                new_cards.push(((row.added_at, row.added_position), 0.0, card.clone()));
             }
+        }
 
-            if let Some(card) = card_hashes.get(&row.card_hash) {
-                cards.push(card.clone());
+        let mut queue = reviews;
+        queue.append(&mut new_cards);
+        match order {
+            QueueOrder::Due => {}
+            QueueOrder::Added => queue.sort_by(|(a_added, _, a), (b_added, _, b)| {
+                a_added
+                    .cmp(b_added)
+                    .then_with(|| a.card_hash.cmp(&b.card_hash))
+            }),
+            QueueOrder::Random => {
+                queue.sort_by(|(_, _, a), (_, _, b)| a.card_hash.cmp(&b.card_hash));
+                shuffle(&mut queue, daily_seed(today(day_cutoff_hour)));
+            }
+            // Stable, so new cards (all 0.0) stay last and in due order.
+            QueueOrder::Overdue => {
+                queue.sort_by(|(_, a, _), (_, b, _)| b.total_cmp(a));
+            }
+        }
+        let cards = queue.into_iter().map(|(_, _, card)| card).collect();
+        Ok(cards)
+    }
 
-                if is_new {
-                    num_new_cards += 1;
-                }
+    /// When the first of `card_hashes` that isn't due yet comes due, skipping
+    /// suspended cards.
+    pub async fn next_due(
+        &self,
+        card_hashes: &HashMap<String, Card>,
+    ) -> Result<Option<chrono::DateTime<chrono::Utc>>> {
+        let now = chrono::Utc::now();
+        let mut rows = sqlx::query!(
+            r#"
+            SELECT
+                card_hash,
+                due_date as "due_date!: chrono::DateTime<chrono::Utc>"
+            FROM cards
+            WHERE due_date > ? AND suspended = 0 AND archived_at IS NULL
+            ORDER BY due_date ASC
+            "#,
+            now
+        )
+        .fetch(&self.pool);
 
-                if let Some(limit) = card_limit
-                    && cards.len() >= limit
-                {
-                    break;
-                }
+        while let Some(row) = rows.try_next().await? {
+            if card_hashes.contains_key(&row.card_hash) {
+                return Ok(Some(row.due_date));
             }
         }
+        Ok(None)
+    }
+}
 
-        Ok(cards)
+/// Days past `due_date` as a share of the interval, at least one day, that led to it.
+fn overdue_ratio(
+    due_date: chrono::DateTime<chrono::Utc>,
+    interval_days: i64,
+    now: chrono::DateTime<chrono::Utc>,
+) -> f64 {
+    let days_overdue = (now - due_date).num_seconds() as f64 / 86_400.0;
+    days_overdue.max(0.0) / interval_days.max(1) as f64
+}
+
+/// Seed that stays the same all study day, so a restarted session keeps its order.
+fn daily_seed(day: chrono::NaiveDate) -> u64 {
+    let hash = blake3::hash(day.to_string().as_bytes());
+    u64::from_le_bytes(hash.as_bytes()[..8].try_into().expect("8 bytes"))
+}
+
+/// Fisher-Yates driven by splitmix64.
+fn shuffle<T>(items: &mut [T], seed: u64) {
+    let mut state = seed;
+    let mut next = || {
+        state = state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    };
+    for i in (1..items.len()).rev() {
+        let j = (next() % (i as u64 + 1)) as usize;
+        items.swap(i, j);
     }
 }
 
 #[cfg(test)]
 mod tests {
 
-    use std::collections::HashMap;
-    use std::path::PathBuf;
+    use std::collections::{HashMap, HashSet};
+    use std::path::{Path, PathBuf};
 
-    use crate::fsrs::{Performance, ReviewStatus};
-    use crate::stats::CardLifeCycle;
-    use crate::utils::content_to_card;
+    use crate::fsrs::{Performance, ReviewStatus, SchedulerParams};
+    use crate::stats::{CardLifeCycle, DEFAULT_FORECAST_DAYS};
+    use crate::utils::{
+        DEFAULT_DAY_CUTOFF_HOUR, content_to_card, parse_cards_from_reader, study_day_end,
+    };
 
-    use super::DB;
+    use super::{Card, CardState, DB, QueueOrder, RehashOutcome, shuffle};
+
+    #[tokio::test]
+    async fn rehash_cards_moves_history_to_the_new_hash() {
+        let db = DB::new_in_memory().await.unwrap();
+        let path = PathBuf::from("test.md");
+        let old = content_to_card(&path, "Q: old?\nA: b", "---", 0, 2).unwrap();
+        let new = content_to_card(&path, "Q: new?\nA: b", "---", 0, 2).unwrap();
+        let reviewed_old = content_to_card(&path, "Q: kept?\nA: b", "---", 0, 2).unwrap();
+        let reviewed_new = content_to_card(&path, "Q: taken?\nA: b", "---", 0, 2).unwrap();
+        let params = SchedulerParams::default();
+        let now = chrono::Utc::now();
+        for card in [&old, &new, &reviewed_old, &reviewed_new] {
+            db.add_card(card).await.unwrap();
+        }
+        for card in [&old, &reviewed_old, &reviewed_new] {
+            db.update_card_performance(card, ReviewStatus::Good, Some(now), &params, None)
+                .await
+                .unwrap();
+        }
+        let before = db.get_card_performance(&old).await.unwrap();
+
+        let outcome = db
+            .rehash_cards(&[
+                (old.card_hash.clone(), new.card_hash.clone()),
+                (
+                    reviewed_old.card_hash.clone(),
+                    reviewed_new.card_hash.clone(),
+                ),
+                ("missing".to_string(), "other".to_string()),
+            ])
+            .await
+            .unwrap();
+        assert_eq!(
+            outcome,
+            RehashOutcome {
+                moved: 1,
+                conflicts: 1
+            }
+        );
+        assert_eq!(db.get_card_performance(&new).await.unwrap(), before);
+        assert!(!db.card_exists(&old).await.unwrap());
+        assert!(db.card_exists(&reviewed_old).await.unwrap());
+        let moved_reviews: i64 =
+            sqlx::query_scalar("SELECT COUNT(*) FROM reviews WHERE card_hash = ?")
+                .bind(&new.card_hash)
+                .fetch_one(&db.pool)
+                .await
+                .unwrap();
+        assert_eq!(moved_reviews, 1);
+    }
+
+    #[tokio::test]
+    async fn reset_cards_makes_reviewed_cards_new_again() {
+        let db = DB::new_in_memory().await.unwrap();
+        let card = content_to_card(&PathBuf::from("test.md"), "Q: a?\nA: b", "---", 0, 2).unwrap();
+        db.add_card(&card).await.unwrap();
+        let params = SchedulerParams::default();
+        let now = chrono::Utc::now();
+        db.update_card_performance(&card, ReviewStatus::Good, Some(now), &params, None)
+            .await
+            .unwrap();
+        db.update_card_performance(&card, ReviewStatus::Again, Some(now), &params, None)
+            .await
+            .unwrap();
+        assert_ne!(
+            db.get_card_performance(&card).await.unwrap(),
+            Performance::New
+        );
+
+        db.reset_cards(std::slice::from_ref(&card.card_hash))
+            .await
+            .unwrap();
+        assert_eq!(
+            db.get_card_performance(&card).await.unwrap(),
+            Performance::New
+        );
+        let rows = db.card_rows().await.unwrap();
+        assert_eq!((rows[0].review_count, rows[0].lapses), (0, 0));
+        assert_eq!(db.review_history(&card).await.unwrap().len(), 2);
+    }
 
     #[tokio::test]
     async fn follow_card_progress() {
@@ -258,7 +1010,11 @@ mod tests {
 
         // add card
         let db = DB::new_in_memory().await.unwrap();
-        let card = content_to_card(&card_path, content, 1, 1).unwrap();
+        let params = SchedulerParams {
+            enable_fuzz: false,
+            ..SchedulerParams::default()
+        };
+        let card = content_to_card(&card_path, content, "---", 1, 1).unwrap();
         db.add_card(&card.clone()).await.unwrap();
 
         // should exist
@@ -266,18 +1022,30 @@ mod tests {
 
         // should be in stats
         let card_hashes = HashMap::from([(card.card_hash.clone(), card.clone())]);
-        let stats = db.collection_stats(&card_hashes).await.unwrap();
+        let stats = db
+            .collection_stats(&card_hashes, DEFAULT_FORECAST_DAYS, DEFAULT_DAY_CUTOFF_HOUR)
+            .await
+            .unwrap();
         assert_eq!(stats.num_cards, 1);
         assert_eq!(stats.due_cards, 1);
         assert_eq!(stats.card_lifecycles.get(&CardLifeCycle::New).unwrap(), &1);
 
         // should be due today
-        let due_today_cards = db.due_today(&card_hashes, None, None).await.unwrap();
+        let due_today_cards = db
+            .due_today(
+                &card_hashes,
+                None,
+                None,
+                QueueOrder::Due,
+                DEFAULT_DAY_CUTOFF_HOUR,
+            )
+            .await
+            .unwrap();
         assert_eq!(due_today_cards.len(), 1);
 
         // check short-term scheduling
         for _ in 0..3 {
-            db.update_card_performance(&card, ReviewStatus::Pass, None)
+            db.update_card_performance(&card, ReviewStatus::Good, None, &params, None)
                 .await
                 .unwrap();
         }
@@ -292,37 +1060,41 @@ mod tests {
 
         // wait the interval and then pass again
         let mut future_time = chrono::Utc::now() + chrono::Duration::days(1);
-        db.update_card_performance(&card, ReviewStatus::Pass, Some(future_time))
+        db.update_card_performance(&card, ReviewStatus::Good, Some(future_time), &params, None)
             .await
             .unwrap();
 
         match db.get_card_performance(&card).await.unwrap() {
             Performance::Reviewed(reviewed) => {
                 assert_eq!(reviewed.review_count, 4);
-                assert_eq!(reviewed.interval_raw, 6.0);
+                assert_eq!(reviewed.interval_days, 6);
+                assert_eq!(reviewed.interval_raw.round(), 6.0);
             }
             _ => panic!(),
         }
 
         // now collapse it with a failure
         future_time += chrono::Duration::days(6);
-        db.update_card_performance(&card, ReviewStatus::Fail, Some(future_time))
+        db.update_card_performance(&card, ReviewStatus::Again, Some(future_time), &params, None)
             .await
             .unwrap();
 
         match db.get_card_performance(&card).await.unwrap() {
             Performance::Reviewed(reviewed) => {
                 assert_eq!(reviewed.review_count, 5);
-                assert_eq!(reviewed.interval_raw, 2.0);
+                assert_eq!(reviewed.interval_days, 2);
+                assert_eq!(reviewed.interval_raw.round(), 2.0);
             }
             _ => panic!(),
         }
 
         // another failure
         future_time += chrono::Duration::days(2);
-        db.update_card_performance(&card, ReviewStatus::Fail, Some(future_time))
+        let outcome = db
+            .update_card_performance(&card, ReviewStatus::Again, Some(future_time), &params, None)
             .await
             .unwrap();
+        assert_eq!(outcome.lapses, 2);
 
         match db.get_card_performance(&card).await.unwrap() {
             Performance::Reviewed(reviewed) => {
@@ -331,5 +1103,678 @@ mod tests {
             }
             _ => panic!(),
         }
+
+        let history = db.review_history(&card).await.unwrap();
+        assert_eq!(history.len(), 6);
+        assert!(
+            history
+                .iter()
+                .all(|entry| entry.card_hash == card.card_hash)
+        );
+        assert_eq!(history[0].rating, ReviewStatus::Good);
+        assert_eq!(history[0].elapsed_days, 0.0);
+        assert_eq!(history[0].previous_stability, None);
+        assert_eq!(history[4].rating, ReviewStatus::Again);
+        assert!((history[4].elapsed_days - 6.0).abs() < 1e-3);
+        assert_eq!(history[5].interval_days, 1);
+        assert!(
+            history
+                .windows(2)
+                .all(|pair| pair[0].reviewed_at <= pair[1].reviewed_at)
+        );
+    }
+
+    #[tokio::test]
+    async fn prune_missing_removes_orphaned_cards() {
+        let db = DB::new_in_memory().await.unwrap();
+        let card_path = PathBuf::from("test.md");
+        let kept = content_to_card(&card_path, "Q: kept?\nA: yes", "---", 1, 1).unwrap();
+        let mut removed = content_to_card(&card_path, "Q: removed?\nA: yes", "---", 1, 1).unwrap();
+        removed.tags = vec!["gone".into()];
+        db.add_card(&kept).await.unwrap();
+        db.add_cards_batch(std::slice::from_ref(&removed))
+            .await
+            .unwrap();
+        db.update_card_performance(
+            &removed,
+            ReviewStatus::Good,
+            None,
+            &SchedulerParams::default(),
+            None,
+        )
+        .await
+        .unwrap();
+
+        let current = HashSet::from([kept.card_hash.clone()]);
+        assert_eq!(db.prune_missing(&current).await.unwrap(), 1);
+        assert!(db.card_exists(&kept).await.unwrap());
+        assert!(!db.card_exists(&removed).await.unwrap());
+        assert!(db.review_history(&removed).await.unwrap().is_empty());
+        assert!(db.tagged_hashes(&["gone".into()]).await.unwrap().is_empty());
+
+        assert_eq!(db.prune_missing(&current).await.unwrap(), 0);
+    }
+
+    #[tokio::test]
+    async fn undoing_a_review_restores_the_card_exactly() {
+        let db = DB::new_in_memory().await.unwrap();
+        let card_path = PathBuf::from("test.md");
+        let card = content_to_card(&card_path, "Q: undo?\nA: yes", "---", 1, 1).unwrap();
+        db.add_card(&card).await.unwrap();
+        let params = SchedulerParams {
+            enable_fuzz: false,
+            ..SchedulerParams::default()
+        };
+        let first_review = chrono::Utc::now() - chrono::Duration::days(10);
+        db.update_card_performance(&card, ReviewStatus::Good, Some(first_review), &params, None)
+            .await
+            .unwrap();
+        let before = db.card_schedule(&card).await.unwrap();
+
+        let outcome = db
+            .update_card_performance(&card, ReviewStatus::Again, None, &params, Some(1_500))
+            .await
+            .unwrap();
+        db.mark_leech(&card, true).await.unwrap();
+        assert_eq!(outcome.lapses, 1);
+        assert_ne!(db.card_schedule(&card).await.unwrap(), before);
+
+        db.undo_review(&before, outcome.review_id.unwrap())
+            .await
+            .unwrap();
+        assert_eq!(db.card_schedule(&card).await.unwrap(), before);
+        let history = db.review_history(&card).await.unwrap();
+        assert_eq!(history.len(), 1);
+        assert_eq!(history[0].rating, ReviewStatus::Good);
+    }
+
+    #[tokio::test]
+    async fn archived_cards_keep_their_schedule_until_unarchived() {
+        let db = DB::new_in_memory().await.unwrap();
+        let card_path = PathBuf::from("test.md");
+        let kept = content_to_card(&card_path, "Q: kept?\nA: yes", "---", 1, 1).unwrap();
+        let moved = content_to_card(&card_path, "Q: moved?\nA: yes", "---", 3, 3).unwrap();
+        db.add_cards_batch(&[kept.clone(), moved.clone()])
+            .await
+            .unwrap();
+        let reviewed_at = chrono::Utc::now() - chrono::Duration::days(30);
+        db.update_card_performance(
+            &moved,
+            ReviewStatus::Good,
+            Some(reviewed_at),
+            &SchedulerParams::default(),
+            None,
+        )
+        .await
+        .unwrap();
+        let before = db.get_card_performance(&moved).await.unwrap();
+
+        let current = HashSet::from([kept.card_hash.clone()]);
+        assert_eq!(db.archive_missing(&current).await.unwrap(), 1);
+        assert_eq!(db.archive_missing(&current).await.unwrap(), 0);
+        assert!(db.missing_hashes(&current).await.unwrap().is_empty());
+        assert_eq!(
+            db.archived_hashes().await.unwrap(),
+            HashSet::from([moved.card_hash.clone()])
+        );
+
+        // The file comes back: the card is registered again but stays archived.
+        let both = HashMap::from([
+            (kept.card_hash.clone(), kept.clone()),
+            (moved.card_hash.clone(), moved.clone()),
+        ]);
+        db.add_cards_batch(std::slice::from_ref(&moved))
+            .await
+            .unwrap();
+        let due = db
+            .due_today(&both, None, None, QueueOrder::Due, DEFAULT_DAY_CUTOFF_HOUR)
+            .await
+            .unwrap();
+        let due: Vec<&str> = due.iter().map(|card| card.card_hash.as_str()).collect();
+        assert_eq!(due, vec![kept.card_hash.as_str()]);
+        let stats = db
+            .collection_stats(&both, DEFAULT_FORECAST_DAYS, DEFAULT_DAY_CUTOFF_HOUR)
+            .await
+            .unwrap();
+        assert_eq!(stats.total_cards_in_db, 1);
+
+        db.unarchive_cards(std::slice::from_ref(&moved.card_hash))
+            .await
+            .unwrap();
+        assert!(db.archived_hashes().await.unwrap().is_empty());
+        assert_eq!(db.get_card_performance(&moved).await.unwrap(), before);
+        assert_eq!(db.review_history(&moved).await.unwrap().len(), 1);
+        let due = db
+            .due_today(&both, None, None, QueueOrder::Due, DEFAULT_DAY_CUTOFF_HOUR)
+            .await
+            .unwrap();
+        assert_eq!(due.len(), 2);
+    }
+
+    #[tokio::test]
+    async fn suspended_cards_are_not_due() {
+        let db = DB::new_in_memory().await.unwrap();
+        let card_path = PathBuf::from("test.md");
+        let card = content_to_card(&card_path, "Q: hidden?\nA: yes", "---", 1, 1).unwrap();
+        db.add_card(&card).await.unwrap();
+        let hash_cards = HashMap::from([(card.card_hash.clone(), card.clone())]);
+
+        db.set_suspended(&card, true).await.unwrap();
+        assert!(
+            db.due_today(
+                &hash_cards,
+                None,
+                None,
+                QueueOrder::Due,
+                DEFAULT_DAY_CUTOFF_HOUR,
+            )
+            .await
+            .unwrap()
+            .is_empty()
+        );
+        assert!(
+            db.suspended_hashes()
+                .await
+                .unwrap()
+                .contains(&card.card_hash)
+        );
+
+        db.set_suspended(&card, false).await.unwrap();
+        assert_eq!(
+            db.due_today(
+                &hash_cards,
+                None,
+                None,
+                QueueOrder::Due,
+                DEFAULT_DAY_CUTOFF_HOUR,
+            )
+            .await
+            .unwrap()
+            .len(),
+            1
+        );
+        assert!(db.suspended_hashes().await.unwrap().is_empty());
+    }
+
+    /// `reviewed` overdue cards followed by `new` unseen ones.
+    async fn due_fixture(reviewed: usize, new: usize) -> (DB, HashMap<String, Card>) {
+        let db = DB::new_in_memory().await.unwrap();
+        let card_path = PathBuf::from("test.md");
+        let now = chrono::Utc::now();
+        let mut hash_cards = HashMap::new();
+        let mut states = Vec::new();
+        for i in 0..reviewed + new {
+            let card =
+                content_to_card(&card_path, &format!("Q: {i}?\nA: {i}"), "---", i, i).unwrap();
+            db.add_card(&card).await.unwrap();
+            if i < reviewed {
+                states.push(CardState {
+                    card_hash: card.card_hash.clone(),
+                    added_at: now,
+                    last_reviewed_at: Some(now - chrono::Duration::days(4)),
+                    stability: Some(3.0),
+                    difficulty: Some(5.0),
+                    interval_days: Some(3),
+                    due_date: Some(now - chrono::Duration::days(1)),
+                    review_count: 2,
+                });
+            }
+            hash_cards.insert(card.card_hash.clone(), card);
+        }
+        db.import_card_states(&states, true).await.unwrap();
+        (db, hash_cards)
+    }
+
+    async fn due_counts(
+        db: &DB,
+        hash_cards: &HashMap<String, Card>,
+        card_limit: Option<usize>,
+        new_card_limit: Option<usize>,
+    ) -> (usize, usize) {
+        let due = db.due_today(
+            hash_cards,
+            card_limit,
+            new_card_limit,
+            QueueOrder::Due,
+            DEFAULT_DAY_CUTOFF_HOUR,
+        );
+        let mut counts = (0, 0);
+        for card in due.await.unwrap() {
+            match db.get_card_performance(&card).await.unwrap() {
+                Performance::Reviewed(_) => counts.0 += 1,
+                Performance::New => counts.1 += 1,
+            }
+        }
+        counts
+    }
+
+    #[tokio::test]
+    async fn limits_with_only_new_cards() {
+        let (db, hash_cards) = due_fixture(0, 5).await;
+        assert_eq!(due_counts(&db, &hash_cards, None, Some(3)).await, (0, 3));
+        assert_eq!(due_counts(&db, &hash_cards, Some(2), Some(3)).await, (0, 3));
+        assert_eq!(due_counts(&db, &hash_cards, None, Some(0)).await, (0, 0));
+        assert_eq!(due_counts(&db, &hash_cards, Some(0), None).await, (0, 5));
+    }
+
+    #[tokio::test]
+    async fn limits_with_only_reviews() {
+        let (db, hash_cards) = due_fixture(5, 0).await;
+        assert_eq!(due_counts(&db, &hash_cards, None, Some(0)).await, (5, 0));
+        assert_eq!(due_counts(&db, &hash_cards, Some(3), Some(1)).await, (3, 0));
+        assert_eq!(due_counts(&db, &hash_cards, Some(0), None).await, (0, 0));
+    }
+
+    #[tokio::test]
+    async fn card_and_new_card_limits_are_independent() {
+        let (db, hash_cards) = due_fixture(4, 6).await;
+        assert_eq!(due_counts(&db, &hash_cards, None, None).await, (4, 6));
+        assert_eq!(due_counts(&db, &hash_cards, Some(6), Some(2)).await, (4, 2));
+        assert_eq!(due_counts(&db, &hash_cards, Some(3), Some(2)).await, (3, 2));
+        assert_eq!(due_counts(&db, &hash_cards, Some(3), None).await, (3, 6));
+        // `--card-limit 0`: new cards only.
+        assert_eq!(due_counts(&db, &hash_cards, Some(0), None).await, (0, 6));
+        assert_eq!(due_counts(&db, &hash_cards, Some(0), Some(2)).await, (0, 2));
+        // `--new-card-limit 0`: already-learned cards only.
+        assert_eq!(due_counts(&db, &hash_cards, None, Some(0)).await, (4, 0));
+        assert_eq!(due_counts(&db, &hash_cards, Some(0), Some(0)).await, (0, 0));
+    }
+
+    #[tokio::test]
+    async fn queue_orders_are_stable() {
+        let (db, hash_cards) = due_fixture(3, 5).await;
+        let hashes = |cards: Vec<Card>| -> Vec<String> {
+            cards.into_iter().map(|card| card.card_hash).collect()
+        };
+        let random = hashes(
+            db.due_today(
+                &hash_cards,
+                None,
+                None,
+                QueueOrder::Random,
+                DEFAULT_DAY_CUTOFF_HOUR,
+            )
+            .await
+            .unwrap(),
+        );
+        let again = hashes(
+            db.due_today(
+                &hash_cards,
+                None,
+                None,
+                QueueOrder::Random,
+                DEFAULT_DAY_CUTOFF_HOUR,
+            )
+            .await
+            .unwrap(),
+        );
+        assert_eq!(random, again);
+        assert_eq!(random.len(), 8);
+
+        let due = hashes(
+            db.due_today(
+                &hash_cards,
+                Some(2),
+                Some(2),
+                QueueOrder::Due,
+                DEFAULT_DAY_CUTOFF_HOUR,
+            )
+            .await
+            .unwrap(),
+        );
+        let mut limited_random = hashes(
+            db.due_today(
+                &hash_cards,
+                Some(2),
+                Some(2),
+                QueueOrder::Random,
+                DEFAULT_DAY_CUTOFF_HOUR,
+            )
+            .await
+            .unwrap(),
+        );
+        limited_random.sort();
+        let mut sorted_due = due.clone();
+        sorted_due.sort();
+        assert_eq!(limited_random, sorted_due);
+    }
+
+    #[tokio::test]
+    async fn added_order_introduces_new_cards_as_written() {
+        let db = DB::new_in_memory().await.unwrap();
+        let lesson = (1..=6)
+            .map(|step| format!("Q: step {step}?\nA: {step}"))
+            .collect::<Vec<_>>()
+            .join("\n\n---\n");
+        let (cards, _) =
+            parse_cards_from_reader(Path::new("lesson.md"), lesson.as_bytes(), "---").unwrap();
+        db.add_cards_batch(&cards).await.unwrap();
+        let hash_cards: HashMap<String, Card> = cards
+            .iter()
+            .map(|card| (card.card_hash.clone(), card.clone()))
+            .collect();
+        let written: Vec<String> = cards.iter().map(|card| card.card_hash.clone()).collect();
+
+        let hashes = |cards: Vec<Card>| -> Vec<String> {
+            cards.into_iter().map(|card| card.card_hash).collect()
+        };
+        let added = db.due_today(
+            &hash_cards,
+            None,
+            None,
+            QueueOrder::Added,
+            DEFAULT_DAY_CUTOFF_HOUR,
+        );
+        assert_eq!(hashes(added.await.unwrap()), written);
+        let limited = db.due_today(
+            &hash_cards,
+            None,
+            Some(2),
+            QueueOrder::Added,
+            DEFAULT_DAY_CUTOFF_HOUR,
+        );
+        assert_eq!(hashes(limited.await.unwrap()), written[..2]);
+    }
+
+    #[tokio::test]
+    async fn overdue_order_weighs_lateness_by_interval() {
+        let db = DB::new_in_memory().await.unwrap();
+        let card_path = PathBuf::from("test.md");
+        let now = chrono::Utc::now();
+        let mut hash_cards = HashMap::new();
+        let mut hashes = Vec::new();
+        for name in ["yearly", "daily", "new"] {
+            let card =
+                content_to_card(&card_path, &format!("Q: {name}?\nA: a"), "---", 0, 1).unwrap();
+            db.add_card(&card).await.unwrap();
+            hashes.push(card.card_hash.clone());
+            hash_cards.insert(card.card_hash.clone(), card);
+        }
+        let state = |card_hash: &String, interval: i64, days_late: i64| CardState {
+            card_hash: card_hash.clone(),
+            added_at: now,
+            last_reviewed_at: Some(now - chrono::Duration::days(interval + days_late)),
+            stability: Some(interval as f64),
+            difficulty: Some(5.0),
+            interval_days: Some(interval),
+            due_date: Some(now - chrono::Duration::days(days_late)),
+            review_count: 3,
+        };
+        let states = [state(&hashes[0], 365, 7), state(&hashes[1], 1, 2)];
+        db.import_card_states(&states, true).await.unwrap();
+
+        let order = |cards: Vec<Card>| -> Vec<String> {
+            cards.into_iter().map(|card| card.card_hash).collect()
+        };
+        let due = db.due_today(
+            &hash_cards,
+            None,
+            None,
+            QueueOrder::Due,
+            DEFAULT_DAY_CUTOFF_HOUR,
+        );
+        assert_eq!(order(due.await.unwrap()), hashes);
+        let overdue = db.due_today(
+            &hash_cards,
+            None,
+            None,
+            QueueOrder::Overdue,
+            DEFAULT_DAY_CUTOFF_HOUR,
+        );
+        assert_eq!(
+            order(overdue.await.unwrap()),
+            [hashes[1].clone(), hashes[0].clone(), hashes[2].clone()]
+        );
+    }
+
+    #[tokio::test]
+    async fn day_cutoff_hour_decides_when_day_long_cards_fall_due() {
+        let db = DB::new_in_memory().await.unwrap();
+        let card =
+            content_to_card(&PathBuf::from("test.md"), "Q: when?\nA: now", "---", 0, 1).unwrap();
+        db.add_card(&card).await.unwrap();
+        let hash_cards = HashMap::from([(card.card_hash.clone(), card.clone())]);
+
+        let now = chrono::Utc::now();
+        let mut ends = [0, 12].map(|hour| (study_day_end(now, hour), hour));
+        ends.sort();
+        let [(early_end, early_hour), (_, late_hour)] = ends;
+        // Due after the study day with the earlier end is over, but before the other ends.
+        let due_date = early_end + chrono::Duration::hours(1);
+        let state = CardState {
+            card_hash: card.card_hash.clone(),
+            added_at: now,
+            last_reviewed_at: Some(due_date - chrono::Duration::days(3)),
+            stability: Some(3.0),
+            difficulty: Some(5.0),
+            interval_days: Some(3),
+            due_date: Some(due_date),
+            review_count: 1,
+        };
+        db.import_card_states(&[state], true).await.unwrap();
+
+        let due = |hour| db.due_today(&hash_cards, None, None, QueueOrder::Due, hour);
+        assert_eq!(due(late_hour).await.unwrap().len(), 1);
+        assert!(due(early_hour).await.unwrap().is_empty());
+    }
+
+    #[test]
+    fn shuffle_depends_only_on_the_seed() {
+        let mut a: Vec<u32> = (0..20).collect();
+        let mut b = a.clone();
+        shuffle(&mut a, 7);
+        shuffle(&mut b, 7);
+        assert_eq!(a, b);
+        assert_ne!(a, (0..20).collect::<Vec<_>>());
+        let mut c: Vec<u32> = (0..20).collect();
+        shuffle(&mut c, 8);
+        assert_ne!(a, c);
+    }
+
+    #[tokio::test]
+    async fn mark_leech_optionally_suspends() {
+        let db = DB::new_in_memory().await.unwrap();
+        let card_path = PathBuf::from("test.md");
+        let card = content_to_card(&card_path, "Q: hard?\nA: very", "---", 1, 1).unwrap();
+        db.add_card(&card).await.unwrap();
+
+        db.mark_leech(&card, false).await.unwrap();
+        let rows = db.card_rows().await.unwrap();
+        assert!(rows[0].leech);
+        assert!(!rows[0].suspended);
+
+        db.mark_leech(&card, true).await.unwrap();
+        let rows = db.card_rows().await.unwrap();
+        assert!(rows[0].leech);
+        assert!(rows[0].suspended);
+    }
+
+    #[tokio::test]
+    async fn tags_are_synced_on_registration() {
+        let db = DB::new_in_memory().await.unwrap();
+        let card_path = PathBuf::from("test.md");
+        let mut spanish = content_to_card(&card_path, "Q: perro?\nA: dog", "---", 1, 1).unwrap();
+        spanish.tags = vec!["spanish".into(), "animals".into()];
+        let mut other = content_to_card(&card_path, "Q: 2+2?\nA: 4", "---", 3, 3).unwrap();
+        other.tags = vec!["math".into()];
+        db.add_cards_batch(&[spanish.clone(), other.clone()])
+            .await
+            .unwrap();
+
+        let tagged = db.tagged_hashes(&["spanish".into()]).await.unwrap();
+        assert_eq!(tagged, HashSet::from([spanish.card_hash.clone()]));
+        let tagged = db
+            .tagged_hashes(&["animals".into(), "math".into()])
+            .await
+            .unwrap();
+        assert_eq!(tagged.len(), 2);
+        assert!(db.tagged_hashes(&["nope".into()]).await.unwrap().is_empty());
+
+        spanish.tags.clear();
+        db.add_cards_batch(&[spanish]).await.unwrap();
+        assert!(
+            db.tagged_hashes(&["spanish".into()])
+                .await
+                .unwrap()
+                .is_empty()
+        );
+    }
+
+    #[tokio::test]
+    async fn studying_ahead_uses_actual_elapsed_time() {
+        let db = DB::new_in_memory().await.unwrap();
+        let params = SchedulerParams {
+            enable_fuzz: false,
+            ..SchedulerParams::default()
+        };
+        let card_path = PathBuf::from("test.md");
+        let on_time = content_to_card(&card_path, "Q: on time?\nA: yes", "---", 1, 1).unwrap();
+        let ahead = content_to_card(&card_path, "Q: ahead?\nA: yes", "---", 3, 3).unwrap();
+        let card_hashes = HashMap::from([
+            (on_time.card_hash.clone(), on_time.clone()),
+            (ahead.card_hash.clone(), ahead.clone()),
+        ]);
+        let start = chrono::Utc::now();
+        for card in [&on_time, &ahead] {
+            db.add_card(card).await.unwrap();
+            for _ in 0..3 {
+                db.update_card_performance(card, ReviewStatus::Good, Some(start), &params, None)
+                    .await
+                    .unwrap();
+            }
+        }
+        assert!(
+            db.due_today(
+                &card_hashes,
+                None,
+                None,
+                QueueOrder::Due,
+                DEFAULT_DAY_CUTOFF_HOUR,
+            )
+            .await
+            .unwrap()
+            .is_empty()
+        );
+        let cutoff = start + chrono::Duration::days(2);
+        assert_eq!(
+            db.due_by(
+                &card_hashes,
+                None,
+                None,
+                cutoff,
+                QueueOrder::Due,
+                DEFAULT_DAY_CUTOFF_HOUR,
+            )
+            .await
+            .unwrap()
+            .len(),
+            2
+        );
+
+        let due = start + chrono::Duration::days(1);
+        let on_time_outcome = db
+            .update_card_performance(&on_time, ReviewStatus::Good, Some(due), &params, None)
+            .await
+            .unwrap();
+        let ahead_outcome = db
+            .update_card_performance(
+                &ahead,
+                ReviewStatus::Good,
+                Some(start + chrono::Duration::hours(2)),
+                &params,
+                None,
+            )
+            .await
+            .unwrap();
+        assert!(ahead_outcome.scheduled_days < on_time_outcome.scheduled_days);
+    }
+
+    #[tokio::test]
+    async fn next_due_is_the_earliest_future_registered_card() {
+        let db = DB::new_in_memory().await.unwrap();
+        let card_path = PathBuf::from("test.md");
+        let new = content_to_card(&card_path, "Q: new?\nA: yes", "---", 1, 1).unwrap();
+        db.add_card(&new).await.unwrap();
+        let mut hash_cards = HashMap::from([(new.card_hash.clone(), new.clone())]);
+        assert_eq!(db.next_due(&hash_cards).await.unwrap(), None);
+
+        let now = chrono::Utc::now();
+        let state = |card_hash: &str, hours: i64| CardState {
+            card_hash: card_hash.into(),
+            added_at: now,
+            last_reviewed_at: Some(now),
+            stability: Some(1.0),
+            difficulty: Some(5.0),
+            interval_days: Some(1),
+            due_date: Some(now + chrono::Duration::hours(hours)),
+            review_count: 1,
+        };
+        let soon = content_to_card(&card_path, "Q: soon?\nA: yes", "---", 1, 1).unwrap();
+        let later = content_to_card(&card_path, "Q: later?\nA: yes", "---", 1, 1).unwrap();
+        db.import_card_states(
+            &[
+                state(&soon.card_hash, 3),
+                state(&later.card_hash, 30),
+                state("unregistered", 1),
+            ],
+            true,
+        )
+        .await
+        .unwrap();
+        hash_cards.insert(later.card_hash.clone(), later.clone());
+        let due = db.next_due(&hash_cards).await.unwrap().unwrap();
+        assert!(due > now + chrono::Duration::hours(29));
+
+        hash_cards.insert(soon.card_hash.clone(), soon.clone());
+        db.set_suspended(&later, true).await.unwrap();
+        let due = db.next_due(&hash_cards).await.unwrap().unwrap();
+        assert!(due < now + chrono::Duration::hours(4));
+    }
+
+    #[tokio::test]
+    async fn import_keeps_reviewed_cards_unless_overwriting() {
+        let db = DB::new_in_memory().await.unwrap();
+        let params = SchedulerParams::default();
+        let card_path = PathBuf::from("test.md");
+        let reviewed = content_to_card(&card_path, "Q: kept?\nA: yes", "---", 1, 1).unwrap();
+        db.add_card(&reviewed).await.unwrap();
+        db.update_card_performance(&reviewed, ReviewStatus::Good, None, &params, None)
+            .await
+            .unwrap();
+
+        let now = chrono::Utc::now();
+        let imported = |card_hash: &str| CardState {
+            card_hash: card_hash.into(),
+            added_at: now,
+            last_reviewed_at: Some(now),
+            stability: Some(12.0),
+            difficulty: Some(4.0),
+            interval_days: Some(12),
+            due_date: Some(now + chrono::Duration::days(12)),
+            review_count: 7,
+        };
+        let states = [imported(&reviewed.card_hash), imported("restored")];
+
+        assert_eq!(db.import_card_states(&states, false).await.unwrap(), 1);
+        async fn by_hash(db: &DB, hash: &str) -> CardState {
+            db.card_states()
+                .await
+                .unwrap()
+                .into_iter()
+                .find(|state| state.card_hash == hash)
+                .unwrap()
+        }
+        assert_eq!(by_hash(&db, &reviewed.card_hash).await.review_count, 1);
+        assert_eq!(by_hash(&db, "restored").await.review_count, 7);
+
+        assert_eq!(db.import_card_states(&states, true).await.unwrap(), 2);
+        assert_eq!(by_hash(&db, &reviewed.card_hash).await.review_count, 7);
+        match db.get_card_performance(&reviewed).await.unwrap() {
+            Performance::Reviewed(performance) => {
+                assert_eq!(performance.stability, 12.0);
+                assert_eq!(performance.interval_raw, 12.0);
+            }
+            Performance::New => panic!("imported card should be reviewed"),
+        }
     }
 }
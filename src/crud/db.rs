@@ -1,11 +1,20 @@
 use anyhow::Result;
-use directories::ProjectDirs;
 use sqlx::SqlitePool;
-use sqlx::sqlite::{SqliteConnectOptions, SqlitePoolOptions};
+use sqlx::migrate::Migrator;
+use sqlx::sqlite::{SqliteConnectOptions, SqliteJournalMode, SqlitePoolOptions, SqliteSynchronous};
 
-use std::str::FromStr;
+use std::env;
+use std::ffi::OsString;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use crate::profile;
 
-use anyhow::anyhow;
+pub const DB_PATH_ENV: &str = "REPEATER_DB";
+
+/// Ordered `migrations/NNNN_*.sql` steps. Applied versions are recorded in
+/// `_sqlx_migrations`, so opening an older database upgrades it in place.
+static MIGRATOR: Migrator = sqlx::migrate!("./migrations");
 
 #[derive(Clone)]
 pub struct DB {
@@ -13,31 +22,127 @@ pub struct DB {
 }
 
 impl DB {
-    pub async fn new() -> Result<Self> {
-        let proj_dirs = ProjectDirs::from("", "", "repeater")
-            .ok_or_else(|| anyhow!("Could not determine project directory"))?;
+    /// Directory holding `cards.db` and other local state for the selected profile, created if missing.
+    pub fn data_dir() -> Result<PathBuf> {
+        let proj_dirs = profile::project_dirs()?;
 
         let data_dir = proj_dirs.data_dir();
         std::fs::create_dir_all(data_dir)?;
+        Ok(data_dir.to_path_buf())
+    }
 
-        let db_path = data_dir.join("cards.db");
+    pub async fn new() -> Result<Self> {
+        Self::open(None).await
+    }
 
-        let options =
-            SqliteConnectOptions::from_str(&db_path.to_string_lossy())?.create_if_missing(true);
+    /// Opens the database at `path`, falling back to `REPEATER_DB` and then to
+    /// `cards.db` in the data directory.
+    pub async fn open(path: Option<&Path>) -> Result<Self> {
+        let db_path = Self::resolve_path(path, env::var_os(DB_PATH_ENV))?;
+        if let Some(parent) = db_path.parent()
+            && !parent.as_os_str().is_empty()
+        {
+            std::fs::create_dir_all(parent)?;
+        }
+
+        // WAL lets `watch` keep registering cards while a drill writes reviews; its
+        // `-wal` and `-shm` files sit next to the database until `close` checkpoints them.
+        let options = SqliteConnectOptions::new()
+            .filename(&db_path)
+            .create_if_missing(true)
+            .journal_mode(SqliteJournalMode::Wal)
+            .synchronous(SqliteSynchronous::Normal)
+            .foreign_keys(true)
+            .busy_timeout(Duration::from_secs(5));
 
         Self::connect(options).await
     }
+
+    fn resolve_path(flag: Option<&Path>, env_path: Option<OsString>) -> Result<PathBuf> {
+        if let Some(path) = flag {
+            return Ok(path.to_path_buf());
+        }
+        match env_path {
+            Some(path) if !path.is_empty() => Ok(PathBuf::from(path)),
+            _ => Ok(Self::data_dir()?.join("cards.db")),
+        }
+    }
     async fn connect(options: SqliteConnectOptions) -> Result<Self> {
         let pool = SqlitePoolOptions::new()
             .max_connections(5)
             .connect_with(options)
             .await?;
 
-        sqlx::migrate!("./migrations").run(&pool).await?;
+        MIGRATOR.run(&pool).await?;
         Ok(Self { pool })
     }
+
+    /// Closes every connection, which checkpoints the WAL into the database file and
+    /// removes the `-wal` and `-shm` files.
+    pub async fn close(self) {
+        // A connection dropped just before this is handed back to the pool by a spawned
+        // task, and `SqlitePool::close` can miss one that arrives while it waits.
+        let _ = tokio::time::timeout(Duration::from_secs(1), async {
+            while self.pool.num_idle() < self.pool.size() as usize {
+                tokio::task::yield_now().await;
+            }
+        })
+        .await;
+        self.pool.close().await;
+    }
+
+    /// Latest migration version applied to this database.
+    pub async fn schema_version(&self) -> Result<i64> {
+        // `_sqlx_migrations` is owned by sqlx, so it isn't part of the offline query cache.
+        let version: i64 = sqlx::query_scalar(
+            "SELECT COALESCE(MAX(version), 0) FROM _sqlx_migrations WHERE success = 1",
+        )
+        .fetch_one(&self.pool)
+        .await?;
+        Ok(version)
+    }
+
+    /// Path of the main database file, or `None` for an in-memory database.
+    pub async fn file_path(&self) -> Result<Option<PathBuf>> {
+        let file: String =
+            sqlx::query_scalar("SELECT file FROM pragma_database_list WHERE name = 'main'")
+                .fetch_one(&self.pool)
+                .await?;
+        Ok((!file.is_empty()).then(|| PathBuf::from(file)))
+    }
+
+    /// Rebuilds the file to reclaim free pages, then refreshes the query planner statistics.
+    pub async fn vacuum(&self) -> Result<()> {
+        sqlx::query("VACUUM").execute(&self.pool).await?;
+        // Otherwise the rebuilt pages wait in the WAL and the file doesn't shrink yet.
+        sqlx::query("PRAGMA wal_checkpoint(TRUNCATE)")
+            .execute(&self.pool)
+            .await?;
+        sqlx::query("PRAGMA optimize").execute(&self.pool).await?;
+        Ok(())
+    }
+
+    /// Problems reported by `PRAGMA integrity_check`; empty when the database is healthy.
+    pub async fn integrity_check(&self) -> Result<Vec<String>> {
+        let rows: Vec<String> = sqlx::query_scalar("PRAGMA integrity_check")
+            .fetch_all(&self.pool)
+            .await?;
+        Ok(rows.into_iter().filter(|row| row != "ok").collect())
+    }
+
+    /// Version of the newest migration bundled with this build.
+    pub fn latest_schema_version() -> i64 {
+        MIGRATOR
+            .iter()
+            .map(|migration| migration.version)
+            .max()
+            .unwrap_or(0)
+    }
 }
 
+#[cfg(test)]
+use std::str::FromStr;
+
 #[cfg(test)]
 impl DB {
     pub async fn new_in_memory() -> Result<Self> {
@@ -45,3 +150,124 @@ impl DB {
         Self::connect(options).await
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::borrow::Cow;
+    use std::str::FromStr;
+
+    use sqlx::sqlite::{SqliteConnectOptions, SqlitePoolOptions};
+
+    use std::ffi::OsString;
+    use std::path::{Path, PathBuf};
+
+    use super::{DB, MIGRATOR};
+
+    #[test]
+    fn flag_takes_precedence_over_env() {
+        let resolved =
+            DB::resolve_path(Some(Path::new("flag.db")), Some(OsString::from("env.db"))).unwrap();
+        assert_eq!(resolved, PathBuf::from("flag.db"));
+        let resolved = DB::resolve_path(None, Some(OsString::from("env.db"))).unwrap();
+        assert_eq!(resolved, PathBuf::from("env.db"));
+    }
+
+    #[tokio::test]
+    async fn vacuums_and_checks_a_file_database() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("cards.db");
+        let db = DB::open(Some(&path)).await.unwrap();
+        assert_eq!(
+            db.file_path().await.unwrap().unwrap().file_name(),
+            path.file_name()
+        );
+        db.vacuum().await.unwrap();
+        assert!(db.integrity_check().await.unwrap().is_empty());
+
+        let memory = DB::new_in_memory().await.unwrap();
+        assert_eq!(memory.file_path().await.unwrap(), None);
+    }
+
+    #[tokio::test]
+    async fn uses_wal_and_cleans_it_up_on_close() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("cards.db");
+        let db = DB::open(Some(&path)).await.unwrap();
+        let journal_mode: String = sqlx::query_scalar("PRAGMA journal_mode")
+            .fetch_one(&db.pool)
+            .await
+            .unwrap();
+        assert_eq!(journal_mode, "wal");
+        let foreign_keys: i64 = sqlx::query_scalar("PRAGMA foreign_keys")
+            .fetch_one(&db.pool)
+            .await
+            .unwrap();
+        assert_eq!(foreign_keys, 1);
+        assert!(dir.path().join("cards.db-wal").exists());
+
+        db.close().await;
+        assert!(!dir.path().join("cards.db-wal").exists());
+        assert!(!dir.path().join("cards.db-shm").exists());
+    }
+
+    #[tokio::test]
+    async fn opens_a_database_at_a_custom_path() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("nested").join("portable.db");
+        let db = DB::open(Some(&path)).await.unwrap();
+        assert!(path.is_file());
+        assert_eq!(
+            db.schema_version().await.unwrap(),
+            DB::latest_schema_version()
+        );
+    }
+
+    #[tokio::test]
+    async fn in_memory_db_is_fully_migrated() {
+        let db = DB::new_in_memory().await.unwrap();
+        assert_eq!(
+            db.schema_version().await.unwrap(),
+            DB::latest_schema_version()
+        );
+    }
+
+    #[tokio::test]
+    async fn upgrades_old_database_in_place() {
+        let dir = tempfile::tempdir().unwrap();
+        let options =
+            SqliteConnectOptions::from_str(&dir.path().join("cards.db").to_string_lossy())
+                .unwrap()
+                .create_if_missing(true);
+
+        // Simulate a database created by a release that only shipped the first migration.
+        let pool = SqlitePoolOptions::new()
+            .connect_with(options.clone())
+            .await
+            .unwrap();
+        let mut first_only = sqlx::migrate!("./migrations");
+        first_only.migrations = Cow::Owned(first_only.migrations[..1].to_vec());
+        first_only.run(&pool).await.unwrap();
+        sqlx::query(
+            "INSERT INTO cards (card_hash, added_at, stability, review_count) \
+             VALUES ('abc', '2024-01-01T00:00:00+00:00', 4.5, 3)",
+        )
+        .execute(&pool)
+        .await
+        .unwrap();
+        pool.close().await;
+
+        let db = DB::connect(options).await.unwrap();
+        assert_eq!(
+            db.schema_version().await.unwrap(),
+            DB::latest_schema_version()
+        );
+        let (stability, review_count): (f64, i64) =
+            sqlx::query_as("SELECT stability, review_count FROM cards WHERE card_hash = 'abc'")
+                .fetch_one(&db.pool)
+                .await
+                .unwrap();
+        assert_eq!(stability, 4.5);
+        assert_eq!(review_count, 3);
+        assert!(MIGRATOR.iter().count() > 1);
+    }
+}
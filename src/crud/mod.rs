@@ -1,7 +1,11 @@
 pub mod cards;
 pub mod db;
+pub mod reviews;
+pub mod sessions;
 pub mod stats;
 pub mod version;
 
 pub use crate::check_version::VersionUpdateStats;
 pub use db::DB;
+pub use reviews::{RetentionCounts, ReviewLogEntry, ReviewPeriod};
+pub use sessions::{GradeCounts, SessionRecord};
@@ -0,0 +1,498 @@
+use std::collections::{BTreeMap, HashSet};
+
+use anyhow::{Result, anyhow};
+use serde::Serialize;
+use sqlx::SqliteConnection;
+
+use crate::card::Card;
+use crate::fsrs::ReviewStatus;
+use crate::utils::{day_start, study_day, today};
+
+use super::DB;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct ReviewLogEntry {
+    pub card_hash: String,
+    pub reviewed_at: chrono::DateTime<chrono::Utc>,
+    pub rating: ReviewStatus,
+    pub elapsed_days: f64,
+    pub previous_stability: Option<f64>,
+    pub new_stability: f64,
+    pub interval_days: i64,
+    /// Time from showing the card to grading it, when the review came from a drill.
+    pub duration_ms: Option<i64>,
+}
+
+/// Pass/fail counts for reviews of cards that were already out of learning.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize)]
+pub struct RetentionCounts {
+    pub passed: i64,
+    pub failed: i64,
+}
+
+impl RetentionCounts {
+    pub fn rate(&self) -> Option<f64> {
+        let total = self.passed + self.failed;
+        (total > 0).then(|| self.passed as f64 / total as f64)
+    }
+}
+
+impl DB {
+    /// True retention over `period`: reviews at least a day after the previous one,
+    /// counting `Again` as a fail and every other grade as a pass.
+    pub async fn true_retention(
+        &self,
+        period: ReviewPeriod,
+        day_cutoff_hour: u32,
+    ) -> Result<RetentionCounts> {
+        let (since, until) = (period.since(day_cutoff_hour), period.until(day_cutoff_hour));
+        let row = sqlx::query!(
+            r#"
+            SELECT
+                COALESCE(SUM(CASE WHEN rating > 1 THEN 1 ELSE 0 END), 0) as "passed!: i64",
+                COALESCE(SUM(CASE WHEN rating = 1 THEN 1 ELSE 0 END), 0) as "failed!: i64"
+            FROM reviews
+            WHERE reviewed_at >= ? AND reviewed_at < ?
+                AND previous_stability IS NOT NULL
+                AND elapsed_days >= 1.0
+            "#,
+            since,
+            until
+        )
+        .fetch_one(&self.pool)
+        .await?;
+
+        Ok(RetentionCounts {
+            passed: row.passed,
+            failed: row.failed,
+        })
+    }
+
+    pub async fn record_review(&self, entry: &ReviewLogEntry) -> Result<()> {
+        let mut conn = self.pool.acquire().await?;
+        insert_review(&mut conn, entry).await?;
+        Ok(())
+    }
+
+    pub async fn review_history(&self, card: &Card) -> Result<Vec<ReviewLogEntry>> {
+        let rows = sqlx::query_as!(
+            ReviewRow,
+            r#"
+            SELECT
+                card_hash,
+                reviewed_at as "reviewed_at!: chrono::DateTime<chrono::Utc>",
+                rating as "rating!: i64",
+                elapsed_days as "elapsed_days!: f64",
+                previous_stability as "previous_stability?: f64",
+                new_stability as "new_stability!: f64",
+                interval_days as "interval_days!: i64",
+                duration_ms as "duration_ms?: i64"
+            FROM reviews
+            WHERE card_hash = ?
+            ORDER BY reviewed_at ASC, id ASC
+            "#,
+            card.card_hash
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        rows.into_iter().map(ReviewLogEntry::try_from).collect()
+    }
+
+    /// Number of reviews on each study day of `period`.
+    pub async fn reviews_per_day(
+        &self,
+        period: ReviewPeriod,
+        day_cutoff_hour: u32,
+    ) -> Result<BTreeMap<chrono::NaiveDate, usize>> {
+        let (since, until) = (period.since(day_cutoff_hour), period.until(day_cutoff_hour));
+        let reviewed_at = sqlx::query_scalar!(
+            r#"
+            SELECT reviewed_at as "reviewed_at!: chrono::DateTime<chrono::Utc>"
+            FROM reviews
+            WHERE reviewed_at >= ? AND reviewed_at < ?
+            "#,
+            since,
+            until
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        let mut per_day = BTreeMap::new();
+        for reviewed_at in reviewed_at {
+            if let Some(day) = period.day_of(reviewed_at, day_cutoff_hour) {
+                *per_day.entry(day).or_insert(0) += 1;
+            }
+        }
+        Ok(per_day)
+    }
+
+    /// Logged answer times in milliseconds on each study day of `period`. Reviews
+    /// recorded without a duration are left out.
+    pub async fn review_durations_per_day(
+        &self,
+        period: ReviewPeriod,
+        day_cutoff_hour: u32,
+    ) -> Result<BTreeMap<chrono::NaiveDate, Vec<i64>>> {
+        let (since, until) = (period.since(day_cutoff_hour), period.until(day_cutoff_hour));
+        let rows = sqlx::query!(
+            r#"
+            SELECT
+                reviewed_at as "reviewed_at!: chrono::DateTime<chrono::Utc>",
+                duration_ms as "duration_ms!: i64"
+            FROM reviews
+            WHERE reviewed_at >= ? AND reviewed_at < ? AND duration_ms IS NOT NULL
+            "#,
+            since,
+            until
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        let mut per_day: BTreeMap<chrono::NaiveDate, Vec<i64>> = BTreeMap::new();
+        for row in rows {
+            if let Some(day) = period.day_of(row.reviewed_at, day_cutoff_hour) {
+                per_day.entry(day).or_default().push(row.duration_ms);
+            }
+        }
+        Ok(per_day)
+    }
+
+    /// Every logged review, grouped by card and in the order they happened.
+    pub async fn all_reviews(&self) -> Result<Vec<ReviewLogEntry>> {
+        let rows = sqlx::query_as!(
+            ReviewRow,
+            r#"
+            SELECT
+                card_hash,
+                reviewed_at as "reviewed_at!: chrono::DateTime<chrono::Utc>",
+                rating as "rating!: i64",
+                elapsed_days as "elapsed_days!: f64",
+                previous_stability as "previous_stability?: f64",
+                new_stability as "new_stability!: f64",
+                interval_days as "interval_days!: i64",
+                duration_ms as "duration_ms?: i64"
+            FROM reviews
+            ORDER BY card_hash ASC, reviewed_at ASC, id ASC
+            "#
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        rows.into_iter().map(ReviewLogEntry::try_from).collect()
+    }
+
+    /// Hashes of the cards reviewed at or after `since`.
+    pub async fn reviewed_since(
+        &self,
+        since: chrono::DateTime<chrono::Utc>,
+    ) -> Result<HashSet<String>> {
+        let rows = sqlx::query!(
+            r#"
+            SELECT DISTINCT card_hash as "card_hash!: String"
+            FROM reviews
+            WHERE reviewed_at >= ?
+            "#,
+            since
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(rows.into_iter().map(|row| row.card_hash).collect())
+    }
+}
+
+/// Study days from `first` to `last`, both included, that metrics taken from the review
+/// log cover.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+pub struct ReviewPeriod {
+    pub first: chrono::NaiveDate,
+    pub last: chrono::NaiveDate,
+}
+
+impl ReviewPeriod {
+    /// The last `days` study days, today included.
+    pub fn last_days(days: i64, cutoff_hour: u32) -> Self {
+        Self::ending(today(cutoff_hour), days)
+    }
+
+    pub fn ending(last: chrono::NaiveDate, days: i64) -> Self {
+        Self {
+            first: last - chrono::Duration::days(days - 1),
+            last,
+        }
+    }
+
+    pub fn days(&self) -> i64 {
+        (self.last - self.first).num_days() + 1
+    }
+
+    /// `last 30 days` when the period ends `today`, otherwise its first and last day.
+    pub fn describe(&self, today: chrono::NaiveDate) -> String {
+        if self.last == today {
+            format!("last {} days", self.days())
+        } else {
+            format!("{} to {}", self.first, self.last)
+        }
+    }
+
+    fn since(&self, cutoff_hour: u32) -> chrono::DateTime<chrono::Utc> {
+        day_start(self.first, cutoff_hour)
+    }
+
+    fn until(&self, cutoff_hour: u32) -> chrono::DateTime<chrono::Utc> {
+        day_start(self.last + chrono::Duration::days(1), cutoff_hour)
+    }
+
+    fn day_of(
+        &self,
+        at: chrono::DateTime<chrono::Utc>,
+        cutoff_hour: u32,
+    ) -> Option<chrono::NaiveDate> {
+        let day = study_day(at, cutoff_hour);
+        (self.first..=self.last).contains(&day).then_some(day)
+    }
+}
+
+struct ReviewRow {
+    card_hash: String,
+    reviewed_at: chrono::DateTime<chrono::Utc>,
+    rating: i64,
+    elapsed_days: f64,
+    previous_stability: Option<f64>,
+    new_stability: f64,
+    interval_days: i64,
+    duration_ms: Option<i64>,
+}
+
+impl TryFrom<ReviewRow> for ReviewLogEntry {
+    type Error = anyhow::Error;
+
+    fn try_from(row: ReviewRow) -> Result<Self> {
+        let rating = ReviewStatus::from_score(row.rating)
+            .ok_or_else(|| anyhow!("invalid rating {} in review log", row.rating))?;
+        Ok(ReviewLogEntry {
+            card_hash: row.card_hash,
+            reviewed_at: row.reviewed_at,
+            rating,
+            elapsed_days: row.elapsed_days,
+            previous_stability: row.previous_stability,
+            new_stability: row.new_stability,
+            interval_days: row.interval_days,
+            duration_ms: row.duration_ms,
+        })
+    }
+}
+
+/// Logs `entry` and returns the id of its row.
+pub(super) async fn insert_review(
+    conn: &mut SqliteConnection,
+    entry: &ReviewLogEntry,
+) -> Result<i64> {
+    let rating = entry.rating.score() as i64;
+    let result = sqlx::query!(
+        r#"
+        INSERT INTO reviews (
+            card_hash,
+            reviewed_at,
+            rating,
+            elapsed_days,
+            previous_stability,
+            new_stability,
+            interval_days,
+            duration_ms
+        )
+        VALUES (?, ?, ?, ?, ?, ?, ?, ?)
+        "#,
+        entry.card_hash,
+        entry.reviewed_at,
+        rating,
+        entry.elapsed_days,
+        entry.previous_stability,
+        entry.new_stability,
+        entry.interval_days,
+        entry.duration_ms,
+    )
+    .execute(&mut *conn)
+    .await?;
+
+    Ok(result.last_insert_rowid())
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{Duration, Utc};
+
+    use super::*;
+    use crate::utils::DEFAULT_DAY_CUTOFF_HOUR;
+
+    fn entry(rating: ReviewStatus, elapsed_days: f64, days_ago: i64) -> ReviewLogEntry {
+        ReviewLogEntry {
+            card_hash: "hash".into(),
+            reviewed_at: Utc::now() - Duration::days(days_ago),
+            rating,
+            elapsed_days,
+            previous_stability: Some(3.0),
+            new_stability: 5.0,
+            interval_days: 4,
+            duration_ms: None,
+        }
+    }
+
+    /// A database holding the card `entry` logs reviews for, which the foreign key needs.
+    async fn db_with_card() -> DB {
+        let db = DB::new_in_memory().await.unwrap();
+        sqlx::query("INSERT INTO cards (card_hash, added_at, review_count) VALUES ('hash', ?, 1)")
+            .bind(Utc::now())
+            .execute(&db.pool)
+            .await
+            .unwrap();
+        db
+    }
+
+    #[tokio::test]
+    async fn true_retention_counts_recent_review_state_grades() {
+        let db = db_with_card().await;
+        for review in [
+            entry(ReviewStatus::Good, 3.0, 1),
+            entry(ReviewStatus::Easy, 5.0, 2),
+            entry(ReviewStatus::Hard, 2.0, 3),
+            entry(ReviewStatus::Again, 4.0, 4),
+            // learning step, same day
+            entry(ReviewStatus::Again, 0.01, 1),
+            // outside the window
+            entry(ReviewStatus::Again, 6.0, 45),
+            ReviewLogEntry {
+                previous_stability: None,
+                ..entry(ReviewStatus::Good, 0.0, 1)
+            },
+        ] {
+            db.record_review(&review).await.unwrap();
+        }
+
+        let counts = db
+            .true_retention(
+                ReviewPeriod::last_days(30, DEFAULT_DAY_CUTOFF_HOUR),
+                DEFAULT_DAY_CUTOFF_HOUR,
+            )
+            .await
+            .unwrap();
+        assert_eq!(
+            counts,
+            RetentionCounts {
+                passed: 3,
+                failed: 1
+            }
+        );
+        assert_eq!(counts.rate(), Some(0.75));
+        assert_eq!(RetentionCounts::default().rate(), None);
+    }
+
+    #[tokio::test]
+    async fn counts_reviews_per_local_day() {
+        let db = db_with_card().await;
+        let now = Utc::now();
+        for reviewed_at in [now, now, now - Duration::days(3), now - Duration::days(400)] {
+            db.record_review(&ReviewLogEntry {
+                reviewed_at,
+                ..entry(ReviewStatus::Good, 1.0, 0)
+            })
+            .await
+            .unwrap();
+        }
+
+        let per_day = db
+            .reviews_per_day(
+                ReviewPeriod::last_days(365, DEFAULT_DAY_CUTOFF_HOUR),
+                DEFAULT_DAY_CUTOFF_HOUR,
+            )
+            .await
+            .unwrap();
+        assert_eq!(per_day.values().sum::<usize>(), 3);
+        assert_eq!(per_day[&study_day(now, DEFAULT_DAY_CUTOFF_HOUR)], 2);
+        assert_eq!(
+            per_day[&study_day(now - Duration::days(3), DEFAULT_DAY_CUTOFF_HOUR)],
+            1
+        );
+    }
+
+    #[tokio::test]
+    async fn groups_logged_answer_times_by_day() {
+        let db = db_with_card().await;
+        for duration_ms in [Some(4_000), Some(6_000), None] {
+            db.record_review(&ReviewLogEntry {
+                duration_ms,
+                ..entry(ReviewStatus::Good, 1.0, 0)
+            })
+            .await
+            .unwrap();
+        }
+
+        let per_day = db
+            .review_durations_per_day(
+                ReviewPeriod::last_days(7, DEFAULT_DAY_CUTOFF_HOUR),
+                DEFAULT_DAY_CUTOFF_HOUR,
+            )
+            .await
+            .unwrap();
+        let today = today(DEFAULT_DAY_CUTOFF_HOUR);
+        assert_eq!(per_day.len(), 1);
+        assert_eq!(per_day[&today], vec![4_000, 6_000]);
+    }
+
+    #[tokio::test]
+    async fn periods_leave_out_reviews_after_their_last_day() {
+        let db = db_with_card().await;
+        let now = Utc::now();
+        for days_ago in [0, 2, 2, 5] {
+            db.record_review(&ReviewLogEntry {
+                reviewed_at: now - Duration::days(days_ago),
+                ..entry(ReviewStatus::Good, 3.0, 0)
+            })
+            .await
+            .unwrap();
+        }
+
+        let period = ReviewPeriod::ending(
+            study_day(now - Duration::days(2), DEFAULT_DAY_CUTOFF_HOUR),
+            2,
+        );
+        assert_eq!(period.days(), 2);
+        let per_day = db
+            .reviews_per_day(period, DEFAULT_DAY_CUTOFF_HOUR)
+            .await
+            .unwrap();
+        assert_eq!(per_day.values().sum::<usize>(), 2);
+        assert_eq!(
+            db.true_retention(period, DEFAULT_DAY_CUTOFF_HOUR)
+                .await
+                .unwrap()
+                .passed,
+            2
+        );
+        assert_eq!(period.describe(period.last), "last 2 days",);
+        assert_eq!(
+            period.describe(today(DEFAULT_DAY_CUTOFF_HOUR) + Duration::days(1)),
+            format!("{} to {}", period.first, period.last)
+        );
+    }
+
+    #[tokio::test]
+    async fn reviews_need_a_card_and_go_with_it() {
+        let db = db_with_card().await;
+        let orphan = ReviewLogEntry {
+            card_hash: "missing".into(),
+            ..entry(ReviewStatus::Good, 1.0, 0)
+        };
+        assert!(db.record_review(&orphan).await.is_err());
+
+        db.record_review(&entry(ReviewStatus::Good, 1.0, 0))
+            .await
+            .unwrap();
+        sqlx::query("DELETE FROM cards WHERE card_hash = 'hash'")
+            .execute(&db.pool)
+            .await
+            .unwrap();
+        assert!(db.all_reviews().await.unwrap().is_empty());
+    }
+}
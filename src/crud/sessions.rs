@@ -0,0 +1,147 @@
+use anyhow::Result;
+use serde::Serialize;
+
+use crate::fsrs::ReviewStatus;
+
+use super::DB;
+
+/// How many answers of each grade a drill session gave, requeued cards included.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize)]
+pub struct GradeCounts {
+    pub again: i64,
+    pub hard: i64,
+    pub good: i64,
+    pub easy: i64,
+}
+
+impl GradeCounts {
+    pub fn add(&mut self, status: ReviewStatus) {
+        *self.count_mut(status) += 1;
+    }
+
+    pub fn count(&self, status: ReviewStatus) -> i64 {
+        match status {
+            ReviewStatus::Again => self.again,
+            ReviewStatus::Hard => self.hard,
+            ReviewStatus::Good => self.good,
+            ReviewStatus::Easy => self.easy,
+        }
+    }
+
+    fn count_mut(&mut self, status: ReviewStatus) -> &mut i64 {
+        match status {
+            ReviewStatus::Again => &mut self.again,
+            ReviewStatus::Hard => &mut self.hard,
+            ReviewStatus::Good => &mut self.good,
+            ReviewStatus::Easy => &mut self.easy,
+        }
+    }
+
+    pub fn total(&self) -> i64 {
+        self.again + self.hard + self.good + self.easy
+    }
+
+    /// Share of answers that weren't `Again`.
+    pub fn retention(&self) -> Option<f64> {
+        let total = self.total();
+        (total > 0).then(|| (total - self.again) as f64 / total as f64)
+    }
+}
+
+/// One finished drill session.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct SessionRecord {
+    pub ended_at: chrono::DateTime<chrono::Utc>,
+    pub grades: GradeCounts,
+    pub duration_ms: i64,
+}
+
+impl DB {
+    pub async fn record_session(&self, session: &SessionRecord) -> Result<()> {
+        sqlx::query!(
+            r#"
+            INSERT INTO sessions (ended_at, again, hard, good, easy, duration_ms)
+            VALUES (?, ?, ?, ?, ?, ?)
+            "#,
+            session.ended_at,
+            session.grades.again,
+            session.grades.hard,
+            session.grades.good,
+            session.grades.easy,
+            session.duration_ms,
+        )
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    /// The latest `limit` sessions, oldest first.
+    pub async fn session_history(&self, limit: i64) -> Result<Vec<SessionRecord>> {
+        let rows = sqlx::query!(
+            r#"
+            SELECT
+                ended_at as "ended_at!: chrono::DateTime<chrono::Utc>",
+                again as "again!: i64",
+                hard as "hard!: i64",
+                good as "good!: i64",
+                easy as "easy!: i64",
+                duration_ms as "duration_ms!: i64"
+            FROM sessions
+            ORDER BY ended_at DESC, id DESC
+            LIMIT ?
+            "#,
+            limit
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(rows
+            .into_iter()
+            .rev()
+            .map(|row| SessionRecord {
+                ended_at: row.ended_at,
+                grades: GradeCounts {
+                    again: row.again,
+                    hard: row.hard,
+                    good: row.good,
+                    easy: row.easy,
+                },
+                duration_ms: row.duration_ms,
+            })
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{Duration, Utc};
+
+    use super::*;
+
+    #[tokio::test]
+    async fn sessions_come_back_oldest_first() {
+        let db = DB::new_in_memory().await.unwrap();
+        let mut grades = GradeCounts::default();
+        for status in [ReviewStatus::Again, ReviewStatus::Good, ReviewStatus::Good] {
+            grades.add(status);
+        }
+        assert_eq!(grades.count(ReviewStatus::Good), 2);
+        assert_eq!(grades.retention(), Some(2.0 / 3.0));
+        assert_eq!(GradeCounts::default().retention(), None);
+
+        let now = Utc::now();
+        for days_ago in [3, 1, 2] {
+            db.record_session(&SessionRecord {
+                ended_at: now - Duration::days(days_ago),
+                grades,
+                duration_ms: days_ago * 1000,
+            })
+            .await
+            .unwrap();
+        }
+        let history = db.session_history(2).await.unwrap();
+        let durations: Vec<i64> = history.iter().map(|session| session.duration_ms).collect();
+        assert_eq!(durations, [2000, 1000]);
+        assert_eq!(history[0].grades, grades);
+    }
+}
@@ -17,12 +17,20 @@ pub struct CardStatsRow {
     pub difficulty: Option<f64>,
     pub stability: Option<f64>,
     pub last_reviewed_at: Option<chrono::DateTime<chrono::Utc>>,
+    pub leech: bool,
 }
 
 impl DB {
-    pub async fn collection_stats(&self, card_hashes: &HashMap<String, Card>) -> Result<CardStats> {
+    pub async fn collection_stats(
+        &self,
+        card_hashes: &HashMap<String, Card>,
+        forecast_days: usize,
+        day_cutoff_hour: u32,
+    ) -> Result<CardStats> {
         let mut stats = CardStats {
             num_cards: card_hashes.len() as i64,
+            forecast: vec![0; forecast_days],
+            day_cutoff_hour,
             ..Default::default()
         };
 
@@ -36,8 +44,10 @@ impl DB {
                 interval_raw as "interval_raw?: f64",
                 difficulty as "difficulty?: f64",
                 stability as "stability?: f64",
-                last_reviewed_at as "last_reviewed_at?: chrono::DateTime<chrono::Utc>"
+                last_reviewed_at as "last_reviewed_at?: chrono::DateTime<chrono::Utc>",
+                leech as "leech!: bool"
             FROM cards
+            WHERE archived_at IS NULL
             "#,
         )
         .fetch(&self.pool);
@@ -0,0 +1,111 @@
+//! Just enough RFC 4180 CSV for `repeater export` and importing it back.
+
+use std::borrow::Cow;
+
+use anyhow::{Result, bail};
+
+/// Quotes a field when it contains a delimiter, quote, or line break.
+pub fn field(field: &str) -> Cow<'_, str> {
+    if field.contains([',', '"', '\n', '\r']) {
+        Cow::Owned(format!("\"{}\"", field.replace('"', "\"\"")))
+    } else {
+        Cow::Borrowed(field)
+    }
+}
+
+/// A parsed row and the 1-based line it starts on.
+#[derive(Debug, PartialEq)]
+pub struct Record {
+    pub line: usize,
+    pub fields: Vec<String>,
+}
+
+/// Splits `text` into records, honoring quoted fields that span lines. Blank lines are skipped.
+pub fn parse(text: &str) -> Result<Vec<Record>> {
+    let mut records = Vec::new();
+    let mut fields = Vec::new();
+    let mut current = String::new();
+    let mut in_quotes = false;
+    let mut line = 1;
+    let mut record_line = 1;
+    let mut chars = text.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        if in_quotes {
+            match c {
+                '"' if chars.peek() == Some(&'"') => {
+                    chars.next();
+                    current.push('"');
+                }
+                '"' => in_quotes = false,
+                _ => {
+                    if c == '\n' {
+                        line += 1;
+                    }
+                    current.push(c);
+                }
+            }
+            continue;
+        }
+        match c {
+            '"' if current.is_empty() => in_quotes = true,
+            ',' => fields.push(std::mem::take(&mut current)),
+            '\r' if chars.peek() == Some(&'\n') => {}
+            '\n' => {
+                fields.push(std::mem::take(&mut current));
+                push_record(&mut records, record_line, std::mem::take(&mut fields));
+                line += 1;
+                record_line = line;
+            }
+            _ => current.push(c),
+        }
+    }
+    if in_quotes {
+        bail!("line {record_line}: unterminated quoted field");
+    }
+    if !current.is_empty() || !fields.is_empty() {
+        fields.push(current);
+        push_record(&mut records, record_line, fields);
+    }
+    Ok(records)
+}
+
+fn push_record(records: &mut Vec<Record>, line: usize, fields: Vec<String>) {
+    if fields.len() == 1 && fields[0].is_empty() {
+        return;
+    }
+    records.push(Record { line, fields });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn quotes_fields_that_need_it() {
+        assert_eq!(field("plain.md"), "plain.md");
+        assert_eq!(field("a,b.md"), "\"a,b.md\"");
+        assert_eq!(field("say \"hi\".md"), "\"say \"\"hi\"\".md\"");
+        assert_eq!(field("two\nlines"), "\"two\nlines\"");
+    }
+
+    #[test]
+    fn parses_what_it_writes() {
+        let values = ["a,b.md", "say \"hi\"", "two\nlines", "", "plain"];
+        let row: Vec<_> = values.iter().map(|value| field(value)).collect();
+        let text = format!("h1,h2,h3,h4,h5\r\n\n{}\nlast,,,,", row.join(","));
+
+        let records = parse(&text).unwrap();
+        assert_eq!(records.len(), 3);
+        assert_eq!(records[1].line, 3);
+        assert_eq!(records[1].fields, values);
+        assert_eq!(records[2].line, 5);
+        assert_eq!(records[2].fields, ["last", "", "", "", ""]);
+    }
+
+    #[test]
+    fn rejects_unterminated_quotes() {
+        let err = parse("a,b\n\"open,c\n").unwrap_err();
+        assert!(err.to_string().contains("line 2"));
+    }
+}
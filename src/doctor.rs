@@ -0,0 +1,284 @@
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+
+use anyhow::{Result, bail};
+
+use crate::config::Config;
+use crate::crud::DB;
+use crate::utils::{ScanOptions, register_cards};
+
+fn plural(count: usize) -> &'static str {
+    if count == 1 { "" } else { "s" }
+}
+
+/// Malformed cards listed under the failing check before the rest are summarized.
+const LISTED_ISSUES: usize = 5;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Status {
+    Pass,
+    Warn,
+    Fail,
+}
+
+#[derive(Debug)]
+struct Finding {
+    status: Status,
+    summary: String,
+    details: Vec<String>,
+}
+
+impl Finding {
+    fn new(status: Status, summary: impl Into<String>) -> Self {
+        Self {
+            status,
+            summary: summary.into(),
+            details: Vec::new(),
+        }
+    }
+
+    fn pass(summary: impl Into<String>) -> Self {
+        Self::new(Status::Pass, summary)
+    }
+
+    fn warn(summary: impl Into<String>) -> Self {
+        Self::new(Status::Warn, summary)
+    }
+
+    fn fail(summary: impl Into<String>) -> Self {
+        Self::new(Status::Fail, summary)
+    }
+
+    fn print(&self) {
+        let label = match self.status {
+            Status::Pass => "PASS",
+            Status::Warn => "WARN",
+            Status::Fail => "FAIL",
+        };
+        println!("{label}  {}", self.summary);
+        for detail in &self.details {
+            println!("        {detail}");
+        }
+    }
+}
+
+/// Checks the config, data directory, database, and the decks under `paths`, printing
+/// one line per check. Fails when any check does.
+pub async fn run(db_path: Option<&Path>, paths: Vec<PathBuf>) -> Result<()> {
+    let mut findings = vec![check_config(), check_data_dir()];
+    match DB::open(db_path).await {
+        Ok(db) => {
+            findings.push(match db.file_path().await? {
+                Some(path) => Finding::pass(format!("Database opens: {}", path.display())),
+                None => Finding::pass("Database opens"),
+            });
+            findings.extend(check_database(&db).await?);
+            findings.extend(check_collection(&db, paths).await);
+            db.close().await;
+        }
+        Err(err) => findings.push(Finding::fail(format!("Database opens: {err:#}"))),
+    }
+
+    for finding in &findings {
+        finding.print();
+    }
+    let failed = findings
+        .iter()
+        .filter(|finding| finding.status == Status::Fail)
+        .count();
+    if failed > 0 {
+        bail!("{failed} check{} failed", plural(failed));
+    }
+    println!("No problems found.");
+    Ok(())
+}
+
+fn check_config() -> Finding {
+    let path = match Config::path() {
+        Ok(path) => path,
+        Err(err) => return Finding::fail(format!("Config file: {err:#}")),
+    };
+    if !path.is_file() {
+        return Finding::pass(format!(
+            "Config file: none at {}, using defaults",
+            path.display()
+        ));
+    }
+    match Config::load_from(&path) {
+        Ok(_) => Finding::pass(format!("Config file is valid: {}", path.display())),
+        Err(err) => Finding::fail(format!("Config file: {err:#}")),
+    }
+}
+
+fn check_data_dir() -> Finding {
+    let dir = match DB::data_dir() {
+        Ok(dir) => dir,
+        Err(err) => return Finding::fail(format!("Data directory can't be created: {err:#}")),
+    };
+    match tempfile::NamedTempFile::new_in(&dir) {
+        Ok(_) => Finding::pass(format!("Data directory is writable: {}", dir.display())),
+        Err(err) => Finding::fail(format!(
+            "Data directory is not writable: {}: {err}",
+            dir.display()
+        )),
+    }
+}
+
+async fn check_database(db: &DB) -> Result<Vec<Finding>> {
+    let mut findings = Vec::new();
+    let problems = db.integrity_check().await?;
+    if problems.is_empty() {
+        findings.push(Finding::pass("Database integrity check"));
+    } else {
+        let mut finding = Finding::fail(format!(
+            "Database integrity check found {} problem{}; restore a backup or re-create it from an export",
+            problems.len(),
+            plural(problems.len())
+        ));
+        finding.details = problems;
+        findings.push(finding);
+    }
+
+    let version = db.schema_version().await?;
+    let latest = DB::latest_schema_version();
+    findings.push(if version == latest {
+        Finding::pass(format!("Database schema is up to date (version {version})"))
+    } else {
+        Finding::fail(format!(
+            "Database schema is version {version} but this build expects {latest}; update repeater"
+        ))
+    });
+    Ok(findings)
+}
+
+async fn check_collection(db: &DB, paths: Vec<PathBuf>) -> Vec<Finding> {
+    let mut findings = Vec::new();
+    let (existing, missing): (Vec<PathBuf>, Vec<PathBuf>) =
+        paths.into_iter().partition(|path| path.exists());
+    for path in &missing {
+        findings.push(Finding::fail(format!(
+            "Collection path does not exist: {}",
+            path.display()
+        )));
+    }
+    if existing.is_empty() {
+        return findings;
+    }
+
+    let registration = match register_cards(db, &ScanOptions::default(), existing.clone()).await {
+        Ok(registration) => registration,
+        Err(err) => {
+            findings.push(Finding::fail(format!("Reading decks failed: {err:#}")));
+            return findings;
+        }
+    };
+    let files: HashSet<&PathBuf> = registration
+        .cards
+        .values()
+        .map(|card| &card.file_path)
+        .chain(registration.issues.iter().map(|issue| &issue.file_path))
+        .collect();
+    let searched: Vec<String> = existing
+        .iter()
+        .map(|path| path.display().to_string())
+        .collect();
+    findings.push(if registration.cards.is_empty() {
+        Finding::fail(format!(
+            "No cards found in Markdown files under {}",
+            searched.join(", ")
+        ))
+    } else {
+        Finding::pass(format!(
+            "Found {} card{} in {} Markdown file{}",
+            registration.cards.len(),
+            plural(registration.cards.len()),
+            files.len(),
+            plural(files.len())
+        ))
+    });
+
+    let issues = &registration.issues;
+    findings.push(if issues.is_empty() {
+        Finding::pass("Every card parses")
+    } else {
+        let mut finding = Finding::fail(format!(
+            "{} card{} can't be parsed",
+            issues.len(),
+            plural(issues.len())
+        ));
+        finding.details = issues
+            .iter()
+            .take(LISTED_ISSUES)
+            .map(ToString::to_string)
+            .collect();
+        if issues.len() > LISTED_ISSUES {
+            finding
+                .details
+                .push(format!("…and {} more", issues.len() - LISTED_ISSUES));
+        }
+        finding
+    });
+
+    if !registration.missing_images.is_empty() {
+        let count = registration.missing_images.len();
+        let mut finding = Finding::warn(format!(
+            "{count} referenced image{} missing",
+            if count == 1 { " is" } else { "s are" }
+        ));
+        finding.details = registration
+            .missing_images
+            .iter()
+            .map(|((path, line), image)| format!("{}:{line}: {}", path.display(), image.display()))
+            .collect();
+        findings.push(finding);
+    }
+
+    let current: HashSet<String> = registration.cards.keys().cloned().collect();
+    findings.push(match db.missing_hashes(&current).await {
+        Ok(orphans) if orphans.is_empty() => Finding::pass("No orphaned cards in the database"),
+        Ok(orphans) => Finding::warn(format!(
+            "{} card{} in the database no longer found under {}; `repeater check --prune` archives them",
+            orphans.len(),
+            plural(orphans.len()),
+            searched.join(", ")
+        )),
+        Err(err) => Finding::fail(format!("Looking for orphaned cards failed: {err:#}")),
+    });
+    findings
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Status, check_collection, check_database};
+    use crate::crud::DB;
+
+    fn statuses(findings: &[super::Finding]) -> Vec<Status> {
+        findings.iter().map(|finding| finding.status).collect()
+    }
+
+    #[tokio::test]
+    async fn reports_broken_cards_orphans_and_missing_paths() {
+        let db = DB::new_in_memory().await.unwrap();
+        assert_eq!(
+            statuses(&check_database(&db).await.unwrap()),
+            [Status::Pass, Status::Pass]
+        );
+
+        let dir = tempfile::tempdir().unwrap();
+        let deck = dir.path().join("deck.md");
+        std::fs::write(&deck, "Q: kept?\nA: yes\n\nQ: gone?\nA: soon\n").unwrap();
+        let findings = check_collection(&db, vec![dir.path().to_path_buf()]).await;
+        assert_eq!(statuses(&findings), [Status::Pass; 3]);
+        assert_eq!(findings[0].summary, "Found 2 cards in 1 Markdown file");
+
+        std::fs::write(&deck, "Q: kept?\nA: yes\n\nQ: no answer\n").unwrap();
+        let findings =
+            check_collection(&db, vec![dir.path().to_path_buf(), dir.path().join("nope")]).await;
+        assert_eq!(
+            statuses(&findings),
+            [Status::Fail, Status::Pass, Status::Fail, Status::Warn]
+        );
+        assert!(findings[0].summary.contains("does not exist"));
+        assert_eq!(findings[2].details.len(), 1);
+    }
+}
@@ -1,100 +1,689 @@
-use std::io;
-use std::path::PathBuf;
+use std::collections::{BTreeMap, HashMap, HashSet, VecDeque};
+use std::fmt::Write as _;
+use std::io::{self, IsTerminal, Write};
+use std::path::{Path, PathBuf};
 use std::time::{Duration, Instant};
 
 use crate::card::{Card, CardContent, ClozeRange};
-use crate::crud::DB;
-use crate::fsrs::{LEARN_AHEAD_THRESHOLD_MINS, ReviewStatus};
-use crate::markdown::render_markdown;
-use crate::media::{Media, extract_media};
-use crate::tui::Theme;
-use crate::utils::{register_all_cards, resolve_missing_clozes};
+use crate::card_edit::{self, CardSource, SavedEdit};
+use crate::config::LeechPolicy;
+use crate::create::editor_lines;
+use crate::crud::cards::{CardSchedule, QueueOrder, ReviewOutcome};
+use crate::crud::{DB, GradeCounts, SessionRecord};
+use crate::fsrs::{LEARN_AHEAD_THRESHOLD_MINS, ReviewStatus, SchedulerParams, schedule_review};
+use crate::markdown::render_wrapped_markdown;
+use crate::media::{ImageRef, Media, extract_images, extract_media};
+use crate::session::SavedSession;
+use crate::stats::format_duration;
+use crate::tui::{Editor, MouseCapture, Theme};
+use crate::utils::{
+    ScanOptions, day_start, find_numbered_clozes, read_all_cards, register_all_cards,
+    resolve_missing_clozes, today,
+};
 
 use anyhow::{Context, Result};
 use crossterm::event::KeyModifiers;
 use crossterm::{
     event::{
-        self, Event, KeyCode, KeyEventKind, KeyboardEnhancementFlags, PopKeyboardEnhancementFlags,
-        PushKeyboardEnhancementFlags,
+        self, Event, KeyCode, KeyEvent, KeyEventKind, KeyboardEnhancementFlags, MouseButton,
+        MouseEvent, MouseEventKind, PopKeyboardEnhancementFlags, PushKeyboardEnhancementFlags,
     },
     execute,
     terminal::{EnterAlternateScreen, LeaveAlternateScreen, disable_raw_mode, enable_raw_mode},
 };
 use ratatui::{
-    Terminal,
+    Frame, Terminal,
     backend::CrosstermBackend,
-    layout::{Constraint, Direction, Layout},
+    layout::{Constraint, Direction, Layout, Position, Rect},
+    style::{Modifier, Style},
     text::{Line, Span},
-    widgets::{Paragraph, Wrap},
+    widgets::{LineGauge, Paragraph, Wrap},
 };
+use serde::{Deserialize, Serialize};
 
 const MINUTES_PER_DAY: f64 = 24.0 * 60.0;
 const FLASH_SECS: f64 = 2.0;
+/// Answers averaged for the time-remaining estimate.
+const PACE_WINDOW: usize = 10;
+/// Answers `z` can take back, latest first.
+const UNDO_DEPTH: usize = 3;
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct DrillOptions {
+    pub card_limit: Option<usize>,
+    pub new_card_limit: Option<usize>,
+    pub tags: Vec<String>,
+    /// Also drill cards due within this many days.
+    pub ahead_days: Option<u32>,
+    #[serde(default)]
+    pub order: QueueOrder,
+    /// Put never-reviewed cards before the reviews instead of after them.
+    #[serde(default)]
+    pub new_first: bool,
+    /// Grade cards without saving anything; the footer's projected intervals are all you get.
+    #[serde(default)]
+    pub dry_run: bool,
+    /// Drill every matching card, due or not, as a dry run that never reads or writes
+    /// schedules; the grades are only for the summary.
+    #[serde(default)]
+    pub cram: bool,
+}
+
+impl DrillOptions {
+    /// Whether nothing the session does may be written to the database.
+    fn read_only(&self) -> bool {
+        self.dry_run || self.cram
+    }
+}
+
+/// Scheduling and session behavior taken from the config.
+#[derive(Clone, Debug)]
+pub struct DrillSettings {
+    pub params: SchedulerParams,
+    pub leech_policy: LeechPolicy,
+    pub max_answer_time: Duration,
+    /// Cards shown before one graded Again comes back.
+    pub relearn_gap: usize,
+    /// Reviewing a card defers the other cards from its block to the next day.
+    pub bury_siblings: bool,
+    pub type_in_ignore_case: bool,
+    /// Line that ends a card block, for re-parsing cards edited mid-session.
+    pub card_separator: String,
+}
 
+/// Returns `false` when there was nothing to drill.
 pub async fn run(
     db: &DB,
+    scan: &ScanOptions,
     paths: Vec<PathBuf>,
-    card_limit: Option<usize>,
-    new_card_limit: Option<usize>,
-) -> Result<()> {
-    let hash_cards = register_all_cards(db, paths).await?;
-    let mut cards_due_today = db
-        .due_today(&hash_cards, card_limit, new_card_limit)
+    options: DrillOptions,
+    settings: DrillSettings,
+) -> Result<bool> {
+    let session_path = SavedSession::path()?;
+    let dry_run = options.read_only();
+    // A dry run leaves any saved session alone for the next real one.
+    let saved = if dry_run {
+        None
+    } else {
+        SavedSession::load_from(&session_path).unwrap_or_else(|err| {
+            eprintln!("Ignoring unreadable drill session: {err:#}");
+            None
+        })
+    };
+
+    let (session, mut cards, ahead) = match saved {
+        Some(saved) if confirm_resume(&saved)? => {
+            let hash_cards = register_all_cards(db, scan, saved.paths.clone()).await?;
+            let cards: Vec<Card> = saved
+                .remaining
+                .iter()
+                .filter_map(|card_hash| hash_cards.get(card_hash).cloned())
+                .collect();
+            let ahead = saved.ahead.iter().cloned().collect();
+            (saved, cards, ahead)
+        }
+        _ => {
+            if !dry_run {
+                SavedSession::clear(&session_path)?;
+            }
+            let (cards, ahead) = if options.cram {
+                (
+                    cram_cards(db, scan, paths.clone(), &options).await?,
+                    HashSet::new(),
+                )
+            } else {
+                due_cards(db, scan, paths.clone(), &options, &settings).await?
+            };
+            (SavedSession::new(paths, options), cards, ahead)
+        }
+    };
+
+    if cards.is_empty() {
+        if !dry_run {
+            SavedSession::clear(&session_path)?;
+        }
+        if !session.remaining.is_empty() {
+            println!("None of the saved session's cards exist anymore.");
+        }
+        return Ok(false);
+    }
+
+    resolve_missing_clozes(&mut cards).await?;
+    let studied_ahead = session.options.ahead_days.is_some();
+    let session_cram = session.options.cram;
+    let summary = start_drill_session(db, cards, ahead, session, session_path, settings).await?;
+    if let Some(line) = summary.describe(studied_ahead) {
+        println!("{line}");
+    }
+    if let Some(line) = summary.grade_breakdown() {
+        println!("{line}");
+    }
+    if !dry_run && summary.grades.total() > 0 {
+        db.record_session(&SessionRecord {
+            ended_at: chrono::Utc::now(),
+            grades: summary.grades,
+            duration_ms: summary.time_spent.as_millis() as i64,
+        })
         .await?;
+    }
+    if session_cram {
+        println!("Cram: no schedules were read or changed.");
+    } else if dry_run {
+        println!("Dry run: no reviews were saved.");
+    }
 
-    if cards_due_today.is_empty() {
-        println!("All caught up—no cards due today.");
-        return Ok(());
+    Ok(true)
+}
+
+/// Prints how many new and review cards a drill with `options` would hold, per file,
+/// without starting it. Returns `false` when it would have nothing to drill.
+pub async fn plan(
+    db: &DB,
+    scan: &ScanOptions,
+    paths: Vec<PathBuf>,
+    options: DrillOptions,
+    settings: DrillSettings,
+) -> Result<bool> {
+    let (cards, ahead) = due_cards(db, scan, paths, &options, &settings).await?;
+    if cards.is_empty() {
+        return Ok(false);
+    }
+    let new = new_hashes(db, &cards).await?;
+    print!("{}", format_plan(&cards, &new, ahead.len()));
+    Ok(true)
+}
+
+fn format_plan(cards: &[Card], new: &HashSet<String>, ahead: usize) -> String {
+    let mut per_file: BTreeMap<&Path, (usize, usize)> = BTreeMap::new();
+    for card in cards {
+        let counts = per_file.entry(card.file_path.as_path()).or_default();
+        if new.contains(&card.card_hash) {
+            counts.0 += 1;
+        } else {
+            counts.1 += 1;
+        }
     }
+    let rows: Vec<(String, (usize, usize))> = per_file
+        .into_iter()
+        .map(|(path, counts)| (path.display().to_string(), counts))
+        .collect();
+    let total_new: usize = rows.iter().map(|(_, (new, _))| new).sum();
+    let total_review = cards.len() - total_new;
+    let width = rows
+        .iter()
+        .map(|(name, _)| name.chars().count())
+        .max()
+        .unwrap_or(0)
+        .max("FILE".len());
 
-    resolve_missing_clozes(&mut cards_due_today).await?;
-    start_drill_session(db, cards_due_today).await?;
+    let mut out = String::new();
+    let _ = writeln!(out, "{:<width$}  {:>6}  {:>6}", "FILE", "NEW", "REVIEW");
+    for (name, (new, review)) in rows {
+        let _ = writeln!(out, "{name:<width$}  {new:>6}  {review:>6}");
+    }
+    let _ = writeln!(
+        out,
+        "{:<width$}  {total_new:>6}  {total_review:>6}",
+        "TOTAL"
+    );
+    let plural = if cards.len() == 1 { "" } else { "s" };
+    let _ = write!(
+        out,
+        "\nThe session would drill {} card{plural}",
+        cards.len()
+    );
+    if ahead > 0 {
+        let _ = write!(out, ", {ahead} of them ahead of schedule");
+    }
+    let _ = writeln!(out, ".");
+    out
+}
 
-    Ok(())
+/// Cards to drill for `options`, plus the hashes of those that aren't due yet.
+/// Prints why when there is nothing to drill.
+async fn due_cards(
+    db: &DB,
+    scan: &ScanOptions,
+    paths: Vec<PathBuf>,
+    options: &DrillOptions,
+    settings: &DrillSettings,
+) -> Result<(Vec<Card>, HashSet<String>)> {
+    let day_cutoff_hour = settings.params.day_cutoff_hour;
+    let mut hash_cards = register_all_cards(db, scan, paths).await?;
+    if !options.tags.is_empty() {
+        let tagged = db.tagged_hashes(&options.tags).await?;
+        hash_cards.retain(|card_hash, _| tagged.contains(card_hash));
+        if hash_cards.is_empty() {
+            println!("No cards tagged {}.", options.tags.join(" or "));
+            return Ok((Vec::new(), HashSet::new()));
+        }
+    }
+    let mut ahead = HashSet::new();
+    let cards = match options.ahead_days {
+        Some(days) => {
+            let due_now: HashSet<String> = db
+                .due_today(&hash_cards, None, None, QueueOrder::Due, day_cutoff_hour)
+                .await?
+                .into_iter()
+                .map(|card| card.card_hash)
+                .collect();
+            let cutoff = chrono::Utc::now()
+                + LEARN_AHEAD_THRESHOLD_MINS
+                + chrono::Duration::days(i64::from(days));
+            let cards = db
+                .due_by(
+                    &hash_cards,
+                    options.card_limit,
+                    options.new_card_limit,
+                    cutoff,
+                    options.order,
+                    day_cutoff_hour,
+                )
+                .await?;
+            ahead.extend(
+                cards
+                    .iter()
+                    .filter(|card| !due_now.contains(&card.card_hash))
+                    .map(|card| card.card_hash.clone()),
+            );
+            cards
+        }
+        None => {
+            db.due_today(
+                &hash_cards,
+                options.card_limit,
+                options.new_card_limit,
+                options.order,
+                day_cutoff_hour,
+            )
+            .await?
+        }
+    };
+
+    let cards = if settings.bury_siblings {
+        let reviewed_today = db
+            .reviewed_since(day_start(today(day_cutoff_hour), day_cutoff_hour))
+            .await?;
+        without_reviewed_siblings(cards, &hash_cards, &reviewed_today)
+    } else {
+        cards
+    };
+    let new = new_hashes(db, &cards).await?;
+    let cards = group_new_cards(cards, &new, options.new_first);
+
+    if cards.is_empty() {
+        let next = match db.next_due(&hash_cards).await? {
+            Some(due) => format!(" {}", next_due_text(due, chrono::Utc::now())),
+            None => String::new(),
+        };
+        match options.ahead_days {
+            Some(days) => println!("No cards due in the next {days} days.{next}"),
+            None => println!("No cards due.{next}"),
+        }
+    }
+    Ok((cards, ahead))
+}
+
+/// `cards` with the never-reviewed ones moved before or after the reviews, keeping the
+/// order within each group. The limits already picked the cards, so this never changes
+/// which are drilled.
+fn group_new_cards(cards: Vec<Card>, new: &HashSet<String>, new_first: bool) -> Vec<Card> {
+    let (new_cards, reviews): (Vec<Card>, Vec<Card>) = cards
+        .into_iter()
+        .partition(|card| new.contains(&card.card_hash));
+    if new_first {
+        new_cards.into_iter().chain(reviews).collect()
+    } else {
+        reviews.into_iter().chain(new_cards).collect()
+    }
+}
+
+fn next_due_text(due: chrono::DateTime<chrono::Utc>, now: chrono::DateTime<chrono::Utc>) -> String {
+    let days = (due - now).num_seconds().max(0) as f64 / 86_400.0;
+    format!("Next card due in {}.", format_interval(days))
+}
+
+/// Drops cards with a sibling among the `reviewed` hashes; they stay due and come
+/// back in tomorrow's drill.
+fn without_reviewed_siblings(
+    cards: Vec<Card>,
+    hash_cards: &HashMap<String, Card>,
+    reviewed: &HashSet<String>,
+) -> Vec<Card> {
+    let mut reviewed_by_origin: HashMap<&str, Vec<&str>> = HashMap::new();
+    for card_hash in reviewed {
+        if let Some(card) = hash_cards.get(card_hash) {
+            reviewed_by_origin
+                .entry(card.origin.as_str())
+                .or_default()
+                .push(card_hash);
+        }
+    }
+    cards
+        .into_iter()
+        .filter(|card| {
+            !reviewed_by_origin
+                .get(card.origin.as_str())
+                .is_some_and(|hashes| hashes.iter().any(|hash| *hash != card.card_hash))
+        })
+        .collect()
+}
+
+fn confirm_resume(saved: &SavedSession) -> Result<bool> {
+    if !io::stdin().is_terminal() {
+        return Ok(false);
+    }
+    let left = saved.remaining.len();
+    let plural = if left == 1 { "" } else { "s" };
+    print!("An unfinished drill session has {left} card{plural} left. Resume it? [Y/n]: ");
+    io::stdout().flush()?;
+
+    let mut answer = String::new();
+    io::stdin().read_line(&mut answer)?;
+    let answer = answer.trim().to_ascii_lowercase();
+    Ok(answer != "n" && answer != "no")
+}
+
+/// Hashes of the `cards` that have never been reviewed.
+async fn new_hashes(db: &DB, cards: &[Card]) -> Result<HashSet<String>> {
+    let queued: HashSet<&str> = cards.iter().map(|card| card.card_hash.as_str()).collect();
+    Ok(db
+        .card_rows()
+        .await?
+        .into_iter()
+        .filter(|row| row.review_count == 0 && queued.contains(row.card_hash.as_str()))
+        .map(|row| row.card_hash)
+        .collect())
+}
+
+/// Every unsuspended card under `paths` carrying one of the tags, due or not, in the
+/// order they are written. The cards are read from their files rather than registered,
+/// so a cram leaves the database as it found it.
+async fn cram_cards(
+    db: &DB,
+    scan: &ScanOptions,
+    paths: Vec<PathBuf>,
+    options: &DrillOptions,
+) -> Result<Vec<Card>> {
+    let suspended = db.suspended_hashes().await?;
+    let mut cards: Vec<Card> = read_all_cards(scan, paths)
+        .await?
+        .into_values()
+        .filter(|card| {
+            options.tags.is_empty() || card.tags.iter().any(|tag| options.tags.contains(tag))
+        })
+        .filter(|card| !suspended.contains(&card.card_hash))
+        .collect();
+    cards.sort_by(|a, b| (&a.file_path, a.file_card_range).cmp(&(&b.file_path, b.file_card_range)));
+    if let Some(limit) = options.card_limit {
+        cards.truncate(limit);
+    }
+    if cards.is_empty() {
+        if options.tags.is_empty() {
+            println!("No cards to cram.");
+        } else {
+            println!("No cards tagged {}.", options.tags.join(" or "));
+        }
+    }
+    Ok(cards)
+}
+
+/// Where a card graded Again at `current_idx` goes back into this pass, `gap` cards
+/// later; `None` when the pass ends sooner and it should come after it instead.
+fn relearn_position(current_idx: usize, pass_len: usize, gap: usize) -> Option<usize> {
+    let position = current_idx + 1 + gap;
+    (position <= pass_len).then_some(position)
+}
+
+/// Hashes still queued: the rest of this pass, then cards coming again.
+fn remaining_hashes(cards: &[Card], current_idx: usize, redo_cards: &[Card]) -> Vec<String> {
+    cards
+        .get(current_idx..)
+        .unwrap_or_default()
+        .iter()
+        .chain(redo_cards)
+        .map(|card| card.card_hash.clone())
+        .collect()
+}
+
+/// How far along a session is, counting each card once however often it comes again.
+#[derive(Debug, PartialEq)]
+struct Progress {
+    done: usize,
+    total: usize,
+    new: usize,
+    review: usize,
+    remaining: Option<Duration>,
+}
+
+impl Progress {
+    fn new(
+        remaining: &[String],
+        reviewed: &HashSet<String>,
+        new_cards: &HashSet<String>,
+        recent_answers: &VecDeque<Duration>,
+    ) -> Self {
+        let queued: HashSet<&String> = remaining.iter().collect();
+        let total = queued.len()
+            + reviewed
+                .iter()
+                .filter(|hash| !queued.contains(hash))
+                .count();
+        let new = queued
+            .iter()
+            .filter(|hash| new_cards.contains(**hash) && !reviewed.contains(**hash))
+            .count();
+        // Every queued answer still takes a turn, requeues included.
+        let pace = (!recent_answers.is_empty())
+            .then(|| recent_answers.iter().sum::<Duration>() / recent_answers.len() as u32);
+        Self {
+            done: total - queued.len(),
+            total,
+            new,
+            review: queued.len() - new,
+            remaining: pace.map(|pace| pace * remaining.len() as u32),
+        }
+    }
+
+    fn ratio(&self) -> f64 {
+        if self.total == 0 {
+            1.0
+        } else {
+            self.done as f64 / self.total as f64
+        }
+    }
+
+    fn label(&self) -> String {
+        let mut label = format!(
+            "{} / {} reviewed • {} new • {} review",
+            self.done, self.total, self.new, self.review
+        );
+        if let Some(remaining) = self.remaining {
+            label.push_str(&format!(
+                " • ~{} left",
+                format_duration(remaining.as_secs_f64())
+            ));
+        }
+        label
+    }
+}
+
+struct SessionSummary {
+    reviewed: usize,
+    ahead: usize,
+    /// Sum of the logged answer times.
+    time_spent: Duration,
+    grades: GradeCounts,
+}
+
+impl SessionSummary {
+    fn describe(&self, studied_ahead: bool) -> Option<String> {
+        if self.reviewed == 0 {
+            return None;
+        }
+        let plural = if self.reviewed == 1 { "" } else { "s" };
+        let mut line = format!("Reviewed {} card{plural}", self.reviewed);
+        if studied_ahead {
+            line.push_str(&format!(", {} ahead of schedule", self.ahead));
+        }
+        if !self.time_spent.is_zero() {
+            line.push_str(&format!(
+                " in {}",
+                format_duration(self.time_spent.as_secs_f64())
+            ));
+        }
+        line.push('.');
+        Some(line)
+    }
+
+    /// Answers per grade and the share recalled, e.g. `Again 1 • Hard 0 • Good 3 • Easy 0 • 75% recalled`.
+    fn grade_breakdown(&self) -> Option<String> {
+        let retention = self.grades.retention()?;
+        let mut parts: Vec<String> = ReviewStatus::ALL
+            .iter()
+            .map(|status| format!("{} {}", status.label(), self.grades.count(*status)))
+            .collect();
+        parts.push(format!("{}% recalled", (retention * 100.0).round()));
+        Some(parts.join(" • "))
+    }
 }
 
 struct DrillState<'a> {
     db: &'a DB,
+    settings: DrillSettings,
     cards: Vec<Card>,
+    /// Cards that were not due yet when the session started.
+    ahead: HashSet<String>,
+    reviewed: HashSet<String>,
+    grades: GradeCounts,
+    /// Cards that had never been reviewed when the session started.
+    new_cards: HashSet<String>,
+    /// The latest answer times, for the pace estimate.
+    recent_answers: VecDeque<Duration>,
+    session: SavedSession,
+    session_path: PathBuf,
     redo_cards: Vec<Card>,
     current_idx: usize,
     show_answer: bool,
+    /// What was typed for a type-in card, and the grade that suggests once checked.
+    answer_input: Editor,
+    suggested: Option<ReviewStatus>,
+    /// The current card's lines from its file while `E` has them open.
+    editing: Option<CardEdit>,
+    /// Why the last key did nothing, shown until the next one.
+    notice: Option<String>,
     last_action: Option<LastAction>,
+    /// The latest answers, for `z`; cleared by anything that changes the queue otherwise.
+    undo: VecDeque<UndoStep>,
     current_medias: Vec<Media>,
+    projection: Option<Projection>,
+    /// When the current card was first drawn, for the logged answer time.
+    shown_at: Option<Instant>,
+    time_spent: Duration,
+    /// Where the card and controls were last drawn, for mouse clicks.
+    card_area: Rect,
+    footer_area: Rect,
+}
+struct CardEdit {
+    source: CardSource,
+    editor: Editor,
+    error: Option<String>,
+}
+struct Projection {
+    card_hash: String,
+    intervals: Vec<(ReviewStatus, f64)>,
 }
+#[derive(Clone)]
 struct LastAction {
     action: ReviewStatus,
-    show_again_duration: f64,
+    /// `None` while cramming, when no interval was computed.
+    show_again_duration: Option<f64>,
+    leech: Option<Leech>,
     last_reviewed_at: Instant,
 }
+/// The session as it was before an answer, and the card's schedule and logged review
+/// when the answer was saved.
+struct UndoStep {
+    cards: Vec<Card>,
+    redo_cards: Vec<Card>,
+    current_idx: usize,
+    reviewed: HashSet<String>,
+    grades: GradeCounts,
+    recent_answers: VecDeque<Duration>,
+    time_spent: Duration,
+    last_action: Option<LastAction>,
+    saved: Option<(CardSchedule, i64)>,
+}
+#[derive(Clone, Copy)]
+enum Leech {
+    Flagged,
+    Suspended,
+}
 impl LastAction {
     fn print(&self) -> String {
+        let Some(show_again_duration) = self.show_again_duration else {
+            return format!(" {}", self.action.label());
+        };
         let mut show_again = String::new();
-        if self.show_again_duration <= 15.0 / MINUTES_PER_DAY {
+        if show_again_duration <= 15.0 / MINUTES_PER_DAY {
             show_again.push_str("<15 mins");
-        } else if self.show_again_duration <= 30.0 / MINUTES_PER_DAY {
+        } else if show_again_duration <= 30.0 / MINUTES_PER_DAY {
             show_again.push_str("<30 mins");
-        } else if self.show_again_duration <= 0.5 {
+        } else if show_again_duration <= 0.5 {
             show_again.push_str("<12 hours");
-        } else if self.show_again_duration <= 1.0 {
+        } else if show_again_duration <= 1.0 {
             show_again.push_str("<1 day");
         } else {
-            show_again.push_str(format!("{} days", self.show_again_duration as i64).as_str());
+            show_again.push_str(format!("{} days", show_again_duration as i64).as_str());
+        }
+        match self.leech {
+            Some(Leech::Suspended) => format!(" {} (Leech, suspended)", self.action.label()),
+            Some(Leech::Flagged) => format!(
+                " {} (Leech, see again in {})",
+                self.action.label(),
+                show_again
+            ),
+            None => format!(" {} (See again in {})", self.action.label(), show_again,),
         }
-        format!(" {} (See again in {})", self.action.label(), show_again,)
     }
 }
 
 impl<'a> DrillState<'a> {
-    fn new(db: &'a DB, cards: Vec<Card>) -> Self {
+    fn new(
+        db: &'a DB,
+        cards: Vec<Card>,
+        ahead: HashSet<String>,
+        new_cards: HashSet<String>,
+        session: SavedSession,
+        session_path: PathBuf,
+        settings: DrillSettings,
+    ) -> Self {
         Self {
             db,
+            settings,
             cards,
+            ahead,
+            reviewed: HashSet::new(),
+            grades: GradeCounts::default(),
+            new_cards,
+            recent_answers: VecDeque::with_capacity(PACE_WINDOW),
+            session,
+            session_path,
             redo_cards: Vec::new(),
             current_idx: 0,
             show_answer: false,
+            answer_input: Editor::single_line(),
+            suggested: None,
+            editing: None,
+            notice: None,
             last_action: None,
+            undo: VecDeque::with_capacity(UNDO_DEPTH),
             current_medias: Vec::new(),
+            projection: None,
+            shown_at: None,
+            time_spent: Duration::ZERO,
+            card_area: Rect::default(),
+            footer_area: Rect::default(),
         }
     }
 
@@ -111,39 +700,410 @@ impl<'a> DrillState<'a> {
 
     fn reveal_answer(&mut self) {
         self.show_answer = true;
+        let ignore_case = self.settings.type_in_ignore_case;
+        self.suggested = self.current_card().and_then(|card| {
+            let expected = expected_answer(&card)?;
+            Some(
+                if answer_matches(&self.answer_input.content(), expected, ignore_case) {
+                    ReviewStatus::Good
+                } else {
+                    ReviewStatus::Again
+                },
+            )
+        });
+    }
+
+    /// The grade Space and Enter give: the type-in suggestion, or Good.
+    fn default_grade(&self) -> ReviewStatus {
+        self.suggested.unwrap_or(ReviewStatus::Good)
+    }
+
+    /// Whether keys currently go to the answer field instead of the shortcuts.
+    fn typing_answer(&mut self) -> bool {
+        !self.show_answer
+            && self
+                .current_card()
+                .is_some_and(|card| expected_answer(&card).is_some())
+    }
+
+    fn next_card(&mut self) {
+        self.current_idx += 1;
+        self.reset_card();
+    }
+
+    /// Shows the current card from the front again.
+    fn reset_card(&mut self) {
+        self.show_answer = false;
+        self.answer_input = Editor::single_line();
+        self.suggested = None;
+        self.projection = None;
+    }
+
+    async fn refresh_projection(&mut self) -> Result<()> {
+        // Crammed cards may not even be registered, and their schedules don't matter.
+        if self.session.options.cram {
+            return Ok(());
+        }
+        let Some(card) = self.current_card() else {
+            return Ok(());
+        };
+        if self
+            .projection
+            .as_ref()
+            .is_some_and(|projection| projection.card_hash == card.card_hash)
+        {
+            return Ok(());
+        }
+        let performance = self.db.get_card_performance(&card).await?;
+        let now = chrono::Utc::now();
+        let intervals = ReviewStatus::ALL
+            .into_iter()
+            .map(|status| {
+                let next = schedule_review(
+                    &card.card_hash,
+                    performance,
+                    status,
+                    now,
+                    &self.settings.params,
+                );
+                (status, next.scheduled_days())
+            })
+            .collect();
+        self.projection = Some(Projection {
+            card_hash: card.card_hash,
+            intervals,
+        });
+        Ok(())
     }
 
     async fn handle_review(&mut self, action: ReviewStatus) -> Result<()> {
         let current_card = self
             .current_card()
             .expect("card should exist when handling review");
-        let show_again_duration = self
-            .db
-            .update_card_performance(&current_card, action, None)
-            .await?;
-        if action == ReviewStatus::Fail
-            || show_again_duration
-                < (LEARN_AHEAD_THRESHOLD_MINS.num_minutes() as f64 / MINUTES_PER_DAY)
+        let answer_time = self
+            .shown_at
+            .take()
+            .map(|shown_at| shown_at.elapsed().min(self.settings.max_answer_time));
+        let mut undo = UndoStep {
+            cards: self.cards.clone(),
+            redo_cards: self.redo_cards.clone(),
+            current_idx: self.current_idx,
+            reviewed: self.reviewed.clone(),
+            grades: self.grades,
+            recent_answers: self.recent_answers.clone(),
+            time_spent: self.time_spent,
+            last_action: self.last_action.clone(),
+            saved: None,
+        };
+        let previous = if self.dry_run() {
+            None
+        } else {
+            Some(self.db.card_schedule(&current_card).await?)
+        };
+        let outcome = if self.dry_run() {
+            self.refresh_projection().await?;
+            ReviewOutcome {
+                scheduled_days: self.projected_interval(action).unwrap_or_default(),
+                lapses: 0,
+                review_id: None,
+            }
+        } else {
+            self.db
+                .update_card_performance(
+                    &current_card,
+                    action,
+                    None,
+                    &self.settings.params,
+                    answer_time.map(|time| time.as_millis() as i64),
+                )
+                .await?
+        };
+        self.time_spent += answer_time.unwrap_or_default();
+        if let Some(answer_time) = answer_time {
+            if self.recent_answers.len() == PACE_WINDOW {
+                self.recent_answers.pop_front();
+            }
+            self.recent_answers.push_back(answer_time);
+        }
+        undo.saved = previous.zip(outcome.review_id);
+        if self.undo.len() == UNDO_DEPTH {
+            self.undo.pop_back();
+        }
+        self.undo.push_front(undo);
+        let show_again_duration = outcome.scheduled_days;
+        self.reviewed.insert(current_card.card_hash.clone());
+        self.grades.add(action);
+        let leech = if !self.dry_run()
+            && action == ReviewStatus::Again
+            && self.settings.leech_policy.is_leech(outcome.lapses)
         {
-            self.redo_cards.push(current_card.clone());
+            let suspend = self.settings.leech_policy.auto_suspend;
+            self.db.mark_leech(&current_card, suspend).await?;
+            Some(if suspend {
+                Leech::Suspended
+            } else {
+                Leech::Flagged
+            })
+        } else {
+            None
+        };
+        if self.settings.bury_siblings {
+            self.bury_siblings(&current_card);
+        }
+        let requeue = action == ReviewStatus::Again
+            || show_again_duration
+                < (LEARN_AHEAD_THRESHOLD_MINS.num_minutes() as f64 / MINUTES_PER_DAY);
+        // Nothing was saved in a dry run, so a requeued card would project the same step forever.
+        if requeue && !self.dry_run() && !matches!(leech, Some(Leech::Suspended)) {
+            if action == ReviewStatus::Again {
+                let position = relearn_position(
+                    self.current_idx,
+                    self.cards.len(),
+                    self.settings.relearn_gap,
+                );
+                match position {
+                    Some(position) => self.cards.insert(position, current_card.clone()),
+                    None => self.redo_cards.push(current_card.clone()),
+                }
+            } else {
+                self.redo_cards.push(current_card.clone());
+            }
         }
 
         self.last_action = Some(LastAction {
             action,
-            show_again_duration,
+            show_again_duration: (!self.session.options.cram).then_some(show_again_duration),
+            leech,
             last_reviewed_at: std::time::Instant::now(),
         });
-        self.current_idx += 1;
-        self.show_answer = false;
+        self.next_card();
+        self.save_session()
+    }
+
+    /// Takes back the latest answer: the card's schedule and review log go back to how
+    /// they were, and the card is shown again from the front.
+    async fn undo_last(&mut self) -> Result<()> {
+        let Some(step) = self.undo.pop_front() else {
+            self.notice = Some("Nothing to undo".to_string());
+            return Ok(());
+        };
+        if let Some((previous, review_id)) = &step.saved {
+            self.db.undo_review(previous, *review_id).await?;
+        }
+        self.cards = step.cards;
+        self.redo_cards = step.redo_cards;
+        self.current_idx = step.current_idx;
+        self.reviewed = step.reviewed;
+        self.grades = step.grades;
+        self.recent_answers = step.recent_answers;
+        self.time_spent = step.time_spent;
+        self.last_action = step.last_action;
+        self.shown_at = None;
+        self.reset_card();
+        self.save_session()
+    }
+
+    /// Takes the rest of `card`'s block out of this session; the cards stay due for tomorrow.
+    fn bury_siblings(&mut self, card: &Card) {
+        let is_sibling =
+            |other: &Card| other.origin == card.origin && other.card_hash != card.card_hash;
+        let upcoming = self.current_idx + 1;
+        if upcoming < self.cards.len() {
+            let mut rest = self.cards.split_off(upcoming);
+            rest.retain(|other| !is_sibling(other));
+            self.cards.append(&mut rest);
+        }
+        self.redo_cards.retain(|other| !is_sibling(other));
+    }
+
+    async fn suspend_current(&mut self) -> Result<()> {
+        let current_card = self
+            .current_card()
+            .expect("card should exist when suspending");
+        if !self.dry_run() {
+            self.db.set_suspended(&current_card, true).await?;
+        }
+        self.undo.clear();
+        self.shown_at = None;
+        self.next_card();
+        self.save_session()
+    }
+
+    /// Opens the lines the current card was parsed from in the editor.
+    fn start_edit(&mut self) {
+        let Some(card) = self.current_card() else {
+            return;
+        };
+        match CardSource::load(&card) {
+            Ok(source) => {
+                self.editing = Some(CardEdit {
+                    editor: Editor::from_content(&source.text),
+                    source,
+                    error: None,
+                });
+            }
+            Err(err) => self.notice = Some(format!("{err:#}")),
+        }
+    }
+
+    async fn handle_edit_key(&mut self, key: KeyEvent) -> Result<()> {
+        let ctrl = key.modifiers.contains(KeyModifiers::CONTROL);
+        if key.code == KeyCode::Esc || (ctrl && key.code == KeyCode::Char('c')) {
+            self.editing = None;
+            return Ok(());
+        }
+        if ctrl && key.code == KeyCode::Char('s') {
+            return self.save_edit().await;
+        }
+        let Some(edit) = self.editing.as_mut() else {
+            return Ok(());
+        };
+        let editor = &mut edit.editor;
+        match key.code {
+            KeyCode::Char('z') if ctrl => {
+                editor.undo();
+            }
+            KeyCode::Char('y') if ctrl => {
+                editor.redo();
+            }
+            KeyCode::Char(c) if !ctrl => editor.insert_char(c),
+            KeyCode::Enter => editor.insert_newline(),
+            KeyCode::Tab => editor.insert_tab(),
+            KeyCode::Backspace => editor.backspace(),
+            KeyCode::Delete => editor.delete(),
+            KeyCode::Left => editor.move_left(),
+            KeyCode::Right => editor.move_right(),
+            KeyCode::Up => editor.move_up(),
+            KeyCode::Down => editor.move_down(),
+            KeyCode::Home => editor.move_home(),
+            KeyCode::End => editor.move_end(),
+            _ => {}
+        }
         Ok(())
     }
 
+    /// Writes the edited card back to its file and carries its schedule over to the new
+    /// hash. A card that doesn't parse stays open with the error shown.
+    async fn save_edit(&mut self) -> Result<()> {
+        let Some(edit) = self.editing.as_mut() else {
+            return Ok(());
+        };
+        match card_edit::save(
+            self.db,
+            &edit.source,
+            &edit.editor.content(),
+            &self.settings.card_separator,
+        )
+        .await
+        {
+            Ok(saved) => {
+                let path = edit.source.path.clone();
+                self.editing = None;
+                // Undoing would restore cards under the hashes the edit replaced.
+                self.undo.clear();
+                self.apply_edit(&path, &saved);
+                self.save_session()
+            }
+            Err(err) => {
+                edit.error = Some(format!("Unable to save: {err:#}"));
+                Ok(())
+            }
+        }
+    }
+
+    /// Swaps the queued cards from `path` for how the file reads now, under their new
+    /// hashes where the edit changed them.
+    fn apply_edit(&mut self, path: &Path, saved: &SavedEdit) {
+        let fresh: HashMap<&str, &Card> = saved
+            .cards
+            .iter()
+            .map(|card| (card.card_hash.as_str(), card))
+            .collect();
+        for card in self.cards.iter_mut().chain(self.redo_cards.iter_mut()) {
+            if card.file_path != path {
+                continue;
+            }
+            let card_hash = saved
+                .renamed
+                .get(&card.card_hash)
+                .unwrap_or(&card.card_hash);
+            if let Some(updated) = fresh.get(card_hash.as_str()) {
+                *card = (*updated).clone();
+            }
+        }
+        for hashes in [&mut self.reviewed, &mut self.new_cards, &mut self.ahead] {
+            for (old, new) in &saved.renamed {
+                if hashes.remove(old) {
+                    hashes.insert(new.clone());
+                }
+            }
+        }
+        self.projection = None;
+        self.shown_at = None;
+    }
+
+    fn projected_interval(&self, status: ReviewStatus) -> Option<f64> {
+        self.projection.as_ref().and_then(|projection| {
+            projection
+                .intervals
+                .iter()
+                .find(|(candidate, _)| *candidate == status)
+                .map(|(_, days)| *days)
+        })
+    }
+
+    fn dry_run(&self) -> bool {
+        self.session.options.read_only()
+    }
+
+    /// Writes the remaining queue so an interrupted session can be resumed.
+    fn save_session(&mut self) -> Result<()> {
+        if self.dry_run() {
+            return Ok(());
+        }
+        self.session.remaining = remaining_hashes(&self.cards, self.current_idx, &self.redo_cards);
+        self.session.ahead = self
+            .session
+            .remaining
+            .iter()
+            .filter(|card_hash| self.ahead.contains(*card_hash))
+            .cloned()
+            .collect();
+        self.session.save_to(&self.session_path)
+    }
+
+    fn progress(&self) -> Progress {
+        Progress::new(
+            &remaining_hashes(&self.cards, self.current_idx, &self.redo_cards),
+            &self.reviewed,
+            &self.new_cards,
+            &self.recent_answers,
+        )
+    }
+
+    fn summary(&self) -> SessionSummary {
+        SessionSummary {
+            reviewed: self.reviewed.len(),
+            ahead: self.reviewed.intersection(&self.ahead).count(),
+            time_spent: self.time_spent,
+            grades: self.grades,
+        }
+    }
+
     fn is_complete(&self) -> bool {
         self.current_idx >= self.cards.len() && self.redo_cards.is_empty()
     }
 }
 
-async fn start_drill_session(db: &DB, cards: Vec<Card>) -> Result<()> {
+async fn start_drill_session(
+    db: &DB,
+    cards: Vec<Card>,
+    ahead: HashSet<String>,
+    session: SavedSession,
+    session_path: PathBuf,
+    settings: DrillSettings,
+) -> Result<SessionSummary> {
+    let new_cards = new_hashes(db, &cards).await?;
     enable_raw_mode().context("failed to enable raw mode")?;
     let mut stdout = io::stdout();
     execute!(
@@ -158,17 +1118,28 @@ async fn start_drill_session(db: &DB, cards: Vec<Card>) -> Result<()> {
     let backend = CrosstermBackend::new(stdout);
     let mut terminal = Terminal::new(backend).context("failed to start terminal")?;
     terminal.hide_cursor().context("failed to hide cursor")?;
+    let mut mouse = MouseCapture::enable().context("failed to capture mouse")?;
 
-    let mut state = DrillState::new(db, cards);
+    let mut state = DrillState::new(db, cards, ahead, new_cards, session, session_path, settings);
 
     let loop_result: Result<()> = async {
+        state.save_session()?;
         loop {
             if state.is_complete() {
+                if !state.dry_run() {
+                    SavedSession::clear(&state.session_path)?;
+                }
                 break Ok(());
             }
+            state.refresh_projection().await?;
+            state.shown_at.get_or_insert_with(Instant::now);
 
             terminal
                 .draw(|frame| {
+                    if let Some(edit) = state.editing.as_mut() {
+                        draw_card_edit(frame, edit);
+                        return;
+                    }
                     let card = state
                         .current_card()
                         .expect("card should exist while session is active");
@@ -176,10 +1147,14 @@ async fn start_drill_session(db: &DB, cards: Vec<Card>) -> Result<()> {
                     frame.render_widget(Theme::backdrop(), area);
                     let chunks = Layout::default()
                         .direction(Direction::Vertical)
-                        .constraints([Constraint::Min(5), Constraint::Length(5)])
+                        .constraints([
+                            Constraint::Min(5),
+                            Constraint::Length(1),
+                            Constraint::Length(5),
+                        ])
                         .split(area);
 
-                    let header_line = Line::from(vec![
+                    let mut header = vec![
                         Theme::label_span(format!(
                             "Card {}/{}",
                             state.current_idx + 1,
@@ -189,30 +1164,70 @@ async fn start_drill_session(db: &DB, cards: Vec<Card>) -> Result<()> {
                         Theme::span(format!("{} coming again", state.redo_cards.len())),
                         Theme::bullet(),
                         Theme::span(card.file_path.display().to_string()),
-                    ]);
+                    ];
+                    if state.session.options.cram {
+                        header.extend([Theme::bullet(), Theme::label_span("Cram")]);
+                    } else if state.dry_run() {
+                        header.extend([Theme::bullet(), Theme::label_span("Dry run")]);
+                    }
+                    let header_line = Line::from(header);
 
                     let content = format_card_text(&card, state.show_answer);
-                    let markdown = render_markdown(&content);
+                    // Prose wraps inside the panel borders; code block lines stay whole.
+                    let mut markdown =
+                        render_wrapped_markdown(&content, chunks[0].width.saturating_sub(2));
+                    let images = extract_images(&content, card.file_path.parent());
+                    if !images.is_empty() {
+                        markdown.push_line(Line::default());
+                        markdown.extend(images.iter().map(image_line));
+                    }
+                    if let Some(expected) = expected_answer(&card) {
+                        markdown.push_line(Line::default());
+                        markdown.push_line(answer_line(&state, expected));
+                    }
                     state.current_medias = extract_media(&content, card.file_path.parent());
 
-                    let card_widget = Paragraph::new(markdown)
-                        .block(Theme::panel_with_line(header_line))
-                        .wrap(Wrap { trim: false });
+                    let card_widget =
+                        Paragraph::new(markdown).block(Theme::panel_with_line(header_line));
                     frame.render_widget(card_widget, chunks[0]);
+                    state.card_area = chunks[0];
+                    state.footer_area = chunks[2];
 
-                    let instructions = instructions_text(&state);
+                    let progress = state.progress();
+                    let gauge = LineGauge::default()
+                        .ratio(progress.ratio())
+                        .label(Theme::span(progress.label()))
+                        .filled_style(Theme::label())
+                        .unfilled_style(Theme::gutter());
+                    frame.render_widget(gauge, chunks[1]);
+
+                    let typing = !state.show_answer && expected_answer(&card).is_some();
+                    let instructions = instructions_text(&state, typing, mouse.is_enabled());
                     let footer = Paragraph::new(instructions)
                         .block(Theme::panel_with_line(Theme::section_header("Controls")));
-                    frame.render_widget(footer, chunks[1]);
+                    frame.render_widget(footer, chunks[2]);
                 })
                 .context("failed to render frame")?;
 
-            if event::poll(Duration::from_millis(16))?
-                && let Event::Key(key) = event::read()?
-            {
+            if event::poll(Duration::from_millis(16))? {
+                let key = match event::read()? {
+                    Event::Key(key) => key,
+                    Event::Mouse(mouse_event) => {
+                        if state.editing.is_none() {
+                            handle_mouse(&mut state, mouse_event).await?;
+                        }
+                        continue;
+                    }
+                    _ => continue,
+                };
                 if key.kind != KeyEventKind::Press {
                     continue;
                 }
+                state.notice = None;
+                if state.editing.is_some() {
+                    state.handle_edit_key(key).await?;
+                    continue;
+                }
 
                 if key.code == KeyCode::Esc
                     || (key.code == KeyCode::Char('c')
@@ -220,16 +1235,43 @@ async fn start_drill_session(db: &DB, cards: Vec<Card>) -> Result<()> {
                 {
                     break Ok(());
                 }
+                if state.typing_answer() {
+                    match key.code {
+                        KeyCode::Enter => state.reveal_answer(),
+                        KeyCode::Char('z') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                            state.undo_last().await?
+                        }
+                        KeyCode::Char(c) if !key.modifiers.contains(KeyModifiers::CONTROL) => {
+                            state.answer_input.insert_char(c)
+                        }
+                        KeyCode::Backspace => state.answer_input.backspace(),
+                        KeyCode::Delete => state.answer_input.delete(),
+                        KeyCode::Left => state.answer_input.move_left(),
+                        KeyCode::Right => state.answer_input.move_right(),
+                        KeyCode::Home => state.answer_input.move_home(),
+                        KeyCode::End => state.answer_input.move_end(),
+                        _ => {}
+                    }
+                    continue;
+                }
                 match key.code {
                     KeyCode::Char(' ') | KeyCode::Enter => {
                         if !state.show_answer {
                             state.reveal_answer();
                         } else {
-                            state.handle_review(ReviewStatus::Pass).await?;
+                            state.handle_review(state.default_grade()).await?;
                         }
                     }
-                    KeyCode::Char('F') | KeyCode::Char('f') if state.show_answer => {
-                        state.handle_review(ReviewStatus::Fail).await?;
+                    KeyCode::Char('S') | KeyCode::Char('s') => state.suspend_current().await?,
+                    KeyCode::Char('Z') | KeyCode::Char('z') => state.undo_last().await?,
+                    KeyCode::Char('M') | KeyCode::Char('m') => mouse.toggle()?,
+                    KeyCode::Char('E') | KeyCode::Char('e') if !state.dry_run() => {
+                        state.start_edit()
+                    }
+                    KeyCode::Char(c) if state.show_answer => {
+                        if let Some(status) = ReviewStatus::from_key(c) {
+                            state.handle_review(status).await?;
+                        }
                     }
                     KeyCode::Char('O') | KeyCode::Char('o')
                         if !state.show_answer && !state.current_medias.is_empty() =>
@@ -244,6 +1286,7 @@ async fn start_drill_session(db: &DB, cards: Vec<Card>) -> Result<()> {
     }
     .await;
 
+    mouse.release().context("failed to release mouse")?;
     disable_raw_mode().context("failed to disable raw mode")?;
     execute!(
         terminal.backend_mut(),
@@ -253,26 +1296,225 @@ async fn start_drill_session(db: &DB, cards: Vec<Card>) -> Result<()> {
     .context("failed to restore terminal")?;
     terminal.show_cursor().context("failed to show cursor")?;
 
-    loop_result
+    loop_result.map(|()| state.summary())
 }
 
-fn instructions_text(state: &DrillState<'_>) -> Vec<Line<'static>> {
+/// The card's lines from its file in an editor, with the save and cancel keys below.
+fn draw_card_edit(frame: &mut Frame, edit: &mut CardEdit) {
+    let area = frame.area();
+    frame.render_widget(Theme::backdrop(), area);
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Min(5), Constraint::Length(5)])
+        .split(area);
+    let editor_area = chunks[0];
+    let view_height = editor_area.height.saturating_sub(2) as usize;
+    edit.editor.ensure_cursor_visible(view_height.max(1));
+    let title = format!(
+        "Editing {}:{}",
+        edit.source.path.display(),
+        edit.source.line()
+    );
+    let editor_widget = Paragraph::new(editor_lines(&edit.editor, 0, &[], 0))
+        .block(Theme::panel(title))
+        .scroll((edit.editor.scroll_top() as u16, 0));
+    frame.render_widget(editor_widget, editor_area);
+
+    let mut help = vec![Line::from(vec![
+        Theme::key_chip("Ctrl+S"),
+        Theme::span(" save and resume"),
+        Theme::bullet(),
+        Theme::key_chip("Ctrl+Z"),
+        Theme::span("/"),
+        Theme::key_chip("Ctrl+Y"),
+        Theme::span(" undo/redo"),
+        Theme::bullet(),
+        Theme::key_chip("Esc"),
+        Theme::span(" cancel"),
+    ])];
+    if let Some(error) = &edit.error {
+        help.push(Line::from(Span::styled(error.clone(), Theme::danger())));
+    }
+    let footer = Paragraph::new(help)
+        .block(Theme::panel_with_line(Theme::section_header("Controls")))
+        .wrap(Wrap { trim: true });
+    frame.render_widget(footer, chunks[1]);
+
+    let (row, col) = edit.editor.cursor();
+    let visible_row = row.saturating_sub(edit.editor.scroll_top());
+    let cursor_x = editor_area.x + 1 + (col as u16).min(editor_area.width.saturating_sub(2));
+    let cursor_y =
+        editor_area.y + 1 + (visible_row as u16).min(editor_area.height.saturating_sub(2));
+    frame.set_cursor_position((cursor_x, cursor_y));
+}
+
+/// A click on a grade in the controls grades the card; a click on the card reveals the answer.
+async fn handle_mouse(state: &mut DrillState<'_>, event: MouseEvent) -> Result<()> {
+    if event.kind != MouseEventKind::Down(MouseButton::Left) {
+        return Ok(());
+    }
+    let position = Position::new(event.column, event.row);
+    if !state.show_answer {
+        if state.card_area.contains(position) {
+            state.reveal_answer();
+        }
+        return Ok(());
+    }
+    // The grades are the first line of the controls, just inside the panel border.
+    let footer = state.footer_area;
+    if footer.width > 2 && event.row == footer.y + 1 && event.column > footer.x {
+        let buttons = grade_buttons(state);
+        if let Some(status) = grade_at(&buttons, event.column - footer.x - 1) {
+            state.handle_review(status).await?;
+        }
+    }
+    Ok(())
+}
+
+fn grade_buttons(state: &DrillState<'_>) -> Vec<(ReviewStatus, Vec<Span<'static>>)> {
+    ReviewStatus::ALL
+        .into_iter()
+        .map(|status| {
+            (
+                status,
+                grade_button(status, state.projected_interval(status)),
+            )
+        })
+        .collect()
+}
+
+fn grade_button(status: ReviewStatus, interval: Option<f64>) -> Vec<Span<'static>> {
+    let mut spans = vec![
+        Theme::key_chip(status.key().to_string()),
+        Span::styled(format!(" {}", status.label()), grade_style(status)),
+    ];
+    if let Some(days) = interval {
+        spans.push(Theme::span(format!(" → {}", format_interval(days))));
+    }
+    spans
+}
+
+/// The grade whose button covers `offset` columns into the line, counting each button's
+/// trailing bullet as part of it.
+fn grade_at(buttons: &[(ReviewStatus, Vec<Span<'static>>)], offset: u16) -> Option<ReviewStatus> {
+    let offset = usize::from(offset);
+    let mut start = 0;
+    for (status, spans) in buttons {
+        let end = start + spans.iter().map(Span::width).sum::<usize>();
+        if (start..end).contains(&offset) {
+            return Some(*status);
+        }
+        start = end + Theme::bullet().width();
+    }
+    None
+}
+
+/// An image the terminal can't show, by its alt text and path.
+fn image_line(image: &ImageRef) -> Line<'static> {
+    let mut spans = vec![Theme::label_span("Image: ")];
+    if !image.alt.trim().is_empty() {
+        spans.push(Span::styled(image.alt.clone(), Theme::emphasis()));
+        spans.push(Theme::span(" "));
+    }
+    spans.push(Theme::span(image.path.display().to_string()));
+    if !image.exists() {
+        spans.push(Span::styled(" (missing)", Theme::danger()));
+    }
+    Line::from(spans)
+}
+
+/// The answer field of a type-in card, or once checked, the typed answer marked up
+/// against the expected one.
+fn answer_line(state: &DrillState<'_>, expected: &str) -> Line<'static> {
+    let typed = state.answer_input.content();
+    if !state.show_answer {
+        let (_, cursor) = state.answer_input.cursor();
+        let split = typed
+            .char_indices()
+            .nth(cursor)
+            .map_or(typed.len(), |(i, _)| i);
+        let (before, after) = typed.split_at(split);
+        let mut chars = after.chars();
+        let under_cursor = chars.next().map_or(" ".to_string(), String::from);
+        return Line::from(vec![
+            Theme::label_span("Your answer: "),
+            Theme::span(before.to_string()),
+            Span::styled(under_cursor, Theme::selection()),
+            Theme::span(chars.as_str().to_string()),
+        ]);
+    }
+    let mut spans = vec![Theme::label_span("You typed: ")];
+    for part in diff_answer(&typed, expected, state.settings.type_in_ignore_case) {
+        spans.push(match part {
+            AnswerDiff::Same(text) => Span::styled(text, Theme::success()),
+            AnswerDiff::Extra(text) => {
+                Span::styled(text, Theme::danger().add_modifier(Modifier::CROSSED_OUT))
+            }
+            AnswerDiff::Missing(text) => {
+                Span::styled(text, Theme::emphasis().add_modifier(Modifier::UNDERLINED))
+            }
+        });
+    }
+    spans.push(match state.suggested {
+        Some(ReviewStatus::Good) => Span::styled("  ✓ correct", Theme::success()),
+        _ => Span::styled("  ✗ incorrect", Theme::danger()),
+    });
+    Line::from(spans)
+}
+
+fn instructions_text(
+    state: &DrillState<'_>,
+    typing: bool,
+    mouse_enabled: bool,
+) -> Vec<Line<'static>> {
     let mut lines = Vec::new();
+    let mouse_hint = [
+        Theme::bullet(),
+        Theme::key_chip("M"),
+        Theme::span(if mouse_enabled {
+            " mouse on"
+        } else {
+            " mouse off"
+        }),
+    ];
     if state.show_answer {
-        lines.push(Line::from(vec![
+        let mut line = Vec::new();
+        for (_, button) in grade_buttons(state) {
+            line.extend(button);
+            line.push(Theme::bullet());
+        }
+        let suggestion = if state.suggested.is_some() {
+            " (suggested)"
+        } else {
+            ""
+        };
+        line.extend([
             Theme::key_chip("Space"),
-            Theme::span(" or "),
-            Theme::key_chip("Enter"),
-            Span::styled(" Pass", Theme::success()),
+            Theme::span(format!(" {}{suggestion}", state.default_grade().label())),
             Theme::bullet(),
-            Theme::key_chip("F"),
-            Span::styled(" Fail", Theme::danger()),
+            Theme::key_chip("S"),
+            Theme::span(" suspend"),
             Theme::bullet(),
+        ]);
+        line.extend(undo_hint(state, "Z"));
+        line.extend(edit_hint(state));
+        line.extend([Theme::key_chip("Esc"), Theme::span(" exit")]);
+        line.extend(mouse_hint);
+        lines.push(Line::from(line));
+    } else if typing {
+        let mut line = vec![
+            Theme::key_chip("Enter"),
+            Theme::span(" check answer"),
+            Theme::bullet(),
+        ];
+        line.extend(undo_hint(state, "Ctrl+Z"));
+        line.extend([
             Theme::key_chip("Esc"),
             Theme::span(" / "),
             Theme::key_chip("Ctrl+C"),
             Theme::span(" exit"),
-        ]));
+        ]);
+        lines.push(Line::from(line));
     } else {
         let mut line = vec![
             Theme::key_chip("Space"),
@@ -280,11 +1522,18 @@ fn instructions_text(state: &DrillState<'_>) -> Vec<Line<'static>> {
             Theme::key_chip("Enter"),
             Theme::span(" show answer"),
             Theme::bullet(),
+            Theme::key_chip("S"),
+            Theme::span(" suspend"),
+            Theme::bullet(),
+        ];
+        line.extend(undo_hint(state, "Z"));
+        line.extend(edit_hint(state));
+        line.extend([
             Theme::key_chip("Esc"),
             Theme::span(" / "),
             Theme::key_chip("Ctrl+C"),
             Theme::span(" exit"),
-        ];
+        ]);
         if !state.current_medias.is_empty() {
             let num_media = state.current_medias.len();
             let plural = if num_media == 1 { "" } else { "s" };
@@ -296,37 +1545,158 @@ fn instructions_text(state: &DrillState<'_>) -> Vec<Line<'static>> {
             line.push(Theme::key_chip("O"));
             line.push(Theme::span(" open"));
         }
+        line.extend(mouse_hint);
         lines.push(Line::from(line));
     }
 
     if let Some(action) = &state.last_action
         && action.last_reviewed_at.elapsed().as_secs_f64() < FLASH_SECS
     {
-        let style = match action.action {
-            ReviewStatus::Pass => Theme::success(),
-            ReviewStatus::Fail => Theme::danger(),
-        };
         lines.push(Line::from(vec![
             Theme::span("Last:"),
-            Span::styled(action.print(), style),
+            Span::styled(action.print(), grade_style(action.action)),
         ]));
     }
+    if let Some(notice) = &state.notice {
+        lines.push(Line::from(Span::styled(notice.clone(), Theme::danger())));
+    }
 
     lines
 }
 
+/// The undo key, shown while there is an answer to take back.
+fn undo_hint(state: &DrillState<'_>, key: &'static str) -> Vec<Span<'static>> {
+    if state.undo.is_empty() {
+        return Vec::new();
+    }
+    vec![Theme::key_chip(key), Theme::span(" undo"), Theme::bullet()]
+}
+
+/// The `E` key, which a dry run leaves out since it would write the card file.
+fn edit_hint(state: &DrillState<'_>) -> Vec<Span<'static>> {
+    if state.dry_run() {
+        return Vec::new();
+    }
+    vec![Theme::key_chip("E"), Theme::span(" edit"), Theme::bullet()]
+}
+
+fn grade_style(status: ReviewStatus) -> Style {
+    match status {
+        ReviewStatus::Again => Theme::danger(),
+        ReviewStatus::Hard => Theme::emphasis(),
+        ReviewStatus::Good | ReviewStatus::Easy => Theme::success(),
+    }
+}
+
+fn format_interval(days: f64) -> String {
+    let minutes = days * MINUTES_PER_DAY;
+    if minutes < 60.0 {
+        format!("{}m", minutes.round().max(1.0) as i64)
+    } else if days < 1.0 {
+        format!("{}h", (minutes / 60.0).round() as i64)
+    } else {
+        format!("{}d", days.round() as i64)
+    }
+}
+
+/// The expected answer when `card` is a type-in card.
+fn expected_answer(card: &Card) -> Option<&str> {
+    match &card.content {
+        CardContent::TypeIn { answer, .. } => Some(answer),
+        _ => None,
+    }
+}
+
+fn chars_match(a: char, b: char, ignore_case: bool) -> bool {
+    a == b || (ignore_case && a.to_lowercase().eq(b.to_lowercase()))
+}
+
+/// Whether `typed` is `expected`, ignoring surrounding whitespace.
+fn answer_matches(typed: &str, expected: &str, ignore_case: bool) -> bool {
+    let (typed, expected) = (typed.trim(), expected.trim());
+    typed.chars().count() == expected.chars().count()
+        && typed
+            .chars()
+            .zip(expected.chars())
+            .all(|(a, b)| chars_match(a, b, ignore_case))
+}
+
+/// A run of a typed answer compared against the expected one.
+#[derive(Debug, PartialEq)]
+enum AnswerDiff {
+    Same(String),
+    /// Typed but not in the expected answer.
+    Extra(String),
+    /// In the expected answer but not typed.
+    Missing(String),
+}
+
+/// Character diff of `typed` against `expected` along their longest common subsequence.
+fn diff_answer(typed: &str, expected: &str, ignore_case: bool) -> Vec<AnswerDiff> {
+    let typed: Vec<char> = typed.trim().chars().collect();
+    let expected: Vec<char> = expected.trim().chars().collect();
+    // common[i][j]: longest common subsequence of typed[i..] and expected[j..].
+    let mut common = vec![vec![0usize; expected.len() + 1]; typed.len() + 1];
+    for i in (0..typed.len()).rev() {
+        for j in (0..expected.len()).rev() {
+            common[i][j] = if chars_match(typed[i], expected[j], ignore_case) {
+                common[i + 1][j + 1] + 1
+            } else {
+                common[i + 1][j].max(common[i][j + 1])
+            };
+        }
+    }
+
+    let mut diff: Vec<AnswerDiff> = Vec::new();
+    let mut push = |part: AnswerDiff| {
+        let merged = match (diff.last_mut(), &part) {
+            (Some(AnswerDiff::Same(run)), AnswerDiff::Same(text))
+            | (Some(AnswerDiff::Extra(run)), AnswerDiff::Extra(text))
+            | (Some(AnswerDiff::Missing(run)), AnswerDiff::Missing(text)) => {
+                run.push_str(text);
+                true
+            }
+            _ => false,
+        };
+        if !merged {
+            diff.push(part);
+        }
+    };
+    let (mut i, mut j) = (0, 0);
+    while i < typed.len() || j < expected.len() {
+        if i < typed.len() && j < expected.len() && chars_match(typed[i], expected[j], ignore_case)
+        {
+            push(AnswerDiff::Same(expected[j].to_string()));
+            i += 1;
+            j += 1;
+        } else if j < expected.len() && (i == typed.len() || common[i][j + 1] >= common[i + 1][j]) {
+            push(AnswerDiff::Missing(expected[j].to_string()));
+            j += 1;
+        } else {
+            push(AnswerDiff::Extra(typed[i].to_string()));
+            i += 1;
+        }
+    }
+    diff
+}
+
 fn format_card_text(card: &Card, show_answer: bool) -> String {
     match &card.content {
-        CardContent::Basic { question, answer } => {
+        CardContent::Basic { question, answer } | CardContent::TypeIn { question, answer } => {
             let mut text = format!("Q:\n{}\n\nA:\n", question);
             if show_answer {
                 text.push_str(answer);
             }
             text
         }
-        CardContent::Cloze { text, cloze_range } => {
-            let body = match (cloze_range, show_answer) {
-                (Some(range), false) => mask_cloze_text(text, range),
+        CardContent::Cloze {
+            text,
+            cloze_range,
+            cloze_index,
+        } => {
+            let body = match (cloze_index, cloze_range, show_answer) {
+                (Some(index), _, _) => render_numbered_cloze(text, *index, show_answer),
+                (None, Some(range), false) => mask_cloze_text(text, range),
                 _ => text.clone(),
             };
             format!("C:\n{}", body)
@@ -345,9 +1715,34 @@ fn mask_cloze_text(text: &str, range: &ClozeRange) -> String {
     masked
 }
 
+/// Blanks the deletions of `index` and shows every other cluster's answer as context.
+fn render_numbered_cloze(text: &str, index: u32, show_answer: bool) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut last = 0;
+    for cloze in find_numbered_clozes(text) {
+        out.push_str(&text[last..cloze.start]);
+        if cloze.index != index {
+            out.push_str(&cloze.answer);
+        } else if show_answer {
+            out.push_str(&format!("[{}]", cloze.answer));
+        } else if let Some(hint) = &cloze.hint {
+            out.push_str(&format!("[{hint}]"));
+        } else {
+            out.push_str(&format!(
+                "[{}]",
+                "_".repeat(cloze.answer.chars().count().max(3))
+            ));
+        }
+        last = cloze.end;
+    }
+    out.push_str(&text[last..]);
+    out
+}
+
 #[cfg(test)]
 mod tests {
-    use crate::utils::find_cloze_ranges;
+    use crate::crud::cards::CardState;
+    use crate::utils::{DEFAULT_CARD_SEPARATOR, DEFAULT_DAY_CUTOFF_HOUR, find_cloze_ranges};
 
     use super::*;
     use std::path::PathBuf;
@@ -361,6 +1756,8 @@ mod tests {
                 answer: answer.into(),
             },
             card_hash: "hash".into(),
+            origin: "hash".into(),
+            tags: Vec::new(),
         }
     }
 
@@ -373,8 +1770,11 @@ mod tests {
             content: CardContent::Cloze {
                 text: text.into(),
                 cloze_range: Some(ClozeRange::new(start, end).unwrap()),
+                cloze_index: None,
             },
             card_hash: "hash".into(),
+            origin: "hash".into(),
+            tags: Vec::new(),
         }
     }
 
@@ -389,6 +1789,32 @@ mod tests {
         assert!(shown.contains("Answer"));
     }
 
+    #[test]
+    fn typed_answers_are_checked_and_diffed() {
+        assert!(answer_matches("  Der Hund ", "der Hund", true));
+        assert!(!answer_matches("Der Hund", "der Hund", false));
+        assert!(!answer_matches("der Hun", "der Hund", true));
+
+        assert_eq!(
+            diff_answer("recieve", "receive", false),
+            vec![
+                AnswerDiff::Same("rec".into()),
+                AnswerDiff::Missing("e".into()),
+                AnswerDiff::Same("i".into()),
+                AnswerDiff::Extra("e".into()),
+                AnswerDiff::Same("ve".into()),
+            ]
+        );
+        assert_eq!(
+            diff_answer("HUND", "Hund", true),
+            vec![AnswerDiff::Same("Hund".into())]
+        );
+        assert_eq!(
+            diff_answer("", "Hund", true),
+            vec![AnswerDiff::Missing("Hund".into())]
+        );
+    }
+
     #[test]
     fn mask_cloze_text_handles_unicode_and_bad_ranges() {
         let text = "Capital of 日本 is [東京]";
@@ -432,9 +1858,527 @@ mod tests {
         assert!(revealed.contains("[東京]"));
     }
 
+    #[test]
+    fn numbered_cloze_blanks_only_its_cluster() {
+        let text = "{{c1::Paris}} is the capital of {{c2::France}}, {{c1::Paris}} again";
+
+        let masked = render_numbered_cloze(text, 1, false);
+        assert_eq!(masked, "[_____] is the capital of France, [_____] again");
+
+        let masked = render_numbered_cloze(text, 2, false);
+        assert_eq!(masked, "Paris is the capital of [______], Paris again");
+
+        let revealed = render_numbered_cloze(text, 2, true);
+        assert_eq!(revealed, "Paris is the capital of [France], Paris again");
+    }
+
+    #[test]
+    fn numbered_cloze_shows_hint_in_blank() {
+        let text = "{{c1::Paris::capital city}} is in {{c2::France::country}}";
+
+        assert_eq!(
+            render_numbered_cloze(text, 1, false),
+            "[capital city] is in France"
+        );
+        assert_eq!(render_numbered_cloze(text, 1, true), "[Paris] is in France");
+    }
+
+    #[test]
+    fn next_due_text_counts_from_now() {
+        let now = chrono::Utc::now();
+        assert_eq!(
+            next_due_text(now + chrono::Duration::hours(3), now),
+            "Next card due in 3h."
+        );
+        assert_eq!(
+            next_due_text(now + chrono::Duration::days(2), now),
+            "Next card due in 2d."
+        );
+    }
+
+    #[test]
+    fn new_cards_go_before_or_after_the_reviews() {
+        let cards: Vec<Card> = ["r1", "n1", "r2", "n2", "r3"]
+            .iter()
+            .map(|question| Card {
+                card_hash: question.to_string(),
+                ..basic_card(question, "answer")
+            })
+            .collect();
+        let new = HashSet::from(["n1".to_string(), "n2".to_string()]);
+        let order = |new_first| -> Vec<String> {
+            group_new_cards(cards.clone(), &new, new_first)
+                .into_iter()
+                .map(|card| card.card_hash)
+                .collect()
+        };
+        assert_eq!(order(false), ["r1", "r2", "r3", "n1", "n2"]);
+        assert_eq!(order(true), ["n1", "n2", "r1", "r2", "r3"]);
+    }
+
+    #[tokio::test]
+    async fn random_order_shuffles_reviews_and_new_cards_separately() {
+        let db = DB::new_in_memory().await.unwrap();
+        let now = chrono::Utc::now();
+        let mut hash_cards = HashMap::new();
+        let mut states = Vec::new();
+        for i in 0..12 {
+            let card = Card {
+                card_hash: format!("card{i}"),
+                ..basic_card(&format!("{i}?"), "answer")
+            };
+            db.add_card(&card).await.unwrap();
+            if i % 2 == 0 {
+                states.push(CardState {
+                    card_hash: card.card_hash.clone(),
+                    added_at: now,
+                    last_reviewed_at: Some(now - chrono::Duration::days(4)),
+                    stability: Some(3.0),
+                    difficulty: Some(5.0),
+                    interval_days: Some(3),
+                    due_date: Some(now - chrono::Duration::days(1)),
+                    review_count: 2,
+                });
+            }
+            hash_cards.insert(card.card_hash.clone(), card);
+        }
+        db.import_card_states(&states, true).await.unwrap();
+
+        let shuffled = db
+            .due_today(
+                &hash_cards,
+                None,
+                None,
+                QueueOrder::Random,
+                DEFAULT_DAY_CUTOFF_HOUR,
+            )
+            .await
+            .unwrap();
+        let new = new_hashes(&db, &shuffled).await.unwrap();
+        assert_eq!(new.len(), 6);
+        let hashes = |cards: Vec<Card>| -> Vec<String> {
+            cards.into_iter().map(|card| card.card_hash).collect()
+        };
+        let (shuffled_new, shuffled_reviews): (Vec<String>, Vec<String>) = hashes(shuffled.clone())
+            .into_iter()
+            .partition(|hash| new.contains(hash));
+
+        let reviews_first = hashes(group_new_cards(shuffled.clone(), &new, false));
+        assert_eq!(reviews_first[..6], shuffled_reviews[..]);
+        assert_eq!(reviews_first[6..], shuffled_new[..]);
+        let new_first = hashes(group_new_cards(shuffled, &new, true));
+        assert_eq!(new_first[..6], shuffled_new[..]);
+        assert_eq!(new_first[6..], shuffled_reviews[..]);
+    }
+
+    #[test]
+    fn plan_counts_new_and_review_cards_per_file() {
+        let in_file = |question: &str, path: &str| Card {
+            card_hash: question.into(),
+            file_path: PathBuf::from(path),
+            ..basic_card(question, "answer")
+        };
+        let cards = vec![
+            in_file("a", "spanish.md"),
+            in_file("b", "french.md"),
+            in_file("c", "spanish.md"),
+        ];
+        let new = HashSet::from(["a".to_string()]);
+        assert_eq!(
+            format_plan(&cards, &new, 0),
+            "FILE           NEW  REVIEW\n\
+             french.md        0       1\n\
+             spanish.md       1       1\n\
+             TOTAL            1       2\n\
+             \n\
+             The session would drill 3 cards.\n"
+        );
+        assert!(
+            format_plan(&cards[..1], &new, 1)
+                .ends_with("drill 1 card, 1 of them ahead of schedule.\n")
+        );
+    }
+
+    #[test]
+    fn format_interval_picks_readable_units() {
+        assert_eq!(format_interval(1.0 / MINUTES_PER_DAY), "1m");
+        assert_eq!(format_interval(10.0 / MINUTES_PER_DAY), "10m");
+        assert_eq!(format_interval(0.25), "6h");
+        assert_eq!(format_interval(4.0), "4d");
+    }
+
     fn extract_placeholder(text: &str) -> String {
         let start = text.find('[').unwrap();
         let end = text[start..].find(']').unwrap() + start;
         text[start + 1..end].to_string()
     }
+
+    #[test]
+    fn session_summary_mentions_cards_studied_ahead() {
+        let summary = SessionSummary {
+            reviewed: 3,
+            ahead: 2,
+            time_spent: Duration::ZERO,
+            grades: GradeCounts::default(),
+        };
+        assert_eq!(
+            summary.describe(true).unwrap(),
+            "Reviewed 3 cards, 2 ahead of schedule."
+        );
+        assert_eq!(summary.describe(false).unwrap(), "Reviewed 3 cards.");
+        let timed = SessionSummary {
+            time_spent: Duration::from_secs(252),
+            ..summary
+        };
+        assert_eq!(
+            timed.describe(false).unwrap(),
+            "Reviewed 3 cards in 4m 12s."
+        );
+        let empty = SessionSummary {
+            reviewed: 0,
+            ahead: 0,
+            time_spent: Duration::ZERO,
+            grades: GradeCounts::default(),
+        };
+        assert!(empty.describe(true).is_none());
+        assert!(empty.grade_breakdown().is_none());
+    }
+
+    #[test]
+    fn grade_breakdown_counts_every_answer() {
+        let mut grades = GradeCounts::default();
+        for status in [
+            ReviewStatus::Again,
+            ReviewStatus::Good,
+            ReviewStatus::Good,
+            ReviewStatus::Easy,
+        ] {
+            grades.add(status);
+        }
+        let summary = SessionSummary {
+            reviewed: 3,
+            ahead: 0,
+            time_spent: Duration::ZERO,
+            grades,
+        };
+        assert_eq!(
+            summary.grade_breakdown().unwrap(),
+            "Again 1 • Hard 0 • Good 2 • Easy 1 • 75% recalled"
+        );
+    }
+
+    #[test]
+    fn remaining_hashes_include_cards_coming_again() {
+        let mut first = basic_card("one", "1");
+        first.card_hash = "a".into();
+        let mut second = basic_card("two", "2");
+        second.card_hash = "b".into();
+        let mut again = basic_card("three", "3");
+        again.card_hash = "c".into();
+
+        let cards = [first, second];
+        assert_eq!(
+            remaining_hashes(&cards, 1, std::slice::from_ref(&again)),
+            ["b", "c"]
+        );
+        assert_eq!(remaining_hashes(&cards, 2, &[again]), ["c"]);
+        assert!(remaining_hashes(&cards, 5, &[]).is_empty());
+    }
+
+    #[test]
+    fn progress_counts_requeued_cards_once() {
+        let hashes = |list: &[&str]| list.iter().map(|hash| hash.to_string()).collect::<Vec<_>>();
+        let new_cards: HashSet<String> = hashes(&["a", "b"]).into_iter().collect();
+        let mut reviewed = HashSet::new();
+        let mut recent = VecDeque::new();
+
+        let start = Progress::new(&hashes(&["a", "b", "c"]), &reviewed, &new_cards, &recent);
+        assert_eq!(
+            (start.done, start.total, start.new, start.review),
+            (0, 3, 2, 1)
+        );
+        assert_eq!(start.remaining, None);
+        assert_eq!(start.label(), "0 / 3 reviewed • 2 new • 1 review");
+
+        // `a` was graded Again and comes back after `c`, so it is queued twice.
+        reviewed.insert("a".to_string());
+        recent.extend([Duration::from_secs(4), Duration::from_secs(8)]);
+        let requeued = Progress::new(
+            &hashes(&["b", "a", "c", "a"]),
+            &reviewed,
+            &new_cards,
+            &recent,
+        );
+        assert_eq!((requeued.done, requeued.total), (0, 3));
+        assert_eq!((requeued.new, requeued.review), (1, 2));
+        assert_eq!(requeued.remaining, Some(Duration::from_secs(24)));
+
+        reviewed.extend(["b".to_string(), "c".to_string()]);
+        let last = Progress::new(&hashes(&["a"]), &reviewed, &new_cards, &recent);
+        assert_eq!((last.done, last.total), (2, 3));
+        assert_eq!(last.label(), "2 / 3 reviewed • 0 new • 1 review • ~6s left");
+        assert_eq!(
+            Progress::new(&[], &reviewed, &new_cards, &recent).ratio(),
+            1.0
+        );
+    }
+
+    #[test]
+    fn relearn_position_skips_the_gap_or_defers_past_the_pass() {
+        assert_eq!(relearn_position(0, 10, 3), Some(4));
+        assert_eq!(relearn_position(4, 10, 0), Some(5));
+        assert_eq!(relearn_position(6, 10, 3), Some(10));
+        assert_eq!(relearn_position(7, 10, 3), None);
+        assert_eq!(relearn_position(9, 10, 3), None);
+    }
+
+    #[test]
+    fn grade_at_maps_columns_to_buttons() {
+        let buttons: Vec<_> = ReviewStatus::ALL
+            .into_iter()
+            .map(|status| (status, grade_button(status, None)))
+            .collect();
+        let again_width: usize = buttons[0].1.iter().map(Span::width).sum();
+        let hard_start = (again_width + Theme::bullet().width()) as u16;
+
+        assert_eq!(grade_at(&buttons, 0), Some(ReviewStatus::Again));
+        assert_eq!(
+            grade_at(&buttons, again_width as u16 - 1),
+            Some(ReviewStatus::Again)
+        );
+        assert_eq!(grade_at(&buttons, again_width as u16), None);
+        assert_eq!(grade_at(&buttons, hard_start), Some(ReviewStatus::Hard));
+        assert_eq!(grade_at(&buttons, 500), None);
+    }
+
+    fn test_settings() -> DrillSettings {
+        DrillSettings {
+            params: SchedulerParams::default(),
+            leech_policy: LeechPolicy::default(),
+            max_answer_time: Duration::from_secs(60),
+            relearn_gap: 3,
+            bury_siblings: false,
+            type_in_ignore_case: true,
+            card_separator: DEFAULT_CARD_SEPARATOR.to_string(),
+        }
+    }
+
+    fn sibling(question: &str, origin: &str) -> Card {
+        Card {
+            card_hash: question.into(),
+            origin: origin.into(),
+            ..basic_card(question, "answer")
+        }
+    }
+
+    #[test]
+    fn siblings_of_cards_reviewed_today_wait_for_tomorrow() {
+        let cards = vec![
+            sibling("front", "block"),
+            sibling("back", "block"),
+            sibling("other", "elsewhere"),
+        ];
+        let hash_cards: HashMap<String, Card> = cards
+            .iter()
+            .map(|card| (card.card_hash.clone(), card.clone()))
+            .collect();
+        let reviewed = HashSet::from(["front".to_string()]);
+        let kept: Vec<String> = without_reviewed_siblings(cards, &hash_cards, &reviewed)
+            .into_iter()
+            .map(|card| card.card_hash)
+            .collect();
+        assert_eq!(kept, ["front", "other"]);
+    }
+
+    #[tokio::test]
+    async fn reviewing_a_card_buries_its_queued_siblings() {
+        let db = DB::new_in_memory().await.unwrap();
+        let cards = vec![
+            sibling("front", "block"),
+            sibling("other", "elsewhere"),
+            sibling("back", "block"),
+        ];
+        for card in &cards {
+            db.add_card(card).await.unwrap();
+        }
+        let dir = tempfile::tempdir().unwrap();
+        let options = DrillOptions {
+            card_limit: None,
+            new_card_limit: None,
+            tags: Vec::new(),
+            ahead_days: None,
+            order: QueueOrder::Due,
+            new_first: false,
+            dry_run: false,
+            cram: false,
+        };
+        let mut state = DrillState::new(
+            &db,
+            cards,
+            HashSet::new(),
+            HashSet::new(),
+            SavedSession::new(Vec::new(), options),
+            dir.path().join("session.json"),
+            DrillSettings {
+                bury_siblings: true,
+                ..test_settings()
+            },
+        );
+
+        // Easy skips the learning steps, so neither card comes back this session.
+        state.handle_review(ReviewStatus::Easy).await.unwrap();
+        assert_eq!(state.current_card().unwrap().card_hash, "other");
+        state.handle_review(ReviewStatus::Easy).await.unwrap();
+        assert!(state.is_complete());
+    }
+
+    #[tokio::test]
+    async fn dry_run_grades_without_saving() {
+        let db = DB::new_in_memory().await.unwrap();
+        let card = basic_card("one", "1");
+        db.add_card(&card).await.unwrap();
+        let dir = tempfile::tempdir().unwrap();
+        let session_path = dir.path().join("session.json");
+        let options = DrillOptions {
+            card_limit: None,
+            new_card_limit: None,
+            tags: Vec::new(),
+            ahead_days: None,
+            order: QueueOrder::Due,
+            new_first: false,
+            dry_run: true,
+            cram: false,
+        };
+        let mut state = DrillState::new(
+            &db,
+            vec![card.clone()],
+            HashSet::new(),
+            HashSet::new(),
+            SavedSession::new(Vec::new(), options),
+            session_path.clone(),
+            test_settings(),
+        );
+
+        state.handle_review(ReviewStatus::Again).await.unwrap();
+        assert!(state.is_complete());
+        assert!(state.last_action.unwrap().show_again_duration.unwrap() > 0.0);
+        assert_eq!(
+            db.get_card_performance(&card).await.unwrap(),
+            crate::fsrs::Performance::New
+        );
+        assert!(!session_path.exists());
+    }
+
+    #[tokio::test]
+    async fn undo_takes_back_answers_a_few_cards_deep() {
+        let db = DB::new_in_memory().await.unwrap();
+        let cards: Vec<Card> = ["one", "two", "three"]
+            .iter()
+            .map(|question| Card {
+                card_hash: question.to_string(),
+                origin: question.to_string(),
+                ..basic_card(question, "answer")
+            })
+            .collect();
+        db.add_cards_batch(&cards).await.unwrap();
+        let before: Vec<CardSchedule> = {
+            let mut schedules = Vec::new();
+            for card in &cards {
+                schedules.push(db.card_schedule(card).await.unwrap());
+            }
+            schedules
+        };
+        let dir = tempfile::tempdir().unwrap();
+        let options = DrillOptions {
+            card_limit: None,
+            new_card_limit: None,
+            tags: Vec::new(),
+            ahead_days: None,
+            order: QueueOrder::Due,
+            new_first: false,
+            dry_run: false,
+            cram: false,
+        };
+        let mut state = DrillState::new(
+            &db,
+            cards.clone(),
+            HashSet::new(),
+            HashSet::new(),
+            SavedSession::new(Vec::new(), options),
+            dir.path().join("session.json"),
+            test_settings(),
+        );
+
+        state.handle_review(ReviewStatus::Easy).await.unwrap();
+        state.handle_review(ReviewStatus::Again).await.unwrap();
+        assert_eq!(state.current_idx, 2);
+        assert_eq!(state.grades.count(ReviewStatus::Again), 1);
+
+        state.undo_last().await.unwrap();
+        assert_eq!(state.current_card().unwrap().card_hash, cards[1].card_hash);
+        assert!(!state.show_answer);
+        assert_eq!(state.cards.len(), 3);
+        assert_eq!(state.grades.count(ReviewStatus::Again), 0);
+        assert_eq!(db.card_schedule(&cards[1]).await.unwrap(), before[1]);
+        assert!(db.review_history(&cards[1]).await.unwrap().is_empty());
+
+        state.undo_last().await.unwrap();
+        assert_eq!(state.current_idx, 0);
+        assert!(state.reviewed.is_empty());
+        assert_eq!(db.card_schedule(&cards[0]).await.unwrap(), before[0]);
+        assert!(db.review_history(&cards[0]).await.unwrap().is_empty());
+
+        state.undo_last().await.unwrap();
+        assert_eq!(state.notice.as_deref(), Some("Nothing to undo"));
+        assert_eq!(state.session.remaining.len(), 3);
+    }
+
+    #[tokio::test]
+    async fn cram_leaves_the_database_untouched() {
+        let db = DB::new_in_memory().await.unwrap();
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(
+            dir.path().join("exam.md"),
+            "---\ntags: [exam]\n---\nQ: one?\nA: 1\n\nQ: two?\nA: 2\n",
+        )
+        .unwrap();
+        std::fs::write(dir.path().join("other.md"), "Q: three?\nA: 3\n").unwrap();
+        let session_path = dir.path().join("session.json");
+        let options = DrillOptions {
+            card_limit: None,
+            new_card_limit: None,
+            tags: vec!["exam".into()],
+            ahead_days: None,
+            order: QueueOrder::Due,
+            new_first: false,
+            dry_run: false,
+            cram: true,
+        };
+
+        let cards = cram_cards(
+            &db,
+            &ScanOptions::default(),
+            vec![dir.path().to_path_buf()],
+            &options,
+        )
+        .await
+        .unwrap();
+        assert_eq!(cards.len(), 2);
+        let mut state = DrillState::new(
+            &db,
+            cards,
+            HashSet::new(),
+            HashSet::new(),
+            SavedSession::new(Vec::new(), options),
+            session_path.clone(),
+            test_settings(),
+        );
+        state.handle_review(ReviewStatus::Again).await.unwrap();
+        state.suspend_current().await.unwrap();
+        assert!(state.is_complete());
+        assert_eq!(state.last_action.unwrap().print(), " Again");
+
+        assert!(db.card_rows().await.unwrap().is_empty());
+        assert!(!session_path.exists());
+    }
 }
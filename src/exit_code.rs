@@ -0,0 +1,54 @@
+use crate::utils::MalformedCards;
+
+/// Status `repeater` exits with, so scripts can tell outcomes apart.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ExitCode {
+    Success = 0,
+    Error = 1,
+    /// `drill` found nothing to drill.
+    NothingDue = 2,
+    /// `--strict` refused to skip malformed cards.
+    MalformedCards = 3,
+    /// The database couldn't be opened, migrated, read, or written.
+    Database = 4,
+}
+
+impl ExitCode {
+    /// Code for a failed command, from the first cause in `err`'s chain that has one.
+    pub fn of_error(err: &anyhow::Error) -> Self {
+        for cause in err.chain() {
+            if cause.is::<MalformedCards>() {
+                return Self::MalformedCards;
+            }
+            if cause.is::<sqlx::Error>() || cause.is::<sqlx::migrate::MigrateError>() {
+                return Self::Database;
+            }
+        }
+        Self::Error
+    }
+
+    pub fn code(self) -> i32 {
+        self as i32
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::{Context, anyhow};
+
+    use super::ExitCode;
+    use crate::utils::MalformedCards;
+
+    #[test]
+    fn errors_map_to_their_category() {
+        let malformed = anyhow::Error::from(MalformedCards(Vec::new())).context("Registering");
+        assert_eq!(ExitCode::of_error(&malformed), ExitCode::MalformedCards);
+
+        let database: anyhow::Result<()> = Err(sqlx::Error::PoolClosed.into());
+        let database = database.context("Loading cards").unwrap_err();
+        assert_eq!(ExitCode::of_error(&database), ExitCode::Database);
+        assert_eq!(ExitCode::Database.code(), 4);
+
+        assert_eq!(ExitCode::of_error(&anyhow!("boom")), ExitCode::Error);
+    }
+}
@@ -0,0 +1,174 @@
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result, bail};
+use clap::ValueEnum;
+
+use crate::apkg::write_apkg;
+use crate::card::Card;
+use crate::crud::DB;
+use crate::crud::cards::CardState;
+use crate::csv;
+use crate::utils::{ScanOptions, register_all_cards};
+
+pub const CSV_HEADER: [&str; 9] = [
+    "card_hash",
+    "file_path",
+    "added_at",
+    "last_reviewed_at",
+    "stability",
+    "difficulty",
+    "interval_days",
+    "due_date",
+    "review_count",
+];
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum ExportFormat {
+    Csv,
+    /// Anki package with the card text; scheduling starts over in Anki.
+    Apkg,
+}
+
+pub async fn run(
+    db: &DB,
+    scan: &ScanOptions,
+    paths: Vec<PathBuf>,
+    format: ExportFormat,
+    output: Option<&Path>,
+) -> Result<()> {
+    let card_hashes = register_all_cards(db, scan, paths).await?;
+    match format {
+        ExportFormat::Csv => export_csv(db, &card_hashes, output).await,
+        ExportFormat::Apkg => {
+            let Some(path) = output else {
+                bail!("--format apkg needs an --output file");
+            };
+            export_apkg(&card_hashes, path).await
+        }
+    }
+}
+
+async fn export_csv(
+    db: &DB,
+    card_hashes: &HashMap<String, Card>,
+    output: Option<&Path>,
+) -> Result<()> {
+    let states = db.card_states().await?;
+
+    let mut writer: Box<dyn Write> = match output {
+        Some(path) => {
+            Box::new(BufWriter::new(File::create(path).with_context(|| {
+                format!("Failed to create {}", path.display())
+            })?))
+        }
+        None => Box::new(BufWriter::new(io::stdout().lock())),
+    };
+    write_csv(&mut writer, &states, card_hashes)?;
+    writer.flush()?;
+
+    if let Some(path) = output {
+        let plural = if states.len() == 1 { "" } else { "s" };
+        crate::info!(
+            "Exported {} card{plural} to {}",
+            states.len(),
+            path.display()
+        );
+    }
+    Ok(())
+}
+
+async fn export_apkg(card_hashes: &HashMap<String, Card>, path: &Path) -> Result<()> {
+    let summary = write_apkg(card_hashes, path).await?;
+    let plural = if summary.notes == 1 { "" } else { "s" };
+    crate::info!(
+        "Exported {} note{plural} ({} cards, {} media files) to {}",
+        summary.notes,
+        summary.cards,
+        summary.media,
+        path.display()
+    );
+    if summary.skipped > 0 {
+        eprintln!(
+            "Skipped {} cloze card{} without a [deletion].",
+            summary.skipped,
+            if summary.skipped == 1 { "" } else { "s" }
+        );
+    }
+    Ok(())
+}
+
+/// One row per card in the database; `file_path` is empty for cards not found under the given paths.
+pub fn write_csv(
+    writer: &mut impl Write,
+    states: &[CardState],
+    card_hashes: &HashMap<String, Card>,
+) -> Result<()> {
+    writeln!(writer, "{}", CSV_HEADER.join(","))?;
+    for state in states {
+        let file_path = card_hashes
+            .get(&state.card_hash)
+            .map(|card| card.file_path.display().to_string())
+            .unwrap_or_default();
+        let date = |date: Option<chrono::DateTime<chrono::Utc>>| {
+            date.map(|date| date.to_rfc3339()).unwrap_or_default()
+        };
+        let fields = [
+            state.card_hash.clone(),
+            file_path,
+            state.added_at.to_rfc3339(),
+            date(state.last_reviewed_at),
+            optional(state.stability),
+            optional(state.difficulty),
+            optional(state.interval_days),
+            date(state.due_date),
+            state.review_count.to_string(),
+        ];
+        let fields: Vec<Cow<str>> = fields.iter().map(|value| csv::field(value)).collect();
+        writeln!(writer, "{}", fields.join(","))?;
+    }
+    Ok(())
+}
+
+fn optional(value: Option<impl ToString>) -> String {
+    value.map(|value| value.to_string()).unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::content_to_card;
+
+    #[tokio::test]
+    async fn writes_every_card_with_its_file_path() {
+        let db = DB::new_in_memory().await.unwrap();
+        let path = PathBuf::from("decks/maths, basics.md");
+        let card = content_to_card(&path, "Q: 2+2?\nA: 4", "---", 1, 1).unwrap();
+        let orphan = content_to_card(&path, "Q: gone?\nA: yes", "---", 3, 3).unwrap();
+        db.add_card(&card).await.unwrap();
+        db.add_card(&orphan).await.unwrap();
+        let card_hashes = HashMap::from([(card.card_hash.clone(), card.clone())]);
+
+        let mut out = Vec::new();
+        write_csv(&mut out, &db.card_states().await.unwrap(), &card_hashes).unwrap();
+        let out = String::from_utf8(out).unwrap();
+        let lines: Vec<&str> = out.lines().collect();
+
+        assert_eq!(lines.len(), 3);
+        assert_eq!(lines[0], CSV_HEADER.join(","));
+        let row = lines
+            .iter()
+            .find(|line| line.starts_with(&card.card_hash))
+            .unwrap();
+        assert!(row.contains(",\"decks/maths, basics.md\","));
+        assert!(row.ends_with(",,,,0,,0"));
+        let orphan_row = lines
+            .iter()
+            .find(|line| line.starts_with(&orphan.card_hash))
+            .unwrap();
+        assert!(orphan_row.starts_with(&format!("{},,", orphan.card_hash)));
+    }
+}
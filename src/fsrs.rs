@@ -1,31 +1,154 @@
+use anyhow::{Result, bail};
 use chrono::Duration;
+use serde::Deserialize;
 
-pub const WEIGHTS: [f64; 19] = [
+use crate::sm2;
+use crate::utils::DEFAULT_DAY_CUTOFF_HOUR;
+
+pub const WEIGHT_COUNT: usize = 19;
+pub const DEFAULT_WEIGHTS: [f64; WEIGHT_COUNT] = [
     0.40255, 1.18385, 3.173, 15.69105, 7.1949, 0.5345, 1.4604, 0.0046, 1.54575, 0.1192, 1.01925,
     1.9395, 0.11, 0.29605, 2.2698, 0.2315, 2.9898, 0.51655, 0.6621,
 ];
 
 const F: f64 = 19.0 / 81.0;
 const C: f64 = -0.5;
-const TARGET_RECALL: f64 = 0.9;
+pub const DEFAULT_DESIRED_RETENTION: f64 = 0.9;
+pub const MIN_DESIRED_RETENTION: f64 = 0.7;
+pub const MAX_DESIRED_RETENTION: f64 = 0.97;
 const MIN_INTERVAL: f64 = 1.0;
-const MAX_INTERVAL: f64 = 256.0;
+/// Ceiling on any computed interval, a hundred years, so due dates stay representable.
+pub(crate) const MAX_INTERVAL: f64 = 36_500.0;
+pub const DEFAULT_MAX_INTERVAL_DAYS: usize = 256;
+pub const DEFAULT_LEARNING_STEPS: [Duration; 2] = [Duration::minutes(1), Duration::minutes(10)];
 pub const LEARN_AHEAD_THRESHOLD_MINS: Duration = Duration::minutes(20);
+// (start, end, factor): each day of the interval inside a range widens the fuzz window by factor.
+const FUZZ_RANGES: [(f64, f64, f64); 3] = [
+    (2.5, 7.0, 0.15),
+    (7.0, 20.0, 0.1),
+    (20.0, f64::INFINITY, 0.05),
+];
 
-fn early_interval_cap(review_count: usize, review_status: ReviewStatus) -> Option<Duration> {
-    match review_count {
-        0 => Some(Duration::minutes(1)),
-        1 => match review_status {
-            ReviewStatus::Pass => Some(Duration::minutes(10)),
-            ReviewStatus::Fail => Some(Duration::minutes(1)),
-        },
-        2 => match review_status {
-            ReviewStatus::Pass => Some(Duration::days(1)),
-            ReviewStatus::Fail => Some(Duration::minutes(10)),
-        },
-        _ => None,
+/// Algorithm that turns a grade into the next interval.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Scheduler {
+    #[default]
+    Fsrs,
+    Sm2,
+}
+
+/// How FSRS's fractional interval becomes a whole number of days.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum IntervalRounding {
+    Floor,
+    #[default]
+    Round,
+    Ceil,
+}
+
+impl IntervalRounding {
+    pub fn apply(&self, interval: f64) -> f64 {
+        match self {
+            IntervalRounding::Floor => interval.floor(),
+            IntervalRounding::Round => interval.round(),
+            IntervalRounding::Ceil => interval.ceil(),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct SchedulerParams {
+    pub scheduler: Scheduler,
+    pub weights: [f64; WEIGHT_COUNT],
+    pub desired_retention: f64,
+    pub enable_fuzz: bool,
+    /// No card is scheduled further out than this, fuzz included.
+    pub max_interval_days: usize,
+    /// Sub-day intervals a new card steps through before FSRS takes over.
+    pub learning_steps: Vec<Duration>,
+    pub interval_rounding: IntervalRounding,
+    /// Local hour at which one study day ends and the next begins.
+    pub day_cutoff_hour: u32,
+}
+
+impl Default for SchedulerParams {
+    fn default() -> Self {
+        Self {
+            scheduler: Scheduler::Fsrs,
+            weights: DEFAULT_WEIGHTS,
+            desired_retention: DEFAULT_DESIRED_RETENTION,
+            enable_fuzz: true,
+            max_interval_days: DEFAULT_MAX_INTERVAL_DAYS,
+            learning_steps: DEFAULT_LEARNING_STEPS.to_vec(),
+            interval_rounding: IntervalRounding::Round,
+            day_cutoff_hour: DEFAULT_DAY_CUTOFF_HOUR,
+        }
     }
 }
+
+pub fn validate_desired_retention(retention: f64) -> Result<f64> {
+    if !(MIN_DESIRED_RETENTION..=MAX_DESIRED_RETENTION).contains(&retention) {
+        bail!(
+            "desired retention must be between {} and {}, got {}",
+            MIN_DESIRED_RETENTION,
+            MAX_DESIRED_RETENTION,
+            retention
+        );
+    }
+    Ok(retention)
+}
+
+/// Parses a learning step such as `30s`, `10m`, or `2h`. Steps must be shorter than a day.
+pub fn parse_learning_step(step: &str) -> Result<Duration> {
+    let step = step.trim();
+    let split = step.len() - step.chars().last().map_or(0, char::len_utf8);
+    let (amount, unit) = step.split_at(split);
+    let Ok(amount) = amount.parse::<i64>() else {
+        bail!("learning step `{step}` must be a number followed by s, m, or h, like `10m`");
+    };
+    let duration = match unit {
+        "s" => Duration::seconds(amount),
+        "m" => Duration::minutes(amount),
+        "h" => Duration::hours(amount),
+        _ => bail!("learning step `{step}` must be a number followed by s, m, or h, like `10m`"),
+    };
+    if duration <= Duration::zero() || duration >= Duration::days(1) {
+        bail!("learning step `{step}` must be longer than zero and shorter than a day");
+    }
+    Ok(duration)
+}
+
+/// Caps the interval of a card still in its learning steps. The `n`th review of a card
+/// waits `steps[n]`, or one step back after `Again`; the review after the last step
+/// graduates it with at most a day. Easy graduates a card immediately.
+fn early_interval_cap(
+    steps: &[Duration],
+    review_count: usize,
+    review_status: ReviewStatus,
+) -> Option<Duration> {
+    if review_status == ReviewStatus::Easy || review_count > steps.len() {
+        return None;
+    }
+    if review_status == ReviewStatus::Again {
+        return steps.get(review_count.saturating_sub(1)).copied();
+    }
+    Some(
+        steps
+            .get(review_count)
+            .copied()
+            .unwrap_or(Duration::days(1)),
+    )
+}
+pub fn elapsed_days(
+    since: chrono::DateTime<chrono::Utc>,
+    until: chrono::DateTime<chrono::Utc>,
+) -> f64 {
+    let days = until.signed_duration_since(since).num_seconds() as f64 / 86_400.0;
+    days.max(0.0)
+}
+
 pub fn calculate_recall(interval: f64, stability: f64) -> f64 {
     (1.0 + F * (interval / stability)).powf(C)
 }
@@ -34,33 +157,39 @@ pub fn calulate_interval(recall: f64, stability: f64) -> f64 {
     (stability / F) * (recall.powf(1.0 / C) - 1.0)
 }
 
-pub fn initial_stability(review_status: ReviewStatus) -> f64 {
-    match review_status {
-        ReviewStatus::Fail => WEIGHTS[0],
-        ReviewStatus::Pass => WEIGHTS[2],
-    }
+pub fn initial_stability(w: &[f64; WEIGHT_COUNT], review_status: ReviewStatus) -> f64 {
+    w[review_status.score() - 1]
 }
 
 fn calculate_stability(
+    w: &[f64; WEIGHT_COUNT],
     difficulty: f64,
     stability: f64,
     recall: f64,
     review_status: ReviewStatus,
 ) -> f64 {
-    if review_status == ReviewStatus::Fail {
-        let d_f = difficulty.powf(-WEIGHTS[12]);
-        let s_f = (stability + 1.0).powf(WEIGHTS[13]) - 1.0;
-        let r_f = f64::exp(WEIGHTS[14] * (1.0 - recall));
-        let c_f = WEIGHTS[11];
+    if review_status == ReviewStatus::Again {
+        let d_f = difficulty.powf(-w[12]);
+        let s_f = (stability + 1.0).powf(w[13]) - 1.0;
+        let r_f = f64::exp(w[14] * (1.0 - recall));
+        let c_f = w[11];
         let s_f = d_f * s_f * r_f * c_f;
         return f64::min(s_f, stability);
     }
     let t_d = 11.0 - difficulty;
-    let t_s = stability.powf(-WEIGHTS[9]);
-    let t_r = f64::exp(WEIGHTS[10] * (1.0 - recall)) - 1.0;
-    let h = 1.0;
-    let b = 1.0;
-    let c = f64::exp(WEIGHTS[8]);
+    let t_s = stability.powf(-w[9]);
+    let t_r = f64::exp(w[10] * (1.0 - recall)) - 1.0;
+    let h = if review_status == ReviewStatus::Hard {
+        w[15]
+    } else {
+        1.0
+    };
+    let b = if review_status == ReviewStatus::Easy {
+        w[16]
+    } else {
+        1.0
+    };
+    let c = f64::exp(w[8]);
     let alpha = 1.0 + t_d * t_s * t_r * h * b * c;
     stability * alpha
 }
@@ -69,46 +198,83 @@ fn clamp_difficulty(difficulty: f64) -> f64 {
     difficulty.clamp(1.0, 10.0)
 }
 
-pub fn initial_difficulty(review_status: ReviewStatus) -> f64 {
+pub fn initial_difficulty(w: &[f64; WEIGHT_COUNT], review_status: ReviewStatus) -> f64 {
     let g: f64 = review_status.score() as f64;
-    clamp_difficulty(WEIGHTS[4] - f64::exp(WEIGHTS[5] * (g - 1.0)) + 1.0)
+    clamp_difficulty(w[4] - f64::exp(w[5] * (g - 1.0)) + 1.0)
 }
 
-pub fn new_difficulty(difficulty: f64, review_status: ReviewStatus) -> f64 {
+pub fn new_difficulty(
+    w: &[f64; WEIGHT_COUNT],
+    difficulty: f64,
+    review_status: ReviewStatus,
+) -> f64 {
     clamp_difficulty(
-        WEIGHTS[7] * initial_difficulty(ReviewStatus::Pass)
-            + (1.0 - WEIGHTS[7]) * dp(difficulty, review_status),
+        w[7] * initial_difficulty(w, ReviewStatus::Good)
+            + (1.0 - w[7]) * dp(w, difficulty, review_status),
     )
 }
 
-fn dp(difficulty: f64, review_status: ReviewStatus) -> f64 {
-    difficulty + delta_d(review_status) * ((10.0 - difficulty) / 9.0)
+fn dp(w: &[f64; WEIGHT_COUNT], difficulty: f64, review_status: ReviewStatus) -> f64 {
+    difficulty + delta_d(w, review_status) * ((10.0 - difficulty) / 9.0)
 }
 
-fn delta_d(review_status: ReviewStatus) -> f64 {
+fn delta_d(w: &[f64; WEIGHT_COUNT], review_status: ReviewStatus) -> f64 {
     let g: f64 = review_status.score() as f64;
-    -WEIGHTS[6] * (g - 3.0)
+    -w[6] * (g - 3.0)
 }
 
-#[derive(Copy, Clone, PartialEq)]
+/// Grade given to a revealed card. Keys and labels live here so every frontend maps input the same way.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
 pub enum ReviewStatus {
-    Pass,
-    Fail,
+    Again,
+    Hard,
+    Good,
+    Easy,
 }
 
 impl ReviewStatus {
+    pub const ALL: [ReviewStatus; 4] = [
+        ReviewStatus::Again,
+        ReviewStatus::Hard,
+        ReviewStatus::Good,
+        ReviewStatus::Easy,
+    ];
+
     pub fn label(&self) -> &'static str {
         match self {
-            ReviewStatus::Pass => "Pass",
-            ReviewStatus::Fail => "Fail",
+            ReviewStatus::Again => "Again",
+            ReviewStatus::Hard => "Hard",
+            ReviewStatus::Good => "Good",
+            ReviewStatus::Easy => "Easy",
         }
     }
     pub fn score(&self) -> usize {
         match self {
-            ReviewStatus::Pass => 3,
-            ReviewStatus::Fail => 1,
+            ReviewStatus::Again => 1,
+            ReviewStatus::Hard => 2,
+            ReviewStatus::Good => 3,
+            ReviewStatus::Easy => 4,
+        }
+    }
+    pub fn key(&self) -> char {
+        match self {
+            ReviewStatus::Again => '1',
+            ReviewStatus::Hard => '2',
+            ReviewStatus::Good => '3',
+            ReviewStatus::Easy => '4',
         }
     }
+    pub fn from_score(score: i64) -> Option<Self> {
+        Self::ALL
+            .into_iter()
+            .find(|status| status.score() as i64 == score)
+    }
+    pub fn from_key(key: char) -> Option<Self> {
+        Self::ALL.into_iter().find(|status| status.key() == key)
+    }
+    pub fn is_pass(&self) -> bool {
+        *self != ReviewStatus::Again
+    }
 }
 
 #[derive(Clone, Copy, Debug, PartialEq)]
@@ -122,6 +288,44 @@ pub struct ReviewedPerformance {
     pub review_count: usize,
 }
 
+impl ReviewedPerformance {
+    /// Days until the card is due again, including any fuzz or early-review cap.
+    pub fn scheduled_days(&self) -> f64 {
+        elapsed_days(self.last_reviewed_at, self.due_date)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct MemoryState {
+    pub stability: f64,
+    pub difficulty: f64,
+}
+
+/// Memory state after a review `elapsed` days after `previous`, or after the first review when `None`.
+pub fn next_memory_state(
+    w: &[f64; WEIGHT_COUNT],
+    previous: Option<MemoryState>,
+    elapsed: f64,
+    review_status: ReviewStatus,
+) -> MemoryState {
+    match previous {
+        None => MemoryState {
+            stability: initial_stability(w, review_status),
+            difficulty: initial_difficulty(w, review_status),
+        },
+        Some(MemoryState {
+            stability,
+            difficulty,
+        }) => {
+            let recall = calculate_recall(elapsed, stability);
+            MemoryState {
+                stability: calculate_stability(w, difficulty, stability, recall, review_status),
+                difficulty: new_difficulty(w, difficulty, review_status),
+            }
+        }
+    }
+}
+
 #[derive(Clone, Copy, Debug, PartialEq, Default)]
 pub enum Performance {
     #[default]
@@ -133,42 +337,40 @@ pub fn update_performance(
     perf: Performance,
     review_status: ReviewStatus,
     reviewed_at: chrono::DateTime<chrono::Utc>,
+    params: &SchedulerParams,
 ) -> ReviewedPerformance {
-    let (stability, difficulty, review_count): (f64, f64, usize) = match perf {
-        Performance::New => (
-            initial_stability(review_status),
-            initial_difficulty(review_status),
-            0,
+    let (previous, elapsed, review_count) = match perf {
+        Performance::New => (None, 0.0, 0),
+        Performance::Reviewed(reviewed) => (
+            Some(MemoryState {
+                stability: reviewed.stability,
+                difficulty: reviewed.difficulty,
+            }),
+            elapsed_days(reviewed.last_reviewed_at, reviewed_at),
+            reviewed.review_count,
         ),
-        Performance::Reviewed(ReviewedPerformance {
-            last_reviewed_at,
-            stability,
-            difficulty,
-            review_count,
-            ..
-        }) => {
-            let elapsed_days = reviewed_at
-                .signed_duration_since(last_reviewed_at)
-                .num_seconds() as f64
-                / 86_400.0;
-            let recall = calculate_recall(elapsed_days.max(0.0), stability);
-            let stability = calculate_stability(difficulty, stability, recall, review_status);
-            let difficulty = new_difficulty(difficulty, review_status);
-            (stability, difficulty, review_count)
-        }
     };
-    let interval_raw: f64 = calulate_interval(TARGET_RECALL, stability);
-    let interval_rounded: f64 = interval_raw.round();
-    let interval_clamped: f64 = interval_rounded.clamp(MIN_INTERVAL, MAX_INTERVAL);
-    let fsrs_duration = Duration::days(interval_clamped as i64);
-
-    let interval_duration = early_interval_cap(review_count, review_status)
-        .map(|cap| fsrs_duration.min(cap))
-        .unwrap_or(fsrs_duration);
-    let interval_effective_days = interval_duration.num_seconds() as f64 / 86_400.0;
+    let MemoryState {
+        stability,
+        difficulty,
+    } = next_memory_state(&params.weights, previous, elapsed, review_status);
+    let interval_precise: f64 =
+        calulate_interval(params.desired_retention, stability).clamp(MIN_INTERVAL, MAX_INTERVAL);
+    let interval_rounded: f64 = params
+        .interval_rounding
+        .apply(interval_precise)
+        .clamp(MIN_INTERVAL, MAX_INTERVAL);
+    let fsrs_duration = Duration::days(interval_rounded as i64);
 
-    let interval_raw = interval_effective_days;
+    // Kept unrounded and uncapped, so raising `max_interval_days` later doesn't lose what
+    // FSRS computed; a learning step shorter than the interval replaces it.
+    let (interval_duration, interval_raw) =
+        match early_interval_cap(&params.learning_steps, review_count, review_status) {
+            Some(cap) if cap < fsrs_duration => (cap, cap.num_seconds() as f64 / 86_400.0),
+            _ => (fsrs_duration, interval_precise),
+        };
 
+    let interval_duration = interval_duration.min(Duration::days(params.max_interval_days as i64));
     let interval_days: usize = interval_duration.num_days().max(1) as usize;
     let due_date: chrono::DateTime<chrono::Utc> = reviewed_at + interval_duration;
     ReviewedPerformance {
@@ -182,12 +384,68 @@ pub fn update_performance(
     }
 }
 
+pub fn schedule_review(
+    card_hash: &str,
+    perf: Performance,
+    review_status: ReviewStatus,
+    reviewed_at: chrono::DateTime<chrono::Utc>,
+    params: &SchedulerParams,
+) -> ReviewedPerformance {
+    let mut next = match params.scheduler {
+        Scheduler::Fsrs => update_performance(perf, review_status, reviewed_at, params),
+        Scheduler::Sm2 => sm2::update_performance(perf, review_status, reviewed_at, params),
+    };
+    // A capped interval stays on the cap rather than being fuzzed below it.
+    if params.enable_fuzz && next.interval_days < params.max_interval_days {
+        let fraction = fuzz_fraction(card_hash, next.review_count);
+        let fuzzed_days = fuzz_interval(next.interval_days, fraction);
+        if fuzzed_days != next.interval_days {
+            next.due_date += Duration::days(fuzzed_days as i64 - next.interval_days as i64);
+            next.interval_days = fuzzed_days;
+        }
+    }
+    if next.interval_days > params.max_interval_days {
+        next.due_date -= Duration::days((next.interval_days - params.max_interval_days) as i64);
+        next.interval_days = params.max_interval_days;
+    }
+    next
+}
+
+fn fuzz_fraction(card_hash: &str, review_count: usize) -> f64 {
+    let seed = blake3::hash(format!("{card_hash}:{review_count}").as_bytes());
+    let mut bytes = [0u8; 8];
+    bytes.copy_from_slice(&seed.as_bytes()[..8]);
+    u64::from_le_bytes(bytes) as f64 / u64::MAX as f64
+}
+
+fn fuzz_delta(interval: f64) -> f64 {
+    if interval < FUZZ_RANGES[0].0 {
+        return 0.0;
+    }
+    FUZZ_RANGES.iter().fold(1.0, |delta, (start, end, factor)| {
+        delta + factor * (interval.min(*end) - start).max(0.0)
+    })
+}
+
+pub fn fuzz_interval(interval_days: usize, fraction: f64) -> usize {
+    let interval = interval_days as f64;
+    let delta = fuzz_delta(interval);
+    if delta == 0.0 {
+        return interval_days;
+    }
+    let min = (interval - delta).round().max(2.0);
+    let max = (interval + delta).round();
+    (min + fraction * (max - min + 1.0)).floor().min(max) as usize
+}
+
 #[cfg(test)]
 mod tests {
 
     use super::{
-        MAX_INTERVAL, MIN_INTERVAL, Performance, ReviewStatus, ReviewedPerformance,
-        update_performance,
+        DEFAULT_LEARNING_STEPS, DEFAULT_MAX_INTERVAL_DAYS, DEFAULT_WEIGHTS, IntervalRounding,
+        MIN_INTERVAL, Performance, ReviewStatus, ReviewedPerformance, SchedulerParams,
+        early_interval_cap, fuzz_interval, parse_learning_step, schedule_review,
+        update_performance, validate_desired_retention,
     };
 
     use chrono::Duration;
@@ -199,7 +457,12 @@ mod tests {
     #[test]
     fn test_update_new_card() {
         let reviewed_at = chrono::Utc::now();
-        let result = update_performance(Performance::New, ReviewStatus::Pass, reviewed_at);
+        let result = update_performance(
+            Performance::New,
+            ReviewStatus::Good,
+            reviewed_at,
+            &SchedulerParams::default(),
+        );
         let ReviewedPerformance {
             last_reviewed_at,
             stability,
@@ -234,8 +497,9 @@ mod tests {
         let reviewed_at = now;
         let result = update_performance(
             Performance::Reviewed(initial_perf),
-            ReviewStatus::Pass,
+            ReviewStatus::Good,
             reviewed_at,
+            &SchedulerParams::default(),
         );
         let ReviewedPerformance {
             last_reviewed_at,
@@ -257,18 +521,25 @@ mod tests {
     #[test]
     fn test_reviews() {
         let mut reviewed_at = chrono::Utc::now();
-        let mut performance = update_performance(Performance::New, ReviewStatus::Pass, reviewed_at);
+        let mut performance = update_performance(
+            Performance::New,
+            ReviewStatus::Good,
+            reviewed_at,
+            &SchedulerParams::default(),
+        );
         for _ in 0..100 {
             let interval_raw = performance.interval_raw;
             let interval_rounded: f64 = interval_raw.round();
-            let interval_clamped: f64 = interval_rounded.clamp(MIN_INTERVAL, MAX_INTERVAL);
+            let interval_clamped: f64 =
+                interval_rounded.clamp(MIN_INTERVAL, DEFAULT_MAX_INTERVAL_DAYS as f64);
             let interval_duration: Duration = Duration::days(interval_clamped as i64);
             reviewed_at += interval_duration;
 
             performance = update_performance(
                 Performance::Reviewed(performance),
-                ReviewStatus::Pass,
+                ReviewStatus::Good,
                 reviewed_at,
+                &SchedulerParams::default(),
             );
         }
         assert_eq!(performance.review_count, 101);
@@ -279,14 +550,16 @@ mod tests {
         for _ in 0..100 {
             let interval_raw = performance.interval_raw;
             let interval_rounded: f64 = interval_raw.round();
-            let interval_clamped: f64 = interval_rounded.clamp(MIN_INTERVAL, MAX_INTERVAL);
+            let interval_clamped: f64 =
+                interval_rounded.clamp(MIN_INTERVAL, DEFAULT_MAX_INTERVAL_DAYS as f64);
             let interval_duration: Duration = Duration::days(interval_clamped as i64);
             reviewed_at += interval_duration;
 
             performance = update_performance(
                 Performance::Reviewed(performance),
-                ReviewStatus::Fail,
+                ReviewStatus::Again,
                 reviewed_at,
+                &SchedulerParams::default(),
             );
         }
         assert_eq!(performance.review_count, 201);
@@ -294,4 +567,232 @@ mod tests {
         assert!(approx_eq(performance.difficulty, 9.9337));
         assert!(approx_eq(performance.stability, 0.148424));
     }
+
+    #[test]
+    fn lower_retention_schedules_longer_intervals() {
+        let now = chrono::Utc::now();
+        let reviewed = ReviewedPerformance {
+            last_reviewed_at: now - Duration::days(10),
+            stability: 10.0,
+            difficulty: 5.0,
+            interval_raw: 10.0,
+            interval_days: 10,
+            due_date: now,
+            review_count: 5,
+        };
+        let strict = SchedulerParams {
+            desired_retention: 0.95,
+            ..SchedulerParams::default()
+        };
+        let relaxed = SchedulerParams {
+            desired_retention: 0.8,
+            ..SchedulerParams::default()
+        };
+        let strict_interval = update_performance(
+            Performance::Reviewed(reviewed),
+            ReviewStatus::Good,
+            now,
+            &strict,
+        )
+        .interval_raw;
+        let relaxed_interval = update_performance(
+            Performance::Reviewed(reviewed),
+            ReviewStatus::Good,
+            now,
+            &relaxed,
+        )
+        .interval_raw;
+        assert!(relaxed_interval > strict_interval);
+    }
+
+    #[test]
+    fn interval_rounding_turns_a_borderline_interval_into_whole_days() {
+        let now = chrono::Utc::now();
+        // At 90% retention the interval equals the stability, so an Easy first review
+        // lands on 4.6 days.
+        let mut weights = DEFAULT_WEIGHTS;
+        weights[3] = 4.6;
+        for (rounding, days) in [
+            (IntervalRounding::Floor, 4),
+            (IntervalRounding::Round, 5),
+            (IntervalRounding::Ceil, 5),
+        ] {
+            let params = SchedulerParams {
+                weights,
+                interval_rounding: rounding,
+                ..SchedulerParams::default()
+            };
+            let performance =
+                update_performance(Performance::New, ReviewStatus::Easy, now, &params);
+            assert!(approx_eq(performance.interval_raw, 4.6), "{rounding:?}");
+            assert_eq!(performance.interval_days, days, "{rounding:?}");
+            assert_eq!(performance.due_date, now + Duration::days(days as i64));
+        }
+
+        weights[3] = 4.2;
+        let ceil = SchedulerParams {
+            weights,
+            interval_rounding: IntervalRounding::Ceil,
+            ..SchedulerParams::default()
+        };
+        let performance = update_performance(Performance::New, ReviewStatus::Easy, now, &ceil);
+        assert_eq!(performance.interval_days, 5);
+    }
+
+    #[test]
+    fn desired_retention_must_be_in_range() {
+        assert!(validate_desired_retention(0.85).is_ok());
+        assert!(validate_desired_retention(0.7).is_ok());
+        assert!(validate_desired_retention(0.97).is_ok());
+        assert!(validate_desired_retention(0.5).is_err());
+        assert!(validate_desired_retention(0.99).is_err());
+    }
+
+    #[test]
+    fn fuzz_stays_within_range_and_skips_short_intervals() {
+        assert_eq!(fuzz_interval(1, 0.99), 1);
+        assert_eq!(fuzz_interval(2, 0.99), 2);
+        for fraction in [0.0, 0.25, 0.5, 0.75, 0.999] {
+            let fuzzed = fuzz_interval(100, fraction) as f64;
+            assert!((93.0..=107.0).contains(&fuzzed), "{fuzzed}");
+        }
+        assert!(fuzz_interval(100, 0.0) < fuzz_interval(100, 0.999));
+    }
+
+    #[test]
+    fn fuzz_is_deterministic_per_card_and_can_be_disabled() {
+        let now = chrono::Utc::now();
+        let reviewed = ReviewedPerformance {
+            last_reviewed_at: now - Duration::days(30),
+            stability: 30.0,
+            difficulty: 5.0,
+            interval_raw: 30.0,
+            interval_days: 30,
+            due_date: now,
+            review_count: 5,
+        };
+        let perf = Performance::Reviewed(reviewed);
+        let params = SchedulerParams::default();
+        let first = schedule_review("card", perf, ReviewStatus::Good, now, &params);
+        let second = schedule_review("card", perf, ReviewStatus::Good, now, &params);
+        assert_eq!(first, second);
+        assert_eq!(
+            first.due_date,
+            now + Duration::days(first.interval_days as i64)
+        );
+
+        let exact = SchedulerParams {
+            enable_fuzz: false,
+            ..SchedulerParams::default()
+        };
+        let unfuzzed = schedule_review("card", perf, ReviewStatus::Good, now, &exact);
+        assert_eq!(
+            unfuzzed,
+            update_performance(perf, ReviewStatus::Good, now, &exact)
+        );
+    }
+
+    #[test]
+    fn intervals_are_pinned_to_the_cap_after_fuzz() {
+        let now = chrono::Utc::now();
+        let reviewed = ReviewedPerformance {
+            last_reviewed_at: now - Duration::days(100),
+            stability: 100.0,
+            difficulty: 5.0,
+            interval_raw: 100.0,
+            interval_days: 100,
+            due_date: now,
+            review_count: 5,
+        };
+        let perf = Performance::Reviewed(reviewed);
+        let params = SchedulerParams {
+            max_interval_days: 30,
+            ..SchedulerParams::default()
+        };
+        for card_hash in ["a", "b", "c", "d"] {
+            let next = schedule_review(card_hash, perf, ReviewStatus::Easy, now, &params);
+            assert_eq!(next.interval_days, 30);
+            assert_eq!(next.due_date, now + Duration::days(30));
+            assert!(next.interval_raw > 30.0);
+        }
+    }
+
+    #[test]
+    fn learning_steps_step_back_on_again_and_graduate_after_the_last() {
+        let steps = DEFAULT_LEARNING_STEPS;
+        let cap = |count, status| early_interval_cap(&steps, count, status);
+        assert_eq!(cap(0, ReviewStatus::Good), Some(Duration::minutes(1)));
+        assert_eq!(cap(0, ReviewStatus::Again), Some(Duration::minutes(1)));
+        assert_eq!(cap(1, ReviewStatus::Good), Some(Duration::minutes(10)));
+        assert_eq!(cap(1, ReviewStatus::Again), Some(Duration::minutes(1)));
+        assert_eq!(cap(2, ReviewStatus::Hard), Some(Duration::days(1)));
+        assert_eq!(cap(2, ReviewStatus::Again), Some(Duration::minutes(10)));
+        assert_eq!(cap(3, ReviewStatus::Good), None);
+        assert_eq!(cap(0, ReviewStatus::Easy), None);
+
+        let long = [
+            Duration::minutes(5),
+            Duration::minutes(30),
+            Duration::hours(2),
+        ];
+        assert_eq!(
+            early_interval_cap(&long, 2, ReviewStatus::Good),
+            Some(Duration::hours(2))
+        );
+        assert_eq!(
+            early_interval_cap(&long, 3, ReviewStatus::Good),
+            Some(Duration::days(1))
+        );
+        assert_eq!(
+            early_interval_cap(&[], 0, ReviewStatus::Good),
+            Some(Duration::days(1))
+        );
+        assert_eq!(early_interval_cap(&[], 0, ReviewStatus::Again), None);
+    }
+
+    #[test]
+    fn parses_learning_steps() {
+        assert_eq!(parse_learning_step("30s").unwrap(), Duration::seconds(30));
+        assert_eq!(parse_learning_step(" 10m ").unwrap(), Duration::minutes(10));
+        assert_eq!(parse_learning_step("2h").unwrap(), Duration::hours(2));
+        assert!(parse_learning_step("1d").is_err());
+        assert!(parse_learning_step("24h").is_err());
+        assert!(parse_learning_step("0m").is_err());
+        assert!(parse_learning_step("m").is_err());
+        assert!(parse_learning_step("").is_err());
+    }
+
+    #[test]
+    fn grades_map_to_number_keys() {
+        for (idx, status) in ReviewStatus::ALL.iter().enumerate() {
+            assert_eq!(status.score(), idx + 1);
+            assert_eq!(ReviewStatus::from_key(status.key()), Some(*status));
+        }
+        assert_eq!(ReviewStatus::from_key('5'), None);
+        assert!(!ReviewStatus::Again.is_pass());
+        assert!(ReviewStatus::Hard.is_pass());
+    }
+
+    #[test]
+    fn higher_grades_schedule_longer_intervals() {
+        let now = chrono::Utc::now();
+        let reviewed = ReviewedPerformance {
+            last_reviewed_at: now - Duration::days(10),
+            stability: 10.0,
+            difficulty: 5.0,
+            interval_raw: 10.0,
+            interval_days: 10,
+            due_date: now,
+            review_count: 5,
+        };
+        let params = SchedulerParams::default();
+        let intervals: Vec<f64> = ReviewStatus::ALL
+            .iter()
+            .map(|status| {
+                update_performance(Performance::Reviewed(reviewed), *status, now, &params)
+                    .interval_raw
+            })
+            .collect();
+        assert!(intervals.windows(2).all(|pair| pair[0] < pair[1]));
+    }
 }
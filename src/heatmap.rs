@@ -0,0 +1,189 @@
+use std::collections::BTreeMap;
+use std::fmt::Write;
+
+use chrono::{Datelike, Duration, NaiveDate};
+
+use crate::crud::ReviewPeriod;
+
+pub const HEATMAP_DAYS: i64 = 365;
+const SHADES: [char; 4] = ['░', '▒', '▓', '█'];
+const EMPTY: char = '·';
+const ROW_LABELS: [&str; 7] = ["Mon", "", "Wed", "", "Fri", "", "Sun"];
+const LABEL_WIDTH: usize = 4;
+
+/// Reviews per day of `period` drawn as a week-by-week grid, oldest week on the left,
+/// Monday on top.
+pub fn format_heatmap(
+    per_day: &BTreeMap<NaiveDate, usize>,
+    period: ReviewPeriod,
+    today: NaiveDate,
+) -> String {
+    let (first_day, last_day, days) = (period.first, period.last, period.days());
+    let grid_start =
+        first_day - Duration::days(i64::from(first_day.weekday().num_days_from_monday()));
+    let weeks = ((last_day - grid_start).num_days() / 7 + 1) as usize;
+    let count = |day: NaiveDate| per_day.get(&day).copied().unwrap_or(0);
+    let max = per_day
+        .iter()
+        .filter(|(day, _)| (first_day..=last_day).contains(day))
+        .map(|(_, count)| *count)
+        .max()
+        .unwrap_or(0);
+
+    let mut out = String::new();
+    let _ = writeln!(out, "{}", month_labels(grid_start, weeks));
+    for (weekday, label) in ROW_LABELS.iter().enumerate() {
+        let mut line = format!("{label:<LABEL_WIDTH$}");
+        for week in 0..weeks {
+            let day = grid_start + Duration::days((week * 7 + weekday) as i64);
+            line.push(if day < first_day || day > last_day {
+                ' '
+            } else {
+                shade(count(day), max)
+            });
+        }
+        let _ = writeln!(out, "{}", line.trim_end());
+    }
+    let legend: String = SHADES.iter().map(|shade| format!(" {shade}")).collect();
+    let _ = writeln!(out, "{:LABEL_WIDTH$}Less {EMPTY}{legend} More", "");
+
+    let total: usize = (0..days)
+        .map(|offset| count(first_day + Duration::days(offset)))
+        .sum();
+    let active = (0..days)
+        .filter(|offset| count(first_day + Duration::days(*offset)) > 0)
+        .count();
+    let plural = if total == 1 { "" } else { "s" };
+    let span = if last_day == today {
+        format!("in the last {days} days")
+    } else {
+        format!("from {first_day} to {last_day}")
+    };
+    let _ = writeln!(
+        out,
+        "\n{total} review{plural} {span} • {active} active days • current streak {} • longest streak {}",
+        current_streak(per_day, last_day),
+        longest_streak(per_day, first_day, last_day)
+    );
+    out
+}
+
+fn shade(count: usize, max: usize) -> char {
+    if count == 0 || max == 0 {
+        return EMPTY;
+    }
+    let level = (count * SHADES.len()).div_ceil(max).clamp(1, SHADES.len());
+    SHADES[level - 1]
+}
+
+/// Month names above the first week that contains the 1st of each month.
+fn month_labels(grid_start: NaiveDate, weeks: usize) -> String {
+    let mut line = " ".repeat(LABEL_WIDTH);
+    for week in 0..weeks {
+        let week_start = grid_start + Duration::days(week as i64 * 7);
+        let column = LABEL_WIDTH + week;
+        let starts_month = (0..7).any(|offset| (week_start + Duration::days(offset)).day() == 1);
+        if starts_month && line.chars().count() <= column {
+            let month = (week_start + Duration::days(6)).format("%b").to_string();
+            while line.chars().count() < column {
+                line.push(' ');
+            }
+            line.push_str(&month);
+        }
+    }
+    line.trim_end().to_string()
+}
+
+/// Consecutive days with reviews ending today, or yesterday if today has none yet.
+fn current_streak(per_day: &BTreeMap<NaiveDate, usize>, today: NaiveDate) -> usize {
+    let reviewed = |day: NaiveDate| per_day.get(&day).is_some_and(|count| *count > 0);
+    let mut day = if reviewed(today) {
+        today
+    } else {
+        today - Duration::days(1)
+    };
+    let mut streak = 0;
+    while reviewed(day) {
+        streak += 1;
+        day -= Duration::days(1);
+    }
+    streak
+}
+
+fn longest_streak(
+    per_day: &BTreeMap<NaiveDate, usize>,
+    first_day: NaiveDate,
+    today: NaiveDate,
+) -> usize {
+    let mut longest = 0;
+    let mut streak = 0;
+    let mut day = first_day;
+    while day <= today {
+        if per_day.get(&day).is_some_and(|count| *count > 0) {
+            streak += 1;
+            longest = longest.max(streak);
+        } else {
+            streak = 0;
+        }
+        day += Duration::days(1);
+    }
+    longest
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn date(y: i32, m: u32, d: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(y, m, d).unwrap()
+    }
+
+    #[test]
+    fn shades_scale_with_the_busiest_day() {
+        assert_eq!(shade(0, 10), EMPTY);
+        assert_eq!(shade(1, 10), '░');
+        assert_eq!(shade(5, 10), '▒');
+        assert_eq!(shade(10, 10), '█');
+    }
+
+    #[test]
+    fn draws_one_column_per_week() {
+        // Sunday 2024-03-10; the window starts on Monday 2024-02-26.
+        let today = date(2024, 3, 10);
+        let per_day = BTreeMap::from([
+            (date(2024, 2, 26), 4),
+            (date(2024, 3, 8), 1),
+            (date(2024, 3, 9), 2),
+            (date(2024, 3, 10), 4),
+        ]);
+        let out = format_heatmap(&per_day, ReviewPeriod::ending(today, 14), today);
+        let lines: Vec<&str> = out.lines().collect();
+
+        assert_eq!(lines[0], "    Mar");
+        assert_eq!(lines[1], "Mon █·");
+        assert_eq!(lines[5], "Fri ·░");
+        assert_eq!(lines[6], "    ·▒");
+        assert_eq!(lines[7], "Sun ·█");
+        assert!(out.contains("11 reviews in the last 14 days • 4 active days"));
+        assert!(out.contains("current streak 3 • longest streak 3"));
+    }
+
+    #[test]
+    fn a_past_period_names_its_days() {
+        let per_day = BTreeMap::from([(date(2024, 1, 30), 2), (date(2024, 2, 2), 5)]);
+        let january = ReviewPeriod {
+            first: date(2024, 1, 1),
+            last: date(2024, 1, 31),
+        };
+        let out = format_heatmap(&per_day, january, date(2024, 3, 10));
+        assert!(out.contains("2 reviews from 2024-01-01 to 2024-01-31 • 1 active days"));
+    }
+
+    #[test]
+    fn streak_survives_until_today_is_reviewed() {
+        let today = date(2024, 3, 10);
+        let per_day = BTreeMap::from([(date(2024, 3, 8), 1), (date(2024, 3, 9), 1)]);
+        assert_eq!(current_streak(&per_day, today), 2);
+        assert_eq!(current_streak(&per_day, date(2024, 3, 11)), 0);
+    }
+}
@@ -4,7 +4,9 @@ use regex::Regex;
 use serde_json::Value;
 use sqlx::{Row, SqlitePool};
 use std::collections::HashMap;
-use std::fs::{self, File};
+use std::collections::HashSet;
+use std::fs::{self, File, OpenOptions};
+use std::io::Write;
 use std::path::{Path, PathBuf};
 use tempfile::NamedTempFile;
 use zip::ZipArchive;
@@ -12,6 +14,7 @@ use zip::ZipArchive;
 use anyhow::{Context, Result, anyhow};
 
 use crate::crud::DB;
+use crate::utils::{DEFAULT_CARD_SEPARATOR, content_to_card, is_markdown};
 
 static TAG_RE: Lazy<Regex> = Lazy::new(|| Regex::new(r"(?is)<[^>]+>").unwrap());
 static CLOZE_RE: Lazy<Regex> =
@@ -27,17 +30,34 @@ struct DeckInfo {
 enum ModelKind {
     Basic,
     Cloze,
+    /// Note types such as image occlusion that have no markdown equivalent.
+    Unsupported,
 }
 
 #[derive(Clone, Debug)]
 struct CardRecord {
+    note_id: i64,
     deck_id: i64,
     model_id: i64,
     ord: i64,
     fields: Vec<String>,
 }
 
-pub async fn run(_db: &DB, anki_path: &Path, export_path: &Path) -> Result<()> {
+#[derive(Debug, Default, PartialEq, Eq)]
+struct ImportSummary {
+    imported: usize,
+    unsupported: usize,
+    invalid: usize,
+}
+
+/// Converts an Anki package into markdown decks. A `.md` export path collects every
+/// card in that file; otherwise each deck gets its own file under the directory.
+pub async fn run(
+    _db: &DB,
+    anki_path: &Path,
+    export_path: &Path,
+    card_extensions: &[String],
+) -> Result<()> {
     validate_path(anki_path)?;
     let db_path = extract_collection_db(anki_path)?;
     let db_url = format!("sqlite://{}", db_path.path().display());
@@ -46,8 +66,34 @@ pub async fn run(_db: &DB, anki_path: &Path, export_path: &Path) -> Result<()> {
         .context("failed to connect to Anki database")?;
     let (decks, models) = load_metadata(&export_db).await?;
     let cards = load_cards(&export_db).await?;
-    let exports = build_exports(cards, &models);
-    write_exports(export_path, &decks, exports)?;
+    let (exports, summary) = build_exports(cards, &models);
+    if is_markdown(export_path, card_extensions) {
+        append_exports(export_path, &decks, exports)?;
+    } else {
+        write_exports(export_path, &decks, exports)?;
+    }
+
+    let plural = |n: usize| if n == 1 { "" } else { "s" };
+    println!(
+        "Imported {} card{} to {}",
+        summary.imported,
+        plural(summary.imported),
+        export_path.display()
+    );
+    if summary.unsupported > 0 {
+        println!(
+            "Skipped {} note{} with an unsupported note type.",
+            summary.unsupported,
+            plural(summary.unsupported)
+        );
+    }
+    if summary.invalid > 0 {
+        println!(
+            "Skipped {} note{} that did not convert to a valid card.",
+            summary.invalid,
+            plural(summary.invalid)
+        );
+    }
     Ok(())
 }
 
@@ -70,9 +116,14 @@ fn extract_collection_db(apkg: &Path) -> Result<NamedTempFile> {
 
     let mut zip = ZipArchive::new(file).context("failed to read apkg as zip archive")?;
 
+    let name = if zip.by_name("collection.anki21").is_ok() {
+        "collection.anki21"
+    } else {
+        "collection.anki2"
+    };
     let mut entry = zip
-        .by_name("collection.anki21")
-        .context("apkg does not contain collection.anki21, the newer export format DB")?;
+        .by_name(name)
+        .context("apkg does not contain collection.anki21 or collection.anki2")?;
 
     let mut temp =
         NamedTempFile::new().context("failed to create temporary file for sqlite database")?;
@@ -123,9 +174,14 @@ fn parse_models(json: &str) -> Result<HashMap<i64, ModelKind>> {
     if let Some(map) = value.as_object() {
         for model in map.values() {
             if let Some(id) = model.get("id").and_then(|v| v.as_i64()) {
+                let field_count = model
+                    .get("flds")
+                    .and_then(|v| v.as_array())
+                    .map_or(2, |fields| fields.len());
                 let kind = match model.get("type").and_then(|v| v.as_i64()).unwrap_or(0) {
                     1 => ModelKind::Cloze,
-                    _ => ModelKind::Basic,
+                    0 if field_count >= 2 => ModelKind::Basic,
+                    _ => ModelKind::Unsupported,
                 };
                 models.insert(id, kind);
             }
@@ -138,6 +194,7 @@ async fn load_cards(pool: &SqlitePool) -> Result<Vec<CardRecord>> {
     let rows = sqlx::query(
         r#"
         SELECT
+            notes.id as nid,
             cards.did as did,
             cards.ord as ord,
             notes.mid as mid,
@@ -151,6 +208,7 @@ async fn load_cards(pool: &SqlitePool) -> Result<Vec<CardRecord>> {
     .await?;
     let mut cards = Vec::with_capacity(rows.len());
     for row in rows {
+        let note_id: i64 = row.try_get("nid")?;
         let deck_id: i64 = row.try_get("did")?;
         let ord: i64 = row.try_get("ord")?;
         let model_id: i64 = row.try_get("mid")?;
@@ -158,6 +216,7 @@ async fn load_cards(pool: &SqlitePool) -> Result<Vec<CardRecord>> {
         //"Examples of supervised methods with built-in feature selection\u{1f}Decision trees<br><div>LASSO (linear regression with L1 regularization)</div>\u{1f}<a href=\"https://machinelearningmastery.com/feature-selection-with-real-and-categorical-data/\">https://machinelearningmastery.com/feature-selection-with-real-and-categorical-data/</a>\u{1f}"
         let fields_raw: String = row.try_get("flds")?;
         let card = CardRecord {
+            note_id,
             deck_id,
             model_id,
             ord,
@@ -171,28 +230,51 @@ async fn load_cards(pool: &SqlitePool) -> Result<Vec<CardRecord>> {
 fn build_exports(
     cards: Vec<CardRecord>,
     models: &HashMap<i64, ModelKind>,
-) -> HashMap<i64, Vec<String>> {
+) -> (HashMap<i64, Vec<String>>, ImportSummary) {
     let mut per_deck: HashMap<i64, Vec<String>> = HashMap::new();
+    let mut summary = ImportSummary::default();
+    let mut unsupported = HashSet::new();
+    let mut invalid = HashSet::new();
+    let mut cloze_notes = HashSet::new();
     for card in cards {
-        let Some(model) = models.get(&card.model_id) else {
-            continue;
-        };
-        let entry = match model {
-            ModelKind::Basic => basic_entry(&card.fields, card.ord),
-            ModelKind::Cloze => cloze_entry(&card.fields),
+        let entry = match models.get(&card.model_id) {
+            Some(ModelKind::Basic) => basic_entry(&card.fields, card.ord),
+            // Every cluster of a cloze note shares the same text.
+            Some(ModelKind::Cloze) if !cloze_notes.insert(card.note_id) => continue,
+            Some(ModelKind::Cloze) => cloze_entry(&card.fields),
+            Some(ModelKind::Unsupported) | None => {
+                unsupported.insert(card.note_id);
+                continue;
+            }
         };
-        if let Some(content) = entry {
-            per_deck.entry(card.deck_id).or_default().push(content);
+        match entry {
+            Some(content)
+                if content_to_card(
+                    Path::new(""),
+                    &content,
+                    DEFAULT_CARD_SEPARATOR,
+                    0,
+                    content.len(),
+                )
+                .is_ok() =>
+            {
+                per_deck.entry(card.deck_id).or_default().push(content);
+                summary.imported += 1;
+            }
+            _ => {
+                invalid.insert(card.note_id);
+            }
         }
     }
-    per_deck
+    summary.unsupported = unsupported.len();
+    summary.invalid = invalid.len();
+    (per_deck, summary)
 }
 
-fn write_exports(
-    export_path: &Path,
+fn sorted_by_deck_name(
     decks: &HashMap<i64, DeckInfo>,
     exports: HashMap<i64, Vec<String>>,
-) -> Result<()> {
+) -> Vec<(i64, Vec<String>)> {
     let mut entries: Vec<(i64, Vec<String>)> = exports
         .into_iter()
         .filter(|(_, cards)| !cards.is_empty())
@@ -202,7 +284,43 @@ fn write_exports(
         let name_b = decks.get(b).map(|d| d.name.as_str()).unwrap_or("");
         name_a.cmp(name_b)
     });
-    for (deck_id, cards) in entries {
+    entries
+}
+
+/// Appends every card to one markdown file, separated the way `repeater create` appends them.
+fn append_exports(
+    file_path: &Path,
+    decks: &HashMap<i64, DeckInfo>,
+    exports: HashMap<i64, Vec<String>>,
+) -> Result<()> {
+    if let Some(parent) = file_path.parent()
+        && !parent.as_os_str().is_empty()
+    {
+        fs::create_dir_all(parent)?;
+    }
+    let existing_len = fs::metadata(file_path).map(|m| m.len()).unwrap_or(0);
+    let mut file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(file_path)
+        .with_context(|| format!("failed to open {}", file_path.display()))?;
+    if existing_len > 0 {
+        writeln!(file)?;
+    }
+    let cards: Vec<String> = sorted_by_deck_name(decks, exports)
+        .into_iter()
+        .flat_map(|(_, cards)| cards)
+        .collect();
+    file.write_all(cards.concat().as_bytes())?;
+    Ok(())
+}
+
+fn write_exports(
+    export_path: &Path,
+    decks: &HashMap<i64, DeckInfo>,
+    exports: HashMap<i64, Vec<String>>,
+) -> Result<()> {
+    for (deck_id, cards) in sorted_by_deck_name(decks, exports) {
         let deck = decks
             .get(&deck_id)
             .ok_or_else(|| anyhow!("missing deck metadata for id {}", deck_id))?;
@@ -351,6 +469,45 @@ mod tests {
         assert_eq!(convert_cloze(text), "Capital [Tokyo] and [Kyoto]");
     }
 
+    #[test]
+    fn build_exports_counts_unsupported_notes_once() {
+        let models = HashMap::from([
+            (1, ModelKind::Basic),
+            (2, ModelKind::Cloze),
+            (3, ModelKind::Unsupported),
+        ]);
+        let record = |note_id, model_id, ord, fields: &[&str]| CardRecord {
+            note_id,
+            deck_id: 7,
+            model_id,
+            ord,
+            fields: fields.iter().map(|f| f.to_string()).collect(),
+        };
+        let cards = vec![
+            record(10, 1, 0, &["Front", "Back"]),
+            record(11, 2, 0, &["{{c1::Tokyo}} and {{c2::Kyoto}}"]),
+            record(11, 2, 1, &["{{c1::Tokyo}} and {{c2::Kyoto}}"]),
+            record(12, 3, 0, &["image", "mask"]),
+            record(12, 3, 1, &["image", "mask"]),
+            record(13, 99, 0, &["orphan"]),
+            record(14, 1, 0, &["Front only", ""]),
+        ];
+
+        let (exports, summary) = build_exports(cards, &models);
+        assert_eq!(
+            summary,
+            ImportSummary {
+                imported: 2,
+                unsupported: 2,
+                invalid: 1,
+            }
+        );
+        assert_eq!(
+            exports[&7],
+            vec!["Q: Front\nA: Back\n\n", "C: [Tokyo] and [Kyoto]\n\n"]
+        );
+    }
+
     #[test]
     fn deck_components_sanitizes_segments_and_falls_back() {
         assert_eq!(
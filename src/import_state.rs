@@ -0,0 +1,195 @@
+use std::path::Path;
+use std::str::FromStr;
+
+use anyhow::{Context, Result, anyhow, bail};
+
+use crate::crud::DB;
+use crate::crud::cards::CardState;
+use crate::csv;
+
+/// Restores scheduling state from a `repeater export` CSV.
+pub async fn run(db: &DB, csv_path: &Path, overwrite: bool) -> Result<()> {
+    let text = std::fs::read_to_string(csv_path)
+        .with_context(|| format!("Failed to read {}", csv_path.display()))?;
+    let states =
+        parse_states(&text).with_context(|| format!("Invalid CSV {}", csv_path.display()))?;
+    let written = db.import_card_states(&states, overwrite).await?;
+
+    let plural = if states.len() == 1 { "" } else { "s" };
+    println!("Imported {written} of {} card{plural}.", states.len());
+    let skipped = states.len() - written;
+    if skipped > 0 {
+        println!(
+            "Kept {skipped} card{} that already had reviews; pass --overwrite to replace them.",
+            if skipped == 1 { "" } else { "s" }
+        );
+    }
+    Ok(())
+}
+
+pub fn parse_states(text: &str) -> Result<Vec<CardState>> {
+    let mut records = csv::parse(text)?.into_iter();
+    let header = records.next().ok_or_else(|| anyhow!("the file is empty"))?;
+    let column = |name: &str| {
+        header
+            .fields
+            .iter()
+            .position(|field| field.trim() == name)
+            .ok_or_else(|| anyhow!("line {}: missing `{name}` column", header.line))
+    };
+    let columns = Columns {
+        width: header.fields.len(),
+        card_hash: column("card_hash")?,
+        added_at: column("added_at")?,
+        last_reviewed_at: column("last_reviewed_at")?,
+        stability: column("stability")?,
+        difficulty: column("difficulty")?,
+        interval_days: column("interval_days")?,
+        due_date: column("due_date")?,
+        review_count: column("review_count")?,
+    };
+
+    records
+        .map(|record| {
+            parse_record(&record.fields, &columns).with_context(|| format!("line {}", record.line))
+        })
+        .collect()
+}
+
+struct Columns {
+    width: usize,
+    card_hash: usize,
+    added_at: usize,
+    last_reviewed_at: usize,
+    stability: usize,
+    difficulty: usize,
+    interval_days: usize,
+    due_date: usize,
+    review_count: usize,
+}
+
+fn parse_record(fields: &[String], columns: &Columns) -> Result<CardState> {
+    if fields.len() != columns.width {
+        bail!("expected {} fields, found {}", columns.width, fields.len());
+    }
+    let raw = |index: usize| fields[index].trim();
+
+    let card_hash = raw(columns.card_hash);
+    if card_hash.is_empty() {
+        bail!("`card_hash` is empty");
+    }
+    let added_at = parse_date("added_at", raw(columns.added_at))?
+        .ok_or_else(|| anyhow!("`added_at` is empty"))?;
+    let review_count = parse_number::<i64>("review_count", raw(columns.review_count))?
+        .ok_or_else(|| anyhow!("`review_count` is empty"))?;
+
+    let state = CardState {
+        card_hash: card_hash.to_string(),
+        added_at,
+        last_reviewed_at: parse_date("last_reviewed_at", raw(columns.last_reviewed_at))?,
+        stability: parse_number("stability", raw(columns.stability))?,
+        difficulty: parse_number("difficulty", raw(columns.difficulty))?,
+        interval_days: parse_number("interval_days", raw(columns.interval_days))?,
+        due_date: parse_date("due_date", raw(columns.due_date))?,
+        review_count,
+    };
+    if state.review_count > 0
+        && (state.last_reviewed_at.is_none()
+            || state.stability.is_none()
+            || state.difficulty.is_none()
+            || state.due_date.is_none())
+    {
+        bail!(
+            "a reviewed card needs `last_reviewed_at`, `stability`, `difficulty`, and `due_date`"
+        );
+    }
+    Ok(state)
+}
+
+fn parse_number<T: FromStr>(name: &str, value: &str) -> Result<Option<T>> {
+    if value.is_empty() {
+        return Ok(None);
+    }
+    value
+        .parse()
+        .map(Some)
+        .map_err(|_| anyhow!("`{name}` is not a number: {value:?}"))
+}
+
+fn parse_date(name: &str, value: &str) -> Result<Option<chrono::DateTime<chrono::Utc>>> {
+    if value.is_empty() {
+        return Ok(None);
+    }
+    chrono::DateTime::parse_from_rfc3339(value)
+        .map(|date| Some(date.with_timezone(&chrono::Utc)))
+        .map_err(|_| anyhow!("`{name}` is not an RFC 3339 date: {value:?}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::*;
+    use crate::export::write_csv;
+
+    use crate::export::CSV_HEADER;
+
+    fn header() -> String {
+        CSV_HEADER.join(",")
+    }
+
+    #[test]
+    fn reads_back_an_export() {
+        let now = chrono::Utc::now();
+        let states = vec![
+            CardState {
+                card_hash: "abc".into(),
+                added_at: now,
+                last_reviewed_at: Some(now),
+                stability: Some(3.5),
+                difficulty: Some(6.25),
+                interval_days: Some(4),
+                due_date: Some(now + chrono::Duration::days(4)),
+                review_count: 3,
+            },
+            CardState {
+                card_hash: "new".into(),
+                added_at: now,
+                last_reviewed_at: None,
+                stability: None,
+                difficulty: None,
+                interval_days: Some(0),
+                due_date: None,
+                review_count: 0,
+            },
+        ];
+        let mut out = Vec::new();
+        write_csv(&mut out, &states, &HashMap::new()).unwrap();
+
+        let parsed = parse_states(&String::from_utf8(out).unwrap()).unwrap();
+        assert_eq!(parsed, states);
+    }
+
+    #[test]
+    fn reports_the_offending_line() {
+        let text = format!(
+            "{}\nabc,,2024-01-01T00:00:00Z,,,,0,,0\ndef,,2024-01-01T00:00:00Z,,lots,,0,,0\n",
+            header()
+        );
+        let err = format!("{:#}", parse_states(&text).unwrap_err());
+        assert!(err.contains("line 3"), "{err}");
+        assert!(err.contains("`stability` is not a number"), "{err}");
+
+        let text = format!("{}\nabc,,yesterday,,,,0,,0\n", header());
+        let err = format!("{:#}", parse_states(&text).unwrap_err());
+        assert!(err.contains("line 2"), "{err}");
+        assert!(err.contains("RFC 3339"), "{err}");
+    }
+
+    #[test]
+    fn rejects_reviewed_cards_without_a_schedule() {
+        let text = format!("{}\nabc,,2024-01-01T00:00:00Z,,,,0,,2\n", header());
+        assert!(parse_states(&text).is_err());
+        assert!(parse_states("card_hash,added_at\n").is_err());
+    }
+}
@@ -0,0 +1,139 @@
+use std::fmt::Write as _;
+use std::path::PathBuf;
+
+use anyhow::{Result, bail};
+
+use crate::card::Card;
+use crate::crud::DB;
+use crate::fsrs::{Performance, calculate_recall};
+use crate::list::card_prompt;
+use crate::stats::CardLifeCycle;
+use crate::utils::{ScanOptions, describe_card_extensions, is_markdown, register_all_cards};
+
+/// Prints the scheduler state of every card in the file at `path`, in file order.
+pub async fn run(db: &DB, scan: &ScanOptions, path: PathBuf) -> Result<()> {
+    if !path.is_file() || !is_markdown(&path, &scan.extensions) {
+        bail!(
+            "{} is not a card file ({})",
+            path.display(),
+            describe_card_extensions(&scan.extensions)
+        );
+    }
+    let mut cards: Vec<Card> = register_all_cards(db, scan, vec![path.clone()])
+        .await?
+        .into_values()
+        .collect();
+    if cards.is_empty() {
+        println!("No cards in {}.", path.display());
+        return Ok(());
+    }
+    cards.sort_by_key(|card| card.file_card_range);
+
+    let now = chrono::Utc::now();
+    let mut out = String::new();
+    for (idx, card) in cards.iter().enumerate() {
+        if idx > 0 {
+            out.push('\n');
+        }
+        let performance = db.get_card_performance(card).await?;
+        out.push_str(&format_info(card, &performance, now));
+    }
+    print!("{out}");
+    Ok(())
+}
+
+fn format_info(
+    card: &Card,
+    performance: &Performance,
+    now: chrono::DateTime<chrono::Utc>,
+) -> String {
+    let mut out = format!(
+        "{}:{}  {}\n",
+        card.file_path.display(),
+        card.file_card_range.0 + 1,
+        card_prompt(card)
+    );
+    let _ = writeln!(out, "  {:<12} {}", "hash", card.card_hash);
+    let Performance::Reviewed(reviewed) = performance else {
+        let _ = writeln!(
+            out,
+            "  {:<12} {:?} (never reviewed)",
+            "state",
+            CardLifeCycle::New
+        );
+        return out;
+    };
+    let lifecycle = CardLifeCycle::classify(reviewed.review_count as i64, reviewed.interval_raw);
+    let elapsed_days = now
+        .signed_duration_since(reviewed.last_reviewed_at)
+        .num_seconds() as f64
+        / 86_400.0;
+    let recall = calculate_recall(elapsed_days.max(0.0), reviewed.stability);
+    let local = |at: chrono::DateTime<chrono::Utc>| {
+        at.with_timezone(&chrono::Local)
+            .format("%Y-%m-%d %H:%M")
+            .to_string()
+    };
+    let rows = [
+        ("state", format!("{lifecycle:?}")),
+        ("stability", format!("{:.2} days", reviewed.stability)),
+        ("difficulty", format!("{:.2}", reviewed.difficulty)),
+        (
+            "interval",
+            format!(
+                "{:.2} days computed, {} scheduled",
+                reviewed.interval_raw, reviewed.interval_days
+            ),
+        ),
+        ("due", local(reviewed.due_date)),
+        ("reviews", reviewed.review_count.to_string()),
+        ("last review", local(reviewed.last_reviewed_at)),
+        ("recall now", format!("{:.0}%", recall * 100.0)),
+    ];
+    for (label, value) in rows {
+        let _ = writeln!(out, "  {label:<12} {value}");
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use chrono::Duration;
+
+    use super::*;
+    use crate::fsrs::ReviewedPerformance;
+    use crate::utils::content_to_card;
+
+    #[test]
+    fn info_shows_the_scheduler_state() {
+        let card =
+            content_to_card(Path::new("verbs.md"), "Q: comer?\nA: to eat", "---", 2, 4).unwrap();
+        let new = format_info(&card, &Performance::New, chrono::Utc::now());
+        assert_eq!(
+            new,
+            format!(
+                "verbs.md:3  comer?\n  hash         {}\n  state        New (never reviewed)\n",
+                card.card_hash
+            )
+        );
+
+        let now = chrono::Utc::now();
+        let reviewed = Performance::Reviewed(ReviewedPerformance {
+            last_reviewed_at: now - Duration::days(10),
+            stability: 10.0,
+            difficulty: 5.5,
+            interval_raw: 10.4,
+            interval_days: 10,
+            due_date: now,
+            review_count: 3,
+        });
+        let info = format_info(&card, &reviewed, now);
+        assert!(info.contains("  state        Young\n"));
+        assert!(info.contains("  stability    10.00 days\n"));
+        assert!(info.contains("  interval     10.40 days computed, 10 scheduled\n"));
+        assert!(info.contains("  reviews      3\n"));
+        assert!(info.contains("  recall now   90%\n"));
+    }
+}
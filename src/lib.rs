@@ -1,14 +1,38 @@
+pub mod apkg;
 pub mod card;
+pub mod card_cache;
+pub mod card_edit;
 pub mod check;
 pub mod check_version;
+pub mod config;
 pub mod create;
 pub mod crud;
+pub mod csv;
+pub mod doctor;
 pub mod drill;
+pub mod exit_code;
+pub mod export;
 pub mod fsrs;
+pub mod heatmap;
 pub mod import;
+pub mod import_state;
+pub mod info;
+pub mod list;
 pub mod llm;
 pub mod markdown;
 pub mod media;
+pub mod optimize;
+pub mod output;
+pub mod profile;
+pub mod rehash;
+pub mod reset;
+pub mod search;
+pub mod session;
+pub mod simulate;
+pub mod sm2;
 pub mod stats;
+pub mod tag;
 pub mod tui;
 pub mod utils;
+pub mod vacuum;
+pub mod watch;
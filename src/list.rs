@@ -0,0 +1,408 @@
+use std::cmp::Ordering;
+use std::collections::BTreeSet;
+use std::ops::Range;
+use std::path::PathBuf;
+
+use anyhow::Result;
+use clap::ValueEnum;
+
+use crate::card::{Card, CardContent};
+use crate::crud::DB;
+use crate::crud::cards::CardRow;
+use crate::fsrs::calculate_recall;
+use crate::stats::CardLifeCycle;
+use crate::tui::Theme;
+use crate::utils::{CardLocation, ScanOptions, is_due_at, register_all_cards, register_cards};
+
+/// Order of the rows `list` prints.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum)]
+pub enum ListSort {
+    /// Soonest due first, unseen cards last.
+    #[default]
+    Due,
+    /// Least likely to be recalled right now first, unseen cards last.
+    Recall,
+}
+
+pub struct ListOptions {
+    pub suspended_only: bool,
+    pub leeches_only: bool,
+    pub due_only: bool,
+    /// Cards per page, at least 1.
+    pub limit: Option<usize>,
+    /// 1-based page of `limit` cards; the first page without a limit.
+    pub page: usize,
+    pub sort: ListSort,
+}
+
+struct ListEntry<'a> {
+    card: &'a Card,
+    row: CardRow,
+}
+
+pub async fn run(
+    db: &DB,
+    scan: &ScanOptions,
+    paths: Vec<PathBuf>,
+    options: ListOptions,
+    day_cutoff_hour: u32,
+) -> Result<()> {
+    let card_hashes = register_all_cards(db, scan, paths).await?;
+    let now = chrono::Utc::now();
+
+    let mut entries: Vec<ListEntry> = db
+        .card_rows()
+        .await?
+        .into_iter()
+        .filter_map(|row| {
+            let card = card_hashes.get(&row.card_hash)?;
+            Some(ListEntry { card, row })
+        })
+        .filter(|entry| !options.suspended_only || entry.row.suspended)
+        .filter(|entry| !options.leeches_only || entry.row.leech)
+        .filter(|entry| !options.due_only || is_due(&entry.row, now, day_cutoff_hour))
+        .collect();
+    match options.sort {
+        ListSort::Due => entries.sort_by(|a, b| by_due_date(&a.row, &b.row)),
+        ListSort::Recall => entries.sort_by(|a, b| by_recall(&a.row, &b.row, now)),
+    }
+
+    if entries.is_empty() {
+        if options.suspended_only {
+            println!("No suspended cards.");
+        } else if options.leeches_only {
+            println!("No leeches.");
+        } else if options.due_only {
+            println!("All caught up—no cards due today.");
+        } else {
+            println!("No cards found.");
+        }
+        return Ok(());
+    }
+
+    let total = entries.len();
+    let shown = page_range(total, options.limit, options.page);
+    if shown.is_empty() {
+        println!(
+            "Page {} is past the last page ({}).",
+            options.page,
+            page_count(total, options.limit)
+        );
+        return Ok(());
+    }
+    println!(
+        "{:<16}  {:>8}  {:>7}  {:>6}  {:<16}  {:<5}  CARD",
+        "DUE", "INTERVAL", "REVIEWS", "RECALL", "STATE", "TYPE"
+    );
+    for entry in &entries[shown.clone()] {
+        println!("{}", format_entry(entry, now));
+    }
+    if shown.len() < total {
+        println!("{}", page_footer(total, options.limit, options.page));
+    }
+    Ok(())
+}
+
+/// Indices of the cards on `page`, empty past the last page.
+fn page_range(total: usize, limit: Option<usize>, page: usize) -> Range<usize> {
+    let Some(limit) = limit else {
+        return if page <= 1 { 0..total } else { 0..0 };
+    };
+    let start = limit.saturating_mul(page.saturating_sub(1)).min(total);
+    start..start.saturating_add(limit).min(total)
+}
+
+fn page_count(total: usize, limit: Option<usize>) -> usize {
+    match limit {
+        Some(limit) if limit > 0 => total.div_ceil(limit).max(1),
+        _ => 1,
+    }
+}
+
+fn page_footer(total: usize, limit: Option<usize>, page: usize) -> String {
+    let shown = page_range(total, limit, page);
+    let pages = page_count(total, limit);
+    let mut footer = format!(
+        "Showing {}–{} of {total} (page {page} of {pages})",
+        shown.start + 1,
+        shown.end
+    );
+    if page < pages {
+        footer.push_str(&format!("; --page {} for more", page + 1));
+    }
+    footer
+}
+
+/// Same rule as `DB::due_today`: never reviewed, or due by [`is_due_at`].
+fn is_due(row: &CardRow, now: chrono::DateTime<chrono::Utc>, day_cutoff_hour: u32) -> bool {
+    !row.suspended
+        && row.due_date.is_none_or(|due| {
+            is_due_at(
+                due,
+                row.interval_raw.unwrap_or_default(),
+                now,
+                day_cutoff_hour,
+            )
+        })
+}
+
+/// Soonest due first, unseen cards last, ties broken by hash so output is stable.
+fn by_due_date(a: &CardRow, b: &CardRow) -> Ordering {
+    match (a.due_date, b.due_date) {
+        (Some(a_due), Some(b_due)) => a_due.cmp(&b_due),
+        (Some(_), None) => Ordering::Less,
+        (None, Some(_)) => Ordering::Greater,
+        (None, None) => Ordering::Equal,
+    }
+    .then_with(|| a.card_hash.cmp(&b.card_hash))
+}
+
+/// Lowest retrievability first, unseen cards last, ties broken by hash so output is stable.
+fn by_recall(a: &CardRow, b: &CardRow, now: chrono::DateTime<chrono::Utc>) -> Ordering {
+    match (retrievability(a, now), retrievability(b, now)) {
+        (Some(a_recall), Some(b_recall)) => a_recall.total_cmp(&b_recall),
+        (Some(_), None) => Ordering::Less,
+        (None, Some(_)) => Ordering::Greater,
+        (None, None) => Ordering::Equal,
+    }
+    .then_with(|| a.card_hash.cmp(&b.card_hash))
+}
+
+/// Chance of recalling the card at `now` on its forgetting curve; `None` until it's reviewed.
+fn retrievability(row: &CardRow, now: chrono::DateTime<chrono::Utc>) -> Option<f64> {
+    let last_reviewed_at = row.last_reviewed_at.filter(|_| row.review_count > 0)?;
+    let stability = row.stability?;
+    let elapsed_days = now.signed_duration_since(last_reviewed_at).num_seconds() as f64 / 86_400.0;
+    Some(calculate_recall(elapsed_days.max(0.0), stability))
+}
+
+fn format_entry(entry: &ListEntry, now: chrono::DateTime<chrono::Utc>) -> String {
+    let row = &entry.row;
+    let due = row.due_date.map_or_else(
+        || "new".to_string(),
+        |due| {
+            due.with_timezone(&chrono::Local)
+                .format("%Y-%m-%d %H:%M")
+                .to_string()
+        },
+    );
+    let interval = row
+        .interval_days
+        .filter(|_| row.review_count > 0)
+        .map_or_else(|| "—".to_string(), |days| format!("{days}d"));
+    let recall = retrievability(row, now).map_or_else(
+        || "—".to_string(),
+        |recall| format!("{:.0}%", recall * 100.0),
+    );
+    let lifecycle = CardLifeCycle::classify(row.review_count, row.interval_raw.unwrap_or_default());
+    let mut state = format!("{lifecycle:?}");
+    if row.leech {
+        state.push_str(", leech");
+    }
+    if row.suspended {
+        state.push_str(", suspended");
+    }
+    let card_type = match entry.card.content {
+        CardContent::Basic { .. } => "basic",
+        CardContent::TypeIn { .. } => "typed",
+        CardContent::Cloze { .. } => "cloze",
+    };
+    let due_style = if row.due_date.is_some_and(|due| due < now) {
+        Theme::overdue()
+    } else {
+        Theme::default_style()
+    };
+    format!(
+        "{}  {interval:>8}  {:>7}  {recall:>6}  {state:<16}  {card_type:<5}  {}:{}  {}",
+        Theme::paint(format!("{due:<16}"), due_style),
+        row.review_count,
+        entry.card.file_path.display(),
+        entry.card.file_card_range.0 + 1,
+        card_prompt(entry.card)
+    )
+}
+
+pub async fn unsuspend(db: &DB, scan: &ScanOptions, paths: Vec<PathBuf>) -> Result<()> {
+    let card_hashes = register_all_cards(db, scan, paths).await?;
+    let suspended = db.suspended_hashes().await?;
+
+    let mut count = 0;
+    for card in card_hashes.values() {
+        if suspended.contains(&card.card_hash) {
+            db.set_suspended(card, false).await?;
+            count += 1;
+        }
+    }
+    let plural = if count == 1 { "" } else { "s" };
+    println!("Unsuspended {count} card{plural}.");
+    Ok(())
+}
+
+/// Clears `archived_at` on the archived cards found under `paths`, so their preserved
+/// schedule and review history count again.
+pub async fn unarchive(db: &DB, scan: &ScanOptions, paths: Vec<PathBuf>) -> Result<()> {
+    let card_hashes = register_all_cards(db, scan, paths).await?;
+    let archived = db.archived_hashes().await?;
+
+    let mut restored: Vec<String> = card_hashes
+        .keys()
+        .filter(|card_hash| archived.contains(*card_hash))
+        .cloned()
+        .collect();
+    restored.sort();
+    db.unarchive_cards(&restored).await?;
+    let count = restored.len();
+    let plural = if count == 1 { "" } else { "s" };
+    println!("Unarchived {count} card{plural}.");
+    Ok(())
+}
+
+/// First line of the prompt, enough to recognize a card in a listing.
+/// Prints every card whose text appears at more than one place. Identical cards share one
+/// hash and so one schedule; only the first location is ever drilled.
+pub async fn duplicates(db: &DB, scan: &ScanOptions, paths: Vec<PathBuf>) -> Result<()> {
+    let registration = register_cards(db, scan, paths).await?;
+    registration.report_issues(scan.strict)?;
+    let mut groups: Vec<(&Card, &BTreeSet<CardLocation>)> = registration
+        .duplicates
+        .iter()
+        .filter_map(|(hash, locations)| Some((registration.cards.get(hash)?, locations)))
+        .collect();
+    groups.sort_by(|a, b| a.1.cmp(b.1));
+    print!("{}", format_duplicates(&groups));
+    Ok(())
+}
+
+fn format_duplicates(groups: &[(&Card, &BTreeSet<CardLocation>)]) -> String {
+    if groups.is_empty() {
+        return "No duplicate cards.\n".to_string();
+    }
+    let plural = if groups.len() == 1 {
+        " appears"
+    } else {
+        "s appear"
+    };
+    let mut out = format!("{} card{plural} in more than one place:\n", groups.len());
+    for (card, locations) in groups {
+        out.push_str(&format!("\n{}\n", card_prompt(card)));
+        for (path, line) in locations.iter() {
+            out.push_str(&format!("  {}:{line}\n", path.display()));
+        }
+    }
+    out
+}
+
+pub fn card_prompt(card: &Card) -> &str {
+    let text = match &card.content {
+        CardContent::Basic { question, .. } | CardContent::TypeIn { question, .. } => question,
+        CardContent::Cloze { text, .. } => text,
+    };
+    text.lines().next().unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{Duration, Utc};
+
+    use super::*;
+    use crate::utils::DEFAULT_DAY_CUTOFF_HOUR;
+
+    fn row(hash: &str, due_in_days: Option<i64>, suspended: bool) -> CardRow {
+        CardRow {
+            card_hash: hash.into(),
+            review_count: if due_in_days.is_some() { 1 } else { 0 },
+            due_date: due_in_days.map(|days| Utc::now() + Duration::days(days)),
+            interval_raw: None,
+            interval_days: None,
+            suspended,
+            lapses: 0,
+            leech: false,
+            last_reviewed_at: due_in_days.map(|_| Utc::now() - Duration::days(2)),
+            stability: due_in_days.map(|days| days.max(1) as f64),
+        }
+    }
+
+    #[test]
+    fn recall_sort_puts_at_risk_cards_first() {
+        let now = Utc::now();
+        let mut rows = [
+            row("a", None, false),
+            row("b", Some(10), false),
+            row("c", Some(1), false),
+        ];
+        assert_eq!(retrievability(&rows[0], now), None);
+        assert!(retrievability(&rows[2], now) < retrievability(&rows[1], now));
+        rows.sort_by(|a, b| by_recall(a, b, now));
+        let order: Vec<_> = rows.iter().map(|row| row.card_hash.as_str()).collect();
+        assert_eq!(order, ["c", "b", "a"]);
+    }
+
+    #[test]
+    fn sorts_by_due_date_with_new_cards_last() {
+        let mut rows = [
+            row("b", None, false),
+            row("c", Some(3), false),
+            row("a", None, false),
+            row("d", Some(-1), false),
+        ];
+        rows.sort_by(by_due_date);
+        let order: Vec<_> = rows.iter().map(|row| row.card_hash.as_str()).collect();
+        assert_eq!(order, ["d", "c", "a", "b"]);
+    }
+
+    #[test]
+    fn pages_split_the_sorted_cards() {
+        assert_eq!(page_range(120, Some(50), 1), 0..50);
+        assert_eq!(page_range(120, Some(50), 3), 100..120);
+        assert_eq!(page_range(120, Some(50), 4), 120..120);
+        assert_eq!(page_range(120, None, 1), 0..120);
+        assert_eq!(page_range(120, None, 2), 0..0);
+        assert_eq!(
+            page_footer(4213, Some(50), 1),
+            "Showing 1–50 of 4213 (page 1 of 85); --page 2 for more"
+        );
+        assert_eq!(
+            page_footer(120, Some(50), 3),
+            "Showing 101–120 of 120 (page 3 of 3)"
+        );
+    }
+
+    #[test]
+    fn due_filter_matches_due_today() {
+        let now = Utc::now();
+        assert!(is_due(&row("a", None, false), now, DEFAULT_DAY_CUTOFF_HOUR));
+        assert!(is_due(
+            &row("b", Some(-2), false),
+            now,
+            DEFAULT_DAY_CUTOFF_HOUR,
+        ));
+        assert!(!is_due(
+            &row("c", Some(2), false),
+            now,
+            DEFAULT_DAY_CUTOFF_HOUR,
+        ));
+        assert!(!is_due(
+            &row("d", Some(-2), true),
+            now,
+            DEFAULT_DAY_CUTOFF_HOUR,
+        ));
+    }
+
+    #[test]
+    fn duplicates_list_every_location() {
+        let card = crate::utils::content_to_card(
+            &PathBuf::from("a.md"),
+            "Q: shared?\nA: yes",
+            "---",
+            0,
+            2,
+        )
+        .unwrap();
+        let locations = BTreeSet::from([(PathBuf::from("a.md"), 1), (PathBuf::from("b.md"), 7)]);
+        assert_eq!(
+            format_duplicates(&[(&card, &locations)]),
+            "1 card appears in more than one place:\n\nshared?\n  a.md:1\n  b.md:7\n"
+        );
+        assert_eq!(format_duplicates(&[]), "No duplicate cards.\n");
+    }
+}
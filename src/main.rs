@@ -1,10 +1,25 @@
+use std::io::IsTerminal;
 use std::path::PathBuf;
 
 use anyhow::{Context, Result, bail};
-use clap::{Parser, Subcommand, ValueHint};
+use chrono::NaiveDate;
+use clap::{CommandFactory, Parser, Subcommand, ValueHint};
+use clap_complete::Shell;
 
+use repeater::config::Config;
 use repeater::crud::DB;
-use repeater::{check, create, drill, import, llm};
+use repeater::crud::cards::QueueOrder;
+use repeater::exit_code::ExitCode;
+use repeater::export::ExportFormat;
+use repeater::fsrs::validate_desired_retention;
+use repeater::list::ListSort;
+use repeater::output::Verbosity;
+use repeater::stats::DEFAULT_FORECAST_DAYS;
+use repeater::tui::Theme;
+use repeater::{
+    check, create, doctor, drill, export, import, import_state, info, list, llm, optimize, output,
+    profile, rehash, reset, search, simulate, stats, tag, utils, vacuum, watch,
+};
 
 #[derive(Parser, Debug)]
 #[command(
@@ -17,6 +32,38 @@ use repeater::{check, create, drill, import, llm};
     disable_help_subcommand = true
 )]
 struct Cli {
+    /// Use a separate collection, config, and data directory for this profile
+    /// (also set by REPEATER_PROFILE)
+    #[arg(long, global = true, value_name = "NAME")]
+    profile: Option<String>,
+    /// Open the SQLite database at this path instead of the data directory
+    /// (also set by REPEATER_DB)
+    #[arg(long, global = true, value_name = "PATH", value_hint = ValueHint::FilePath)]
+    db: Option<PathBuf>,
+    /// Directory drill, stats, list, and search use when given no paths
+    /// (also set by REPEATER_COLLECTION or `collection` in the config file)
+    #[arg(long, global = true, value_name = "DIR", value_hint = ValueHint::DirPath)]
+    collection: Option<PathBuf>,
+    /// Re-read every card file instead of reusing cards parsed from unchanged files
+    #[arg(long, global = true)]
+    no_cache: bool,
+    /// Fail instead of skipping cards that can't be parsed
+    #[arg(long, global = true)]
+    strict: bool,
+    /// Only use card files matching this glob (gitignore syntax, relative to the current
+    /// directory; repeatable)
+    #[arg(long, global = true, value_name = "GLOB")]
+    include: Vec<String>,
+    /// Skip card files matching this glob (gitignore syntax, relative to the current
+    /// directory; repeatable)
+    #[arg(long, global = true, value_name = "GLOB")]
+    exclude: Vec<String>,
+    /// Only print results, warnings, and errors; wins over --verbose
+    #[arg(short, long, global = true)]
+    quiet: bool,
+    /// Also log each card file scanned, with the cards parsed and skipped from it
+    #[arg(short, long, global = true)]
+    verbose: bool,
     #[command(subcommand)]
     command: Command,
 }
@@ -26,20 +73,50 @@ enum Command {
     /// Drill cards
     Drill {
         /// Paths to cards or directories containing them.
-        /// You can pass a single file, multiple files, or a directory.
+        /// You can pass a single file, multiple files, or a directory; without any, the
+        /// collection directory or else the current directory is drilled.
         #[arg(
             value_name = "PATHS",
             num_args = 0..,
-            default_value = ".",
             value_hint = ValueHint::AnyPath
         )]
         paths: Vec<PathBuf>,
-        /// Maximum number of cards to drill in a session. By default, all cards due today are drilled.
+        /// Maximum number of already-reviewed cards to drill in a session; 0 drills only new cards.
+        /// By default, all cards due today are drilled.
         #[arg(long, value_name = "COUNT")]
         card_limit: Option<usize>,
-        /// Maximum number of new cards to drill in a session.
+        /// Maximum number of new cards to drill in a session; 0 drills only already-reviewed cards.
         #[arg(long, value_name = "COUNT")]
         new_card_limit: Option<usize>,
+        /// Only drill cards whose file lists this tag in its frontmatter. Repeat to include several tags.
+        #[arg(long = "tag", value_name = "TAG")]
+        tags: Vec<String>,
+        /// Also drill cards coming due within the next N days, to study ahead of schedule.
+        #[arg(long, value_name = "DAYS")]
+        ahead: Option<u32>,
+        /// Order of the session's cards within the reviews and within the new cards; the
+        /// reviews always come first unless --new-first
+        #[arg(long, value_enum, default_value_t = QueueOrder::Due)]
+        order: QueueOrder,
+        /// Drill new cards before the reviews
+        #[arg(long, conflicts_with_all = ["reviews_first", "cram"])]
+        new_first: bool,
+        /// Clear the reviews before introducing new cards. This is already the default, so
+        /// the flag only restates it
+        #[arg(long, conflicts_with = "cram")]
+        reviews_first: bool,
+        /// Target probability of recalling a card when it comes due (0.7-0.97). Overrides the config file.
+        #[arg(long, alias = "retention", value_name = "RATE", value_parser = parse_desired_retention)]
+        desired_retention: Option<f64>,
+        /// Grade cards to preview their next intervals without saving anything
+        #[arg(long)]
+        dry_run: bool,
+        /// Print how many new and review cards the session would hold, per file, without starting it
+        #[arg(long, conflicts_with = "dry_run")]
+        plan: bool,
+        /// Drill every matching card, due or not, without reading or changing any schedule
+        #[arg(long, conflicts_with_all = ["plan", "ahead", "new_card_limit", "order"])]
+        cram: bool,
     },
     /// Re-index decks and show collection stats
     Check {
@@ -50,21 +127,257 @@ enum Command {
             value_hint = ValueHint::AnyPath
         )]
         paths: Vec<PathBuf>,
+        /// Archive cards in the database that no longer appear in PATHS
+        #[arg(long)]
+        prune: bool,
+        /// With --prune, delete those cards and their review history instead of archiving them
+        #[arg(long, requires = "prune")]
+        hard: bool,
+    },
+    /// Show collection stats without opening the dashboard
+    Stats {
+        #[arg(
+            value_name = "PATHS",
+            num_args = 0..,
+            value_hint = ValueHint::AnyPath
+        )]
+        paths: Vec<PathBuf>,
+        /// Print the stats as JSON instead of text
+        #[arg(long)]
+        json: bool,
+        /// Number of days covered by the workload forecast
+        #[arg(long, value_name = "DAYS", default_value_t = DEFAULT_FORECAST_DAYS)]
+        forecast_days: usize,
+        /// Archive cards in the database that no longer appear in PATHS
+        #[arg(long)]
+        prune: bool,
+        /// With --prune, delete those cards and their review history instead of archiving them
+        #[arg(long, requires = "prune")]
+        hard: bool,
+        /// Show card, due, new, and mature counts for each file
+        #[arg(long, conflicts_with_all = ["json", "by_deck", "heatmap"])]
+        by_file: bool,
+        /// Show the same counts for each top-level directory under PATHS
+        #[arg(long, conflicts_with_all = ["json", "heatmap"])]
+        by_deck: bool,
+        /// Show a calendar of reviews done on each day of the last year
+        #[arg(long, conflicts_with = "json")]
+        heatmap: bool,
+        /// Only count reviews from this study day on (YYYY-MM-DD) in retention, review time,
+        /// and the heatmap
+        #[arg(long, value_name = "DATE", value_parser = parse_date)]
+        since: Option<NaiveDate>,
+        /// Only count reviews up to and including this study day (YYYY-MM-DD)
+        #[arg(long, value_name = "DATE", value_parser = parse_date)]
+        until: Option<NaiveDate>,
+    },
+    /// List the cards in a collection
+    List {
+        #[arg(
+            value_name = "PATHS",
+            num_args = 0..,
+            value_hint = ValueHint::AnyPath
+        )]
+        paths: Vec<PathBuf>,
+        /// Only show suspended cards
+        #[arg(long)]
+        suspended: bool,
+        /// Only show cards flagged as leeches
+        #[arg(long)]
+        leeches: bool,
+        /// Only show cards that a drill would pick up right now
+        #[arg(long)]
+        due_only: bool,
+        /// Maximum number of cards to print
+        #[arg(
+            long,
+            value_name = "N",
+            value_parser = clap::builder::RangedU64ValueParser::<usize>::new().range(1..)
+        )]
+        limit: Option<usize>,
+        /// Which page of --limit cards to print, counting from 1
+        #[arg(
+            long,
+            value_name = "K",
+            default_value_t = 1,
+            requires = "limit",
+            value_parser = clap::builder::RangedU64ValueParser::<usize>::new().range(1..)
+        )]
+        page: usize,
+        /// Order of the rows
+        #[arg(long, value_enum, default_value_t = ListSort::Due)]
+        sort: ListSort,
+        /// Instead of the card table, show cards whose text appears in more than one place
+        #[arg(long, conflicts_with_all = ["suspended", "leeches", "due_only", "limit", "page", "sort"])]
+        dupes: bool,
+    },
+    /// Show the scheduler state of each card in a file
+    Info {
+        #[arg(value_name = "FILE", value_hint = ValueHint::FilePath)]
+        path: PathBuf,
+    },
+    /// Find cards whose question, answer, or cloze text matches a query
+    Search {
+        /// Text to look for; a regular expression with --regex
+        #[arg(value_name = "QUERY")]
+        query: String,
+        #[arg(
+            value_name = "PATHS",
+            num_args = 0..,
+            value_hint = ValueHint::AnyPath
+        )]
+        paths: Vec<PathBuf>,
+        /// Treat the query as a regular expression
+        #[arg(long)]
+        regex: bool,
+        /// Match regardless of letter case
+        #[arg(long, short = 'i')]
+        ignore_case: bool,
+    },
+    /// Make suspended cards due again
+    Unsuspend {
+        #[arg(
+            value_name = "PATHS",
+            num_args = 0..,
+            default_value = ".",
+            value_hint = ValueHint::AnyPath
+        )]
+        paths: Vec<PathBuf>,
+    },
+    /// Bring back archived cards whose files reappeared under PATHS, with their schedules
+    Unarchive {
+        #[arg(
+            value_name = "PATHS",
+            num_args = 0..,
+            default_value = ".",
+            value_hint = ValueHint::AnyPath
+        )]
+        paths: Vec<PathBuf>,
+    },
+    /// Forget the schedule of reviewed cards so they are drilled as new again
+    Reset {
+        #[arg(
+            value_name = "PATHS",
+            num_args = 0..,
+            default_value = ".",
+            value_hint = ValueHint::AnyPath
+        )]
+        paths: Vec<PathBuf>,
+        /// Only reset cards whose file lists this tag in its frontmatter (repeatable)
+        #[arg(long = "tag", value_name = "TAG")]
+        tags: Vec<String>,
+        /// Only reset cards in this file (repeatable)
+        #[arg(long = "file", value_name = "PATH", value_hint = ValueHint::FilePath)]
+        files: Vec<PathBuf>,
+        /// Don't ask for confirmation
+        #[arg(long, short = 'y')]
+        yes: bool,
+    },
+    /// Add or remove a tag in the frontmatter of card files
+    Tag {
+        #[command(subcommand)]
+        action: TagAction,
+    },
+    /// Carry review history over to card hashes computed by a newer release
+    Rehash {
+        #[arg(
+            value_name = "PATHS",
+            num_args = 0..,
+            default_value = ".",
+            value_hint = ValueHint::AnyPath
+        )]
+        paths: Vec<PathBuf>,
+    },
+    /// Export scheduling data as CSV, or cards as an Anki package
+    Export {
+        #[arg(
+            value_name = "PATHS",
+            num_args = 0..,
+            default_value = ".",
+            value_hint = ValueHint::AnyPath
+        )]
+        paths: Vec<PathBuf>,
+        /// Output format
+        #[arg(long, value_enum, default_value_t = ExportFormat::Csv)]
+        format: ExportFormat,
+        /// File to write to. Defaults to stdout; required for apkg.
+        #[arg(long, short, value_name = "FILE", value_hint = ValueHint::FilePath)]
+        output: Option<PathBuf>,
+    },
+    /// Compare the reviews per day your collection would need at another target retention
+    Simulate {
+        #[arg(
+            value_name = "PATHS",
+            num_args = 0..,
+            default_value = ".",
+            value_hint = ValueHint::AnyPath
+        )]
+        paths: Vec<PathBuf>,
+        /// Target retention to compare with the configured one (0.7-0.97)
+        #[arg(long, value_name = "RATE", value_parser = parse_desired_retention)]
+        retention: f64,
+        /// Number of days to project
+        #[arg(
+            long,
+            value_name = "DAYS",
+            default_value_t = 90,
+            value_parser = clap::builder::RangedU64ValueParser::<usize>::new().range(1..=3650)
+        )]
+        days: usize,
+    },
+    /// Fit FSRS weights to your review history and save them to the config file
+    Optimize,
+    /// Check the config, data directory, database, and decks for common problems
+    Doctor {
+        #[arg(
+            value_name = "PATHS",
+            num_args = 0..,
+            default_value = ".",
+            value_hint = ValueHint::AnyPath
+        )]
+        paths: Vec<PathBuf>,
+    },
+    /// Compact the database file and optionally check it for corruption
+    Vacuum {
+        /// Run an integrity check first and stop if it finds problems
+        #[arg(long)]
+        integrity_check: bool,
     },
     /// Create or append to a card
     Create {
         /// Card path
         #[arg(value_name = "PATH", value_hint = ValueHint::FilePath)]
         path: PathBuf,
+        /// Open the existing file for editing; Ctrl+S overwrites it instead of appending
+        #[arg(long)]
+        edit: bool,
     },
     /// Import from Anki
     Import {
-        /// Anki export path. Must be an apkg file
+        /// Anki export (.apkg) to convert, or a CSV from `repeater export` to restore scheduling from
         #[arg(value_name = "PATH", value_hint = ValueHint::FilePath)]
-        anki_path: PathBuf,
-        /// Directory to export to
+        import_path: PathBuf,
+        /// Directory to write converted Anki decks to, or a markdown file to append them to
         #[arg(value_name = "PATH", value_hint = ValueHint::AnyPath)]
-        export_path: PathBuf,
+        export_path: Option<PathBuf>,
+        /// When restoring a CSV, also replace cards that already have reviews
+        #[arg(long)]
+        overwrite: bool,
+    },
+    /// Keep the database in sync with decks, re-indexing whenever a card file changes
+    Watch {
+        #[arg(
+            value_name = "PATHS",
+            num_args = 0..,
+            default_value = ".",
+            value_hint = ValueHint::AnyPath
+        )]
+        paths: Vec<PathBuf>,
+    },
+    /// Print a shell completion script to stdout
+    Completions {
+        #[arg(value_name = "SHELL")]
+        shell: Shell,
     },
     /// Manage LLM helper settings
     Llm {
@@ -80,43 +393,304 @@ enum Command {
     },
 }
 
+#[derive(Subcommand, Debug)]
+enum TagAction {
+    /// Add TAG to the frontmatter of every card file under PATHS, creating it if needed
+    Add {
+        #[arg(value_name = "TAG")]
+        tag: String,
+        #[arg(
+            value_name = "PATHS",
+            num_args = 0..,
+            default_value = ".",
+            value_hint = ValueHint::AnyPath
+        )]
+        paths: Vec<PathBuf>,
+    },
+    /// Remove TAG from the frontmatter of every card file under PATHS
+    Remove {
+        #[arg(value_name = "TAG")]
+        tag: String,
+        #[arg(
+            value_name = "PATHS",
+            num_args = 0..,
+            default_value = ".",
+            value_hint = ValueHint::AnyPath
+        )]
+        paths: Vec<PathBuf>,
+    },
+}
+
 #[tokio::main]
 async fn main() {
-    if let Err(err) = run_cli().await {
+    let code = run_cli().await.unwrap_or_else(|err| {
         eprintln!("{:?}", err);
-        std::process::exit(1);
+        ExitCode::of_error(&err)
+    });
+    if code != ExitCode::Success {
+        std::process::exit(code.code());
     }
 }
 
-async fn run_cli() -> Result<()> {
+async fn run_cli() -> Result<ExitCode> {
     let cli = Cli::parse();
-    let db = DB::new().await?;
+    if let Command::Completions { shell } = cli.command {
+        clap_complete::generate(
+            shell,
+            &mut Cli::command(),
+            "repeater",
+            &mut std::io::stdout(),
+        );
+        return Ok(ExitCode::Success);
+    }
+    output::set_verbosity(Verbosity::from_flags(cli.quiet, cli.verbose));
+    Theme::set_color_output(Theme::wants_color(
+        std::env::var_os("NO_COLOR"),
+        std::io::stdout().is_terminal(),
+    ));
+    profile::select(cli.profile)?;
+    // Runs before anything that could fail on a broken setup, since diagnosing that is its job.
+    if let Command::Doctor { paths } = cli.command {
+        doctor::run(cli.db.as_deref(), paths).await?;
+        return Ok(ExitCode::Success);
+    }
+    let config = Config::load()?;
+    let scan = utils::ScanOptions {
+        scope: utils::PathScope::new(&cli.include, &cli.exclude)?,
+        separator: config.card_separator.clone(),
+        extensions: config.card_extensions.clone(),
+        strict: cli.strict,
+        use_cache: !cli.no_cache,
+    };
+    let db = DB::open(cli.db.as_deref()).await?;
+    let collection = cli.collection;
 
+    let mut code = ExitCode::Success;
     match cli.command {
         Command::Drill {
             paths,
             card_limit,
             new_card_limit,
+            tags,
+            ahead,
+            order,
+            new_first,
+            reviews_first: _,
+            desired_retention,
+            dry_run,
+            plan,
+            cram,
+        } => {
+            let paths = config.collection_paths(paths, collection)?;
+            let mut params = config.scheduler_params();
+            if let Some(retention) = desired_retention {
+                params.desired_retention = retention;
+            }
+            // The daily limits are about scheduling, which a cram doesn't touch.
+            let (card_limit, new_card_limit) = if cram {
+                (card_limit, None)
+            } else {
+                config.drill_limits(card_limit, new_card_limit)
+            };
+            let options = drill::DrillOptions {
+                card_limit,
+                new_card_limit,
+                tags,
+                ahead_days: ahead,
+                order,
+                new_first,
+                dry_run,
+                cram,
+            };
+            let settings = config.drill_settings(params);
+            let drilled = if plan {
+                drill::plan(&db, &scan, paths, options, settings).await?
+            } else {
+                drill::run(&db, &scan, paths, options, settings).await?
+            };
+            if !drilled {
+                code = ExitCode::NothingDue;
+            }
+        }
+        Command::Check { paths, prune, hard } => {
+            let _ = check::run(
+                &db,
+                &scan,
+                paths,
+                stats::Prune::from_flags(prune, hard),
+                config.day_cutoff_hour,
+            )
+            .await?;
+        }
+        Command::Stats {
+            paths,
+            json,
+            prune,
+            hard,
+            forecast_days,
+            by_file,
+            by_deck,
+            heatmap,
+            since,
+            until,
+        } => {
+            let paths = config.collection_paths(paths, collection)?;
+            let view = if by_file {
+                Some(stats::StatsView::ByFile)
+            } else if by_deck {
+                Some(stats::StatsView::ByDeck)
+            } else if heatmap {
+                Some(stats::StatsView::Heatmap)
+            } else {
+                None
+            };
+            let options = stats::StatsOptions {
+                json,
+                prune: stats::Prune::from_flags(prune, hard),
+                forecast_days,
+                view,
+                range: stats::ReviewRange { since, until },
+            };
+            stats::run(&db, &scan, paths, options, config.day_cutoff_hour).await?;
+        }
+        Command::List {
+            paths,
+            suspended,
+            leeches,
+            due_only,
+            limit,
+            page,
+            sort,
+            dupes,
+        } => {
+            let paths = config.collection_paths(paths, collection)?;
+            if dupes {
+                list::duplicates(&db, &scan, paths).await?;
+            } else {
+                let options = list::ListOptions {
+                    suspended_only: suspended,
+                    leeches_only: leeches,
+                    due_only,
+                    limit,
+                    page,
+                    sort,
+                };
+                list::run(&db, &scan, paths, options, config.day_cutoff_hour).await?;
+            }
+        }
+        Command::Info { path } => {
+            info::run(&db, &scan, path).await?;
+        }
+        Command::Search {
+            query,
+            paths,
+            regex,
+            ignore_case,
+        } => {
+            let paths = config.collection_paths(paths, collection)?;
+            let options = search::SearchOptions { regex, ignore_case };
+            search::run(&db, &scan, &query, paths, options).await?;
+        }
+        Command::Unsuspend { paths } => {
+            list::unsuspend(&db, &scan, paths).await?;
+        }
+        Command::Unarchive { paths } => {
+            list::unarchive(&db, &scan, paths).await?;
+        }
+        Command::Reset {
+            paths,
+            tags,
+            files,
+            yes,
         } => {
-            drill::run(&db, paths, card_limit, new_card_limit).await?;
+            let options = reset::ResetOptions { tags, files, yes };
+            reset::run(&db, &scan, paths, options).await?;
         }
-        Command::Check { paths } => {
-            let _ = check::run(&db, paths).await?;
+        Command::Tag { action } => match action {
+            TagAction::Add { tag, paths } => {
+                tag::run(&db, &scan, tag::TagChange::Add, &tag, paths).await?;
+            }
+            TagAction::Remove { tag, paths } => {
+                tag::run(&db, &scan, tag::TagChange::Remove, &tag, paths).await?;
+            }
+        },
+        Command::Rehash { paths } => {
+            rehash::run(&db, &scan, paths).await?;
         }
-        Command::Create { path } => {
-            create::run(&db, path).await?;
+        Command::Export {
+            paths,
+            format,
+            output,
+        } => {
+            export::run(&db, &scan, paths, format, output.as_deref()).await?;
+        }
+        Command::Simulate {
+            paths,
+            retention,
+            days,
+        } => {
+            simulate::run(
+                &db,
+                &scan,
+                paths,
+                config.scheduler_params(),
+                retention,
+                days,
+            )
+            .await?;
+        }
+        Command::Optimize => {
+            optimize::run(&db).await?;
+        }
+        Command::Doctor { .. } | Command::Completions { .. } => {
+            unreachable!("handled before opening the database")
+        }
+        Command::Watch { paths } => {
+            watch::run(&db, &scan, paths).await?;
+        }
+        Command::Vacuum { integrity_check } => {
+            vacuum::run(&db, integrity_check).await?;
+        }
+        Command::Create { path, edit } => {
+            create::run(&db, &scan, path, edit).await?;
         }
         Command::Import {
-            anki_path,
+            import_path,
             export_path,
+            overwrite,
         } => {
-            import::run(&db, &anki_path, &export_path)
-                .await.with_context(|| "Importing from Anki is a work in progress, please report issues on https://github.com/shaankhosla/repeater")?
-        },
+            if import_path.extension() == Some("csv".as_ref()) {
+                if export_path.is_some() {
+                    bail!("Restoring from a CSV doesn't take an output directory");
+                }
+                import_state::run(&db, &import_path, overwrite).await?;
+            } else {
+                let Some(export_path) = export_path else {
+                    bail!(
+                        "Importing from Anki needs a directory or markdown file to write cards to"
+                    );
+                };
+                import::run(&db, &import_path, &export_path, &scan.extensions)
+                    .await.with_context(|| "Importing from Anki is a work in progress, please report issues on https://github.com/shaankhosla/repeater")?
+            }
+        }
         Command::Llm { set, clear, test } => handle_llm_command(set, clear, test).await?,
     }
 
-    Ok(())
+    db.close().await;
+    Ok(code)
+}
+
+fn parse_date(value: &str) -> Result<NaiveDate> {
+    NaiveDate::parse_from_str(value, "%Y-%m-%d")
+        .with_context(|| format!("`{value}` is not a date like 2024-01-31"))
+}
+
+fn parse_desired_retention(value: &str) -> Result<f64> {
+    let retention: f64 = value
+        .parse()
+        .with_context(|| format!("`{value}` is not a number"))?;
+    validate_desired_retention(retention)
 }
 
 async fn handle_llm_command(set: Option<String>, clear: bool, test: bool) -> Result<()> {
@@ -149,3 +723,16 @@ async fn handle_llm_command(set: Option<String>, clear: bool, test: bool) -> Res
     }
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn list_pages_need_at_least_one_card() {
+        let parse = |args: &[&str]| Cli::try_parse_from(["repeater", "list"].iter().chain(args));
+        assert!(parse(&["--limit", "0"]).is_err());
+        assert!(parse(&["--limit", "1", "--page", "0"]).is_err());
+        assert!(parse(&["--limit", "1", "--page", "2"]).is_ok());
+    }
+}
@@ -1,8 +1,34 @@
+use std::collections::HashSet;
+
 use pulldown_cmark::{Event, HeadingLevel, Options, Parser, Tag, TagEnd};
-use ratatui::style::{Modifier, Style};
+use ratatui::style::{Color, Modifier, Style};
 use ratatui::text::{Line, Span, Text};
 
 pub fn render_markdown(md: &str) -> Text<'static> {
+    Text::from(render_lines(md).0)
+}
+
+/// Renders `md` with prose wrapped to `width` columns and code block lines left whole,
+/// for display without the paragraph's own wrapping.
+pub fn render_wrapped_markdown(md: &str, width: u16) -> Text<'static> {
+    let (lines, code_lines) = render_lines(md);
+    let width = usize::from(width);
+    let wrapped: Vec<Line> = lines
+        .into_iter()
+        .enumerate()
+        .flat_map(|(idx, line)| {
+            if code_lines.contains(&idx) {
+                vec![line]
+            } else {
+                wrap_line(line, width)
+            }
+        })
+        .collect();
+    Text::from(wrapped)
+}
+
+/// The rendered lines, plus the indices of those that come from code blocks.
+fn render_lines(md: &str) -> (Vec<Line<'static>>, HashSet<usize>) {
     let parser = Parser::new_ext(md, Options::ENABLE_MATH | Options::ENABLE_TASKLISTS);
     let mut lines: Vec<Line> = Vec::new();
     let mut current_line: Vec<Span> = Vec::new();
@@ -10,6 +36,7 @@ pub fn render_markdown(md: &str) -> Text<'static> {
     let mut list_stack: Vec<ListKind> = Vec::new();
     let mut pending_prefix: Option<String> = None;
     let mut in_code_block = false;
+    let mut code_lines = HashSet::new();
 
     for event in parser {
         match event {
@@ -83,6 +110,7 @@ pub fn render_markdown(md: &str) -> Text<'static> {
                 &mut lines,
                 &mut current_line,
                 &mut pending_prefix,
+                &mut code_lines,
             ),
             Event::Code(code) => {
                 maybe_apply_prefix(&mut current_line, &mut pending_prefix);
@@ -98,14 +126,17 @@ pub fn render_markdown(md: &str) -> Text<'static> {
                 &mut lines,
                 &mut current_line,
                 &mut pending_prefix,
+                &mut code_lines,
             ),
+            // The parser already dropped the `$`/`$$` delimiters.
             Event::InlineMath(math) | Event::DisplayMath(math) => push_text(
                 math.as_ref(),
-                current_style(&styles).add_modifier(Modifier::ITALIC),
+                math_style(current_style(&styles)),
                 in_code_block,
                 &mut lines,
                 &mut current_line,
                 &mut pending_prefix,
+                &mut code_lines,
             ),
             Event::FootnoteReference(label) => {
                 let rendered = format!("[^{}]", label);
@@ -116,6 +147,7 @@ pub fn render_markdown(md: &str) -> Text<'static> {
                     &mut lines,
                     &mut current_line,
                     &mut pending_prefix,
+                    &mut code_lines,
                 );
             }
             Event::SoftBreak => {
@@ -148,7 +180,7 @@ pub fn render_markdown(md: &str) -> Text<'static> {
     }
 
     flush_line(&mut lines, &mut current_line);
-    Text::from(lines)
+    (lines, code_lines)
 }
 
 fn push_text(
@@ -158,6 +190,7 @@ fn push_text(
     lines: &mut Vec<Line<'static>>,
     current_line: &mut Vec<Span<'static>>,
     pending_prefix: &mut Option<String>,
+    code_lines: &mut HashSet<usize>,
 ) {
     if in_code_block {
         let mut segments = text.split('\n').peekable();
@@ -175,6 +208,7 @@ fn push_text(
             }
             maybe_apply_prefix(current_line, pending_prefix);
             current_line.push(Span::styled(segment.to_string(), style));
+            code_lines.insert(lines.len());
         }
     } else {
         maybe_apply_prefix(current_line, pending_prefix);
@@ -182,6 +216,50 @@ fn push_text(
     }
 }
 
+/// Splits `line` at the last whitespace that fits in `width` columns, or mid-word when a
+/// word alone is too long. The whitespace at each break is dropped.
+fn wrap_line(line: Line<'static>, width: usize) -> Vec<Line<'static>> {
+    let cells: Vec<(char, Style)> = line
+        .spans
+        .iter()
+        .flat_map(|span| span.content.chars().map(move |ch| (ch, span.style)))
+        .collect();
+    if width == 0 || cells.len() <= width {
+        return vec![line];
+    }
+    let mut rows = Vec::new();
+    let mut rest = cells.as_slice();
+    while rest.len() > width {
+        let split = rest[..=width]
+            .iter()
+            .rposition(|(ch, _)| ch.is_whitespace())
+            .filter(|&idx| idx > 0);
+        match split {
+            Some(idx) => {
+                rows.push(&rest[..idx]);
+                rest = &rest[idx + 1..];
+            }
+            None => {
+                rows.push(&rest[..width]);
+                rest = &rest[width..];
+            }
+        }
+    }
+    rows.push(rest);
+    rows.into_iter().map(styled_line).collect()
+}
+
+fn styled_line(cells: &[(char, Style)]) -> Line<'static> {
+    let mut spans: Vec<Span<'static>> = Vec::new();
+    for &(ch, style) in cells {
+        match spans.last_mut() {
+            Some(span) if span.style == style => span.content.to_mut().push(ch),
+            _ => spans.push(Span::styled(ch.to_string(), style)),
+        }
+    }
+    Line::from(spans)
+}
+
 fn flush_line(lines: &mut Vec<Line<'static>>, current_line: &mut Vec<Span<'static>>) {
     if current_line.is_empty() {
         return;
@@ -215,6 +293,10 @@ fn maybe_apply_prefix(current_line: &mut Vec<Span<'static>>, pending_prefix: &mu
     }
 }
 
+fn math_style(base: Style) -> Style {
+    base.fg(Color::Cyan).add_modifier(Modifier::ITALIC)
+}
+
 fn heading_style(level: HeadingLevel) -> Style {
     let mut style = Style::default().add_modifier(Modifier::BOLD);
     if matches!(level, HeadingLevel::H1 | HeadingLevel::H2) {
@@ -263,12 +345,74 @@ fn list_prefix(stack: &mut [ListKind]) -> String {
 
 #[cfg(test)]
 mod tests {
-    use super::render_markdown;
+    use super::{render_markdown, render_wrapped_markdown};
     use proptest::prelude::*;
+    use ratatui::style::{Color, Modifier};
+    use ratatui::text::Text;
+
+    fn plain(text: &Text) -> Vec<String> {
+        text.lines.iter().map(|line| line.to_string()).collect()
+    }
+
     proptest! {
         #[test]
         fn test_markdown_render( content in "\\PC*") {
             render_markdown(&content);
         }
+
+        #[test]
+        fn wrapped_prose_fits_the_width(content in "[a-z][a-z ]{0,199}", width in 1u16..40) {
+            let text = render_wrapped_markdown(&content, width);
+            for line in &text.lines {
+                prop_assert!(line.to_string().chars().count() <= usize::from(width));
+            }
+        }
+    }
+
+    #[test]
+    fn formats_emphasis_code_and_lists() {
+        let text = render_markdown("**bold** *it* `code`\n\n- one\n- two\n");
+        let first = &text.lines[0];
+        assert!(first.spans[0].style.add_modifier.contains(Modifier::BOLD));
+        assert!(first.spans[2].style.add_modifier.contains(Modifier::ITALIC));
+        assert!(
+            first.spans[4]
+                .style
+                .add_modifier
+                .contains(Modifier::REVERSED)
+        );
+        assert_eq!(plain(&text)[2..4], ["- one", "- two"]);
+    }
+
+    #[test]
+    fn math_drops_delimiters_and_gets_its_own_style() {
+        let text =
+            render_markdown("Euler: $e^{i\\pi} = -1$ and $$\\int_0^1 x\\,dx$$ but \\$5 stays");
+        assert_eq!(
+            plain(&text)[0],
+            "Euler: e^{i\\pi} = -1 and \\int_0^1 x\\,dx but $5 stays"
+        );
+        let spans = &text.lines[0].spans;
+        assert_eq!(spans[1].content, "e^{i\\pi} = -1");
+        assert_eq!(spans[1].style.fg, Some(Color::Cyan));
+        assert_eq!(spans[0].style.fg, None);
+    }
+
+    #[test]
+    fn wraps_prose_but_not_code_blocks() {
+        let md = "one two three four\n\n```\nfn main() {\n    let long_name = 1;\n}\n```\n";
+        let text = render_wrapped_markdown(md, 10);
+        assert_eq!(
+            plain(&text),
+            [
+                "one two",
+                "three four",
+                "",
+                "fn main() {",
+                "    let long_name = 1;",
+                "}",
+                "",
+            ]
+        );
     }
 }
@@ -28,7 +28,7 @@ impl Media {
     }
 }
 
-fn media_kind_from_path(path: &Path) -> Option<MediaKind> {
+pub(crate) fn media_kind_from_path(path: &Path) -> Option<MediaKind> {
     let ext = path
         .extension()
         .and_then(|e| e.to_str())
@@ -48,7 +48,7 @@ fn media_kind_from_path(path: &Path) -> Option<MediaKind> {
     }
 }
 
-fn resolve_media_path(path: PathBuf, base_dir: Option<&Path>) -> PathBuf {
+pub(crate) fn resolve_media_path(path: PathBuf, base_dir: Option<&Path>) -> PathBuf {
     if path.is_relative()
         && let Some(dir) = base_dir
     {
@@ -57,6 +57,48 @@ fn resolve_media_path(path: PathBuf, base_dir: Option<&Path>) -> PathBuf {
     path
 }
 
+/// A local `![alt](path)` image in card text.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ImageRef {
+    pub alt: String,
+    /// Relative paths are resolved against the card file's directory.
+    pub path: PathBuf,
+}
+
+impl ImageRef {
+    pub fn exists(&self) -> bool {
+        self.path.is_file()
+    }
+}
+
+fn is_remote(dest: &str) -> bool {
+    dest.contains("://") || dest.starts_with("data:")
+}
+
+/// Every image in `markdown` stored on disk, whatever its extension; URLs are skipped.
+pub fn extract_images(markdown: &str, base_dir: Option<&Path>) -> Vec<ImageRef> {
+    let mut images = Vec::new();
+    let mut current: Option<ImageRef> = None;
+    for event in Parser::new(markdown) {
+        match event {
+            Event::Start(Tag::Image { dest_url, .. }) if !is_remote(&dest_url) => {
+                current = Some(ImageRef {
+                    alt: String::new(),
+                    path: resolve_media_path(PathBuf::from(dest_url.as_ref()), base_dir),
+                });
+            }
+            Event::Text(text) | Event::Code(text) => {
+                if let Some(image) = current.as_mut() {
+                    image.alt.push_str(&text);
+                }
+            }
+            Event::End(TagEnd::Image) => images.extend(current.take()),
+            _ => {}
+        }
+    }
+    images
+}
+
 pub fn extract_media(markdown: &str, base_dir: Option<&Path>) -> Vec<Media> {
     let parser = Parser::new(markdown);
 
@@ -90,10 +132,8 @@ pub fn extract_media(markdown: &str, base_dir: Option<&Path>) -> Vec<Media> {
                 }
             }
 
-            Event::Text(text) => {
-                if current_path.is_some() {
-                    current_label.push_str(&text);
-                }
+            Event::Text(text) if current_path.is_some() => {
+                current_label.push_str(&text);
             }
 
             Event::End(TagEnd::Link) => {
@@ -125,9 +165,9 @@ pub fn extract_media(markdown: &str, base_dir: Option<&Path>) -> Vec<Media> {
 mod tests {
     use std::path::{Path, PathBuf};
 
-    use crate::media::{Media, MediaKind};
+    use crate::media::{ImageRef, Media, MediaKind};
 
-    use super::extract_media;
+    use super::{extract_images, extract_media};
 
     #[test]
     fn test_markdown_parsing() {
@@ -185,4 +225,18 @@ This is a normal link and should be ignored:
         ];
         assert_eq!(medias, expected);
     }
+
+    #[test]
+    fn extracts_local_images_with_alt_text() {
+        let contents = "![Krebs *cycle*](diagrams/krebs.svg) and ![](https://example.com/a.png)";
+        let images = extract_images(contents, Some(Path::new("notes")));
+        assert_eq!(
+            images,
+            vec![ImageRef {
+                alt: "Krebs cycle".to_string(),
+                path: PathBuf::from("notes/diagrams/krebs.svg"),
+            }]
+        );
+        assert!(!images[0].exists());
+    }
 }
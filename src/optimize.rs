@@ -0,0 +1,291 @@
+use std::fmt::Write;
+
+use anyhow::{Result, bail};
+
+use crate::config::Config;
+use crate::crud::{DB, ReviewLogEntry};
+use crate::fsrs::{MemoryState, WEIGHT_COUNT, calculate_recall, next_memory_state};
+
+pub const MIN_REVIEWS: usize = 400;
+const ITERATIONS: usize = 250;
+/// Adam step size, as a fraction of each weight's allowed range.
+const LEARNING_RATE: f64 = 0.002;
+/// Finite-difference step, as a fraction of each weight's allowed range.
+const GRADIENT_STEP: f64 = 1e-5;
+const RECALL_EPSILON: f64 = 1e-6;
+const MIN_STABILITY: f64 = 0.01;
+
+/// (min, max) for each weight, following the ranges the reference FSRS optimizer allows.
+const WEIGHT_BOUNDS: [(f64, f64); WEIGHT_COUNT] = [
+    (0.01, 100.0),
+    (0.01, 100.0),
+    (0.01, 100.0),
+    (0.01, 100.0),
+    (1.0, 10.0),
+    (0.001, 4.0),
+    (0.001, 4.0),
+    (0.001, 0.75),
+    (0.0, 4.5),
+    (0.0, 0.8),
+    (0.001, 3.5),
+    (0.001, 5.0),
+    (0.001, 0.25),
+    (0.001, 0.9),
+    (0.0, 4.0),
+    (0.0, 1.0),
+    (1.0, 6.0),
+    (0.0, 2.0),
+    (0.0, 2.0),
+];
+
+/// How well a set of weights predicts the logged reviews of learned cards.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Evaluation {
+    pub reviews: usize,
+    pub log_loss: f64,
+    pub predicted_retention: f64,
+    pub actual_retention: f64,
+}
+
+pub struct Optimized {
+    pub weights: [f64; WEIGHT_COUNT],
+    pub before: Evaluation,
+    pub after: Evaluation,
+}
+
+pub async fn run(db: &DB) -> Result<()> {
+    let reviews = db.all_reviews().await?;
+    let path = Config::path()?;
+    let config = Config::load_from(&path)?;
+    let optimized = optimize(&reviews, &config.scheduler_params().weights)?;
+
+    print!("{}", format_summary(&optimized));
+    Config::save_weights(&path, &optimized.weights)?;
+    println!("Saved optimized weights to {}", path.display());
+    Ok(())
+}
+
+/// Fits weights with Adam on the log loss of predicted recall, starting from `start`.
+pub fn optimize(reviews: &[ReviewLogEntry], start: &[f64; WEIGHT_COUNT]) -> Result<Optimized> {
+    let before = evaluate(reviews, start);
+    if before.reviews < MIN_REVIEWS {
+        bail!(
+            "Optimizing needs at least {} reviews of learned cards (a day or more after the previous review), found {}",
+            MIN_REVIEWS,
+            before.reviews
+        );
+    }
+
+    let mut weights = *start;
+    clamp_weights(&mut weights);
+    let mut best = (evaluate(reviews, &weights).log_loss, weights);
+    let mut m = [0.0; WEIGHT_COUNT];
+    let mut v = [0.0; WEIGHT_COUNT];
+    let (beta1, beta2) = (0.9_f64, 0.999_f64);
+
+    for step in 1..=ITERATIONS {
+        let gradient = gradient(reviews, &weights);
+        for (i, weight) in weights.iter_mut().enumerate() {
+            m[i] = beta1 * m[i] + (1.0 - beta1) * gradient[i];
+            v[i] = beta2 * v[i] + (1.0 - beta2) * gradient[i] * gradient[i];
+            let m_hat = m[i] / (1.0 - beta1.powi(step as i32));
+            let v_hat = v[i] / (1.0 - beta2.powi(step as i32));
+            let (lo, hi) = WEIGHT_BOUNDS[i];
+            *weight -= LEARNING_RATE * (hi - lo) * m_hat / (v_hat.sqrt() + 1e-8);
+        }
+        clamp_weights(&mut weights);
+
+        let loss = evaluate(reviews, &weights).log_loss;
+        if loss < best.0 {
+            best = (loss, weights);
+        }
+    }
+
+    let weights = best.1.map(|w| (w * 1e5).round() / 1e5);
+    Ok(Optimized {
+        weights,
+        before,
+        after: evaluate(reviews, &weights),
+    })
+}
+
+fn gradient(reviews: &[ReviewLogEntry], weights: &[f64; WEIGHT_COUNT]) -> [f64; WEIGHT_COUNT] {
+    let mut gradient = [0.0; WEIGHT_COUNT];
+    for (i, slot) in gradient.iter_mut().enumerate() {
+        let (lo, hi) = WEIGHT_BOUNDS[i];
+        let h = GRADIENT_STEP * (hi - lo);
+        let mut up = *weights;
+        up[i] += h;
+        let mut down = *weights;
+        down[i] -= h;
+        *slot = (evaluate(reviews, &up).log_loss - evaluate(reviews, &down).log_loss) / (2.0 * h);
+    }
+    gradient
+}
+
+fn clamp_weights(weights: &mut [f64; WEIGHT_COUNT]) {
+    for (w, (lo, hi)) in weights.iter_mut().zip(WEIGHT_BOUNDS) {
+        *w = w.clamp(lo, hi);
+    }
+}
+
+/// Replays each card's log with `weights` and scores the predicted recall of every
+/// review made at least a day after the previous one (same rule as true retention).
+pub fn evaluate(reviews: &[ReviewLogEntry], weights: &[f64; WEIGHT_COUNT]) -> Evaluation {
+    let mut count = 0usize;
+    let mut loss = 0.0;
+    let mut predicted = 0.0;
+    let mut passed = 0usize;
+    let mut state: Option<MemoryState> = None;
+    let mut card_hash: Option<&str> = None;
+
+    for review in reviews {
+        if card_hash != Some(review.card_hash.as_str()) || review.previous_stability.is_none() {
+            state = None;
+            card_hash = Some(&review.card_hash);
+        }
+        if let Some(current) = state
+            && review.elapsed_days >= 1.0
+        {
+            let recall = calculate_recall(review.elapsed_days, current.stability)
+                .clamp(RECALL_EPSILON, 1.0 - RECALL_EPSILON);
+            let pass = review.rating.is_pass();
+            loss -= if pass {
+                recall.ln()
+            } else {
+                (1.0 - recall).ln()
+            };
+            predicted += recall;
+            passed += usize::from(pass);
+            count += 1;
+        }
+        let mut next = next_memory_state(weights, state, review.elapsed_days, review.rating);
+        next.stability = next.stability.max(MIN_STABILITY);
+        state = Some(next);
+    }
+
+    let n = count.max(1) as f64;
+    Evaluation {
+        reviews: count,
+        log_loss: loss / n,
+        predicted_retention: predicted / n,
+        actual_retention: passed as f64 / n,
+    }
+}
+
+pub fn format_summary(optimized: &Optimized) -> String {
+    let Optimized {
+        weights,
+        before,
+        after,
+    } = optimized;
+    let mut out = String::new();
+    let _ = writeln!(
+        out,
+        "Fitted FSRS weights to {} reviews of learned cards.",
+        after.reviews
+    );
+    let _ = writeln!(
+        out,
+        "  Actual retention:    {:.1}%",
+        after.actual_retention * 100.0
+    );
+    let _ = writeln!(
+        out,
+        "  Predicted retention: {:.1}% -> {:.1}%",
+        before.predicted_retention * 100.0,
+        after.predicted_retention * 100.0
+    );
+    let _ = writeln!(
+        out,
+        "  Log loss:            {:.4} -> {:.4}",
+        before.log_loss, after.log_loss
+    );
+    let weights: Vec<String> = weights.iter().map(|w| w.to_string()).collect();
+    let _ = writeln!(out, "  Weights: [{}]", weights.join(", "));
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{Duration, Utc};
+
+    use super::*;
+    use crate::fsrs::{DEFAULT_WEIGHTS, ReviewStatus, calulate_interval};
+
+    /// Reviews of cards that forget faster than the default weights assume.
+    fn simulated_log(cards: usize, reviews_per_card: usize) -> Vec<ReviewLogEntry> {
+        let mut true_weights = DEFAULT_WEIGHTS;
+        for w in &mut true_weights[..4] {
+            *w *= 0.3;
+        }
+        let mut seed: u64 = 42;
+        let mut next_random = move || {
+            seed = seed
+                .wrapping_mul(6364136223846793005)
+                .wrapping_add(1442695040888963407);
+            (seed >> 11) as f64 / (1u64 << 53) as f64
+        };
+
+        let now = Utc::now();
+        let mut log = Vec::new();
+        for card in 0..cards {
+            let card_hash = format!("card-{card:03}");
+            let mut state = None;
+            let mut elapsed = 0.0;
+            for i in 0..reviews_per_card {
+                let rating = match state {
+                    None => ReviewStatus::Good,
+                    Some(MemoryState { stability, .. }) => {
+                        if next_random() < calculate_recall(elapsed, stability) {
+                            ReviewStatus::Good
+                        } else {
+                            ReviewStatus::Again
+                        }
+                    }
+                };
+                let next = next_memory_state(&true_weights, state, elapsed, rating);
+                log.push(ReviewLogEntry {
+                    card_hash: card_hash.clone(),
+                    reviewed_at: now + Duration::days(i as i64),
+                    rating,
+                    elapsed_days: elapsed,
+                    previous_stability: state.map(|s: MemoryState| s.stability),
+                    new_stability: next.stability,
+                    interval_days: 1,
+                    duration_ms: None,
+                });
+                elapsed = calulate_interval(0.9, next.stability).round().max(1.0);
+                state = Some(next);
+            }
+        }
+        log
+    }
+
+    #[test]
+    fn requires_enough_reviews() {
+        let err = optimize(&simulated_log(5, 4), &DEFAULT_WEIGHTS)
+            .err()
+            .unwrap();
+        assert!(err.to_string().contains("at least 400"));
+    }
+
+    #[test]
+    fn evaluation_skips_first_reviews() {
+        let evaluation = evaluate(&simulated_log(3, 4), &DEFAULT_WEIGHTS);
+        assert_eq!(evaluation.reviews, 9);
+        assert!(evaluation.log_loss > 0.0);
+    }
+
+    #[test]
+    fn fitted_weights_do_not_predict_worse() {
+        let log = simulated_log(60, 9);
+        let optimized = optimize(&log, &DEFAULT_WEIGHTS).unwrap();
+        assert_eq!(optimized.before.reviews, 480);
+        assert!(optimized.after.log_loss <= optimized.before.log_loss);
+        for (w, (lo, hi)) in optimized.weights.iter().zip(WEIGHT_BOUNDS) {
+            assert!((lo..=hi).contains(w));
+        }
+        assert!(format_summary(&optimized).contains("480 reviews"));
+    }
+}
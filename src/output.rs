@@ -0,0 +1,67 @@
+use std::sync::atomic::{AtomicU8, Ordering};
+
+/// How much the non-interactive commands print besides their results, set by `--quiet`
+/// and `--verbose`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Verbosity {
+    Quiet,
+    Normal,
+    Verbose,
+}
+
+static VERBOSITY: AtomicU8 = AtomicU8::new(Verbosity::Normal as u8);
+
+impl Verbosity {
+    /// `quiet` wins when both flags are given.
+    pub fn from_flags(quiet: bool, verbose: bool) -> Self {
+        match (quiet, verbose) {
+            (true, _) => Self::Quiet,
+            (false, true) => Self::Verbose,
+            (false, false) => Self::Normal,
+        }
+    }
+}
+
+pub fn set_verbosity(verbosity: Verbosity) {
+    VERBOSITY.store(verbosity as u8, Ordering::Relaxed);
+}
+
+pub fn verbosity() -> Verbosity {
+    match VERBOSITY.load(Ordering::Relaxed) {
+        0 => Verbosity::Quiet,
+        1 => Verbosity::Normal,
+        _ => Verbosity::Verbose,
+    }
+}
+
+/// Prints a status line to stderr unless `--quiet` was given.
+#[macro_export]
+macro_rules! info {
+    ($($arg:tt)*) => {
+        if $crate::output::verbosity() >= $crate::output::Verbosity::Normal {
+            eprintln!($($arg)*);
+        }
+    };
+}
+
+/// Prints a line to stderr only with `--verbose`.
+#[macro_export]
+macro_rules! verbose {
+    ($($arg:tt)*) => {
+        if $crate::output::verbosity() >= $crate::output::Verbosity::Verbose {
+            eprintln!($($arg)*);
+        }
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Verbosity;
+
+    #[test]
+    fn quiet_wins_over_verbose() {
+        assert_eq!(Verbosity::from_flags(true, true), Verbosity::Quiet);
+        assert_eq!(Verbosity::from_flags(false, true), Verbosity::Verbose);
+        assert_eq!(Verbosity::from_flags(false, false), Verbosity::Normal);
+    }
+}
@@ -0,0 +1,71 @@
+use std::env;
+use std::sync::OnceLock;
+
+use anyhow::{Result, anyhow, bail};
+use directories::ProjectDirs;
+
+pub const PROFILE_ENV: &str = "REPEATER_PROFILE";
+const APP_NAME: &str = "repeater";
+
+static PROFILE: OnceLock<Option<String>> = OnceLock::new();
+
+/// Picks the profile for this process: `name` if given, else `REPEATER_PROFILE`.
+/// Each profile keeps its own database, config, and session in separate directories.
+pub fn select(name: Option<String>) -> Result<()> {
+    let name = name.or_else(|| match env::var(PROFILE_ENV) {
+        Ok(value) if !value.trim().is_empty() => Some(value.trim().to_string()),
+        _ => None,
+    });
+    if let Some(name) = &name {
+        validate(name)?;
+    }
+    PROFILE
+        .set(name)
+        .map_err(|_| anyhow!("Profile already selected"))
+}
+
+pub fn current() -> Option<&'static str> {
+    PROFILE.get().and_then(|name| name.as_deref())
+}
+
+pub fn project_dirs() -> Result<ProjectDirs> {
+    ProjectDirs::from("", "", &app_name(current()))
+        .ok_or_else(|| anyhow!("Could not determine project directory"))
+}
+
+fn app_name(profile: Option<&str>) -> String {
+    match profile {
+        Some(name) => format!("{APP_NAME}-{name}"),
+        None => APP_NAME.to_string(),
+    }
+}
+
+fn validate(name: &str) -> Result<()> {
+    if name.is_empty()
+        || !name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+    {
+        bail!("Invalid profile name {name:?}: use letters, digits, '-' or '_'");
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn profiles_get_their_own_app_name() {
+        assert_eq!(app_name(None), "repeater");
+        assert_eq!(app_name(Some("work")), "repeater-work");
+    }
+
+    #[test]
+    fn rejects_names_that_are_not_directory_safe() {
+        assert!(validate("spanish_2").is_ok());
+        assert!(validate("").is_err());
+        assert!(validate("../work").is_err());
+        assert!(validate("my profile").is_err());
+    }
+}
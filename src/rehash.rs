@@ -0,0 +1,31 @@
+use std::path::PathBuf;
+
+use anyhow::Result;
+
+use crate::crud::DB;
+use crate::utils::{ScanOptions, changed_hashes};
+
+/// Moves review history recorded under the hashes cards had before their text was
+/// NFC-normalized to the hashes they have now.
+pub async fn run(db: &DB, scan: &ScanOptions, paths: Vec<PathBuf>) -> Result<()> {
+    let scan = scan.clone();
+    let pairs = tokio::task::spawn_blocking(move || changed_hashes(&scan, &paths)).await??;
+    if pairs.is_empty() {
+        println!("No card hashes changed; nothing to rehash.");
+        return Ok(());
+    }
+    let outcome = db.rehash_cards(&pairs).await?;
+    let plural = if outcome.moved == 1 { "" } else { "s" };
+    println!(
+        "Moved the history of {} card{plural} to their new hash.",
+        outcome.moved
+    );
+    if outcome.conflicts > 0 {
+        let plural = if outcome.conflicts == 1 { "" } else { "s" };
+        println!(
+            "Left {} card{plural} alone: they were already reviewed under their new hash.",
+            outcome.conflicts
+        );
+    }
+    Ok(())
+}
@@ -0,0 +1,75 @@
+use std::collections::HashSet;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+
+use crate::crud::DB;
+use crate::utils::{ScanOptions, register_all_cards};
+
+pub struct ResetOptions {
+    pub tags: Vec<String>,
+    pub files: Vec<PathBuf>,
+    pub yes: bool,
+}
+
+/// Makes the matching cards new again, asking first unless `options.yes`.
+pub async fn run(
+    db: &DB,
+    scan: &ScanOptions,
+    paths: Vec<PathBuf>,
+    options: ResetOptions,
+) -> Result<()> {
+    let mut hash_cards = register_all_cards(db, scan, paths).await?;
+    if !options.tags.is_empty() {
+        let tagged = db.tagged_hashes(&options.tags).await?;
+        hash_cards.retain(|card_hash, _| tagged.contains(card_hash));
+    }
+    if !options.files.is_empty() {
+        let files = options
+            .files
+            .iter()
+            .map(|file| {
+                file.canonicalize()
+                    .with_context(|| format!("Cannot read {}", file.display()))
+            })
+            .collect::<Result<HashSet<_>>>()?;
+        hash_cards.retain(|_, card| in_files(&card.file_path, &files));
+    }
+
+    let reviewed: Vec<String> = db
+        .card_rows()
+        .await?
+        .into_iter()
+        .filter(|row| row.review_count > 0 && hash_cards.contains_key(&row.card_hash))
+        .map(|row| row.card_hash)
+        .collect();
+    if reviewed.is_empty() {
+        println!("No reviewed cards to reset.");
+        return Ok(());
+    }
+
+    let plural = if reviewed.len() == 1 { "" } else { "s" };
+    if !options.yes && !confirm(reviewed.len(), plural)? {
+        println!("Aborting; nothing was reset.");
+        return Ok(());
+    }
+    db.reset_cards(&reviewed).await?;
+    println!("Reset {} card{plural} to new.", reviewed.len());
+    Ok(())
+}
+
+fn in_files(path: &Path, files: &HashSet<PathBuf>) -> bool {
+    path.canonicalize().is_ok_and(|path| files.contains(&path))
+}
+
+fn confirm(count: usize, plural: &str) -> io::Result<bool> {
+    print!(
+        "Reset {count} reviewed card{plural} to new? Their schedules are lost (the review log is kept). [y/N]: "
+    );
+    io::stdout().flush()?;
+    let mut answer = String::new();
+    io::stdin().read_line(&mut answer)?;
+    let trimmed = answer.trim().to_lowercase();
+    Ok(trimmed == "y" || trimmed == "yes")
+}
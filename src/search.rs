@@ -0,0 +1,143 @@
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+use regex::{Regex, RegexBuilder};
+
+use crate::card::{Card, CardContent};
+use crate::crud::DB;
+use crate::utils::{ScanOptions, register_all_cards};
+
+pub struct SearchOptions {
+    pub regex: bool,
+    pub ignore_case: bool,
+}
+
+/// One line of a card's parsed text that matched the query.
+#[derive(Debug, PartialEq, Eq)]
+pub struct SearchMatch<'a> {
+    pub file_path: &'a Path,
+    /// 1-based line where the card starts in its file.
+    pub card_line: usize,
+    pub text: &'a str,
+}
+
+pub async fn run(
+    db: &DB,
+    scan: &ScanOptions,
+    query: &str,
+    paths: Vec<PathBuf>,
+    options: SearchOptions,
+) -> Result<()> {
+    let pattern = build_pattern(query, &options)?;
+    let card_hashes = register_all_cards(db, scan, paths).await?;
+    let matches = search(&card_hashes, &pattern);
+
+    if matches.is_empty() {
+        println!("No cards match {query:?}.");
+        return Ok(());
+    }
+    for found in &matches {
+        println!(
+            "{}:{}  {}",
+            found.file_path.display(),
+            found.card_line,
+            found.text.trim()
+        );
+    }
+    Ok(())
+}
+
+pub fn build_pattern(query: &str, options: &SearchOptions) -> Result<Regex> {
+    let pattern = if options.regex {
+        query.to_string()
+    } else {
+        regex::escape(query)
+    };
+    RegexBuilder::new(&pattern)
+        .case_insensitive(options.ignore_case)
+        .build()
+        .with_context(|| format!("Invalid search pattern {query:?}"))
+}
+
+/// Matches against the parsed question, answer, or cloze text, so `Q:`/`A:`/`C:`
+/// prefixes never match. Results are ordered by file and position.
+pub fn search<'a>(card_hashes: &'a HashMap<String, Card>, pattern: &Regex) -> Vec<SearchMatch<'a>> {
+    // Reversed twins and cloze siblings share a block; search each block once.
+    let mut blocks: BTreeMap<(&Path, usize), &Card> = BTreeMap::new();
+    for card in card_hashes.values() {
+        blocks
+            .entry((card.file_path.as_path(), card.file_card_range.0))
+            .or_insert(card);
+    }
+
+    let mut matches = Vec::new();
+    for ((file_path, start), card) in blocks {
+        let fields: Vec<&str> = match &card.content {
+            CardContent::Basic { question, answer } | CardContent::TypeIn { question, answer } => {
+                vec![question.as_str(), answer.as_str()]
+            }
+            CardContent::Cloze { text, .. } => vec![text.as_str()],
+        };
+        for line in fields.into_iter().flat_map(str::lines) {
+            if pattern.is_match(line) {
+                matches.push(SearchMatch {
+                    file_path,
+                    card_line: start + 1,
+                    text: line,
+                });
+            }
+        }
+    }
+    matches
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::content_to_cards;
+
+    fn cards() -> HashMap<String, Card> {
+        let path = Path::new("deck.md");
+        let mut cards = HashMap::new();
+        for (start, block) in [
+            (0, "Q: Capital of France?\nA: Paris"),
+            (3, "R: Hund\nA: dog"),
+            (6, "C: {{c1::Berlin}} is in {{c2::Germany}}"),
+        ] {
+            for card in content_to_cards(path, block, "---", start, start + 2).unwrap() {
+                cards.insert(card.card_hash.clone(), card);
+            }
+        }
+        cards
+    }
+
+    fn find(query: &str, regex: bool, ignore_case: bool) -> Vec<(usize, String)> {
+        let cards = cards();
+        let pattern = build_pattern(query, &SearchOptions { regex, ignore_case }).unwrap();
+        search(&cards, &pattern)
+            .into_iter()
+            .map(|found| (found.card_line, found.text.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn matches_parsed_text_once_per_block() {
+        assert_eq!(find("dog", false, false), vec![(4, "dog".to_string())]);
+        assert_eq!(find("Berlin", false, false).len(), 1);
+        assert!(find("Q:", false, false).is_empty());
+        assert!(find("paris", false, false).is_empty());
+        assert_eq!(find("paris", false, true), vec![(1, "Paris".to_string())]);
+    }
+
+    #[test]
+    fn supports_regular_expressions() {
+        assert_eq!(find("^Capital.*\\?$", true, false).len(), 1);
+        assert!(find("^Capital.*\\?$", false, false).is_empty());
+        let options = SearchOptions {
+            regex: true,
+            ignore_case: false,
+        };
+        assert!(build_pattern("(", &options).is_err());
+    }
+}
@@ -0,0 +1,107 @@
+use std::fs;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+
+use crate::crud::DB;
+use crate::drill::DrillOptions;
+
+const SESSION_FILE: &str = "session.json";
+
+/// Queue of a drill that didn't finish, so the next `repeater drill` can pick up where it stopped.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct SavedSession {
+    pub paths: Vec<PathBuf>,
+    pub options: DrillOptions,
+    /// Card hashes still to review, in queue order.
+    pub remaining: Vec<String>,
+    /// The subset of `remaining` that wasn't due yet when the session started.
+    pub ahead: Vec<String>,
+}
+
+impl SavedSession {
+    pub fn new(paths: Vec<PathBuf>, options: DrillOptions) -> Self {
+        Self {
+            paths,
+            options,
+            remaining: Vec::new(),
+            ahead: Vec::new(),
+        }
+    }
+
+    pub fn path() -> Result<PathBuf> {
+        Ok(DB::data_dir()?.join(SESSION_FILE))
+    }
+
+    pub fn load_from(path: &Path) -> Result<Option<Self>> {
+        let contents = match fs::read_to_string(path) {
+            Ok(contents) => contents,
+            Err(err) if err.kind() == ErrorKind::NotFound => return Ok(None),
+            Err(err) => {
+                return Err(err).with_context(|| format!("Failed to read {}", path.display()));
+            }
+        };
+        let session = serde_json::from_str(&contents)
+            .with_context(|| format!("Invalid drill session file {}", path.display()))?;
+        Ok(Some(session))
+    }
+
+    pub fn save_to(&self, path: &Path) -> Result<()> {
+        let contents = serde_json::to_string(self)?;
+        fs::write(path, contents).with_context(|| format!("Failed to write {}", path.display()))
+    }
+
+    pub fn clear(path: &Path) -> Result<()> {
+        match fs::remove_file(path) {
+            Err(err) if err.kind() != ErrorKind::NotFound => {
+                Err(err).with_context(|| format!("Failed to remove {}", path.display()))
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crud::cards::QueueOrder;
+
+    #[test]
+    fn round_trips_and_clears() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(SESSION_FILE);
+        assert_eq!(SavedSession::load_from(&path).unwrap(), None);
+
+        let mut session = SavedSession::new(
+            vec![PathBuf::from("decks")],
+            DrillOptions {
+                card_limit: Some(20),
+                new_card_limit: None,
+                tags: vec!["spanish".into()],
+                ahead_days: Some(2),
+                order: QueueOrder::Random,
+                new_first: false,
+                dry_run: false,
+                cram: false,
+            },
+        );
+        session.remaining = vec!["a".into(), "b".into()];
+        session.ahead = vec!["b".into()];
+        session.save_to(&path).unwrap();
+        assert_eq!(SavedSession::load_from(&path).unwrap(), Some(session));
+
+        SavedSession::clear(&path).unwrap();
+        assert_eq!(SavedSession::load_from(&path).unwrap(), None);
+        SavedSession::clear(&path).unwrap();
+    }
+
+    #[test]
+    fn rejects_corrupt_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(SESSION_FILE);
+        fs::write(&path, "not json").unwrap();
+        assert!(SavedSession::load_from(&path).is_err());
+    }
+}
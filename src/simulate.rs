@@ -0,0 +1,241 @@
+use std::fmt::Write as _;
+use std::path::PathBuf;
+
+use anyhow::Result;
+use chrono::Duration;
+
+use crate::crud::DB;
+use crate::fsrs::{
+    Performance, ReviewStatus, ReviewedPerformance, SchedulerParams, calculate_recall,
+    elapsed_days, schedule_review,
+};
+use crate::utils::{ScanOptions, day_start, read_all_cards, today};
+
+/// Reviews a single card may get in one run, so a card stuck on sub-day steps can't
+/// spin forever.
+const MAX_REVIEWS_PER_CARD: usize = 1_000;
+
+/// Projects the reviews per day of the cards under `paths` over the next `days` days,
+/// at the configured retention and at `retention`, and prints the two side by side.
+/// Nothing is registered or written.
+pub async fn run(
+    db: &DB,
+    scan: &ScanOptions,
+    paths: Vec<PathBuf>,
+    params: SchedulerParams,
+    retention: f64,
+    days: usize,
+) -> Result<()> {
+    let cards = read_all_cards(scan, paths).await?;
+    let mut performances = db.reviewed_performances().await?;
+    performances.retain(|card_hash, _| cards.contains_key(card_hash));
+    if performances.is_empty() {
+        println!("No reviewed cards to simulate; review some cards first.");
+        return Ok(());
+    }
+    let mut performances: Vec<(String, ReviewedPerformance)> = performances.into_iter().collect();
+    performances.sort_by(|a, b| a.0.cmp(&b.0));
+
+    let start = day_start(today(params.day_cutoff_hour), params.day_cutoff_hour);
+    let current = simulate_load(&performances, &params, start, days);
+    let alternative_params = SchedulerParams {
+        desired_retention: retention,
+        ..params.clone()
+    };
+    let alternative = simulate_load(&performances, &alternative_params, start, days);
+    print!(
+        "{}",
+        format_comparison(
+            performances.len(),
+            (params.desired_retention, current.as_slice()),
+            (retention, alternative.as_slice()),
+        )
+    );
+    Ok(())
+}
+
+/// Reviews on each of the `days` days from `start` when every card is studied the day
+/// it comes due under `params`, overdue cards on the first day. Whether each review is
+/// recalled is drawn from the card's forgetting curve with a seed taken from the card
+/// and its review count, so runs are repeatable.
+fn simulate_load(
+    cards: &[(String, ReviewedPerformance)],
+    params: &SchedulerParams,
+    start: chrono::DateTime<chrono::Utc>,
+    days: usize,
+) -> Vec<usize> {
+    let end = start + Duration::days(days as i64);
+    let mut load = vec![0; days];
+    for (card_hash, performance) in cards {
+        let mut performance = *performance;
+        for _ in 0..MAX_REVIEWS_PER_CARD {
+            let reviewed_at = performance.due_date.max(start);
+            if reviewed_at >= end {
+                break;
+            }
+            let day = (reviewed_at - start).num_days() as usize;
+            load[day.min(days - 1)] += 1;
+
+            let elapsed = elapsed_days(performance.last_reviewed_at, reviewed_at);
+            let recall = calculate_recall(elapsed, performance.stability);
+            let status = if draw(card_hash, performance.review_count) < recall {
+                ReviewStatus::Good
+            } else {
+                ReviewStatus::Again
+            };
+            performance = schedule_review(
+                card_hash,
+                Performance::Reviewed(performance),
+                status,
+                reviewed_at,
+                params,
+            );
+        }
+    }
+    load
+}
+
+/// A number in `[0, 1)` fixed by the card and how often it has been reviewed.
+fn draw(card_hash: &str, review_count: usize) -> f64 {
+    let seed = blake3::hash(format!("simulate:{card_hash}:{review_count}").as_bytes());
+    let mut bytes = [0u8; 8];
+    bytes.copy_from_slice(&seed.as_bytes()[..8]);
+    (u64::from_le_bytes(bytes) >> 11) as f64 / (1u64 << 53) as f64
+}
+
+/// Average reviews per day for each week, the totals, and what the alternative saves.
+fn format_comparison(
+    cards: usize,
+    (current_retention, current): (f64, &[usize]),
+    (retention, alternative): (f64, &[usize]),
+) -> String {
+    let days = current.len();
+    let plural = if cards == 1 { "" } else { "s" };
+    let mut out =
+        format!("Projected reviews of {cards} studied card{plural} over {days} days.\n\n");
+    let current_label = format!("AT {current_retention:.2}");
+    let alternative_label = format!("AT {retention:.2}");
+    let _ = writeln!(
+        out,
+        "{:<14}  {current_label:>10}  {alternative_label:>10}",
+        "AVG PER DAY"
+    );
+    for (week, (current, alternative)) in current.chunks(7).zip(alternative.chunks(7)).enumerate() {
+        let first = week * 7 + 1;
+        let last = first + current.len() - 1;
+        let average = |load: &[usize]| load.iter().sum::<usize>() as f64 / load.len() as f64;
+        let label = if first == last {
+            format!("Day {first}")
+        } else {
+            format!("Days {first}-{last}")
+        };
+        let _ = writeln!(
+            out,
+            "{:<14}  {:>10.1}  {:>10.1}",
+            label,
+            average(current),
+            average(alternative)
+        );
+    }
+    let current_total: usize = current.iter().sum();
+    let alternative_total: usize = alternative.iter().sum();
+    let _ = writeln!(
+        out,
+        "{:<14}  {current_total:>10}  {alternative_total:>10}",
+        "TOTAL"
+    );
+    let busiest = |load: &[usize]| load.iter().copied().max().unwrap_or(0);
+    let _ = writeln!(
+        out,
+        "{:<14}  {:>10}  {:>10}",
+        "BUSIEST DAY",
+        busiest(current),
+        busiest(alternative)
+    );
+
+    out.push('\n');
+    let difference = alternative_total.abs_diff(current_total);
+    if difference == 0 || current_total == 0 {
+        let _ = writeln!(
+            out,
+            "A retention of {retention:.2} would not change the number of reviews."
+        );
+    } else {
+        let fewer = if alternative_total < current_total {
+            "fewer"
+        } else {
+            "more"
+        };
+        let share = difference as f64 / current_total as f64 * 100.0;
+        let _ = writeln!(
+            out,
+            "A retention of {retention:.2} would mean {difference} {fewer} reviews ({share:.0}%), \
+             about {:.1} a day, while you'd recall about {:.0}% of due cards instead of {:.0}%.",
+            difference as f64 / days as f64,
+            retention * 100.0,
+            current_retention * 100.0
+        );
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::DEFAULT_DAY_CUTOFF_HOUR;
+
+    fn reviewed(start: chrono::DateTime<chrono::Utc>, stability: f64) -> ReviewedPerformance {
+        ReviewedPerformance {
+            last_reviewed_at: start - Duration::days(5),
+            stability,
+            difficulty: 5.0,
+            interval_raw: 5.0,
+            interval_days: 5,
+            due_date: start,
+            review_count: 2,
+        }
+    }
+
+    #[test]
+    fn lower_retention_means_fewer_reviews() {
+        let start = day_start(today(DEFAULT_DAY_CUTOFF_HOUR), DEFAULT_DAY_CUTOFF_HOUR);
+        let cards: Vec<(String, ReviewedPerformance)> = (0..50)
+            .map(|idx| (format!("card-{idx}"), reviewed(start, 5.0)))
+            .collect();
+        let params = SchedulerParams::default();
+        let current = simulate_load(&cards, &params, start, 60);
+        let relaxed = simulate_load(
+            &cards,
+            &SchedulerParams {
+                desired_retention: 0.8,
+                ..params.clone()
+            },
+            start,
+            60,
+        );
+        assert_eq!(current.len(), 60);
+        assert!(current[0] >= 50);
+        assert_eq!(current, simulate_load(&cards, &params, start, 60));
+        assert!(relaxed.iter().sum::<usize>() < current.iter().sum::<usize>());
+    }
+
+    #[test]
+    fn comparison_lists_weeks_and_totals() {
+        let out = format_comparison(
+            2,
+            (0.9, &[4, 2, 0, 0, 0, 0, 0, 4]),
+            (0.85, &[4, 1, 0, 0, 0, 0, 0, 2]),
+        );
+        assert_eq!(
+            out,
+            "Projected reviews of 2 studied cards over 8 days.\n\n\
+             AVG PER DAY        AT 0.90     AT 0.85\n\
+             Days 1-7               0.9         0.7\n\
+             Day 8                  4.0         2.0\n\
+             TOTAL                   10           7\n\
+             BUSIEST DAY              4           4\n\n\
+             A retention of 0.85 would mean 3 fewer reviews (30%), about 0.4 a day, \
+             while you'd recall about 85% of due cards instead of 90%.\n"
+        );
+    }
+}
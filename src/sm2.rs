@@ -0,0 +1,130 @@
+use chrono::Duration;
+
+use crate::fsrs::{MAX_INTERVAL, Performance, ReviewStatus, ReviewedPerformance, SchedulerParams};
+
+pub const INITIAL_EASE: f64 = 2.5;
+pub const MIN_EASE: f64 = 1.3;
+const FIRST_INTERVAL: usize = 1;
+const SECOND_INTERVAL: usize = 6;
+
+/// SM-2 response quality (0-5) of a passing grade.
+fn quality(review_status: ReviewStatus) -> f64 {
+    match review_status {
+        ReviewStatus::Again => 0.0,
+        ReviewStatus::Hard => 3.0,
+        ReviewStatus::Good => 4.0,
+        ReviewStatus::Easy => 5.0,
+    }
+}
+
+/// A failed card restarts its repetitions without changing its ease.
+fn next_ease(ease: f64, review_status: ReviewStatus) -> f64 {
+    if !review_status.is_pass() {
+        return ease;
+    }
+    let miss = 5.0 - quality(review_status);
+    (ease + 0.1 - miss * (0.08 + miss * 0.02)).max(MIN_EASE)
+}
+
+/// Same contract as [`crate::fsrs::update_performance`]. The ease factor is kept in `difficulty`
+/// and the interval doubles as `stability`, so the rest of the app reads SM-2 cards unchanged.
+/// SM-2 has no weights or retention target, so only `max_interval_days` is read from `params`.
+pub fn update_performance(
+    perf: Performance,
+    review_status: ReviewStatus,
+    reviewed_at: chrono::DateTime<chrono::Utc>,
+    params: &SchedulerParams,
+) -> ReviewedPerformance {
+    let (ease, previous_interval, review_count) = match perf {
+        Performance::New => (INITIAL_EASE, None, 0),
+        Performance::Reviewed(reviewed) => (
+            reviewed.difficulty.max(MIN_EASE),
+            Some(reviewed.interval_days),
+            reviewed.review_count,
+        ),
+    };
+    let ease = next_ease(ease, review_status);
+    let interval_uncapped = match previous_interval {
+        _ if !review_status.is_pass() => FIRST_INTERVAL,
+        None => FIRST_INTERVAL,
+        Some(previous) if previous <= FIRST_INTERVAL => SECOND_INTERVAL,
+        Some(previous) => (previous as f64 * ease).round() as usize,
+    }
+    .min(MAX_INTERVAL as usize);
+    let interval_days = interval_uncapped.min(params.max_interval_days);
+
+    ReviewedPerformance {
+        last_reviewed_at: reviewed_at,
+        stability: interval_days as f64,
+        difficulty: ease,
+        interval_raw: interval_uncapped as f64,
+        interval_days,
+        due_date: reviewed_at + Duration::days(interval_days as i64),
+        review_count: review_count + 1,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fsrs::{Scheduler, schedule_review};
+
+    fn review(perf: Performance, status: ReviewStatus) -> ReviewedPerformance {
+        update_performance(
+            perf,
+            status,
+            chrono::Utc::now(),
+            &SchedulerParams::default(),
+        )
+    }
+
+    #[test]
+    fn intervals_grow_by_the_ease_factor() {
+        let first = review(Performance::New, ReviewStatus::Good);
+        assert_eq!(first.interval_days, 1);
+        assert_eq!(first.difficulty, INITIAL_EASE);
+        let second = review(Performance::Reviewed(first), ReviewStatus::Good);
+        assert_eq!(second.interval_days, 6);
+        let third = review(Performance::Reviewed(second), ReviewStatus::Good);
+        assert_eq!(third.interval_days, 15);
+        assert_eq!(third.review_count, 3);
+        assert_eq!(third.stability, 15.0);
+    }
+
+    #[test]
+    fn grades_adjust_ease_and_failures_restart() {
+        assert!((next_ease(2.5, ReviewStatus::Hard) - 2.36).abs() < 1e-9);
+        assert_eq!(next_ease(2.5, ReviewStatus::Good), 2.5);
+        assert!((next_ease(2.5, ReviewStatus::Easy) - 2.6).abs() < 1e-9);
+        assert_eq!(next_ease(1.35, ReviewStatus::Hard), MIN_EASE);
+
+        let mut mature = review(Performance::New, ReviewStatus::Good);
+        mature.interval_days = 40;
+        let lapsed = review(Performance::Reviewed(mature), ReviewStatus::Again);
+        assert_eq!(lapsed.interval_days, 1);
+        assert_eq!(lapsed.difficulty, INITIAL_EASE);
+        let relearned = review(Performance::Reviewed(lapsed), ReviewStatus::Good);
+        assert_eq!(relearned.interval_days, 6);
+    }
+
+    #[test]
+    fn schedule_review_uses_the_configured_scheduler() {
+        let now = chrono::Utc::now();
+        let params = SchedulerParams {
+            scheduler: Scheduler::Sm2,
+            enable_fuzz: false,
+            ..SchedulerParams::default()
+        };
+        let mut reviewed = review(Performance::New, ReviewStatus::Good);
+        reviewed.interval_days = 10;
+        let perf = Performance::Reviewed(reviewed);
+        assert_eq!(
+            schedule_review("card", perf, ReviewStatus::Good, now, &params),
+            update_performance(perf, ReviewStatus::Good, now, &params)
+        );
+        assert_eq!(
+            schedule_review("card", perf, ReviewStatus::Good, now, &params).interval_days,
+            25
+        );
+    }
+}
@@ -1,22 +1,248 @@
-use std::collections::{BTreeMap, HashMap};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fmt::Write;
 
-use std::path::PathBuf;
+use std::path::{Component, Path, PathBuf};
+
+use anyhow::{Result, bail};
+use chrono::NaiveDate;
+use serde::ser::SerializeStruct;
+use serde::{Serialize, Serializer};
 
 use crate::card::Card;
 use crate::crud::stats::CardStatsRow;
-use crate::fsrs::{LEARN_AHEAD_THRESHOLD_MINS, calculate_recall};
+use crate::crud::{DB, RetentionCounts, ReviewPeriod};
+use crate::fsrs::calculate_recall;
+use crate::heatmap::{HEATMAP_DAYS, format_heatmap};
+use crate::tui::Theme;
+use crate::utils::{ScanOptions, is_due_at, register_all_cards, study_day, today};
+
+const BAR_WIDTH: usize = 30;
+pub const DEFAULT_FORECAST_DAYS: usize = 30;
+pub const RETENTION_WINDOW_DAYS: i64 = 30;
+pub const REVIEW_TIME_WINDOW_DAYS: i64 = 7;
+/// Overdue cards waiting longer than this many days are counted separately, for triage.
+pub const LONG_OVERDUE_DAYS: i64 = 3;
+
+/// What `stats` prints instead of the summary.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum StatsView {
+    ByFile,
+    /// Top-level directory under each collection root.
+    ByDeck,
+    /// Reviews done on each day of the last year.
+    Heatmap,
+}
 
-#[derive(Debug, Default)]
+/// `--since`/`--until` bounds on the stats taken from the review log.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct ReviewRange {
+    pub since: Option<NaiveDate>,
+    pub until: Option<NaiveDate>,
+}
+
+impl ReviewRange {
+    /// Days from `since` to `until`, defaulting to `today` for the end and to the
+    /// `default_days` ending there for the start.
+    pub fn period(&self, default_days: i64, today: NaiveDate) -> ReviewPeriod {
+        let last = self.until.unwrap_or(today);
+        match self.since {
+            Some(first) => ReviewPeriod { first, last },
+            None => ReviewPeriod::ending(last, default_days),
+        }
+    }
+}
+
+pub struct StatsOptions {
+    pub json: bool,
+    pub prune: Option<Prune>,
+    pub forecast_days: usize,
+    pub view: Option<StatsView>,
+    pub range: ReviewRange,
+}
+
+pub async fn run(
+    db: &DB,
+    scan: &ScanOptions,
+    paths: Vec<PathBuf>,
+    options: StatsOptions,
+    day_cutoff_hour: u32,
+) -> Result<()> {
+    let StatsOptions {
+        json,
+        prune,
+        forecast_days,
+        view,
+        range,
+    } = options;
+    if let ReviewRange {
+        since: Some(since),
+        until: Some(until),
+    } = range
+        && since > until
+    {
+        bail!("--since {since} is after --until {until}");
+    }
+    let card_hashes = register_all_cards(db, scan, paths.clone()).await?;
+    if let Some(prune) = prune {
+        let message = prune
+            .run(db, &card_hashes.keys().cloned().collect())
+            .await?;
+        crate::info!("{message}");
+    }
+    let mut stats = db
+        .collection_stats(&card_hashes, forecast_days, day_cutoff_hour)
+        .await?;
+    let today = today(day_cutoff_hour);
+    let retention_period = range.period(RETENTION_WINDOW_DAYS, today);
+    stats.true_retention = Some(db.true_retention(retention_period, day_cutoff_hour).await?);
+    stats.retention_period = Some(retention_period);
+    let review_time_period = range.period(REVIEW_TIME_WINDOW_DAYS, today);
+    let durations = db
+        .review_durations_per_day(review_time_period, day_cutoff_hour)
+        .await?;
+    stats.review_time = Some(ReviewTime::from_durations(&durations));
+    stats.review_time_period = Some(review_time_period);
+    if json {
+        println!("{}", serde_json::to_string_pretty(&stats)?);
+        return Ok(());
+    }
+    match view {
+        Some(StatsView::ByFile) => {
+            let rows = stats
+                .files
+                .iter()
+                .map(|(path, group)| (path.display().to_string(), *group));
+            print!("{}", format_breakdown("FILE", rows));
+        }
+        Some(StatsView::ByDeck) => {
+            let decks = group_by_deck(&stats.files, &paths);
+            print!("{}", format_breakdown("DECK", decks.into_iter()));
+        }
+        Some(StatsView::Heatmap) => {
+            let period = range.period(HEATMAP_DAYS, today);
+            let per_day = db.reviews_per_day(period, day_cutoff_hour).await?;
+            print!("{}", format_heatmap(&per_day, period, today));
+        }
+        None => print!("{}", format_stats(&stats)),
+    }
+    Ok(())
+}
+
+#[derive(Debug, Default, Serialize)]
 pub struct CardStats {
     pub total_cards_in_db: i64,
     pub num_cards: i64,
     pub card_lifecycles: HashMap<CardLifeCycle, i64>,
     pub due_cards: i64,
+    /// Due cards whose due date was before today.
+    pub overdue_cards: i64,
+    /// Overdue cards more than [`LONG_OVERDUE_DAYS`] days past their due date.
+    pub long_overdue_cards: i64,
+    pub leeches: i64,
     pub upcoming_week: BTreeMap<String, usize>,
     pub upcoming_month: i64,
+    /// Cards due on each of the next days; index 0 is today and includes anything overdue.
+    pub forecast: Vec<usize>,
     pub file_paths: HashMap<PathBuf, usize>,
+    /// Card counts for each file, sorted by path.
+    pub files: BTreeMap<PathBuf, GroupStats>,
     pub difficulty_histogram: Histogram<5>,
     pub retrievability_histogram: Histogram<5>,
+    /// Pass/fail counts over `retention_period`, when loaded.
+    pub true_retention: Option<RetentionCounts>,
+    /// The last [`RETENTION_WINDOW_DAYS`] unless `--since`/`--until` say otherwise.
+    pub retention_period: Option<ReviewPeriod>,
+    /// Answer times over `review_time_period`, when loaded.
+    pub review_time: Option<ReviewTime>,
+    /// The last [`REVIEW_TIME_WINDOW_DAYS`] unless `--since`/`--until` say otherwise.
+    pub review_time_period: Option<ReviewPeriod>,
+    /// Local hour the study days these counts are bucketed into roll over at.
+    #[serde(skip)]
+    pub day_cutoff_hour: u32,
+}
+
+/// Cards due on one day of the next week.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct UpcomingBucket {
+    /// `YYYY-MM-DD`.
+    pub day: String,
+    pub count: usize,
+}
+
+impl UpcomingBucket {
+    /// The day as a short label like `Mon 04`, or as-is if it isn't a date.
+    pub fn label(&self) -> String {
+        NaiveDate::parse_from_str(&self.day, "%Y-%m-%d")
+            .map(|date| date.format("%a %d").to_string())
+            .unwrap_or_else(|_| self.day.clone())
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize)]
+pub struct ReviewTime {
+    /// Keyed by local date; days without timed reviews are left out.
+    pub days: BTreeMap<String, DayReviewTime>,
+    pub average_secs_per_card: Option<f64>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize)]
+pub struct DayReviewTime {
+    pub reviews: usize,
+    pub total_secs: f64,
+    pub median_secs: f64,
+}
+
+impl ReviewTime {
+    pub fn from_durations(per_day: &BTreeMap<NaiveDate, Vec<i64>>) -> Self {
+        let mut days = BTreeMap::new();
+        let mut reviews = 0;
+        let mut total_ms = 0;
+        for (day, durations) in per_day {
+            if durations.is_empty() {
+                continue;
+            }
+            let mut sorted = durations.clone();
+            sorted.sort_unstable();
+            let mid = sorted.len() / 2;
+            let median_ms = if sorted.len().is_multiple_of(2) {
+                (sorted[mid - 1] + sorted[mid]) as f64 / 2.0
+            } else {
+                sorted[mid] as f64
+            };
+            let day_total: i64 = sorted.iter().sum();
+            reviews += sorted.len();
+            total_ms += day_total;
+            days.insert(
+                day.format("%Y-%m-%d").to_string(),
+                DayReviewTime {
+                    reviews: sorted.len(),
+                    total_secs: day_total as f64 / 1000.0,
+                    median_secs: median_ms / 1000.0,
+                },
+            );
+        }
+        Self {
+            days,
+            average_secs_per_card: (reviews > 0).then(|| total_ms as f64 / 1000.0 / reviews as f64),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize)]
+pub struct GroupStats {
+    pub cards: usize,
+    pub due: usize,
+    pub new: usize,
+    pub mature: usize,
+}
+
+impl GroupStats {
+    fn add(&mut self, other: &GroupStats) {
+        self.cards += other.cards;
+        self.due += other.due;
+        self.new += other.new;
+        self.mature += other.mature;
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -36,6 +262,16 @@ impl<const N: usize> Default for Histogram<N> {
         }
     }
 }
+impl<const N: usize> Serialize for Histogram<N> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut state = serializer.serialize_struct("Histogram", 3)?;
+        state.serialize_field("bins", &self.bins[..])?;
+        state.serialize_field("count", &self.count)?;
+        state.serialize_field("mean", &self.mean())?;
+        state.end()
+    }
+}
+
 impl<const N: usize> Histogram<N> {
     pub fn update(&mut self, value: f64) {
         let v = value.clamp(0.0, 1.0);
@@ -54,7 +290,7 @@ impl<const N: usize> Histogram<N> {
     }
 }
 
-#[derive(Debug, Clone, Hash, Eq, PartialEq)]
+#[derive(Debug, Clone, Hash, Eq, PartialEq, Serialize)]
 pub enum CardLifeCycle {
     New,
     Young,
@@ -62,7 +298,50 @@ pub enum CardLifeCycle {
 }
 const MATURE_INTERVAL: f64 = 21.0;
 
+impl CardLifeCycle {
+    pub fn classify(review_count: i64, interval_raw: f64) -> Self {
+        if review_count == 0 {
+            CardLifeCycle::New
+        } else if interval_raw > MATURE_INTERVAL {
+            CardLifeCycle::Mature
+        } else {
+            CardLifeCycle::Young
+        }
+    }
+}
+
 impl CardStats {
+    pub fn lifecycle_count(&self, lifecycle: CardLifeCycle) -> i64 {
+        self.card_lifecycles.get(&lifecycle).copied().unwrap_or(0)
+    }
+
+    /// Tracked cards that have never been reviewed.
+    pub fn new_cards(&self) -> i64 {
+        self.lifecycle_count(CardLifeCycle::New)
+    }
+
+    /// Tracked cards reviewed at least once, young and mature alike.
+    pub fn reviewed_cards(&self) -> i64 {
+        self.lifecycle_count(CardLifeCycle::Young) + self.lifecycle_count(CardLifeCycle::Mature)
+    }
+
+    /// Cards due over the next week, today's due cards included.
+    pub fn upcoming_week_total(&self) -> usize {
+        self.upcoming_week.values().sum()
+    }
+
+    /// `upcoming_week` as one bucket per day that has cards due, earliest first.
+    pub fn upcoming_week_buckets(&self) -> Vec<UpcomingBucket> {
+        // ISO dates sort chronologically as strings.
+        self.upcoming_week
+            .iter()
+            .map(|(day, count)| UpcomingBucket {
+                day: day.clone(),
+                count: *count,
+            })
+            .collect()
+    }
+
     // row is a Record
     pub fn update(&mut self, card: &Card, row: &CardStatsRow) {
         let review_count = row.review_count;
@@ -73,36 +352,57 @@ impl CardStats {
         let last_reviewed_at = row.last_reviewed_at;
 
         let now = chrono::Utc::now();
+        let cutoff_hour = self.day_cutoff_hour;
+        let today = study_day(now, cutoff_hour);
+        let is_due =
+            due_date.is_none_or(|due_date| is_due_at(due_date, interval, now, cutoff_hour));
         let week_horizon = now + chrono::Duration::days(7);
         let month_horizon = now + chrono::Duration::days(30);
         *self.file_paths.entry(card.file_path.clone()).or_insert(0) += 1;
 
-        let lifecycle = if review_count == 0 {
-            CardLifeCycle::New
-        } else if interval > MATURE_INTERVAL {
-            CardLifeCycle::Mature
-        } else {
-            CardLifeCycle::Young
-        };
+        let lifecycle = CardLifeCycle::classify(review_count, interval);
+        let group = self.files.entry(card.file_path.clone()).or_default();
+        group.cards += 1;
+        group.due += usize::from(is_due);
+        group.new += usize::from(lifecycle == CardLifeCycle::New);
+        group.mature += usize::from(lifecycle == CardLifeCycle::Mature);
 
         *self.card_lifecycles.entry(lifecycle).or_insert(0) += 1;
+        if row.leech {
+            self.leeches += 1;
+        }
+
+        let forecast_day = match due_date {
+            Some(due_date) if !is_due => {
+                (study_day(due_date, cutoff_hour) - today).num_days() as usize
+            }
+            _ => 0,
+        };
+        if let Some(bucket) = self.forecast.get_mut(forecast_day) {
+            *bucket += 1;
+        }
 
         match due_date {
             None => {
                 self.due_cards += 1;
-                let day = now.format("%Y-%m-%d").to_string();
+                let day = today.format("%Y-%m-%d").to_string();
                 *self.upcoming_week.entry(day).or_insert(0) += 1;
                 self.upcoming_month += 1;
             }
             Some(due_date) => {
-                if due_date <= now + LEARN_AHEAD_THRESHOLD_MINS {
+                if is_due {
                     self.due_cards += 1;
-                    let day = now.format("%Y-%m-%d").to_string();
+                    let days_overdue = (today - study_day(due_date, cutoff_hour)).num_days();
+                    self.overdue_cards += i64::from(days_overdue > 0);
+                    self.long_overdue_cards += i64::from(days_overdue > LONG_OVERDUE_DAYS);
+                    let day = today.format("%Y-%m-%d").to_string();
                     *self.upcoming_week.entry(day).or_insert(0) += 1;
                     self.upcoming_month += 1;
                 } else {
                     if due_date <= week_horizon {
-                        let day = due_date.format("%Y-%m-%d").to_string();
+                        let day = study_day(due_date, cutoff_hour)
+                            .format("%Y-%m-%d")
+                            .to_string();
                         *self.upcoming_week.entry(day).or_insert(0) += 1;
                     }
 
@@ -126,6 +426,275 @@ impl CardStats {
     }
 }
 
+/// Sums `files` by the first directory below whichever of `roots` contains them.
+/// A file directly inside a root, or passed as a root itself, is its own deck.
+pub fn group_by_deck(
+    files: &BTreeMap<PathBuf, GroupStats>,
+    roots: &[PathBuf],
+) -> BTreeMap<String, GroupStats> {
+    let mut decks: BTreeMap<String, GroupStats> = BTreeMap::new();
+    for (path, group) in files {
+        decks.entry(deck_name(path, roots)).or_default().add(group);
+    }
+    decks
+}
+
+fn deck_name(path: &Path, roots: &[PathBuf]) -> String {
+    let relative = roots
+        .iter()
+        .filter_map(|root| path.strip_prefix(root).ok())
+        .filter(|relative| !relative.as_os_str().is_empty())
+        .min_by_key(|relative| relative.components().count())
+        .unwrap_or(path);
+    relative
+        .components()
+        .find(|component| matches!(component, Component::Normal(_)))
+        .map_or_else(
+            || path.display().to_string(),
+            |first| first.as_os_str().to_string_lossy().into_owned(),
+        )
+}
+
+pub fn format_breakdown(label: &str, rows: impl Iterator<Item = (String, GroupStats)>) -> String {
+    let rows: Vec<(String, GroupStats)> = rows.collect();
+    let mut out = String::new();
+    if rows.is_empty() {
+        let _ = writeln!(out, "No cards found.");
+        return out;
+    }
+    let width = rows
+        .iter()
+        .map(|(name, _)| name.chars().count())
+        .max()
+        .unwrap_or(0)
+        .max(label.len());
+    let _ = writeln!(
+        out,
+        "{label:<width$}  {:>6}  {:>6}  {:>6}  {:>6}",
+        "CARDS", "DUE", "NEW", "MATURE"
+    );
+    for (name, group) in rows {
+        let _ = writeln!(
+            out,
+            "{name:<width$}  {:>6}  {:>6}  {:>6}  {:>6}",
+            group.cards, group.due, group.new, group.mature
+        );
+    }
+    out
+}
+
+/// What `--prune` does with cards no longer found under the scanned paths.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Prune {
+    /// Keep their schedule and review log, but leave them out until `repeater unarchive`.
+    Archive,
+    /// Delete them with their review log and tags (`--hard`).
+    Delete,
+}
+
+impl Prune {
+    pub fn from_flags(prune: bool, hard: bool) -> Option<Self> {
+        match (prune, hard) {
+            (false, _) => None,
+            (true, false) => Some(Prune::Archive),
+            (true, true) => Some(Prune::Delete),
+        }
+    }
+
+    /// Prunes the cards missing from `current_hashes` and says what was done.
+    pub async fn run(self, db: &DB, current_hashes: &HashSet<String>) -> Result<String> {
+        Ok(match self {
+            Prune::Archive => archived_message(db.archive_missing(current_hashes).await?),
+            Prune::Delete => pruned_message(db.prune_missing(current_hashes).await?),
+        })
+    }
+}
+
+fn pruned_message(pruned: usize) -> String {
+    let plural = if pruned == 1 { "" } else { "s" };
+    format!("Removed {pruned} orphaned card{plural} from the database.")
+}
+
+fn archived_message(archived: usize) -> String {
+    let plural = if archived == 1 { "" } else { "s" };
+    format!(
+        "Archived {archived} card{plural} no longer found; `repeater unarchive` restores them \
+         with their schedules if their files come back."
+    )
+}
+
+pub fn format_stats(stats: &CardStats) -> String {
+    let today = today(stats.day_cutoff_hour);
+    let lifecycle = |kind: CardLifeCycle| stats.lifecycle_count(kind);
+    let mut out = String::new();
+
+    let _ = writeln!(out, "Collection");
+    let _ = writeln!(out, "  Tracked cards: {}", stats.num_cards);
+    let _ = writeln!(
+        out,
+        "  New: {} • Young: {} • Mature: {}",
+        lifecycle(CardLifeCycle::New),
+        lifecycle(CardLifeCycle::Young),
+        lifecycle(CardLifeCycle::Mature)
+    );
+    let _ = writeln!(out, "  Leeches: {}", stats.leeches);
+    let _ = writeln!(out, "  Files in collection: {}", stats.file_paths.len());
+    let _ = writeln!(
+        out,
+        "  Total cards indexed in DB: {}",
+        stats.total_cards_in_db
+    );
+
+    let _ = writeln!(out, "\nDue");
+    let due_style = if stats.due_cards == 0 {
+        Theme::success()
+    } else {
+        Theme::emphasis()
+    };
+    let overdue_style = |count: i64| {
+        if count == 0 {
+            Theme::default_style()
+        } else {
+            Theme::overdue()
+        }
+    };
+    let _ = writeln!(
+        out,
+        "  Due now: {}",
+        Theme::paint(stats.due_cards, due_style)
+    );
+    let _ = writeln!(
+        out,
+        "  Overdue: {} • {} by more than {LONG_OVERDUE_DAYS} days",
+        Theme::paint(stats.overdue_cards, overdue_style(stats.overdue_cards)),
+        Theme::paint(
+            stats.long_overdue_cards,
+            overdue_style(stats.long_overdue_cards)
+        )
+    );
+    let _ = writeln!(out, "  Next 7 days: {}", stats.upcoming_week_total());
+    let _ = writeln!(out, "  Next 30 days: {}", stats.upcoming_month);
+
+    if let Some(retention) = stats.true_retention {
+        let period = describe_period(stats.retention_period, RETENTION_WINDOW_DAYS, today);
+        let _ = writeln!(out, "\nTrue retention ({period})");
+        match retention.rate() {
+            Some(rate) => {
+                let _ = writeln!(
+                    out,
+                    "  {:.1}% • {} passed • {} failed",
+                    rate * 100.0,
+                    retention.passed,
+                    retention.failed
+                );
+            }
+            None => {
+                let _ = writeln!(out, "  NA - No reviews of learned cards yet");
+            }
+        }
+    }
+
+    if let Some(review_time) = &stats.review_time {
+        let period = describe_period(stats.review_time_period, REVIEW_TIME_WINDOW_DAYS, today);
+        let _ = writeln!(out, "\nReview time ({period})");
+        match review_time.average_secs_per_card {
+            Some(average) => {
+                for (day, time) in &review_time.days {
+                    let label = NaiveDate::parse_from_str(day, "%Y-%m-%d")
+                        .map(|date| date.format("%a %d").to_string())
+                        .unwrap_or_else(|_| day.clone());
+                    let _ = writeln!(
+                        out,
+                        "  {label:<8} {} total • {:.1}s median • {} reviews",
+                        format_duration(time.total_secs),
+                        time.median_secs,
+                        time.reviews
+                    );
+                }
+                let _ = writeln!(out, "  Average: {average:.1}s per card");
+            }
+            None => {
+                let _ = writeln!(out, "  NA - No timed reviews yet");
+            }
+        }
+    }
+
+    let _ = writeln!(out, "\nNext 7 days");
+    if stats.upcoming_week.is_empty() {
+        let _ = writeln!(out, "  You're clear for the next 7 days.");
+    } else {
+        let buckets = stats.upcoming_week_buckets();
+        let max = buckets.iter().map(|bucket| bucket.count).max().unwrap_or(0);
+        for bucket in &buckets {
+            let _ = writeln!(
+                out,
+                "  {:<8} {} {}",
+                bucket.label(),
+                text_bar(bucket.count, max),
+                bucket.count
+            );
+        }
+    }
+
+    if !stats.forecast.is_empty() {
+        let _ = writeln!(out, "\nForecast (next {} days)", stats.forecast.len());
+        let max = stats.forecast.iter().copied().max().unwrap_or(0);
+        for (offset, count) in stats.forecast.iter().enumerate() {
+            let label = (today + chrono::Duration::days(offset as i64)).format("%a %d");
+            let _ = writeln!(out, "  {label:<8} {} {count}", text_bar(*count, max));
+        }
+    }
+
+    write_histogram(&mut out, "Difficulty", &stats.difficulty_histogram);
+    write_histogram(&mut out, "Retrievability", &stats.retrievability_histogram);
+    out
+}
+
+fn describe_period(period: Option<ReviewPeriod>, default_days: i64, today: NaiveDate) -> String {
+    match period {
+        Some(period) => period.describe(today),
+        None => format!("last {default_days} days"),
+    }
+}
+
+/// Whole seconds as `45s`, `4m 05s`, or `1h 02m`.
+pub fn format_duration(secs: f64) -> String {
+    let secs = secs.round() as u64;
+    if secs >= 3600 {
+        format!("{}h {:02}m", secs / 3600, secs % 3600 / 60)
+    } else if secs >= 60 {
+        format!("{}m {:02}s", secs / 60, secs % 60)
+    } else {
+        format!("{secs}s")
+    }
+}
+
+fn write_histogram<const N: usize>(out: &mut String, title: &str, histogram: &Histogram<N>) {
+    let average = histogram.mean().map_or_else(
+        || "NA - No cards reviewed".to_string(),
+        |v| format!("{}%", (v * 100.0).round()),
+    );
+    let _ = writeln!(out, "\n{title} (average {average})");
+    let max = histogram.bins.iter().copied().max().unwrap_or(0) as usize;
+    let step_size = 100 / N.max(1);
+    for (i, count) in histogram.bins.iter().enumerate() {
+        let label = format!("{}%-{}%", step_size * i, step_size * (i + 1));
+        let _ = writeln!(
+            out,
+            "  {label:<8} {} {count}",
+            text_bar(*count as usize, max)
+        );
+    }
+}
+
+fn text_bar(count: usize, max: usize) -> String {
+    if max == 0 {
+        return String::new();
+    }
+    let width = (count * BAR_WIDTH).div_ceil(max);
+    Theme::paint("█".repeat(width), Theme::bar())
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -142,6 +711,8 @@ mod tests {
                 answer: "A".into(),
             },
             card_hash: "hash".into(),
+            origin: "hash".into(),
+            tags: Vec::new(),
         }
     }
 
@@ -154,6 +725,7 @@ mod tests {
             difficulty: None,
             stability: None,
             last_reviewed_at: None,
+            leech: false,
         }
     }
 
@@ -211,6 +783,273 @@ mod tests {
         assert_eq!(stats.retrievability_histogram.bins[idx], 1);
     }
 
+    #[test]
+    fn format_stats_includes_histograms() {
+        let mut stats = CardStats {
+            num_cards: 2,
+            ..Default::default()
+        };
+        let card = sample_card("deck/file.md");
+        let mut row = default_row();
+        row.review_count = 3;
+        row.difficulty = Some(7.5);
+        row.stability = Some(10.0);
+        row.interval_raw = Some(3.0);
+        row.last_reviewed_at = Some(Utc::now() - Duration::days(2));
+        row.due_date = Some(Utc::now() + Duration::days(1));
+        stats.update(&card, &row);
+        stats.update(&card, &default_row());
+
+        let output = format_stats(&stats);
+        assert!(output.contains("Tracked cards: 2"));
+        assert!(output.contains("New: 1 • Young: 1 • Mature: 0"));
+        assert!(output.contains("Leeches: 0"));
+        assert!(output.contains("Difficulty (average 75%)"));
+        assert!(output.contains("Retrievability (average"));
+        assert!(output.contains("60%-80%  ██████████████████████████████ 1"));
+    }
+
+    #[test]
+    fn derives_counts_from_lifecycles() {
+        let stats = CardStats {
+            num_cards: 6,
+            card_lifecycles: HashMap::from([
+                (CardLifeCycle::New, 3),
+                (CardLifeCycle::Young, 2),
+                (CardLifeCycle::Mature, 1),
+            ]),
+            upcoming_week: BTreeMap::from([
+                ("2024-03-04".to_string(), 2),
+                ("2024-03-06".to_string(), 5),
+            ]),
+            ..CardStats::default()
+        };
+        assert_eq!(stats.new_cards(), 3);
+        assert_eq!(stats.reviewed_cards(), 3);
+        assert_eq!(stats.lifecycle_count(CardLifeCycle::Mature), 1);
+        assert_eq!(stats.upcoming_week_total(), 7);
+
+        let empty = CardStats::default();
+        assert_eq!((empty.new_cards(), empty.reviewed_cards()), (0, 0));
+    }
+
+    #[test]
+    fn upcoming_week_buckets_are_in_date_order() {
+        let stats = CardStats {
+            upcoming_week: BTreeMap::from([
+                ("2024-03-11".to_string(), 1),
+                ("2024-03-04".to_string(), 2),
+                ("2024-03-06".to_string(), 5),
+            ]),
+            ..CardStats::default()
+        };
+        let buckets = stats.upcoming_week_buckets();
+        let days: Vec<&str> = buckets.iter().map(|bucket| bucket.day.as_str()).collect();
+        assert_eq!(days, ["2024-03-04", "2024-03-06", "2024-03-11"]);
+        assert_eq!(buckets[1].count, 5);
+        assert_eq!(buckets[0].label(), "Mon 04");
+        assert_eq!(
+            serde_json::to_value(&buckets[0]).unwrap(),
+            serde_json::json!({ "day": "2024-03-04", "count": 2 })
+        );
+    }
+
+    #[test]
+    fn counts_overdue_cards_by_how_long_they_waited() {
+        let mut stats = CardStats::default();
+        let card = sample_card("deck/file.md");
+        for days_ago in [0, 1, 3, 4, 10] {
+            let mut row = default_row();
+            row.review_count = 2;
+            row.difficulty = Some(5.0);
+            row.stability = Some(5.0);
+            row.last_reviewed_at = Some(Utc::now() - Duration::days(days_ago + 5));
+            row.due_date = Some(Utc::now() - Duration::days(days_ago));
+            stats.update(&card, &row);
+        }
+        stats.update(&card, &default_row());
+
+        assert_eq!(stats.due_cards, 6);
+        assert_eq!(stats.overdue_cards, 4);
+        assert_eq!(stats.long_overdue_cards, 2);
+        assert!(format_stats(&stats).contains("Overdue: 4 • 2 by more than 3 days"));
+    }
+
+    #[test]
+    fn serializes_stats_as_json() {
+        let mut stats = CardStats::default();
+        let card = sample_card("deck/file.md");
+        let mut row = default_row();
+        row.review_count = 2;
+        row.difficulty = Some(5.0);
+        row.stability = Some(5.0);
+        row.last_reviewed_at = Some(Utc::now() - Duration::days(1));
+        row.leech = true;
+        stats.update(&card, &row);
+
+        let value = serde_json::to_value(&stats).unwrap();
+        assert_eq!(value["due_cards"], 1);
+        assert_eq!(value["leeches"], 1);
+        assert_eq!(value["card_lifecycles"]["Young"], 1);
+        assert_eq!(value["difficulty_histogram"]["bins"][2], 1);
+        assert_eq!(value["difficulty_histogram"]["mean"], 0.5);
+        assert!(value["retrievability_histogram"]["mean"].is_number());
+        assert!(value["upcoming_week"].is_object());
+
+        stats.retention_period = Some(ReviewPeriod {
+            first: NaiveDate::from_ymd_opt(2024, 1, 1).unwrap(),
+            last: NaiveDate::from_ymd_opt(2024, 1, 31).unwrap(),
+        });
+        let value = serde_json::to_value(&stats).unwrap();
+        assert_eq!(
+            value["retention_period"],
+            serde_json::json!({ "first": "2024-01-01", "last": "2024-01-31" })
+        );
+    }
+
+    #[test]
+    fn forecast_buckets_cards_by_due_day() {
+        let mut stats = CardStats {
+            forecast: vec![0; 10],
+            ..Default::default()
+        };
+        let card = sample_card("deck/file.md");
+        let now = Utc::now();
+        for days in [0, 3, 3, 9, 40] {
+            let mut row = default_row();
+            row.review_count = 1;
+            row.due_date = Some(now + Duration::days(days) - Duration::minutes(30));
+            stats.update(&card, &row);
+        }
+        stats.update(&card, &default_row());
+
+        assert_eq!(stats.forecast.iter().sum::<usize>(), 5);
+        assert_eq!(stats.forecast[0], 2);
+        assert_eq!(
+            stats.forecast.iter().filter(|count| **count == 2).count(),
+            2
+        );
+        assert!(format_stats(&stats).contains("Forecast (next 10 days)"));
+    }
+
+    #[test]
+    fn format_stats_prints_true_retention() {
+        let mut stats = CardStats::default();
+        assert!(!format_stats(&stats).contains("True retention"));
+
+        stats.true_retention = Some(RetentionCounts {
+            passed: 9,
+            failed: 1,
+        });
+        assert!(format_stats(&stats).contains("90.0% • 9 passed • 1 failed"));
+    }
+
+    #[test]
+    fn review_range_fills_in_open_ends() {
+        let today = NaiveDate::from_ymd_opt(2024, 3, 10).unwrap();
+        let jan = |day| NaiveDate::from_ymd_opt(2024, 1, day).unwrap();
+        let period = |since, until| ReviewRange { since, until }.period(7, today);
+
+        assert_eq!(period(None, None), ReviewPeriod::ending(today, 7));
+        assert_eq!(
+            period(Some(jan(1)), Some(jan(31))),
+            ReviewPeriod {
+                first: jan(1),
+                last: jan(31)
+            }
+        );
+        assert_eq!(period(Some(jan(1)), None).last, today);
+        assert_eq!(
+            period(None, Some(jan(31))),
+            ReviewPeriod::ending(jan(31), 7)
+        );
+    }
+
+    #[test]
+    fn summarizes_answer_times_per_day() {
+        let monday = NaiveDate::from_ymd_opt(2024, 3, 4).unwrap();
+        let tuesday = monday.succ_opt().unwrap();
+        let per_day = BTreeMap::from([
+            (monday, vec![9_000, 3_000, 6_000]),
+            (tuesday, vec![2_000, 4_000]),
+        ]);
+        let review_time = ReviewTime::from_durations(&per_day);
+
+        assert_eq!(
+            review_time.days["2024-03-04"],
+            DayReviewTime {
+                reviews: 3,
+                total_secs: 18.0,
+                median_secs: 6.0,
+            }
+        );
+        assert_eq!(review_time.days["2024-03-05"].median_secs, 3.0);
+        assert_eq!(review_time.average_secs_per_card, Some(4.8));
+
+        let stats = CardStats {
+            review_time: Some(review_time),
+            ..CardStats::default()
+        };
+        let text = format_stats(&stats);
+        assert!(text.contains("Mon 04   18s total • 6.0s median • 3 reviews"));
+        assert!(text.contains("Average: 4.8s per card"));
+        assert_eq!(format_duration(245.0), "4m 05s");
+        assert_eq!(format_duration(3720.0), "1h 02m");
+    }
+
+    #[test]
+    fn groups_files_into_decks() {
+        let mut stats = CardStats::default();
+        for path in [
+            "notes/science/physics.md",
+            "notes/science/chemistry/acids.md",
+            "notes/history.md",
+            "extra.md",
+        ] {
+            stats.update(&sample_card(path), &default_row());
+        }
+        let mut reviewed = default_row();
+        reviewed.review_count = 4;
+        reviewed.interval_raw = Some(40.0);
+        reviewed.due_date = Some(Utc::now() + Duration::days(10));
+        stats.update(&sample_card("notes/science/physics.md"), &reviewed);
+
+        let physics = stats.files[&PathBuf::from("notes/science/physics.md")];
+        assert_eq!(
+            physics,
+            GroupStats {
+                cards: 2,
+                due: 1,
+                new: 1,
+                mature: 1,
+            }
+        );
+
+        let roots = [PathBuf::from("notes"), PathBuf::from("extra.md")];
+        let decks = group_by_deck(&stats.files, &roots);
+        assert_eq!(
+            decks.keys().collect::<Vec<_>>(),
+            vec!["extra.md", "history.md", "science"]
+        );
+        assert_eq!(decks["science"].cards, 3);
+
+        let table = format_breakdown("DECK", decks.into_iter());
+        assert!(table.starts_with(&format!("DECK{}CARDS", " ".repeat(9))));
+        assert!(table.contains(&format!(
+            "science{}3{gap}2{gap}2{gap}1",
+            " ".repeat(10),
+            gap = " ".repeat(7)
+        )));
+    }
+
+    #[test]
+    fn text_bar_scales_to_max() {
+        assert_eq!(text_bar(0, 0), "");
+        assert_eq!(text_bar(5, 10).chars().count(), BAR_WIDTH / 2);
+        assert_eq!(text_bar(10, 10).chars().count(), BAR_WIDTH);
+        assert_eq!(text_bar(1, 100).chars().count(), 1);
+    }
+
     #[test]
     fn histogram_mean_returns_none_when_empty() {
         let histogram: Histogram<5> = Histogram::default();
@@ -0,0 +1,390 @@
+use std::fs;
+use std::path::PathBuf;
+
+use anyhow::{Context, Result, bail};
+
+use crate::crud::DB;
+use crate::utils::{
+    ScanOptions, UTF8_BOM, card_files, card_text, register_all_cards, write_file_atomically,
+};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TagChange {
+    Add,
+    Remove,
+}
+
+/// Adds `tag` to, or removes it from, the frontmatter of every card file under `paths`,
+/// then registers the cards again so `drill --tag` sees the change. Files that aren't
+/// UTF-8 text are left alone, as the card scan skips them too.
+pub async fn run(
+    db: &DB,
+    scan: &ScanOptions,
+    change: TagChange,
+    tag: &str,
+    paths: Vec<PathBuf>,
+) -> Result<()> {
+    validate_tag(tag)?;
+    let mut changed = 0;
+    for path in card_files(scan, &paths)? {
+        let bytes =
+            fs::read(&path).with_context(|| format!("Failed to read {}", path.display()))?;
+        let Some(contents) = card_text(bytes) else {
+            eprintln!(
+                "Skipping {}: not UTF-8 text (saved as UTF-16, or a binary file?)",
+                path.display()
+            );
+            continue;
+        };
+        let updated = match change {
+            TagChange::Add => keeping_bom(&contents, |text| add_tag(text, tag)),
+            TagChange::Remove => keeping_bom(&contents, |text| remove_tag(text, tag)),
+        };
+        let Some(updated) = updated else {
+            continue;
+        };
+        write_file_atomically(&path, updated.as_bytes())?;
+        crate::verbose!("Updated {}", path.display());
+        changed += 1;
+    }
+    register_all_cards(db, scan, paths).await?;
+
+    let plural = if changed == 1 { "" } else { "s" };
+    match change {
+        TagChange::Add => println!("Tagged {changed} file{plural} with `{tag}`."),
+        TagChange::Remove => println!("Removed `{tag}` from {changed} file{plural}."),
+    }
+    Ok(())
+}
+
+/// Applies `edit` to `contents` past any UTF-8 byte order mark, which the result keeps,
+/// so frontmatter after the mark is found like the card parser finds it.
+fn keeping_bom(contents: &str, edit: impl FnOnce(&str) -> Option<String>) -> Option<String> {
+    match contents.strip_prefix(UTF8_BOM) {
+        Some(text) => edit(text).map(|updated| format!("{UTF8_BOM}{updated}")),
+        None => edit(contents),
+    }
+}
+
+/// Rejects tags that would not read back as the same single tag from frontmatter.
+fn validate_tag(tag: &str) -> Result<()> {
+    if tag.trim().is_empty() {
+        bail!("A tag can't be empty");
+    }
+    if tag.trim() != tag
+        || tag.starts_with('-')
+        || tag.contains([',', '[', ']', '"', '\'', '\n', '\r'])
+    {
+        bail!(
+            "`{tag}` can't be used as a tag: it may not contain commas, brackets, quotes, or \
+             line breaks, start with `-`, or start or end with spaces"
+        );
+    }
+    Ok(())
+}
+
+/// `contents` with `tag` in its frontmatter, creating the frontmatter or its `tags:`
+/// entry if needed; `None` when the file already carries the tag.
+fn add_tag(contents: &str, tag: &str) -> Option<String> {
+    let lines: Vec<&str> = contents.split_inclusive('\n').collect();
+    let Some(close) = frontmatter_end(&lines) else {
+        return Some(format!("---\ntags: [{tag}]\n---\n\n{contents}"));
+    };
+    let mut yaml: Vec<String> = lines[1..close]
+        .iter()
+        .map(|line| line.to_string())
+        .collect();
+    let newline = line_ending(lines[0]);
+    match yaml.iter().position(|line| line.starts_with("tags:")) {
+        None => yaml.push(format!("tags: [{tag}]{newline}")),
+        Some(key) => {
+            let value = yaml[key]["tags:".len()..].trim();
+            if value.is_empty() {
+                let items = block_items(&yaml, key);
+                if items.iter().any(|&idx| item_value(&yaml[idx]) == tag) {
+                    return None;
+                }
+                let (at, indent) = match items.last() {
+                    Some(&last) => {
+                        let line = &yaml[last];
+                        (
+                            last + 1,
+                            line[..line.len() - line.trim_start().len()].to_string(),
+                        )
+                    }
+                    None => (key + 1, String::from("  ")),
+                };
+                yaml.insert(at, format!("{indent}- {tag}{newline}"));
+            } else {
+                let mut tags = InlineTags::parse(value);
+                if tags.contains(tag) {
+                    return None;
+                }
+                tags.items.push(tag.to_string());
+                yaml[key] = tags.line(line_ending(&yaml[key]));
+            }
+        }
+    }
+    Some(join(&lines, close, &yaml))
+}
+
+/// `contents` without `tag` in its frontmatter, dropping a `tags:` entry, and then the
+/// frontmatter itself, left empty; `None` when the file doesn't carry the tag.
+fn remove_tag(contents: &str, tag: &str) -> Option<String> {
+    let lines: Vec<&str> = contents.split_inclusive('\n').collect();
+    let close = frontmatter_end(&lines)?;
+    let mut yaml: Vec<String> = lines[1..close]
+        .iter()
+        .map(|line| line.to_string())
+        .collect();
+    let key = yaml.iter().position(|line| line.starts_with("tags:"))?;
+    let value = yaml[key]["tags:".len()..].trim();
+    if value.is_empty() {
+        let items = block_items(&yaml, key);
+        let matching: Vec<usize> = items
+            .iter()
+            .copied()
+            .filter(|&idx| item_value(&yaml[idx]) == tag)
+            .collect();
+        if matching.is_empty() {
+            return None;
+        }
+        for idx in matching.iter().rev() {
+            yaml.remove(*idx);
+        }
+        if matching.len() == items.len() {
+            yaml.remove(key);
+        }
+    } else {
+        let mut tags = InlineTags::parse(value);
+        if !tags.contains(tag) {
+            return None;
+        }
+        tags.items.retain(|item| unquote(item) != tag);
+        if tags.items.is_empty() {
+            yaml.remove(key);
+        } else {
+            yaml[key] = tags.line(line_ending(&yaml[key]));
+        }
+    }
+
+    if yaml.iter().all(|line| line.trim().is_empty()) {
+        let rest = &lines[close + 1..];
+        let rest = match rest.first() {
+            Some(first) if first.trim().is_empty() => &rest[1..],
+            _ => rest,
+        };
+        return Some(rest.concat());
+    }
+    Some(join(&lines, close, &yaml))
+}
+
+/// Index of the line closing the frontmatter that opens `lines`, read the way the card
+/// parser reads it: a card before the closing `---` means there is no frontmatter.
+fn frontmatter_end(lines: &[&str]) -> Option<usize> {
+    if lines.first()?.trim_end() != "---" {
+        return None;
+    }
+    for (idx, line) in lines.iter().enumerate().skip(1) {
+        if ["Q:", "R:", "T:", "C:"]
+            .iter()
+            .any(|prefix| line.starts_with(prefix))
+        {
+            return None;
+        }
+        if line.trim_end() == "---" {
+            return Some(idx);
+        }
+    }
+    None
+}
+
+/// Indices of the `- tag` lines of the block list under the `tags:` line at `key`.
+fn block_items(yaml: &[String], key: usize) -> Vec<usize> {
+    let mut items = Vec::new();
+    for (idx, line) in yaml.iter().enumerate().skip(key + 1) {
+        if line.trim_start().starts_with('-') {
+            items.push(idx);
+        } else if !line.trim().is_empty() {
+            break;
+        }
+    }
+    items
+}
+
+fn item_value(line: &str) -> &str {
+    let item = line.trim_start().strip_prefix('-').unwrap_or(line);
+    unquote(item.trim())
+}
+
+fn unquote(item: &str) -> &str {
+    item.trim().trim_matches(|c| c == '"' || c == '\'')
+}
+
+fn line_ending(line: &str) -> &'static str {
+    if line.ends_with("\r\n") { "\r\n" } else { "\n" }
+}
+
+fn join(lines: &[&str], close: usize, yaml: &[String]) -> String {
+    let mut out = lines[0].to_string();
+    for line in yaml {
+        out.push_str(line);
+    }
+    for line in &lines[close..] {
+        out.push_str(line);
+    }
+    out
+}
+
+/// A `tags: [a, b]` or `tags: a, b` entry, keeping each tag as written.
+struct InlineTags {
+    bracketed: bool,
+    items: Vec<String>,
+}
+
+impl InlineTags {
+    fn parse(value: &str) -> Self {
+        let inner = value
+            .strip_prefix('[')
+            .and_then(|value| value.strip_suffix(']'));
+        let items = inner
+            .unwrap_or(value)
+            .split(',')
+            .map(str::trim)
+            .filter(|item| !item.is_empty())
+            .map(str::to_string)
+            .collect();
+        Self {
+            bracketed: inner.is_some(),
+            items,
+        }
+    }
+
+    fn contains(&self, tag: &str) -> bool {
+        self.items.iter().any(|item| unquote(item) == tag)
+    }
+
+    fn line(&self, newline: &str) -> String {
+        let items = self.items.join(", ");
+        if self.bracketed {
+            format!("tags: [{items}]{newline}")
+        } else {
+            format!("tags: {items}{newline}")
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn adding_creates_or_extends_the_tags_entry() {
+        assert_eq!(
+            add_tag("Q: a?\nA: b\n", "spanish").unwrap(),
+            "---\ntags: [spanish]\n---\n\nQ: a?\nA: b\n"
+        );
+        assert_eq!(
+            add_tag("---\ntitle: Verbs\n---\nQ: a?\nA: b\n", "spanish").unwrap(),
+            "---\ntitle: Verbs\ntags: [spanish]\n---\nQ: a?\nA: b\n"
+        );
+        assert_eq!(
+            add_tag("---\ntags: [verbs, \"irregular\"]\n---\nQ: a?\n", "spanish").unwrap(),
+            "---\ntags: [verbs, \"irregular\", spanish]\n---\nQ: a?\n"
+        );
+        assert_eq!(
+            add_tag("---\ntags: verbs\n---\n", "spanish").unwrap(),
+            "---\ntags: verbs, spanish\n---\n"
+        );
+        assert_eq!(
+            add_tag("---\ntags:\n    - verbs\nauthor: me\n---\n", "spanish").unwrap(),
+            "---\ntags:\n    - verbs\n    - spanish\nauthor: me\n---\n"
+        );
+        assert_eq!(add_tag("---\ntags: [spanish]\n---\n", "spanish"), None);
+        assert_eq!(add_tag("---\ntags:\n- 'spanish'\n---\n", "spanish"), None);
+    }
+
+    #[test]
+    fn a_leading_separator_is_not_frontmatter() {
+        assert_eq!(
+            add_tag("---\nQ: a?\nA: b\n---\n", "spanish").unwrap(),
+            "---\ntags: [spanish]\n---\n\n---\nQ: a?\nA: b\n---\n"
+        );
+    }
+
+    #[test]
+    fn removing_drops_emptied_entries_and_frontmatter() {
+        assert_eq!(
+            remove_tag("---\ntags: [verbs, spanish]\n---\nQ: a?\n", "spanish").unwrap(),
+            "---\ntags: [verbs]\n---\nQ: a?\n"
+        );
+        assert_eq!(
+            remove_tag(
+                "---\ntitle: Verbs\ntags:\n  - spanish\n---\nQ: a?\n",
+                "spanish"
+            )
+            .unwrap(),
+            "---\ntitle: Verbs\n---\nQ: a?\n"
+        );
+        assert_eq!(
+            remove_tag("---\ntags: [spanish]\n---\n\nQ: a?\n", "spanish").unwrap(),
+            "Q: a?\n"
+        );
+        assert_eq!(remove_tag("---\ntags: [verbs]\n---\n", "spanish"), None);
+        assert_eq!(remove_tag("Q: a?\nA: b\n", "spanish"), None);
+    }
+
+    #[test]
+    fn frontmatter_after_a_byte_order_mark_is_edited_in_place() {
+        let add = |contents: &str| keeping_bom(contents, |text| add_tag(text, "spanish"));
+        assert_eq!(
+            add("\u{feff}---\ntitle: Verbs\n---\nQ: a?\n").unwrap(),
+            "\u{feff}---\ntitle: Verbs\ntags: [spanish]\n---\nQ: a?\n"
+        );
+        assert_eq!(
+            add("\u{feff}Q: a?\n").unwrap(),
+            "\u{feff}---\ntags: [spanish]\n---\n\nQ: a?\n"
+        );
+        assert_eq!(
+            keeping_bom("\u{feff}---\ntags: [spanish]\n---\nQ: a?\n", |text| {
+                remove_tag(text, "spanish")
+            })
+            .unwrap(),
+            "\u{feff}Q: a?\n"
+        );
+    }
+
+    #[tokio::test]
+    async fn files_that_are_not_utf8_are_skipped() {
+        let dir = tempfile::tempdir().unwrap();
+        let binary = dir.path().join("binary.md");
+        let bom = dir.path().join("bom.md");
+        std::fs::write(&binary, [0x00, 0x9F, 0x92, 0x96]).unwrap();
+        std::fs::write(&bom, "\u{feff}---\ntags: [verbs]\n---\nQ: a?\nA: b\n").unwrap();
+
+        let db = DB::new_in_memory().await.unwrap();
+        run(
+            &db,
+            &ScanOptions::default(),
+            TagChange::Add,
+            "spanish",
+            vec![dir.path().to_path_buf()],
+        )
+        .await
+        .unwrap();
+        assert_eq!(std::fs::read(&binary).unwrap(), [0x00, 0x9F, 0x92, 0x96]);
+        assert_eq!(
+            std::fs::read_to_string(&bom).unwrap(),
+            "\u{feff}---\ntags: [verbs, spanish]\n---\nQ: a?\nA: b\n"
+        );
+    }
+
+    #[test]
+    fn tags_must_read_back_as_one_tag() {
+        assert!(validate_tag("spanish-verbs").is_ok());
+        assert!(validate_tag("").is_err());
+        assert!(validate_tag("a, b").is_err());
+        assert!(validate_tag(" padded").is_err());
+        assert!(validate_tag("-x").is_err());
+    }
+}
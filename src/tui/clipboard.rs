@@ -0,0 +1,15 @@
+use anyhow::{Context, Result};
+
+pub fn read_text() -> Result<String> {
+    let mut clipboard = arboard::Clipboard::new().context("clipboard unavailable")?;
+    clipboard
+        .get_text()
+        .context("clipboard does not contain text")
+}
+
+pub fn write_text(text: &str) -> Result<()> {
+    let mut clipboard = arboard::Clipboard::new().context("clipboard unavailable")?;
+    clipboard
+        .set_text(text)
+        .context("unable to write to clipboard")
+}
@@ -1,13 +1,31 @@
-use std::cmp::min;
+use std::cmp::{Ordering, min};
 
 use crate::card::CardType;
 
+const MAX_UNDO_DEPTH: usize = 200;
+
+#[derive(Clone)]
+struct Snapshot {
+    lines: Vec<String>,
+    cursor_row: usize,
+    cursor_col: usize,
+}
+
 pub struct Editor {
     lines: Vec<String>,
     cursor_row: usize,
     cursor_col: usize,
     scroll_top: usize,
     pub card_type: CardType,
+    undo_stack: Vec<Snapshot>,
+    redo_stack: Vec<Snapshot>,
+    /// Cursor position right after the last typed character, so a run of
+    /// typing undoes as one step.
+    typing_at: Option<(usize, usize)>,
+    /// Where a Shift+movement selection started; the cursor is the other end.
+    anchor: Option<(usize, usize)>,
+    /// Newlines are refused and pasted line breaks become spaces.
+    single_line: bool,
 }
 
 impl Editor {
@@ -20,11 +38,54 @@ impl Editor {
             cursor_col: 3,
             scroll_top: 0,
             card_type,
+            undo_stack: Vec::new(),
+            redo_stack: Vec::new(),
+            typing_at: None,
+            anchor: None,
+            single_line: false,
+        }
+    }
+
+    /// An empty one-line input, like the answer field of a type-in card.
+    pub fn single_line() -> Self {
+        Self {
+            lines: vec![String::new()],
+            cursor_row: 0,
+            cursor_col: 0,
+            scroll_top: 0,
+            card_type: CardType::Basic,
+            undo_stack: Vec::new(),
+            redo_stack: Vec::new(),
+            typing_at: None,
+            anchor: None,
+            single_line: true,
         }
     }
+
+    pub fn from_content(contents: &str) -> Self {
+        let mut lines: Vec<String> = contents.lines().map(str::to_string).collect();
+        if lines.is_empty() {
+            lines.push(String::new());
+        }
+        Self {
+            lines,
+            cursor_row: 0,
+            cursor_col: 0,
+            scroll_top: 0,
+            card_type: CardType::Basic,
+            undo_stack: Vec::new(),
+            redo_stack: Vec::new(),
+            typing_at: None,
+            anchor: None,
+            single_line: false,
+        }
+    }
+
     fn init_lines(card_type: &CardType) -> Vec<String> {
         match card_type {
             CardType::Basic => vec!["Q: ".to_string(), "A: ".to_string()],
+            CardType::BasicReversed => vec!["R: ".to_string(), "A: ".to_string()],
+            CardType::TypeIn => vec!["T: ".to_string(), "A: ".to_string()],
             CardType::Cloze => vec!["C: ".to_string()],
         }
     }
@@ -34,12 +95,90 @@ impl Editor {
     }
 
     pub fn clear(&mut self) {
+        self.checkpoint();
+        self.anchor = None;
         self.lines = Self::init_lines(&self.card_type);
         self.cursor_row = 0;
         self.cursor_col = 3;
         self.scroll_top = 0;
     }
 
+    pub fn lines(&self) -> &[String] {
+        &self.lines
+    }
+
+    /// Call before a cursor movement: starts or extends the selection when
+    /// `selecting`, otherwise drops it.
+    pub fn set_selecting(&mut self, selecting: bool) {
+        if !selecting {
+            self.anchor = None;
+        } else if self.anchor.is_none() {
+            self.anchor = Some((self.cursor_row, self.cursor_col));
+        }
+    }
+
+    /// Ordered `(start, end)` positions of the selection, if non-empty.
+    pub fn selection(&self) -> Option<((usize, usize), (usize, usize))> {
+        let anchor = self.anchor?;
+        let cursor = (self.cursor_row, self.cursor_col);
+        match anchor.cmp(&cursor) {
+            Ordering::Less => Some((anchor, cursor)),
+            Ordering::Greater => Some((cursor, anchor)),
+            Ordering::Equal => None,
+        }
+    }
+
+    /// Selected character columns within `row`, for rendering.
+    pub fn selection_in_line(&self, row: usize) -> Option<(usize, usize)> {
+        let ((start_row, start_col), (end_row, end_col)) = self.selection()?;
+        if row < start_row || row > end_row {
+            return None;
+        }
+        let start = if row == start_row { start_col } else { 0 };
+        let end = if row == end_row {
+            end_col
+        } else {
+            self.line_len(row)
+        };
+        Some((start, end))
+    }
+
+    pub fn selected_text(&self) -> Option<String> {
+        let ((start_row, _), (end_row, _)) = self.selection()?;
+        let mut out = String::new();
+        for row in start_row..=end_row {
+            let (start, end) = self.selection_in_line(row)?;
+            let line = &self.lines[row];
+            if row > start_row {
+                out.push('\n');
+            }
+            out.push_str(
+                &line[Self::char_to_byte_index(line, start)..Self::char_to_byte_index(line, end)],
+            );
+        }
+        Some(out)
+    }
+
+    /// Removes the selected text as one undo step. Returns whether anything was removed.
+    pub fn delete_selection(&mut self) -> bool {
+        let Some(((start_row, start_col), (end_row, end_col))) = self.selection() else {
+            self.anchor = None;
+            return false;
+        };
+        self.checkpoint();
+        let end_line = &self.lines[end_row];
+        let tail = end_line[Self::char_to_byte_index(end_line, end_col)..].to_string();
+        let start_line = &mut self.lines[start_row];
+        let start_idx = Self::char_to_byte_index(start_line, start_col);
+        start_line.truncate(start_idx);
+        start_line.push_str(&tail);
+        self.lines.drain(start_row + 1..=end_row);
+        self.cursor_row = start_row;
+        self.cursor_col = start_col;
+        self.anchor = None;
+        true
+    }
+
     pub fn cursor(&self) -> (usize, usize) {
         (self.cursor_row, self.cursor_col)
     }
@@ -64,15 +203,181 @@ impl Editor {
         }
     }
 
+    /// Scrolls the view by `delta` lines, dragging the cursor along when it would leave the view.
+    pub fn scroll(&mut self, delta: isize, view_height: usize) {
+        let view_height = view_height.max(1);
+        let max_top = self.lines.len().saturating_sub(view_height);
+        self.scroll_top = self.scroll_top.saturating_add_signed(delta).min(max_top);
+        let bottom = (self.scroll_top + view_height - 1).min(self.lines.len().saturating_sub(1));
+        let row = self.cursor_row.clamp(self.scroll_top, bottom);
+        if row != self.cursor_row {
+            self.cursor_row = row;
+            self.cursor_col = min(self.cursor_col, self.line_len(row));
+        }
+    }
+
+    /// Puts the cursor at `(row, col)`, clamped to the buffer, e.g. where the user clicked.
+    pub fn move_to(&mut self, row: usize, col: usize) {
+        self.cursor_row = row.min(self.lines.len().saturating_sub(1));
+        self.cursor_col = col.min(self.line_len(self.cursor_row));
+        self.typing_at = None;
+    }
+
+    /// `(row, column)` of every occurrence of `query`, in buffer order. Matches don't span lines.
+    pub fn find_all(&self, query: &str, case_sensitive: bool) -> Vec<(usize, usize)> {
+        let query: Vec<char> = query.chars().collect();
+        if query.is_empty() {
+            return Vec::new();
+        }
+        let same =
+            |a: char, b: char| a == b || (!case_sensitive && a.to_lowercase().eq(b.to_lowercase()));
+        let mut matches = Vec::new();
+        for (row, line) in self.lines.iter().enumerate() {
+            let chars: Vec<char> = line.chars().collect();
+            let mut col = 0;
+            while col + query.len() <= chars.len() {
+                if chars[col..col + query.len()]
+                    .iter()
+                    .zip(&query)
+                    .all(|(a, b)| same(*a, *b))
+                {
+                    matches.push((row, col));
+                    col += query.len();
+                } else {
+                    col += 1;
+                }
+            }
+        }
+        matches
+    }
+
+    /// Moves the cursor to the next (or previous) match of `query`, wrapping around the buffer.
+    /// Returns false when there is no match.
+    pub fn find_next(&mut self, query: &str, case_sensitive: bool, forward: bool) -> bool {
+        let matches = self.find_all(query, case_sensitive);
+        let cursor = (self.cursor_row, self.cursor_col);
+        let target = if forward {
+            matches
+                .iter()
+                .find(|found| **found > cursor)
+                .or(matches.first())
+        } else {
+            matches
+                .iter()
+                .rev()
+                .find(|found| **found < cursor)
+                .or(matches.last())
+        };
+        let Some(&(row, col)) = target else {
+            return false;
+        };
+        self.anchor = None;
+        self.move_to(row, col);
+        true
+    }
+
+    pub fn undo(&mut self) -> bool {
+        let Some(snapshot) = self.undo_stack.pop() else {
+            return false;
+        };
+        let current = self.snapshot();
+        self.redo_stack.push(current);
+        self.restore(snapshot);
+        true
+    }
+
+    pub fn redo(&mut self) -> bool {
+        let Some(snapshot) = self.redo_stack.pop() else {
+            return false;
+        };
+        let current = self.snapshot();
+        self.undo_stack.push(current);
+        self.restore(snapshot);
+        true
+    }
+
+    fn snapshot(&self) -> Snapshot {
+        Snapshot {
+            lines: self.lines.clone(),
+            cursor_row: self.cursor_row,
+            cursor_col: self.cursor_col,
+        }
+    }
+
+    fn restore(&mut self, snapshot: Snapshot) {
+        self.lines = snapshot.lines;
+        self.cursor_row = snapshot.cursor_row;
+        self.cursor_col = snapshot.cursor_col;
+        self.typing_at = None;
+        self.anchor = None;
+    }
+
+    /// Records the buffer before an edit.
+    fn checkpoint(&mut self) {
+        self.typing_at = None;
+        let snapshot = self.snapshot();
+        self.undo_stack.push(snapshot);
+        if self.undo_stack.len() > MAX_UNDO_DEPTH {
+            self.undo_stack.remove(0);
+        }
+        self.redo_stack.clear();
+    }
+
     pub fn insert_char(&mut self, ch: char) {
+        if !self.delete_selection() && self.typing_at != Some((self.cursor_row, self.cursor_col)) {
+            self.checkpoint();
+        }
         let column = self.cursor_col;
         let line = self.current_line_mut();
         let idx = Self::char_to_byte_index(line, column);
         line.insert(idx, ch);
         self.cursor_col += 1;
+        self.typing_at = Some((self.cursor_row, self.cursor_col));
+    }
+
+    /// Inserts pasted text in one undo step. Line endings are normalized and
+    /// control characters other than tabs are dropped.
+    pub fn insert_text(&mut self, text: &str) {
+        let mut sanitized: String = text
+            .replace("\r\n", "\n")
+            .replace('\r', "\n")
+            .chars()
+            .filter(|ch| *ch == '\n' || *ch == '\t' || !ch.is_control())
+            .collect();
+        if self.single_line {
+            sanitized = sanitized.replace('\n', " ");
+        }
+        if sanitized.is_empty() {
+            return;
+        }
+        if !self.delete_selection() {
+            self.checkpoint();
+        }
+
+        let column = self.cursor_col;
+        let line = self.current_line_mut();
+        let idx = Self::char_to_byte_index(line, column);
+        let tail = line.split_off(idx);
+
+        let mut segments = sanitized.split('\n');
+        if let Some(first) = segments.next() {
+            self.current_line_mut().push_str(first);
+        }
+        for segment in segments {
+            self.cursor_row += 1;
+            self.lines.insert(self.cursor_row, segment.to_string());
+        }
+        self.cursor_col = self.line_len(self.cursor_row);
+        self.current_line_mut().push_str(&tail);
     }
 
     pub fn insert_newline(&mut self) {
+        if self.single_line {
+            return;
+        }
+        if !self.delete_selection() {
+            self.checkpoint();
+        }
         let column = self.cursor_col;
         let line = self.current_line_mut();
         let idx = Self::char_to_byte_index(line, column);
@@ -87,6 +392,13 @@ impl Editor {
     }
 
     pub fn backspace(&mut self) {
+        if self.delete_selection() {
+            return;
+        }
+        if self.cursor_col == 0 && self.cursor_row == 0 {
+            return;
+        }
+        self.checkpoint();
         if self.cursor_col > 0 {
             let column = self.cursor_col;
             let line = self.current_line_mut();
@@ -97,10 +409,6 @@ impl Editor {
             return;
         }
 
-        if self.cursor_row == 0 {
-            return;
-        }
-
         let current_line = self.lines.remove(self.cursor_row);
         self.cursor_row -= 1;
         let new_col = self.line_len(self.cursor_row);
@@ -110,7 +418,14 @@ impl Editor {
     }
 
     pub fn delete(&mut self) {
+        if self.delete_selection() {
+            return;
+        }
         let line_len = self.line_len(self.cursor_row);
+        if self.cursor_col >= line_len && self.cursor_row + 1 >= self.lines.len() {
+            return;
+        }
+        self.checkpoint();
         if self.cursor_col < line_len {
             let column = self.cursor_col;
             let line = self.current_line_mut();
@@ -120,15 +435,34 @@ impl Editor {
             return;
         }
 
-        if self.cursor_row + 1 >= self.lines.len() {
-            return;
-        }
-
         let next_line = self.lines.remove(self.cursor_row + 1);
         let line = self.current_line_mut();
         line.push_str(&next_line);
     }
 
+    /// Removes the cursor's line, keeping the cursor's column on the line that takes its place.
+    pub fn delete_line(&mut self) {
+        self.checkpoint();
+        self.anchor = None;
+        if self.lines.len() == 1 {
+            self.lines[0].clear();
+            self.cursor_col = 0;
+            return;
+        }
+        self.lines.remove(self.cursor_row);
+        self.cursor_row = min(self.cursor_row, self.lines.len() - 1);
+        self.cursor_col = min(self.cursor_col, self.line_len(self.cursor_row));
+    }
+
+    /// Copies the cursor's line below itself and moves the cursor onto the copy.
+    pub fn duplicate_line(&mut self) {
+        self.checkpoint();
+        self.anchor = None;
+        let line = self.lines[self.cursor_row].clone();
+        self.lines.insert(self.cursor_row + 1, line);
+        self.cursor_row += 1;
+    }
+
     pub fn move_left(&mut self) {
         if self.cursor_col > 0 {
             self.cursor_col -= 1;
@@ -172,6 +506,68 @@ impl Editor {
         self.cursor_col = self.line_len(self.cursor_row);
     }
 
+    /// Moves to the start of the previous word. At the start of a line, moves to the end of
+    /// the line above.
+    pub fn move_word_left(&mut self) {
+        if self.cursor_col == 0 {
+            self.move_left();
+            return;
+        }
+        let chars: Vec<char> = self.lines[self.cursor_row].chars().collect();
+        let mut col = min(self.cursor_col, chars.len());
+        while col > 0 && chars[col - 1].is_whitespace() {
+            col -= 1;
+        }
+        if col > 0 {
+            let class = char_class(chars[col - 1]);
+            while col > 0 && char_class(chars[col - 1]) == class {
+                col -= 1;
+            }
+        }
+        self.cursor_col = col;
+    }
+
+    /// Moves past the end of the next word and the whitespace after it. At the end of a
+    /// line, moves to the start of the line below.
+    pub fn move_word_right(&mut self) {
+        let chars: Vec<char> = self.lines[self.cursor_row].chars().collect();
+        if self.cursor_col >= chars.len() {
+            self.move_right();
+            return;
+        }
+        let mut col = self.cursor_col;
+        let class = char_class(chars[col]);
+        if class != CharClass::Space {
+            while col < chars.len() && char_class(chars[col]) == class {
+                col += 1;
+            }
+        }
+        while col < chars.len() && chars[col].is_whitespace() {
+            col += 1;
+        }
+        self.cursor_col = col;
+    }
+
+    /// Deletes from the cursor back to where [`Self::move_word_left`] would land, as one undo step.
+    pub fn delete_word_back(&mut self) {
+        if self.delete_selection() {
+            return;
+        }
+        self.anchor = Some((self.cursor_row, self.cursor_col));
+        self.move_word_left();
+        self.delete_selection();
+    }
+
+    /// Deletes from the cursor up to where [`Self::move_word_right`] would land, as one undo step.
+    pub fn delete_word_forward(&mut self) {
+        if self.delete_selection() {
+            return;
+        }
+        self.anchor = Some((self.cursor_row, self.cursor_col));
+        self.move_word_right();
+        self.delete_selection();
+    }
+
     fn current_line_mut(&mut self) -> &mut String {
         if self.lines.is_empty() {
             self.lines.push(String::new());
@@ -194,8 +590,295 @@ impl Editor {
     }
 }
 
+/// Word boundaries fall wherever the class changes, so `don't` is three words.
+#[derive(Clone, Copy, PartialEq)]
+enum CharClass {
+    Space,
+    Punctuation,
+    Word,
+}
+
+fn char_class(ch: char) -> CharClass {
+    if ch.is_whitespace() {
+        CharClass::Space
+    } else if ch.is_alphanumeric() || ch == '_' {
+        CharClass::Word
+    } else {
+        CharClass::Punctuation
+    }
+}
+
 impl Default for Editor {
     fn default() -> Self {
         Self::new()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn type_str(editor: &mut Editor, text: &str) {
+        for ch in text.chars() {
+            editor.insert_char(ch);
+        }
+    }
+
+    #[test]
+    fn undo_restores_cleared_buffer() {
+        let mut editor = Editor::new();
+        type_str(&mut editor, "half-typed");
+        let before = editor.content();
+
+        editor.card_type = CardType::Cloze;
+        editor.clear();
+        assert_eq!(editor.content(), "C: ");
+
+        assert!(editor.undo());
+        assert_eq!(editor.content(), before);
+        assert!(editor.redo());
+        assert_eq!(editor.content(), "C: ");
+    }
+
+    #[test]
+    fn typing_run_undoes_as_one_step() {
+        let mut editor = Editor::new();
+        type_str(&mut editor, "abc");
+        editor.insert_newline();
+        type_str(&mut editor, "def");
+
+        assert!(editor.undo());
+        assert_eq!(editor.content(), "Q: abc\n\nA: ");
+        assert!(editor.undo());
+        assert_eq!(editor.content(), "Q: abc\nA: ");
+        assert!(editor.undo());
+        assert_eq!(editor.content(), "Q: \nA: ");
+        assert!(!editor.undo());
+    }
+
+    #[test]
+    fn insert_text_splits_lines_and_strips_control_chars() {
+        let mut editor = Editor::new();
+        editor.insert_text("one\r\ntwo\u{7}\tx\nthree");
+
+        assert_eq!(editor.content(), "Q: one\ntwo\tx\nthree\nA: ");
+        assert_eq!(editor.cursor(), (2, 5));
+
+        assert!(editor.undo());
+        assert_eq!(editor.content(), "Q: \nA: ");
+    }
+
+    #[test]
+    fn single_line_mode_refuses_line_breaks() {
+        let mut editor = Editor::single_line();
+        type_str(&mut editor, "der");
+        editor.insert_newline();
+        editor.insert_text(" Hund\nist\r\nda");
+        assert_eq!(editor.lines(), ["der Hund ist da"]);
+        assert_eq!(editor.cursor(), (0, 15));
+    }
+
+    #[test]
+    fn insert_text_keeps_text_after_cursor() {
+        let mut editor = Editor::from_content("Q: ab");
+        editor.move_end();
+        editor.move_left();
+        editor.insert_text("X\nY");
+        assert_eq!(editor.content(), "Q: aX\nYb");
+        assert_eq!(editor.cursor(), (1, 1));
+    }
+
+    fn select(editor: &mut Editor, moves: impl Fn(&mut Editor)) {
+        editor.set_selecting(true);
+        moves(editor);
+    }
+
+    #[test]
+    fn shift_selection_spans_lines_and_cuts() {
+        let mut editor = Editor::from_content("Q: first\nsecond\nA: third");
+        editor.move_right();
+        editor.move_right();
+        editor.move_right();
+        select(&mut editor, |e| {
+            e.move_down();
+            e.move_down();
+        });
+        assert_eq!(editor.selected_text().unwrap(), "first\nsecond\nA: ");
+        assert_eq!(editor.selection_in_line(1), Some((0, 6)));
+
+        assert!(editor.delete_selection());
+        assert_eq!(editor.content(), "Q: third");
+        assert_eq!(editor.cursor(), (0, 3));
+        assert!(editor.selection().is_none());
+
+        assert!(editor.undo());
+        assert_eq!(editor.content(), "Q: first\nsecond\nA: third");
+    }
+
+    #[test]
+    fn typing_replaces_selection_and_plain_moves_clear_it() {
+        let mut editor = Editor::from_content("hello world");
+        select(&mut editor, |e| {
+            for _ in 0..5 {
+                e.move_right();
+            }
+        });
+        editor.insert_char('J');
+        assert_eq!(editor.content(), "J world");
+
+        editor.set_selecting(true);
+        editor.move_end();
+        editor.set_selecting(false);
+        editor.move_left();
+        assert!(editor.selected_text().is_none());
+    }
+
+    #[test]
+    fn new_edit_clears_redo_and_depth_is_bounded() {
+        let mut editor = Editor::new();
+        type_str(&mut editor, "a");
+        editor.undo();
+        editor.backspace();
+        assert!(!editor.redo());
+
+        for _ in 0..MAX_UNDO_DEPTH + 10 {
+            editor.insert_newline();
+        }
+        assert_eq!(editor.undo_stack.len(), MAX_UNDO_DEPTH);
+    }
+
+    #[test]
+    fn move_to_clamps_to_the_buffer() {
+        let mut editor = Editor::from_content("Q: short\nA: a longer line");
+        editor.move_to(1, 5);
+        assert_eq!(editor.cursor(), (1, 5));
+        editor.move_to(0, 40);
+        assert_eq!(editor.cursor(), (0, 8));
+        editor.move_to(9, 2);
+        assert_eq!(editor.cursor(), (1, 2));
+    }
+
+    #[test]
+    fn scrolling_drags_the_cursor_into_view() {
+        let content: Vec<String> = (0..20).map(|i| format!("line {i}")).collect();
+        let mut editor = Editor::from_content(&content.join("\n"));
+        editor.scroll(3, 5);
+        assert_eq!(editor.scroll_top(), 3);
+        assert_eq!(editor.cursor(), (3, 0));
+
+        editor.scroll(100, 5);
+        assert_eq!(editor.scroll_top(), 15);
+        assert_eq!(editor.cursor().0, 15);
+
+        editor.move_to(19, 0);
+        editor.scroll(-100, 5);
+        assert_eq!(editor.scroll_top(), 0);
+        assert_eq!(editor.cursor().0, 4);
+    }
+
+    #[test]
+    fn word_moves_stop_at_punctuation_and_cross_lines() {
+        let mut editor = Editor::from_content(
+            "Q: {{c1::Paris}} is
+A: x",
+        );
+        let mut stops = Vec::new();
+        for _ in 0..8 {
+            editor.move_word_right();
+            stops.push(editor.cursor());
+        }
+        assert_eq!(
+            stops,
+            [
+                (0, 1),
+                (0, 3),
+                (0, 5),
+                (0, 7),
+                (0, 9),
+                (0, 14),
+                (0, 17),
+                (0, 19)
+            ]
+        );
+        editor.move_word_right();
+        assert_eq!(editor.cursor(), (1, 0));
+
+        editor.move_word_left();
+        assert_eq!(editor.cursor(), (0, 19));
+        editor.move_word_left();
+        assert_eq!(editor.cursor(), (0, 17));
+        editor.move_word_left();
+        assert_eq!(editor.cursor(), (0, 14));
+    }
+
+    #[test]
+    fn word_deletes_join_lines_and_undo_in_one_step() {
+        let mut editor = Editor::from_content(
+            "A: one two
+three",
+        );
+        editor.move_end();
+        editor.delete_word_back();
+        assert_eq!(editor.content(), "A: one \nthree");
+        editor.delete_word_forward();
+        assert_eq!(editor.content(), "A: one three");
+        editor.delete_word_forward();
+        assert_eq!(editor.content(), "A: one ");
+
+        assert!(editor.undo());
+        assert_eq!(editor.content(), "A: one three");
+        assert!(editor.undo());
+        assert_eq!(editor.content(), "A: one \nthree");
+        assert!(editor.undo());
+        assert_eq!(editor.content(), "A: one two\nthree");
+    }
+
+    #[test]
+    fn line_commands_keep_the_column_and_undo() {
+        let mut editor = Editor::from_content("C: {{c1::one}}\nC: two");
+        editor.move_to(0, 10);
+        editor.duplicate_line();
+        assert_eq!(editor.content(), "C: {{c1::one}}\nC: {{c1::one}}\nC: two");
+        assert_eq!(editor.cursor(), (1, 10));
+
+        editor.move_down();
+        editor.move_to(2, 4);
+        editor.delete_line();
+        assert_eq!(editor.content(), "C: {{c1::one}}\nC: {{c1::one}}");
+        assert_eq!(editor.cursor(), (1, 4));
+
+        editor.move_up();
+        editor.move_end();
+        editor.delete_line();
+        editor.delete_line();
+        assert_eq!(editor.content(), "");
+        assert_eq!(editor.cursor(), (0, 0));
+
+        assert!(editor.undo());
+        assert!(editor.undo());
+        assert_eq!(editor.content(), "C: {{c1::one}}\nC: {{c1::one}}");
+        assert!(editor.undo());
+        assert!(editor.undo());
+        assert_eq!(editor.content(), "C: {{c1::one}}\nC: two");
+    }
+
+    #[test]
+    fn search_is_case_insensitive_unless_asked_and_wraps() {
+        let mut editor = Editor::from_content("Q: Paris\nA: paris, PARIS\nC: none");
+        assert_eq!(editor.find_all("paris", false), [(0, 3), (1, 3), (1, 10)]);
+        assert_eq!(editor.find_all("paris", true), [(1, 3)]);
+        assert!(editor.find_all("", false).is_empty());
+
+        assert!(editor.find_next("paris", false, true));
+        assert_eq!(editor.cursor(), (0, 3));
+        assert!(editor.find_next("paris", false, true));
+        assert!(editor.find_next("paris", false, true));
+        assert_eq!(editor.cursor(), (1, 10));
+        assert!(editor.find_next("paris", false, true));
+        assert_eq!(editor.cursor(), (0, 3));
+        assert!(editor.find_next("paris", false, false));
+        assert_eq!(editor.cursor(), (1, 10));
+        assert!(!editor.find_next("london", false, true));
+        assert_eq!(editor.cursor(), (1, 10));
+    }
+}
@@ -1,5 +1,8 @@
+pub mod clipboard;
 pub mod editor;
+pub mod mouse;
 pub mod theme;
 
 pub use editor::Editor;
+pub use mouse::MouseCapture;
 pub use theme::Theme;
@@ -0,0 +1,40 @@
+use std::io;
+
+use crossterm::event::{DisableMouseCapture, EnableMouseCapture};
+use crossterm::execute;
+
+/// Whether a full-screen view receives mouse events. While captured, the terminal's
+/// own text selection doesn't work, so views let the user switch it off.
+pub struct MouseCapture {
+    enabled: bool,
+}
+
+impl MouseCapture {
+    pub fn enable() -> io::Result<Self> {
+        execute!(io::stdout(), EnableMouseCapture)?;
+        Ok(Self { enabled: true })
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+
+    pub fn toggle(&mut self) -> io::Result<()> {
+        if self.enabled {
+            execute!(io::stdout(), DisableMouseCapture)?;
+        } else {
+            execute!(io::stdout(), EnableMouseCapture)?;
+        }
+        self.enabled = !self.enabled;
+        Ok(())
+    }
+
+    /// Hands the mouse back to the terminal before leaving the view.
+    pub fn release(&mut self) -> io::Result<()> {
+        if self.enabled {
+            execute!(io::stdout(), DisableMouseCapture)?;
+            self.enabled = false;
+        }
+        Ok(())
+    }
+}
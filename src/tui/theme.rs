@@ -1,3 +1,7 @@
+use std::ffi::OsString;
+use std::fmt::Display;
+use std::sync::atomic::{AtomicBool, Ordering};
+
 use ratatui::{
     layout::Alignment,
     style::{Color, Modifier, Style},
@@ -5,7 +9,10 @@ use ratatui::{
     widgets::{Block, BorderType, Borders},
 };
 
-/// Centralized color palette and helpers for a consistent TUI look.
+static COLOR_OUTPUT: AtomicBool = AtomicBool::new(false);
+
+/// Centralized color palette and helpers for a consistent look, in the TUIs and in
+/// printed output alike.
 pub struct Theme;
 
 impl Theme {
@@ -14,6 +21,27 @@ impl Theme {
     pub const BORDER: Color = Color::Gray;
     pub const WARNING: Color = Color::Yellow;
     pub const SUCCESS: Color = Color::Green;
+    pub const OVERDUE: Color = Color::Red;
+
+    /// Printed output is colored only on a terminal, and never when `NO_COLOR` is set
+    /// to a non-empty value.
+    pub fn wants_color(no_color: Option<OsString>, stdout_is_terminal: bool) -> bool {
+        stdout_is_terminal && no_color.is_none_or(|value| value.is_empty())
+    }
+
+    /// Turns ANSI colors in printed output on or off; they start off.
+    pub fn set_color_output(enabled: bool) {
+        COLOR_OUTPUT.store(enabled, Ordering::Relaxed);
+    }
+
+    /// `text` in `style` for printing to the terminal, or unchanged when color output
+    /// is off.
+    pub fn paint(text: impl Display, style: Style) -> String {
+        if !COLOR_OUTPUT.load(Ordering::Relaxed) {
+            return text.to_string();
+        }
+        ansi(text, style)
+    }
 
     pub fn default_style() -> Style {
         Style::default()
@@ -37,10 +65,33 @@ impl Theme {
             .add_modifier(Modifier::BOLD)
     }
 
+    pub fn overdue() -> Style {
+        Style::default()
+            .fg(Self::OVERDUE)
+            .add_modifier(Modifier::BOLD)
+    }
+
+    /// Bars of the histograms in printed stats.
+    pub fn bar() -> Style {
+        Style::default().fg(Self::ACCENT)
+    }
+
     pub fn emphasis() -> Style {
         Style::default().add_modifier(Modifier::BOLD)
     }
 
+    pub fn selection() -> Style {
+        Style::default().add_modifier(Modifier::REVERSED)
+    }
+
+    pub fn search_match() -> Style {
+        Style::default().fg(Color::Black).bg(Self::WARNING)
+    }
+
+    pub fn gutter() -> Style {
+        Style::default().fg(Self::BORDER)
+    }
+
     pub fn panel<'a>(title: impl Into<String>) -> Block<'a> {
         Self::panel_with_line(Self::title_line(title))
     }
@@ -91,3 +142,69 @@ impl Theme {
         Line::from(vec![Span::styled(text.into(), Self::emphasis())])
     }
 }
+
+fn ansi(text: impl Display, style: Style) -> String {
+    let mut codes = Vec::new();
+    if style.add_modifier.contains(Modifier::BOLD) {
+        codes.push("1".to_string());
+    }
+    if style.add_modifier.contains(Modifier::REVERSED) {
+        codes.push("7".to_string());
+    }
+    codes.extend(style.fg.and_then(|color| color_code(color, false)));
+    codes.extend(style.bg.and_then(|color| color_code(color, true)));
+    if codes.is_empty() {
+        return text.to_string();
+    }
+    format!("\x1b[{}m{text}\x1b[0m", codes.join(";"))
+}
+
+fn color_code(color: Color, background: bool) -> Option<String> {
+    let offset = if background { 10 } else { 0 };
+    let named = |code: u8| Some((code + offset).to_string());
+    match color {
+        Color::Reset => None,
+        Color::Black => named(30),
+        Color::Red => named(31),
+        Color::Green => named(32),
+        Color::Yellow => named(33),
+        Color::Blue => named(34),
+        Color::Magenta => named(35),
+        Color::Cyan => named(36),
+        Color::Gray => named(37),
+        Color::DarkGray => named(90),
+        Color::LightRed => named(91),
+        Color::LightGreen => named(92),
+        Color::LightYellow => named(93),
+        Color::LightBlue => named(94),
+        Color::LightMagenta => named(95),
+        Color::LightCyan => named(96),
+        Color::White => named(97),
+        Color::Rgb(r, g, b) => Some(format!("{};2;{r};{g};{b}", 38 + offset)),
+        Color::Indexed(index) => Some(format!("{};5;{index}", 38 + offset)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::ffi::OsString;
+
+    use super::{Theme, ansi};
+
+    #[test]
+    fn color_is_off_when_piped_or_disabled() {
+        assert!(Theme::wants_color(None, true));
+        assert!(Theme::wants_color(Some(OsString::new()), true));
+        assert!(!Theme::wants_color(Some(OsString::from("1")), true));
+        assert!(!Theme::wants_color(None, false));
+    }
+
+    #[test]
+    fn styles_become_ansi_codes() {
+        assert_eq!(ansi(3, Theme::overdue()), "\x1b[1;31m3\x1b[0m");
+        assert_eq!(ansi("█", Theme::bar()), "\x1b[34m█\x1b[0m");
+        assert_eq!(ansi("plain", Theme::default_style()), "plain");
+        // Color output starts off, so printed text stays plain.
+        assert_eq!(Theme::paint("7", Theme::overdue()), "7");
+    }
+}
@@ -1,28 +1,274 @@
 use ignore::WalkBuilder;
+use ignore::gitignore::{Gitignore, GitignoreBuilder};
 use ignore::types::TypesBuilder;
-use std::fs::File;
-use std::io::{BufRead, BufReader};
+use std::fmt;
+use std::fs;
+use std::io::{BufRead, Write};
 use std::path::{Path, PathBuf};
 
-use crate::card::{Card, CardContent, ClozeRange};
+use crate::card::{Card, CardContent, ClozeRange, NumberedCloze};
+use crate::card_cache::{CardCache, Fingerprint};
+use crate::fsrs::LEARN_AHEAD_THRESHOLD_MINS;
 use crate::llm::{ensure_client, request_cloze};
+use crate::media::extract_images;
 use futures::stream::{self, StreamExt};
 use ignore::WalkState;
-use std::collections::HashMap;
+use std::collections::hash_map::Entry;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
 use std::sync::{Arc, Mutex};
 use tokio::sync::mpsc;
+use unicode_normalization::UnicodeNormalization;
 
 use crate::crud::DB;
 
-use anyhow::{Context, Result, anyhow};
+use anyhow::{Context, Result, anyhow, bail};
 
 const MAX_CONCURRENT_LLM_REQUESTS: usize = 4;
 
-pub fn is_markdown(path: &Path) -> bool {
+/// File extensions scanned for cards unless the config lists others.
+pub const DEFAULT_CARD_EXTENSIONS: [&str; 2] = ["md", "markdown"];
+
+/// Lines starting with this end the card above them.
+pub const DEFAULT_CARD_SEPARATOR: &str = "---";
+
+/// How card text is normalized before hashing.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum HashMode {
+    /// NFC-normalized, so different Unicode forms of the same text share a hash.
+    Current,
+    /// Without NFC normalization, like releases before it; `rehash` uses this to find
+    /// the old hash of each card.
+    Legacy,
+}
+
+/// Some editors start UTF-8 files with this; it isn't part of the first card.
+pub const UTF8_BOM: &str = "\u{feff}";
+
+/// File name of the gitignore-style files that keep card files out of every command.
+pub const IGNORE_FILE_NAME: &str = ".repeatignore";
+
+/// `extensions` for messages, like `.md, .markdown`.
+pub fn describe_card_extensions(extensions: &[String]) -> String {
+    extensions
+        .iter()
+        .map(|ext| format!(".{ext}"))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// Local hour at which one study day ends and the next begins, unless the config moves it.
+pub const DEFAULT_DAY_CUTOFF_HOUR: u32 = 0;
+
+/// The study day `at` falls on in the local time zone: a time before `cutoff_hour`
+/// belongs to the day before.
+pub fn study_day(at: chrono::DateTime<chrono::Utc>, cutoff_hour: u32) -> chrono::NaiveDate {
+    study_day_in(at, &chrono::Local, cutoff_hour)
+}
+
+/// Today's study day.
+pub fn today(cutoff_hour: u32) -> chrono::NaiveDate {
+    study_day(chrono::Utc::now(), cutoff_hour)
+}
+
+/// When the study `day` begins.
+pub fn day_start(day: chrono::NaiveDate, cutoff_hour: u32) -> chrono::DateTime<chrono::Utc> {
+    day_start_in(day, &chrono::Local, cutoff_hour)
+}
+
+/// When the study day `at` falls on ends.
+pub fn study_day_end(
+    at: chrono::DateTime<chrono::Utc>,
+    cutoff_hour: u32,
+) -> chrono::DateTime<chrono::Utc> {
+    day_start(
+        study_day(at, cutoff_hour) + chrono::Duration::days(1),
+        cutoff_hour,
+    )
+}
+
+/// Whether a card due at `due_date` counts as due at `now`: within the learn-ahead
+/// window, or anywhere in the current study day once its interval is a day or more.
+pub fn is_due_at(
+    due_date: chrono::DateTime<chrono::Utc>,
+    interval_raw: f64,
+    now: chrono::DateTime<chrono::Utc>,
+    cutoff_hour: u32,
+) -> bool {
+    due_date <= now + LEARN_AHEAD_THRESHOLD_MINS
+        || (interval_raw >= 1.0 && due_date < study_day_end(now, cutoff_hour))
+}
+
+fn study_day_in<Tz: chrono::TimeZone>(
+    at: chrono::DateTime<chrono::Utc>,
+    tz: &Tz,
+    cutoff_hour: u32,
+) -> chrono::NaiveDate {
+    (at.with_timezone(tz).naive_local() - chrono::Duration::hours(i64::from(cutoff_hour))).date()
+}
+
+fn day_start_in<Tz: chrono::TimeZone>(
+    day: chrono::NaiveDate,
+    tz: &Tz,
+    cutoff_hour: u32,
+) -> chrono::DateTime<chrono::Utc> {
+    let start = day
+        .and_hms_opt(cutoff_hour, 0, 0)
+        .expect("the cutoff hour is validated");
+    // A daylight saving gap can skip the cutoff; the day then starts when the clocks resume.
+    (0..=2)
+        .find_map(|hours| {
+            tz.from_local_datetime(&(start + chrono::Duration::hours(hours)))
+                .earliest()
+        })
+        .map_or_else(|| start.and_utc(), |at| at.with_timezone(&chrono::Utc))
+}
+
+pub fn is_card_separator(line: &str, separator: &str) -> bool {
+    line.starts_with(separator)
+}
+
+/// Which card files `register_all_cards` and the other scans pick up, and how they
+/// split into cards.
+#[derive(Clone)]
+pub struct ScanOptions {
+    /// Limits the scan to card files matching the `--include`/`--exclude` globs.
+    pub scope: PathScope,
+    /// Lines starting with this end the card above them.
+    pub separator: String,
+    /// Extensions, without the dot, of the files holding cards.
+    pub extensions: Vec<String>,
+    /// Fail on malformed cards instead of skipping them with a warning.
+    pub strict: bool,
+    /// Reuse the parsed cards of unchanged files from the [`CardCache`].
+    pub use_cache: bool,
+}
+
+impl Default for ScanOptions {
+    fn default() -> Self {
+        Self {
+            scope: PathScope::default(),
+            separator: DEFAULT_CARD_SEPARATOR.to_string(),
+            extensions: DEFAULT_CARD_EXTENSIONS
+                .iter()
+                .map(|ext| ext.to_string())
+                .collect(),
+            strict: false,
+            use_cache: false,
+        }
+    }
+}
+
+/// `--include`/`--exclude` globs in gitignore syntax, relative to the working directory.
+/// A glob matching a directory covers everything inside it.
+#[derive(Clone, Default)]
+pub struct PathScope {
+    include: Option<Gitignore>,
+    exclude: Option<Gitignore>,
+}
+
+impl PathScope {
+    pub fn new(include: &[String], exclude: &[String]) -> Result<Self> {
+        Ok(Self {
+            include: Self::globs(include)?,
+            exclude: Self::globs(exclude)?,
+        })
+    }
+
+    fn globs(globs: &[String]) -> Result<Option<Gitignore>> {
+        if globs.is_empty() {
+            return Ok(None);
+        }
+        let mut builder = GitignoreBuilder::new(std::env::current_dir()?);
+        for glob in globs {
+            builder
+                .add_line(None, glob)
+                .with_context(|| format!("Invalid glob: {glob}"))?;
+        }
+        Ok(Some(builder.build()?))
+    }
+
+    /// Directories are only pruned by `exclude`, since an included file may sit below one.
+    fn allows(&self, path: &Path, is_dir: bool) -> bool {
+        let matches = |globs: &Gitignore| {
+            path.ancestors()
+                .take_while(|ancestor| !ancestor.as_os_str().is_empty())
+                .enumerate()
+                .any(|(depth, ancestor)| globs.matched(ancestor, is_dir || depth > 0).is_ignore())
+        };
+        if self.exclude.as_ref().is_some_and(matches) {
+            return false;
+        }
+        is_dir || self.include.as_ref().is_none_or(matches)
+    }
+}
+
+/// A card block that failed to parse.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ParseIssue {
+    pub file_path: PathBuf,
+    /// 1-based line where the card starts.
+    pub line: usize,
+    pub message: String,
+}
+
+impl fmt::Display for ParseIssue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}:{}: {}",
+            self.file_path.display(),
+            self.line,
+            self.message
+        )
+    }
+}
+
+/// Malformed cards that failed a [`ScanOptions::strict`] registration.
+#[derive(Debug)]
+pub struct MalformedCards(pub Vec<ParseIssue>);
+
+impl fmt::Display for MalformedCards {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let plural = if self.0.len() == 1 { "" } else { "s" };
+        write!(f, "Found {} malformed card{plural}:", self.0.len())?;
+        for issue in &self.0 {
+            write!(f, "\n  {issue}")?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for MalformedCards {}
+
+/// Replaces `path` with `contents` through a synced temp file in the same directory, so a
+/// crash leaves either the old file or the new one, never a truncated mix. The rename
+/// replaces an existing file on Windows too.
+pub fn write_file_atomically(path: &Path, contents: &[u8]) -> Result<()> {
+    let dir = match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        _ => Path::new("."),
+    };
+    let mut temp = tempfile::NamedTempFile::new_in(dir)
+        .with_context(|| format!("Failed to create a temp file in {}", dir.display()))?;
+    temp.write_all(contents)?;
+    if let Ok(metadata) = fs::metadata(path) {
+        temp.as_file().set_permissions(metadata.permissions())?;
+    }
+    temp.as_file().sync_all()?;
+    temp.persist(path)
+        .with_context(|| format!("Failed to replace {}", path.display()))?;
+    Ok(())
+}
+
+/// Whether `path` has one of `extensions`, ignoring case.
+pub fn is_markdown(path: &Path, extensions: &[String]) -> bool {
     path.extension()
         .and_then(|ext| ext.to_str())
-        .map(|ext| ext.eq_ignore_ascii_case("md"))
-        .unwrap_or(false)
+        .is_some_and(|ext| {
+            extensions
+                .iter()
+                .any(|accepted| ext.eq_ignore_ascii_case(accepted))
+        })
 }
 
 pub fn find_cloze_ranges(text: &str) -> Vec<(usize, usize)> {
@@ -44,6 +290,74 @@ pub fn find_cloze_ranges(text: &str) -> Vec<(usize, usize)> {
     ranges
 }
 
+pub fn find_numbered_clozes(text: &str) -> Vec<NumberedCloze> {
+    let mut clozes = Vec::new();
+    let mut offset = 0;
+
+    while let Some(found) = text[offset..].find("{{c") {
+        let start = offset + found;
+        let after_c = start + 3;
+        let digits = text[after_c..]
+            .find(|ch: char| !ch.is_ascii_digit())
+            .unwrap_or(text.len() - after_c);
+        let body_start = after_c + digits + 2;
+        let index = text[after_c..after_c + digits].parse::<u32>().ok();
+        let has_separator = text[after_c + digits..].starts_with("::");
+        let close = text
+            .get(body_start..)
+            .and_then(|body| body.find("}}"))
+            .map(|i| body_start + i);
+
+        match (index, has_separator, close) {
+            (Some(index), true, Some(close)) if index > 0 => {
+                let (answer, hint) = split_cloze_hint(&text[body_start..close]);
+                clozes.push(NumberedCloze {
+                    start,
+                    end: close + 2,
+                    index,
+                    answer,
+                    hint,
+                });
+                offset = close + 2;
+            }
+            _ => offset = after_c,
+        }
+    }
+
+    clozes
+}
+
+/// Splits a deletion body on its first unescaped `::`. Write `\::` to keep a
+/// literal `::` in the answer, e.g. `{{c1::std\::vec}}`.
+fn split_cloze_hint(body: &str) -> (String, Option<String>) {
+    let mut search_from = 0;
+    let separator = loop {
+        match body[search_from..].find("::") {
+            Some(i) if body[..search_from + i].ends_with('\\') => search_from += i + 2,
+            Some(i) => break Some(search_from + i),
+            None => break None,
+        }
+    };
+    let (answer, hint) = match separator {
+        Some(i) => (&body[..i], trim_line(&body[i + 2..]).map(str::to_string)),
+        None => (body, None),
+    };
+    (answer.replace("\\::", "::"), hint)
+}
+
+/// Drops cloze hints so rewording a hint keeps the card's review history.
+fn strip_cloze_hints(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut last = 0;
+    for cloze in find_numbered_clozes(text) {
+        out.push_str(&text[last..cloze.start]);
+        out.push_str(&format!("{{{{c{}::{}}}}}", cloze.index, cloze.answer));
+        last = cloze.end;
+    }
+    out.push_str(&text[last..]);
+    out
+}
+
 pub fn trim_line(line: &str) -> Option<&str> {
     let trimmed = line.trim();
     if trimmed.is_empty() {
@@ -53,7 +367,12 @@ pub fn trim_line(line: &str) -> Option<&str> {
     }
 }
 
-fn parse_card_lines(contents: &str) -> (Option<String>, Option<String>, Option<String>) {
+/// Question, answer, and cloze text of a card block, read up to a line holding just
+/// `separator`.
+pub fn parse_card_lines(
+    contents: &str,
+    separator: &str,
+) -> (Option<String>, Option<String>, Option<String>) {
     #[derive(Copy, Clone)]
     enum Section {
         Question,
@@ -82,7 +401,7 @@ fn parse_card_lines(contents: &str) -> (Option<String>, Option<String>, Option<S
         }
 
         let line = trimmed.unwrap();
-        if line == "---" {
+        if line == separator {
             return (
                 join_nonempty(question_lines),
                 join_nonempty(answer_lines),
@@ -90,7 +409,11 @@ fn parse_card_lines(contents: &str) -> (Option<String>, Option<String>, Option<S
             );
         }
 
-        if let Some(rest) = line.strip_prefix("Q:") {
+        if let Some(rest) = line
+            .strip_prefix("Q:")
+            .or_else(|| line.strip_prefix("R:"))
+            .or_else(|| line.strip_prefix("T:"))
+        {
             section = Section::Question;
             question_lines.clear();
             if let Some(v) = trim_line(rest) {
@@ -156,25 +479,172 @@ fn parse_card_lines(contents: &str) -> (Option<String>, Option<String>, Option<S
         join_nonempty(cloze_lines),
     )
 }
+/// Parses a card block into every card it produces: two for an `R:` block
+/// whose reversed twin asks the answer, one per cluster for `{{cN::...}}`
+/// clozes, and a single card otherwise.
+pub fn content_to_cards(
+    card_path: &Path,
+    contents: &str,
+    separator: &str,
+    file_start_idx: usize,
+    file_end_idx: usize,
+) -> Result<Vec<Card>> {
+    block_to_cards(
+        card_path,
+        contents,
+        separator,
+        (file_start_idx, file_end_idx),
+        HashMode::Current,
+    )
+}
+
+/// [`content_to_cards`] with the card hashes taken the `mode` way.
+fn block_to_cards(
+    card_path: &Path,
+    contents: &str,
+    separator: &str,
+    file_card_range: (usize, usize),
+    mode: HashMode,
+) -> Result<Vec<Card>> {
+    let card = parse_card(card_path, contents, separator, file_card_range, mode)?;
+    match &card.content {
+        CardContent::Basic { question, answer } if is_reversible(contents) => {
+            let reversed = Card {
+                file_path: card.file_path.clone(),
+                file_card_range: card.file_card_range,
+                content: CardContent::Basic {
+                    question: answer.clone(),
+                    answer: question.clone(),
+                },
+                card_hash: reversed_hash(&card.card_hash),
+                origin: card.origin.clone(),
+                tags: card.tags.clone(),
+            };
+            Ok(vec![card, reversed])
+        }
+        CardContent::Cloze { text, .. } => {
+            let clozes = find_numbered_clozes(text);
+            if let Some(marker) = malformed_cloze_marker(text, &clozes) {
+                bail!("Malformed cloze {marker:?}: expected {{{{cN::answer}}}}");
+            }
+            let mut indices: Vec<u32> = clozes.iter().map(|cloze| cloze.index).collect();
+            indices.sort_unstable();
+            indices.dedup();
+            if indices.is_empty() {
+                return Ok(vec![card]);
+            }
+            let base_hash = hash_text(&strip_cloze_hints(contents), mode)
+                .ok_or_else(|| anyhow!("Unable to hash contents"))?;
+            indices
+                .into_iter()
+                .map(|index| {
+                    let first = clozes
+                        .iter()
+                        .find(|cloze| cloze.index == index)
+                        .expect("index comes from clozes");
+                    Ok(Card {
+                        file_path: card.file_path.clone(),
+                        file_card_range: card.file_card_range,
+                        content: CardContent::Cloze {
+                            text: text.clone(),
+                            cloze_range: Some(ClozeRange::new(first.start, first.end)?),
+                            cloze_index: Some(index),
+                        },
+                        card_hash: numbered_cloze_hash(&base_hash, index),
+                        origin: card.origin.clone(),
+                        tags: card.tags.clone(),
+                    })
+                })
+                .collect()
+        }
+        _ => Ok(vec![card]),
+    }
+}
+
+/// Start of the first `{{c` that didn't parse as a numbered cloze, such as an unterminated `{{c1::`.
+fn malformed_cloze_marker<'a>(text: &'a str, clozes: &[NumberedCloze]) -> Option<&'a str> {
+    text.match_indices("{{c")
+        .map(|(start, _)| start)
+        .find(|start| {
+            !clozes
+                .iter()
+                .any(|cloze| (cloze.start..cloze.end).contains(start))
+        })
+        .map(|start| {
+            let rest = &text[start..];
+            let end = rest
+                .char_indices()
+                .nth(20)
+                .map_or(rest.len(), |(end, _)| end);
+            rest[..end].lines().next().unwrap_or_default()
+        })
+}
+
+fn is_type_in(contents: &str) -> bool {
+    contents
+        .lines()
+        .filter_map(trim_line)
+        .any(|line| line.starts_with("T:"))
+}
+
+fn is_reversible(contents: &str) -> bool {
+    contents
+        .lines()
+        .filter_map(trim_line)
+        .any(|line| line.starts_with("R:"))
+}
+
+pub(crate) fn reversed_hash(card_hash: &str) -> String {
+    blake3::hash(format!("{card_hash}:reversed").as_bytes()).to_string()
+}
+
+fn numbered_cloze_hash(card_hash: &str, index: u32) -> String {
+    blake3::hash(format!("{card_hash}:c{index}").as_bytes()).to_string()
+}
+
+/// Parses a card block into its first card. See [`content_to_cards`].
 pub fn content_to_card(
     card_path: &Path,
     contents: &str,
+    separator: &str,
     file_start_idx: usize,
     file_end_idx: usize,
 ) -> Result<Card> {
-    let (question, answer, cloze) = parse_card_lines(contents);
+    content_to_cards(card_path, contents, separator, file_start_idx, file_end_idx)?
+        .into_iter()
+        .next()
+        .ok_or_else(|| anyhow!("Unable to parse a card from contents:\n{}", contents))
+}
+
+fn parse_card(
+    card_path: &Path,
+    contents: &str,
+    separator: &str,
+    file_card_range: (usize, usize),
+    mode: HashMode,
+) -> Result<Card> {
+    let (question, answer, cloze) = parse_card_lines(contents, separator);
 
-    let card_hash = get_hash(contents).ok_or_else(|| anyhow!("Unable to hash contents"))?;
+    let card_hash = hash_text(contents, mode).ok_or_else(|| anyhow!("Unable to hash contents"))?;
     if let (Some(q), Some(a)) = (question, answer) {
-        let content = CardContent::Basic {
-            question: q,
-            answer: a,
+        let content = if is_type_in(contents) {
+            CardContent::TypeIn {
+                question: q,
+                answer: a,
+            }
+        } else {
+            CardContent::Basic {
+                question: q,
+                answer: a,
+            }
         };
         Ok(Card {
             file_path: card_path.to_path_buf(),
-            file_card_range: (file_start_idx, file_end_idx),
+            file_card_range,
             content,
+            origin: card_hash.clone(),
             card_hash,
+            tags: Vec::new(),
         })
     } else if let Some(c) = cloze {
         let cloze_idxs = find_cloze_ranges(&c);
@@ -186,22 +656,36 @@ pub fn content_to_card(
         let content = CardContent::Cloze {
             text: c,
             cloze_range,
+            cloze_index: None,
         };
         Ok(Card {
             file_path: card_path.to_path_buf(),
-            file_card_range: (file_start_idx, file_end_idx),
+            file_card_range,
             content,
+            origin: card_hash.clone(),
             card_hash,
+            tags: Vec::new(),
         })
     } else {
-        Err(anyhow!(
-            "Unable to parse anything from card contents:\n{}",
-            contents
+        Err(anyhow!("{}", contents.trim()).context(
+            "Unable to parse anything from card contents: expected Q:/A:, R:/A:, T:/A:, or C:",
         ))
     }
 }
 
+/// Hashes card text after normalizing it: letters are lowercased and NFC-normalized,
+/// digits and `+`/`-` are kept, and everything else (whitespace, line endings,
+/// punctuation) is dropped, so reformatting a card keeps its hash.
 pub fn get_hash(s: &str) -> Option<String> {
+    hash_text(s, HashMode::Current)
+}
+
+/// [`get_hash`] without NFC normalization, as releases before it hashed cards.
+pub fn legacy_hash(s: &str) -> Option<String> {
+    hash_text(s, HashMode::Legacy)
+}
+
+fn hash_text(s: &str, mode: HashMode) -> Option<String> {
     trim_line(s)?;
     let mut hasher = blake3::Hasher::new();
 
@@ -225,8 +709,16 @@ pub fn get_hash(s: &str) -> Option<String> {
     }
 
     // Unicode-safe fallback (still streaming; no big allocation)
+    match mode {
+        HashMode::Current => hash_chars(&mut hasher, s.nfc()),
+        HashMode::Legacy => hash_chars(&mut hasher, s.chars()),
+    }
+    Some(hasher.finalize().to_string())
+}
+
+fn hash_chars(hasher: &mut blake3::Hasher, chars: impl Iterator<Item = char>) {
     let mut buf = [0u8; 4];
-    for ch in s.chars() {
+    for ch in chars {
         if ch == '+' || ch == '-' {
             hasher.update(&[ch as u8]); // ASCII '+'/'-'
             continue;
@@ -240,21 +732,69 @@ pub fn get_hash(s: &str) -> Option<String> {
             }
         }
     }
+}
+
+pub fn cards_from_md(path: &Path, separator: &str) -> Result<Vec<Card>> {
+    let (cards, issues) = parse_cards_from_md(path, separator, HashMode::Current)?;
+    match issues.into_iter().next() {
+        Some(issue) => Err(anyhow!(issue)),
+        None => Ok(cards),
+    }
+}
 
-    Some(hasher.finalize().to_string())
+/// Parses cards from markdown that isn't on disk yet, attributing them to `path`.
+/// Fails on the first malformed card.
+pub fn cards_from_reader(path: &Path, reader: impl BufRead, separator: &str) -> Result<Vec<Card>> {
+    let (cards, issues) = parse_cards_from_reader(path, reader, separator)?;
+    match issues.into_iter().next() {
+        Some(issue) => Err(anyhow!(issue)),
+        None => Ok(cards),
+    }
 }
 
-pub fn cards_from_md(path: &Path) -> Result<Vec<Card>> {
-    let file = File::open(path)?;
-    let mut reader = BufReader::new(file);
+/// Parses every well-formed card, collecting the malformed ones instead of failing.
+/// Cards are split at lines starting with `separator`; YAML frontmatter is always
+/// fenced by `---`, whatever the separator.
+pub fn parse_cards_from_reader(
+    path: &Path,
+    reader: impl BufRead,
+    separator: &str,
+) -> Result<(Vec<Card>, Vec<ParseIssue>)> {
+    parse_blocks(path, reader, separator, HashMode::Current)
+}
 
+/// [`parse_cards_from_reader`] with the card hashes taken the `mode` way.
+fn parse_blocks(
+    path: &Path,
+    mut reader: impl BufRead,
+    separator: &str,
+    mode: HashMode,
+) -> Result<(Vec<Card>, Vec<ParseIssue>)> {
     let mut cards = Vec::new();
+    let mut issues = Vec::new();
+    let mut push_block =
+        |cards: &mut Vec<Card>, block: &str, start: usize, end: usize| match block_to_cards(
+            path,
+            block,
+            separator,
+            (start, end),
+            mode,
+        ) {
+            Ok(parsed) => cards.extend(parsed),
+            Err(err) => issues.push(ParseIssue {
+                file_path: path.to_path_buf(),
+                line: start + 1,
+                message: err.to_string(),
+            }),
+        };
     let mut track_buffer = false;
     let mut buffer = String::new();
     let mut line = String::new();
     let mut start_idx = 0;
     let mut last_idx = 0;
     let mut line_idx = 0;
+    let mut frontmatter: Option<String> = None;
+    let mut tags = Vec::new();
 
     loop {
         line.clear();
@@ -263,16 +803,40 @@ pub fn cards_from_md(path: &Path) -> Result<Vec<Card>> {
             break;
         }
 
-        if line.starts_with("Q:") || line.starts_with("C:") {
+        let is_card_start = ["Q:", "R:", "T:", "C:"]
+            .iter()
+            .any(|prefix| line.starts_with(prefix));
+        if line_idx == 0 && line.trim_end() == "---" {
+            frontmatter = Some(String::new());
+            line_idx += 1;
+            continue;
+        }
+        // A card before the closing `---` means the opening one was just a separator.
+        if let Some(yaml) = frontmatter.as_mut()
+            && !is_card_start
+        {
+            if line.trim_end() == "---" {
+                tags = parse_frontmatter_tags(yaml);
+                frontmatter = None;
+            } else {
+                yaml.push_str(&line);
+            }
+            last_idx = line_idx;
+            line_idx += 1;
+            continue;
+        }
+        frontmatter = None;
+
+        if is_card_start {
             track_buffer = true;
             if trim_line(&buffer).is_some() {
-                cards.push(content_to_card(path, &buffer, start_idx, line_idx)?);
+                push_block(&mut cards, &buffer, start_idx, line_idx);
                 buffer.clear();
             }
             start_idx = line_idx;
         }
-        if line.starts_with("---") && trim_line(&buffer).is_some() {
-            cards.push(content_to_card(path, &buffer, start_idx, line_idx)?);
+        if is_card_separator(&line, separator) && trim_line(&buffer).is_some() {
+            push_block(&mut cards, &buffer, start_idx, line_idx);
             buffer.clear();
             track_buffer = false;
         }
@@ -283,13 +847,58 @@ pub fn cards_from_md(path: &Path) -> Result<Vec<Card>> {
         line_idx += 1;
     }
     if !buffer.is_empty() {
-        cards.push(content_to_card(path, &buffer, start_idx, last_idx + 1)?);
+        push_block(&mut cards, &buffer, start_idx, last_idx + 1);
+    }
+    if !tags.is_empty() {
+        for card in &mut cards {
+            card.tags = tags.clone();
+        }
+    }
+
+    Ok((cards, issues))
+}
+
+/// Reads `tags: [a, b]`, `tags: a, b`, or a `- a` block list from YAML frontmatter.
+fn parse_frontmatter_tags(frontmatter: &str) -> Vec<String> {
+    let mut raw = Vec::new();
+    let mut in_list = false;
+    for line in frontmatter.lines() {
+        if in_list {
+            if let Some(item) = line.trim_start().strip_prefix('-') {
+                raw.push(item);
+                continue;
+            }
+            if line.trim().is_empty() {
+                continue;
+            }
+            in_list = false;
+        }
+        let Some(value) = line.strip_prefix("tags:") else {
+            continue;
+        };
+        let value = value.trim();
+        if value.is_empty() {
+            in_list = true;
+            continue;
+        }
+        let value = value
+            .strip_prefix('[')
+            .and_then(|value| value.strip_suffix(']'))
+            .unwrap_or(value);
+        raw.extend(value.split(','));
     }
 
-    Ok(cards)
+    let mut tags: Vec<String> = Vec::new();
+    for tag in raw {
+        let tag = tag.trim().trim_matches(|c| c == '"' || c == '\'');
+        if !tag.is_empty() && !tags.iter().any(|existing| existing == tag) {
+            tags.push(tag.to_string());
+        }
+    }
+    tags
 }
 
-fn markdown_walk_builder(paths: &[PathBuf]) -> Result<Option<WalkBuilder>> {
+fn markdown_walk_builder(scan: &ScanOptions, paths: &[PathBuf]) -> Result<Option<WalkBuilder>> {
     let mut iter = paths.iter();
     let Some(first) = iter.next() else {
         return Ok(None);
@@ -298,32 +907,179 @@ fn markdown_walk_builder(paths: &[PathBuf]) -> Result<Option<WalkBuilder>> {
     for path in iter {
         builder.add(path);
     }
-    builder.hidden(false).git_ignore(true).git_exclude(true);
+    builder
+        .hidden(true)
+        .git_ignore(true)
+        .git_exclude(true)
+        .add_custom_ignore_filename(IGNORE_FILE_NAME);
+    let scope = scan.scope.clone();
+    builder.filter_entry(move |entry| {
+        let is_dir = entry.file_type().is_some_and(|ft| ft.is_dir());
+        scope.allows(entry.path(), is_dir)
+    });
     let mut types = TypesBuilder::new();
-    types.add("markdown", "*.md")?;
+    for ext in &scan.extensions {
+        types.add("markdown", &format!("*.{ext}"))?;
+    }
     types.select("markdown");
     builder.types(types.build()?);
     Ok(Some(builder))
 }
 
-fn run_card_walker(paths: Vec<PathBuf>, sender: mpsc::UnboundedSender<Vec<Card>>) -> Result<()> {
-    let Some(builder) = markdown_walk_builder(&paths)? else {
-        return Ok(());
+/// Card files under `paths`, sorted, skipping what the ignore files and the
+/// `--include`/`--exclude` globs leave out.
+pub fn card_files(scan: &ScanOptions, paths: &[PathBuf]) -> Result<Vec<PathBuf>> {
+    let Some(builder) = markdown_walk_builder(scan, paths)? else {
+        return Ok(Vec::new());
+    };
+    let mut files = Vec::new();
+    for entry in builder.build() {
+        let entry = entry?;
+        if entry.file_type().is_some_and(|ft| ft.is_file()) {
+            files.push(entry.into_path());
+        }
+    }
+    files.sort();
+    files.dedup();
+    Ok(files)
+}
+
+/// `bytes` as text, or `None` when they aren't UTF-8 text, like a file saved as UTF-16
+/// or a binary file.
+pub fn card_text(bytes: Vec<u8>) -> Option<String> {
+    String::from_utf8(bytes)
+        .ok()
+        .filter(|text| !text.contains('\0'))
+}
+
+/// Parses the card file at `path`, minus any UTF-8 byte order mark. A file that isn't
+/// UTF-8 text, like one saved as UTF-16, is skipped whole with an issue naming it.
+fn parse_cards_from_md(
+    path: &Path,
+    separator: &str,
+    mode: HashMode,
+) -> Result<(Vec<Card>, Vec<ParseIssue>)> {
+    let text = match card_text(fs::read(path)?) {
+        Some(text) => text,
+        None => {
+            let issue = ParseIssue {
+                file_path: path.to_path_buf(),
+                line: 1,
+                message: String::from(
+                    "not UTF-8 text (saved as UTF-16, or a binary file?); skipped the whole file",
+                ),
+            };
+            return Ok((Vec::new(), vec![issue]));
+        }
+    };
+    let text = text.strip_prefix(UTF8_BOM).unwrap_or(&text);
+    parse_blocks(path, text.as_bytes(), separator, mode)
+}
+
+/// `(old, new)` hashes of the cards under `paths` whose hash changed when card text
+/// started being NFC-normalized before hashing.
+pub fn changed_hashes(scan: &ScanOptions, paths: &[PathBuf]) -> Result<Vec<(String, String)>> {
+    let Some(builder) = markdown_walk_builder(scan, paths)? else {
+        return Ok(Vec::new());
+    };
+    let mut pairs = BTreeSet::new();
+    for entry in builder.build() {
+        let entry = entry?;
+        if !entry.file_type().is_some_and(|ft| ft.is_file()) {
+            continue;
+        }
+        let path = entry.path();
+        let (cards, _) = parse_cards_from_md(path, &scan.separator, HashMode::Current)
+            .with_context(|| format!("Failed to parse {}", path.display()))?;
+        let (legacy, _) = parse_cards_from_md(path, &scan.separator, HashMode::Legacy)
+            .with_context(|| format!("Failed to parse {}", path.display()))?;
+        // Both parses see the same text, so their cards line up one to one.
+        pairs.extend(
+            legacy
+                .into_iter()
+                .zip(cards)
+                .filter(|(old, new)| old.card_hash != new.card_hash)
+                .map(|(old, new)| (old.card_hash, new.card_hash)),
+        );
+    }
+    Ok(pairs.into_iter().collect())
+}
+
+/// Cards of the file at `path`, taken from `cache` when its size and mtime are unchanged.
+/// Files with malformed cards are never cached, so their issues are reported every run.
+fn cards_for_file(
+    path: &Path,
+    separator: &str,
+    cache: Option<&CardCache>,
+    updates: &Mutex<Vec<(PathBuf, Fingerprint, Vec<Card>)>>,
+) -> Result<(Vec<Card>, Vec<ParseIssue>)> {
+    let Some(cache) = cache else {
+        let (cards, issues) = parse_cards_from_md(path, separator, HashMode::Current)?;
+        log_scanned_file(path, &cards, &issues, false);
+        return Ok((cards, issues));
+    };
+    let fingerprint = Fingerprint::of(path);
+    if let Some(fingerprint) = &fingerprint
+        && let Some(cards) = cache.get(path, fingerprint)
+    {
+        log_scanned_file(path, &cards, &[], true);
+        return Ok((cards, Vec::new()));
+    }
+    let (cards, issues) = parse_cards_from_md(path, separator, HashMode::Current)?;
+    log_scanned_file(path, &cards, &issues, false);
+    if let Some(fingerprint) = fingerprint
+        && issues.is_empty()
+    {
+        updates
+            .lock()
+            .unwrap()
+            .push((path.to_path_buf(), fingerprint, cards.clone()));
+    }
+    Ok((cards, issues))
+}
+
+fn log_scanned_file(path: &Path, cards: &[Card], issues: &[ParseIssue], cached: bool) {
+    let source = if cached { ", from the cache" } else { "" };
+    crate::verbose!(
+        "Scanned {}: {} card{}, {} skipped{source}",
+        path.display(),
+        cards.len(),
+        if cards.len() == 1 { "" } else { "s" },
+        issues.len()
+    );
+}
+
+fn run_card_walker(
+    scan: ScanOptions,
+    paths: Vec<PathBuf>,
+    cache: Option<CardCache>,
+    sender: mpsc::UnboundedSender<Vec<Card>>,
+) -> Result<(Option<CardCache>, Vec<ParseIssue>)> {
+    let Some(builder) = markdown_walk_builder(&scan, &paths)? else {
+        return Ok((cache, Vec::new()));
     };
 
     let error_slot = Arc::new(Mutex::new(None));
+    let cache = Arc::new(cache);
+    let updates = Arc::new(Mutex::new(Vec::new()));
+    let issues = Arc::new(Mutex::new(Vec::new()));
 
+    let separator = scan.separator.as_str();
     builder.build_parallel().run(|| {
         let sender = sender.clone();
         let error_slot = Arc::clone(&error_slot);
+        let cache = Arc::clone(&cache);
+        let updates = Arc::clone(&updates);
+        let issues = Arc::clone(&issues);
         Box::new(move |entry| match entry {
             Ok(entry) => {
                 if !entry.file_type().map(|ft| ft.is_file()).unwrap_or(false) {
                     return WalkState::Continue;
                 }
                 let path = entry.path().to_path_buf();
-                match cards_from_md(&path) {
-                    Ok(cards) => {
+                match cards_for_file(&path, separator, cache.as_ref().as_ref(), &updates) {
+                    Ok((cards, file_issues)) => {
+                        issues.lock().unwrap().extend(file_issues);
                         if cards.is_empty() {
                             return WalkState::Continue;
                         }
@@ -351,27 +1107,218 @@ fn run_card_walker(paths: Vec<PathBuf>, sender: mpsc::UnboundedSender<Vec<Card>>
     if let Some(err) = error_slot.lock().unwrap().take() {
         return Err(err);
     }
+    let mut cache = Arc::into_inner(cache).expect("walker threads have finished");
+    if let Some(cache) = &mut cache {
+        for (path, fingerprint, cards) in updates.lock().unwrap().drain(..) {
+            cache.insert(&path, fingerprint, cards);
+        }
+    }
+    let mut issues = std::mem::take(&mut *issues.lock().unwrap());
+    issues.sort_by(|a, b| (&a.file_path, a.line).cmp(&(&b.file_path, b.line)));
+    Ok((cache, issues))
+}
+
+/// Where a card starts: its file and 1-based line.
+pub type CardLocation = (PathBuf, usize);
+
+fn card_location(card: &Card) -> CardLocation {
+    (card.file_path.clone(), card.file_card_range.0 + 1)
+}
+
+/// Keeps the first location of each card hash; a hash seen at another location is
+/// recorded in `duplicates` along with the location it collided with. Files are parsed
+/// in parallel, so the copy kept is chosen by path and position rather than by which
+/// thread finished first.
+fn insert_card(
+    hash_cards: &mut HashMap<String, Card>,
+    duplicates: &mut BTreeMap<String, BTreeSet<CardLocation>>,
+    card: Card,
+) {
+    match hash_cards.entry(card.card_hash.clone()) {
+        Entry::Occupied(mut existing) => {
+            let current = existing.get();
+            if card_location(current) != card_location(&card) {
+                let locations = duplicates.entry(card.card_hash.clone()).or_default();
+                locations.insert(card_location(current));
+                locations.insert(card_location(&card));
+            }
+            if (&card.file_path, card.file_card_range)
+                < (&current.file_path, current.file_card_range)
+            {
+                existing.insert(card);
+            }
+        }
+        Entry::Vacant(slot) => {
+            slot.insert(card);
+        }
+    }
+}
+
+/// Registers every card under `paths`. Malformed cards are skipped with a warning on
+/// stderr, or fail the whole registration when `scan` is [`strict`](ScanOptions::strict).
+pub async fn register_all_cards(
+    db: &DB,
+    scan: &ScanOptions,
+    paths: Vec<PathBuf>,
+) -> Result<HashMap<String, Card>> {
+    let registration = register_cards(db, scan, paths).await?;
+    registration.report_issues(scan.strict)?;
+    Ok(registration.cards)
+}
+
+/// What [`register_cards`] found under the given paths.
+pub struct Registration {
+    pub cards: HashMap<String, Card>,
+    pub issues: Vec<ParseIssue>,
+    /// Hashes of cards whose text appears at more than one location, with every location.
+    /// The card in `cards` is the one at the first location.
+    pub duplicates: BTreeMap<String, BTreeSet<CardLocation>>,
+    /// Local images referenced by cards that don't exist, with the card referencing each.
+    pub missing_images: BTreeSet<(CardLocation, PathBuf)>,
+}
+
+impl Registration {
+    /// Reports malformed cards and missing images like [`register_all_cards`] does,
+    /// failing on malformed cards when `strict`.
+    pub fn report_issues(&self, strict: bool) -> Result<()> {
+        report_missing_images(&self.missing_images);
+        report_parse_issues(&self.issues, strict)
+    }
+}
+
+/// Missing images are only warned about; the card still drills without them.
+fn report_missing_images(missing: &BTreeSet<(CardLocation, PathBuf)>) {
+    if missing.is_empty() {
+        return;
+    }
+    let plural = if missing.len() == 1 { "" } else { "s" };
+    let list: Vec<String> = missing
+        .iter()
+        .map(|((path, line), image)| format!("  {}:{line}: {}", path.display(), image.display()))
+        .collect();
+    eprintln!(
+        "Warning: {} missing image{plural}:\n{}",
+        missing.len(),
+        list.join("\n")
+    );
+}
+
+/// The Markdown fields of a card.
+fn card_texts(card: &Card) -> Vec<&str> {
+    match &card.content {
+        CardContent::Basic { question, answer } | CardContent::TypeIn { question, answer } => {
+            vec![question, answer]
+        }
+        CardContent::Cloze { text, .. } => vec![text],
+    }
+}
+
+fn missing_images<'a>(
+    cards: impl IntoIterator<Item = &'a Card>,
+) -> BTreeSet<(CardLocation, PathBuf)> {
+    let mut missing = BTreeSet::new();
+    for card in cards {
+        for text in card_texts(card) {
+            if !text.contains("![") {
+                continue;
+            }
+            for image in extract_images(text, card.file_path.parent()) {
+                if !image.exists() {
+                    missing.insert((card_location(card), image.path));
+                }
+            }
+        }
+    }
+    missing
+}
+
+fn report_parse_issues(issues: &[ParseIssue], strict: bool) -> Result<()> {
+    if issues.is_empty() {
+        return Ok(());
+    }
+    if strict {
+        return Err(MalformedCards(issues.to_vec()).into());
+    }
+    let plural = if issues.len() == 1 { "" } else { "s" };
+    let list: Vec<String> = issues.iter().map(|issue| format!("  {issue}")).collect();
+    eprintln!(
+        "Skipped {} malformed card{plural}:\n{}",
+        issues.len(),
+        list.join("\n")
+    );
     Ok(())
 }
 
-pub async fn register_all_cards(db: &DB, paths: Vec<PathBuf>) -> Result<HashMap<String, Card>> {
+/// Like [`register_all_cards`], but returns malformed and duplicated cards instead of
+/// reporting them.
+pub async fn register_cards(
+    db: &DB,
+    scan: &ScanOptions,
+    paths: Vec<PathBuf>,
+) -> Result<Registration> {
+    collect_cards(Some(db), scan, paths).await
+}
+
+/// Every card under `paths`, read from the files without touching the database.
+pub async fn read_all_cards(
+    scan: &ScanOptions,
+    paths: Vec<PathBuf>,
+) -> Result<HashMap<String, Card>> {
+    let registration = collect_cards(None, scan, paths).await?;
+    registration.report_issues(scan.strict)?;
+    Ok(registration.cards)
+}
+
+/// Walks `paths` for cards, adding each batch to `db` when there is one.
+async fn collect_cards(
+    db: Option<&DB>,
+    scan: &ScanOptions,
+    paths: Vec<PathBuf>,
+) -> Result<Registration> {
+    let cache_path = if scan.use_cache {
+        Some(CardCache::path()?)
+    } else {
+        None
+    };
+    let cache = cache_path.as_deref().map(CardCache::load_from);
+
     let (tx, mut rx) = mpsc::unbounded_channel::<Vec<Card>>();
-    let walker_handle = tokio::task::spawn_blocking(move || run_card_walker(paths, tx));
+    let scan = scan.clone();
+    let walker_handle =
+        tokio::task::spawn_blocking(move || run_card_walker(scan, paths, cache, tx));
 
     let mut hash_cards = HashMap::new();
+    let mut duplicates = BTreeMap::new();
     while let Some(batch) = rx.recv().await {
         if batch.is_empty() {
             continue;
         }
-        db.add_cards_batch(&batch).await?;
+        if let Some(db) = db {
+            db.add_cards_batch(&batch).await?;
+        }
         for card in batch {
-            hash_cards.insert(card.card_hash.clone(), card);
+            insert_card(&mut hash_cards, &mut duplicates, card);
         }
     }
 
-    walker_handle.await??;
+    let (cache, issues) = walker_handle.await??;
+    if let (Some(mut cache), Some(path)) = (cache, cache_path) {
+        // The cache only saves time, so failing to write it isn't fatal.
+        let _ = cache.save_to(&path);
+    }
 
-    Ok(hash_cards)
+    let missing_images = missing_images(hash_cards.values());
+    crate::verbose!(
+        "Found {} unique card{}",
+        hash_cards.len(),
+        if hash_cards.len() == 1 { "" } else { "s" }
+    );
+    Ok(Registration {
+        cards: hash_cards,
+        issues,
+        duplicates,
+        missing_images,
+    })
 }
 
 pub async fn resolve_missing_clozes(cards: &mut [Card]) -> Result<()> {
@@ -381,6 +1328,7 @@ pub async fn resolve_missing_clozes(cards: &mut [Card]) -> Result<()> {
             if let CardContent::Cloze {
                 text,
                 cloze_range: None,
+                ..
             } = &card.content
             {
                 Some((card.card_hash.clone(), text.clone()))
@@ -522,16 +1470,22 @@ pub fn strip_controls_and_escapes(input: &str) -> String {
 
 #[cfg(test)]
 mod tests {
-    use super::{cards_from_md, content_to_card, parse_card_lines};
+    use super::{
+        MalformedCards, PathScope, ScanOptions, cards_from_md, cards_from_reader, changed_hashes,
+        content_to_card, content_to_cards, day_start_in, describe_card_extensions,
+        find_numbered_clozes, is_markdown, parse_card_lines, parse_cards_from_reader,
+        parse_frontmatter_tags, register_cards, report_parse_issues, study_day_in,
+        write_file_atomically,
+    };
     use crate::card::CardContent;
     use crate::crud::DB;
-    use crate::utils::{get_hash, register_all_cards};
+    use crate::utils::{HashMode, get_hash, legacy_hash, parse_cards_from_md, register_all_cards};
     use proptest::prelude::*;
-    use std::path::PathBuf;
+    use std::path::{Path, PathBuf};
     proptest! {
         #[test]
         fn test_card_parser( content in "\\PC*") {
-            parse_card_lines(&content);
+            parse_card_lines(&content, "---");
             get_hash(&content);
         }
     }
@@ -550,10 +1504,65 @@ mod tests {
         assert_eq!(ha, hc);
     }
 
+    #[test]
+    fn whitespace_and_unicode_forms_do_not_change_hashes() {
+        let lf = get_hash("Q: Où est la café?\nA: Paris\n");
+        assert_eq!(lf, get_hash("Q: Où est la café?  \r\nA: Paris\t\r\n"));
+        // Decomposed `e` + combining acute accent, as some editors save it.
+        assert_eq!(lf, get_hash("Q: Ou\u{300} est la cafe\u{301}?\nA: Paris\n"));
+        assert_ne!(legacy_hash("cafe\u{301}"), get_hash("cafe\u{301}"));
+        assert_eq!(legacy_hash("café"), get_hash("café"));
+    }
+
+    #[test]
+    fn changed_hashes_pairs_old_and_new_hashes() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("deck.md");
+        std::fs::write(
+            &path,
+            "Q: cafe\u{301}?\nA: coffee\n\n---\n\nQ: tea?\nA: thé\n",
+        )
+        .unwrap();
+        let (legacy, _) = parse_cards_from_md(&path, "---", HashMode::Legacy).unwrap();
+        let current = cards_from_md(&path, "---").unwrap();
+        assert_eq!(legacy[1].card_hash, current[1].card_hash);
+        assert_eq!(
+            changed_hashes(&ScanOptions::default(), &[dir.path().to_path_buf()]).unwrap(),
+            [(legacy[0].card_hash.clone(), current[0].card_hash.clone())]
+        );
+    }
+
+    #[test]
+    fn math_delimiters_and_operators_do_not_change_hashes() {
+        let card_path = PathBuf::from("test.md");
+        let hash = |content: &str| {
+            content_to_card(&card_path, content, "---", 1, 1)
+                .unwrap()
+                .card_hash
+        };
+
+        let euler = hash("Q: What is $e^{i\\pi}$?\nA: $-1$\n");
+        assert_eq!(
+            euler,
+            "dd63f8f10854cd882a31d54baf614330927523c3d4acff36378fe48e7e0ae078"
+        );
+        assert_eq!(euler, hash("Q: What is e^{i pi}?\nA: -1\n"));
+        assert_eq!(
+            hash("Q: Derivative of $$x^2$$?\nA: $2x$\n"),
+            "ad9c2db92b1c6bfeb2cad42fb3a8be7ba81011b36ad8967d567adb6450a587f0"
+        );
+        assert_eq!(
+            hash("C: The area of a circle is [$\\pi r^2$].\n"),
+            "7770d9c5aaf935f05b37bffd26aa955e2c71f8af0a15ea9547237927a36acfb7"
+        );
+        // Only letters, digits, `+` and `-` count, so these collide.
+        assert_eq!(get_hash("$x^2$"), get_hash("$x_2$"));
+    }
+
     #[test]
     fn test_card_parsing() {
         let contents = "C:\nRegion: [`us-east-2`]\n\nLocation: [Ohio]\n\n---\n\n";
-        let (question, _, cloze) = parse_card_lines(contents);
+        let (question, _, cloze) = parse_card_lines(contents, "---");
         assert!(question.is_none());
         assert_eq!("Region: [`us-east-2`]\n\nLocation: [Ohio]", cloze.unwrap());
     }
@@ -562,14 +1571,14 @@ mod tests {
     fn basic_qa() {
         let card_path = PathBuf::from("test.md");
 
-        let card = content_to_card(&card_path, "", 1, 1);
+        let card = content_to_card(&card_path, "", "---", 1, 1);
         assert!(card.is_err());
 
-        let card = content_to_card(&card_path, "what am i doing here", 1, 1);
+        let card = content_to_card(&card_path, "what am i doing here", "---", 1, 1);
         assert!(card.is_err());
 
         let content = "Q: what?\nA: yes\n\n";
-        let card = content_to_card(&card_path, content, 1, 1).unwrap();
+        let card = content_to_card(&card_path, content, "---", 1, 1).unwrap();
         assert_eq!(
             card.card_hash,
             "da7c87d9ced65c05181a0cd83c6aa84966b20e6e89f2bff9d9a34927a4c01891"
@@ -582,17 +1591,116 @@ mod tests {
         }
 
         let content = "Q: what?\nA: \n\n";
-        let card = content_to_card(&card_path, content, 1, 1);
+        let card = content_to_card(&card_path, content, "---", 1, 1);
         assert!(card.is_err());
     }
 
+    #[test]
+    fn type_in_cards_parse_with_their_own_hash() {
+        let card_path = PathBuf::from("test.md");
+        let cards = content_to_cards(&card_path, "T: der Hund\nA: the dog\n", "---", 1, 1).unwrap();
+        assert_eq!(cards.len(), 1);
+        let CardContent::TypeIn { question, answer } = &cards[0].content else {
+            panic!("Expected CardContent::TypeIn");
+        };
+        assert_eq!(question, "der Hund");
+        assert_eq!(answer, "the dog");
+
+        let basic = content_to_card(&card_path, "Q: der Hund\nA: the dog\n", "---", 1, 1).unwrap();
+        assert_ne!(basic.card_hash, cards[0].card_hash);
+
+        let deck = cards_from_reader(&card_path, "T: one\nA: 1\nT: two\nA: 2\n".as_bytes(), "---")
+            .unwrap();
+        assert_eq!(deck.len(), 2);
+    }
+
+    #[test]
+    fn reversed_basic_card_produces_both_directions() {
+        let card_path = PathBuf::from("test.md");
+
+        let cards = content_to_cards(&card_path, "Q: der Hund\nA: the dog\n", "---", 1, 1).unwrap();
+        assert_eq!(cards.len(), 1);
+
+        let cards = content_to_cards(&card_path, "R: der Hund\nA: the dog\n", "---", 1, 1).unwrap();
+        assert_eq!(cards.len(), 2);
+        assert_ne!(cards[0].card_hash, cards[1].card_hash);
+        assert_eq!(cards[0].origin, cards[1].origin);
+        let CardContent::Basic { question, answer } = &cards[1].content else {
+            panic!("Expected CardContent::Basic");
+        };
+        assert_eq!(question, "the dog");
+        assert_eq!(answer, "der Hund");
+    }
+
+    #[test]
+    fn numbered_clozes_produce_one_card_per_cluster() {
+        let card_path = PathBuf::from("test.md");
+        let content = "C: {{c1::Paris}} is in {{c2::France}} and {{c1::Paris}} is big";
+
+        let cards = content_to_cards(&card_path, content, "---", 1, 1).unwrap();
+        assert_eq!(cards.len(), 2);
+        assert_ne!(cards[0].card_hash, cards[1].card_hash);
+        assert_eq!(cards[0].origin, cards[1].origin);
+        let indices: Vec<_> = cards
+            .iter()
+            .map(|card| match &card.content {
+                CardContent::Cloze {
+                    cloze_index,
+                    cloze_range,
+                    ..
+                } => {
+                    assert!(cloze_range.is_some());
+                    *cloze_index
+                }
+                _ => panic!("Expected CardContent::Cloze"),
+            })
+            .collect();
+        assert_eq!(indices, vec![Some(1), Some(2)]);
+
+        let again = content_to_cards(&card_path, content, "---", 5, 9).unwrap();
+        assert_eq!(cards[0].card_hash, again[0].card_hash);
+    }
+
+    #[test]
+    fn find_numbered_clozes_skips_malformed_markers() {
+        let clozes = find_numbered_clozes("{{c::x}} {{c1:y}} {{c3::ok}} {{c2::open");
+        assert_eq!(clozes.len(), 1);
+        assert_eq!(clozes[0].index, 3);
+        assert_eq!(clozes[0].answer, "ok");
+    }
+
+    #[test]
+    fn numbered_cloze_hints_are_split_and_ignored_by_hash() {
+        let clozes = find_numbered_clozes(
+            "{{c1::Paris::capital city}} {{c2::std\\::vec::module}} {{c3::a\\::b}}",
+        );
+        assert_eq!(clozes[0].answer, "Paris");
+        assert_eq!(clozes[0].hint.as_deref(), Some("capital city"));
+        assert_eq!(clozes[1].answer, "std::vec");
+        assert_eq!(clozes[1].hint.as_deref(), Some("module"));
+        assert_eq!(clozes[2].answer, "a::b");
+        assert_eq!(clozes[2].hint, None);
+
+        let card_path = PathBuf::from("test.md");
+        let plain = content_to_card(&card_path, "C: {{c1::Paris}} is nice", "---", 1, 1).unwrap();
+        let hinted =
+            content_to_card(&card_path, "C: {{c1::Paris::city}} is nice", "---", 1, 1).unwrap();
+        let other =
+            content_to_card(&card_path, "C: {{c1::Lyon::city}} is nice", "---", 1, 1).unwrap();
+        assert_eq!(plain.card_hash, hinted.card_hash);
+        assert_ne!(hinted.card_hash, other.card_hash);
+    }
+
     #[test]
     fn basic_cloze() {
         let card_path = PathBuf::from("test.md");
 
         let content = "C: ping? [pong]";
-        let card = content_to_card(&card_path, content, 1, 1);
-        if let CardContent::Cloze { text, cloze_range } = &card.expect("should be basic").content {
+        let card = content_to_card(&card_path, content, "---", 1, 1);
+        if let CardContent::Cloze {
+            text, cloze_range, ..
+        } = &card.expect("should be basic").content
+        {
             assert_eq!(text, "ping? [pong]");
             let range = cloze_range.as_ref().expect("range to exist");
             assert_eq!(range.start, 6_usize);
@@ -602,10 +1710,104 @@ mod tests {
         }
     }
 
+    #[test]
+    fn cards_from_reader_matches_file_parsing() {
+        let card_path = PathBuf::from("test_data/test.md");
+        let contents = std::fs::read_to_string(&card_path).unwrap();
+        let from_file = cards_from_md(&card_path, "---").unwrap();
+        let from_reader = cards_from_reader(&card_path, contents.as_bytes(), "---").unwrap();
+
+        let hashes = |cards: &[crate::card::Card]| {
+            cards
+                .iter()
+                .map(|card| card.card_hash.clone())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(hashes(&from_file), hashes(&from_reader));
+    }
+
+    #[test]
+    fn frontmatter_tags_apply_to_every_card() {
+        let path = PathBuf::from("deck.md");
+        let contents = "---\ntitle: Verbs\ntags: [spanish, \"verbs\"]\n---\nQ: comer?\nA: to eat\n---\nR: beber\nA: to drink\n";
+        let cards = cards_from_reader(&path, contents.as_bytes(), "---").unwrap();
+        assert_eq!(cards.len(), 3);
+        for card in &cards {
+            assert_eq!(card.tags, ["spanish", "verbs"]);
+        }
+        assert_eq!(cards[0].file_card_range.0, 4);
+
+        let untagged =
+            cards_from_reader(&path, "Q: comer?\nA: to eat\n".as_bytes(), "---").unwrap();
+        assert_eq!(untagged[0].card_hash, cards[0].card_hash);
+    }
+
+    #[test]
+    fn parses_frontmatter_tag_styles() {
+        assert_eq!(parse_frontmatter_tags("tags: a, b\n"), ["a", "b"]);
+        assert_eq!(
+            parse_frontmatter_tags("tags:\n  - a\n  - 'b'\n  - a\nauthor: me\n"),
+            ["a", "b"]
+        );
+        assert!(parse_frontmatter_tags("title: none\n").is_empty());
+    }
+
+    #[test]
+    fn study_days_roll_over_at_the_cutoff_hour() {
+        use chrono::{FixedOffset, NaiveDate, TimeZone, Utc};
+
+        let tz = FixedOffset::east_opt(2 * 3600).unwrap();
+        let day = NaiveDate::from_ymd_opt(2024, 3, 10).unwrap();
+        // 01:30 local on the 11th
+        let late_night = Utc.with_ymd_and_hms(2024, 3, 10, 23, 30, 0).unwrap();
+        assert_eq!(study_day_in(late_night, &tz, 0), day.succ_opt().unwrap());
+        assert_eq!(study_day_in(late_night, &tz, 4), day);
+
+        assert_eq!(
+            day_start_in(day, &tz, 4),
+            Utc.with_ymd_and_hms(2024, 3, 10, 2, 0, 0).unwrap()
+        );
+        assert_eq!(
+            day_start_in(day, &tz, 0),
+            Utc.with_ymd_and_hms(2024, 3, 9, 22, 0, 0).unwrap()
+        );
+    }
+
+    #[test]
+    fn separators_split_cards_and_may_trail() {
+        let path = PathBuf::from("deck.md");
+        let contents = "Q: one?\nA: 1\n---\nQ: two?\nA: 2\n\n---\n\nC: [three]\n---\n\n";
+        let cards = cards_from_reader(&path, contents.as_bytes(), "---").unwrap();
+        assert_eq!(cards.len(), 3);
+        let hashes: std::collections::HashSet<_> =
+            cards.iter().map(|card| &card.card_hash).collect();
+        assert_eq!(hashes.len(), 3);
+        assert_eq!(cards[1].file_card_range, (3, 6));
+
+        let (custom, issues) = parse_cards_from_reader(
+            &path,
+            "Q: one?\nA: 1\n%%\nQ: two?\nA: 2\n---\n%%\n".as_bytes(),
+            "%%",
+        )
+        .unwrap();
+        assert!(issues.is_empty());
+        assert_eq!(custom.len(), 2);
+        assert_eq!(custom[1].file_card_range, (3, 6));
+    }
+
+    #[test]
+    fn leading_separator_is_not_frontmatter() {
+        let path = PathBuf::from("deck.md");
+        let contents = "---\nQ: one?\nA: 1\n---\nQ: two?\nA: 2\n";
+        let cards = cards_from_reader(&path, contents.as_bytes(), "---").unwrap();
+        assert_eq!(cards.len(), 2);
+        assert!(cards[0].tags.is_empty());
+    }
+
     #[test]
     fn test_file_capture() {
         let card_path = PathBuf::from("test_data/test.md");
-        let cards = cards_from_md(&card_path).expect("should be ok");
+        let cards = cards_from_md(&card_path, "---").expect("should be ok");
 
         assert_eq!(cards.len(), 9);
     }
@@ -616,7 +1818,9 @@ mod tests {
             .await
             .expect("Failed to connect to or initialize database");
         let dir_path = PathBuf::from("test_data");
-        let cards = register_all_cards(&db, vec![dir_path]).await.unwrap();
+        let cards = register_all_cards(&db, &ScanOptions::default(), vec![dir_path])
+            .await
+            .unwrap();
         assert_eq!(cards.len(), 11);
         for card in cards.values() {
             assert!(card.file_path.to_string_lossy().contains("test_data"));
@@ -624,16 +1828,176 @@ mod tests {
 
         let dir_path = PathBuf::from("test_data/");
         let file_path = PathBuf::from("test_data/test.md");
-        let cards = register_all_cards(&db, vec![dir_path, file_path])
+        let cards = register_all_cards(&db, &ScanOptions::default(), vec![dir_path, file_path])
             .await
             .unwrap();
         assert_eq!(cards.len(), 11);
     }
 
+    #[tokio::test]
+    async fn strips_a_bom_and_skips_files_that_are_not_utf8() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("bom.md"), "\u{feff}Q: first?\nA: yes\n").unwrap();
+        std::fs::write(dir.path().join("plain.md"), "Q: first?\nA: yes\n").unwrap();
+        let utf16: Vec<u8> = [0xFF, 0xFE]
+            .into_iter()
+            .chain(
+                "Q: wide?\nA: yes\n"
+                    .encode_utf16()
+                    .flat_map(u16::to_le_bytes),
+            )
+            .collect();
+        std::fs::write(dir.path().join("utf16.md"), utf16).unwrap();
+        std::fs::write(dir.path().join("binary.md"), [0x00, 0x9F, 0x92, 0x96]).unwrap();
+
+        let db = DB::new_in_memory().await.unwrap();
+        let registration =
+            register_cards(&db, &ScanOptions::default(), vec![dir.path().to_path_buf()])
+                .await
+                .unwrap();
+        let bom = cards_from_md(&dir.path().join("bom.md"), "---").unwrap();
+        let plain = cards_from_md(&dir.path().join("plain.md"), "---").unwrap();
+        assert_eq!(bom[0].card_hash, plain[0].card_hash);
+        match &bom[0].content {
+            CardContent::Basic { question, .. } => assert_eq!(question, "first?"),
+            other => panic!("expected a basic card, got {other:?}"),
+        }
+        assert_eq!(registration.cards.len(), 1);
+        let skipped: Vec<_> = registration
+            .issues
+            .iter()
+            .map(|issue| issue.file_path.file_name().unwrap().to_str().unwrap())
+            .collect();
+        assert_eq!(skipped, ["binary.md", "utf16.md"]);
+        assert!(registration.issues[0].message.contains("not UTF-8"));
+    }
+
+    #[tokio::test]
+    async fn scans_default_card_extensions_only() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("a.md"), "Q: md?\nA: 1\n").unwrap();
+        std::fs::write(dir.path().join("b.markdown"), "Q: markdown?\nA: 2\n").unwrap();
+        std::fs::write(dir.path().join("c.txt"), "Q: txt?\nA: 3\n").unwrap();
+
+        let db = DB::new_in_memory().await.unwrap();
+        let cards =
+            register_all_cards(&db, &ScanOptions::default(), vec![dir.path().to_path_buf()])
+                .await
+                .unwrap();
+        assert_eq!(cards.len(), 2);
+        let extensions = ScanOptions::default().extensions;
+        assert!(is_markdown(Path::new("deck.MARKDOWN"), &extensions));
+        assert!(!is_markdown(Path::new("notes.txt"), &extensions));
+        assert_eq!(describe_card_extensions(&extensions), ".md, .markdown");
+    }
+
+    #[tokio::test]
+    async fn scans_configured_card_extensions() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("a.md"), "Q: md?\nA: 1\n").unwrap();
+        std::fs::write(dir.path().join("b.txt"), "Q: txt?\nA: 2\n").unwrap();
+
+        let db = DB::new_in_memory().await.unwrap();
+        let scan = ScanOptions {
+            extensions: vec!["txt".to_string()],
+            ..ScanOptions::default()
+        };
+        let cards = register_all_cards(&db, &scan, vec![dir.path().to_path_buf()])
+            .await
+            .unwrap();
+        assert_eq!(cards.len(), 1);
+        assert!(cards.values().all(|card| card.file_path.ends_with("b.txt")));
+    }
+
+    #[tokio::test]
+    async fn walk_descends_into_subdirectories_but_skips_hidden_and_ignored() {
+        let dir = tempfile::tempdir().unwrap();
+        let write = |relative: &str, contents: &str| {
+            let path = dir.path().join(relative);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(path, contents).unwrap();
+        };
+        write("top.md", "Q: top?\nA: 1\n");
+        write("topic/deep/nested.md", "Q: nested?\nA: 2\n");
+        write(".git/stray.md", "Q: git?\nA: 3\n");
+        write(".drafts/hidden.md", "Q: hidden?\nA: 4\n");
+        write("templates/basic.md", "Q: template?\nA: 5\n");
+        write("topic/archive/old.md", "Q: old?\nA: 6\n");
+        write(".repeatignore", "templates/\narchive\n");
+
+        let db = DB::new_in_memory().await.unwrap();
+        let cards =
+            register_all_cards(&db, &ScanOptions::default(), vec![dir.path().to_path_buf()])
+                .await
+                .unwrap();
+        let mut names: Vec<String> = cards
+            .values()
+            .map(|card| {
+                card.file_path
+                    .file_name()
+                    .unwrap()
+                    .to_string_lossy()
+                    .into_owned()
+            })
+            .collect();
+        names.sort();
+        assert_eq!(names, ["nested.md", "top.md"]);
+    }
+
+    #[test]
+    fn scope_globs_cover_directories_and_their_contents() {
+        let globs = |globs: &[&str]| {
+            globs
+                .iter()
+                .map(|glob| glob.to_string())
+                .collect::<Vec<_>>()
+        };
+        let scope = PathScope::new(&globs(&["rust/**", "*.md"]), &globs(&["archive"])).unwrap();
+        assert!(scope.allows(Path::new("rust/ownership.md"), false));
+        assert!(scope.allows(Path::new("notes/other.md"), false));
+        assert!(!scope.allows(Path::new("rust/archive/old.md"), false));
+        assert!(!scope.allows(Path::new("archive"), true));
+
+        let scope = PathScope::new(&globs(&["rust"]), &[]).unwrap();
+        assert!(scope.allows(Path::new("rust/deep/ownership.md"), false));
+        assert!(!scope.allows(Path::new("go/channels.md"), false));
+        assert!(scope.allows(Path::new("go"), true));
+        assert!(PathScope::default().allows(Path::new("anything.md"), false));
+    }
+
+    #[tokio::test]
+    async fn registration_only_scans_files_in_scope() {
+        let db = DB::new_in_memory().await.unwrap();
+        let scan = ScanOptions {
+            scope: PathScope::new(&[], &["test_data/test.md".to_string()]).unwrap(),
+            ..ScanOptions::default()
+        };
+        let cards = register_all_cards(&db, &scan, vec![PathBuf::from("test_data")])
+            .await
+            .unwrap();
+        assert_eq!(cards.len(), 2);
+        assert!(
+            cards
+                .values()
+                .all(|card| card.file_path != Path::new("test_data/test.md"))
+        );
+    }
+
+    #[test]
+    fn atomic_write_replaces_the_file_without_leftovers() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("deck.md");
+        std::fs::write(&path, "Q: old?\nA: 1\n").unwrap();
+
+        write_file_atomically(&path, b"Q: new?\nA: 2\n").unwrap();
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "Q: new?\nA: 2\n");
+        assert_eq!(std::fs::read_dir(dir.path()).unwrap().count(), 1);
+    }
+
     #[test]
     fn cards_from_md_returns_error_for_nonexistent_file() {
         let path = PathBuf::from("nonexistent_file.md");
-        let result = cards_from_md(&path);
+        let result = cards_from_md(&path, "---");
         assert!(result.is_err());
     }
 
@@ -643,9 +2007,12 @@ mod tests {
 
         // Cloze without brackets still produces a card, but lacks a range
         let content = "C: this has no cloze markers";
-        let card = content_to_card(&card_path, content, 0, 1)
+        let card = content_to_card(&card_path, content, "---", 0, 1)
             .expect("invalid cloze text should still be accepted");
-        if let CardContent::Cloze { text, cloze_range } = card.content {
+        if let CardContent::Cloze {
+            text, cloze_range, ..
+        } = card.content
+        {
             assert_eq!(text, "this has no cloze markers");
             assert!(cloze_range.is_none());
         } else {
@@ -654,9 +2021,9 @@ mod tests {
 
         // Cloze with empty brackets should error out
         let content = "C: this has empty []";
-        let temp = content_to_card(&card_path, content, 0, 1);
+        let temp = content_to_card(&card_path, content, "---", 0, 1);
         dbg!(&temp);
-        assert!(content_to_card(&card_path, content, 0, 1).is_err());
+        assert!(content_to_card(&card_path, content, "---", 0, 1).is_err());
     }
 
     #[test]
@@ -665,7 +2032,7 @@ mod tests {
 
         // Question without answer
         let content = "Q: What is this?\n";
-        let result = content_to_card(&card_path, content, 0, 1);
+        let result = content_to_card(&card_path, content, "---", 0, 1);
         assert!(result.is_err());
         assert!(
             result
@@ -676,14 +2043,14 @@ mod tests {
 
         // Answer without question
         let content = "A: This is an answer\n";
-        let result = content_to_card(&card_path, content, 0, 1);
+        let result = content_to_card(&card_path, content, "---", 0, 1);
         assert!(result.is_err());
     }
 
     #[test]
     fn content_to_card_returns_error_for_empty_content() {
         let card_path = PathBuf::from("test.md");
-        let result = content_to_card(&card_path, "", 0, 1);
+        let result = content_to_card(&card_path, "", "---", 0, 1);
         assert!(result.is_err());
     }
 
@@ -691,38 +2058,148 @@ mod tests {
     fn content_to_card_returns_error_for_whitespace_only() {
         let card_path = PathBuf::from("test.md");
         let content = "   \n  \n  ";
-        let result = content_to_card(&card_path, content, 0, 1);
+        let result = content_to_card(&card_path, content, "---", 0, 1);
         assert!(result.is_err());
     }
 
     #[tokio::test]
-    async fn register_all_cards_returns_error_for_invalid_card_file() {
-        use std::fs;
-        use std::io::Write;
+    async fn duplicate_cards_resolve_to_the_same_file_every_run() {
+        let dir = tempfile::tempdir().unwrap();
+        for name in ["b.md", "a.md", "c.md"] {
+            std::fs::write(dir.path().join(name), "Q: shared?\nA: yes\n").unwrap();
+        }
+        let db = DB::new_in_memory().await.unwrap();
+
+        for _ in 0..5 {
+            let cards =
+                register_all_cards(&db, &ScanOptions::default(), vec![dir.path().to_path_buf()])
+                    .await
+                    .unwrap();
+            assert_eq!(cards.len(), 1);
+            let card = cards.values().next().unwrap();
+            assert_eq!(card.file_path, dir.path().join("a.md"));
+        }
+    }
 
+    #[tokio::test]
+    async fn register_cards_skips_and_reports_malformed_cards() {
         let db = DB::new_in_memory()
             .await
             .expect("Failed to connect to or initialize database");
+        let dir = tempfile::tempdir().unwrap();
+        let deck = dir.path().join("malformed.md");
+        std::fs::write(
+            &deck,
+            "Q: This is a question\nC: This is valid [cloze]\n\nC: {{c1::Paris is the capital\n",
+        )
+        .unwrap();
+
+        let registration =
+            register_cards(&db, &ScanOptions::default(), vec![dir.path().to_path_buf()])
+                .await
+                .unwrap();
+        let issues = registration.issues;
+        assert_eq!(registration.cards.len(), 1);
+        let lines: Vec<usize> = issues.iter().map(|issue| issue.line).collect();
+        assert_eq!(lines, [1, 4]);
+        assert!(issues[0].message.contains("Unable to parse anything"));
+        assert!(issues[1].message.contains("Malformed cloze"));
+        assert!(
+            issues[1]
+                .to_string()
+                .starts_with(&format!("{}:4: ", deck.display()))
+        );
+
+        assert!(report_parse_issues(&issues, false).is_ok());
+        let err = report_parse_issues(&issues, true).unwrap_err();
+        assert!(err.to_string().contains("Found 2 malformed cards"));
+        assert!(err.is::<MalformedCards>());
+        assert!(report_parse_issues(&[], true).is_ok());
 
-        // Create a temporary directory with a malformed markdown file
-        let temp_dir = std::env::temp_dir().join("repeater_test_malformed");
-        fs::create_dir_all(&temp_dir).unwrap();
-        let test_file = temp_dir.join("malformed.md");
+        let strict = ScanOptions {
+            strict: true,
+            ..ScanOptions::default()
+        };
+        let paths = vec![dir.path().to_path_buf()];
+        let err = register_all_cards(&db, &strict, paths.clone())
+            .await
+            .unwrap_err();
+        assert!(err.is::<MalformedCards>());
+        let lenient = register_all_cards(&db, &ScanOptions::default(), paths).await;
+        assert_eq!(lenient.unwrap().len(), 1);
+    }
 
-        // Write malformed card content
-        let mut file = fs::File::create(&test_file).unwrap();
-        writeln!(file, "Q: This is a question").unwrap();
-        writeln!(file, "C: This is invalid [cloze").unwrap(); // Invalid cloze
+    #[tokio::test]
+    async fn register_cards_reports_cards_found_in_several_places() {
+        let db = DB::new_in_memory().await.unwrap();
+        let dir = tempfile::tempdir().unwrap();
+        let a = dir.path().join("a.md");
+        let b = dir.path().join("b.md");
+        std::fs::write(&a, "Q: shared?\nA: yes\n\nQ: only a?\nA: a\n").unwrap();
+        std::fs::write(&b, "Q: only b?\nA: b\n\nQ: shared?\nA: yes\n").unwrap();
+
+        let registration = register_cards(
+            &db,
+            &ScanOptions::default(),
+            vec![dir.path().to_path_buf(), a.clone()],
+        )
+        .await
+        .unwrap();
+        assert_eq!(registration.cards.len(), 3);
+        assert_eq!(registration.duplicates.len(), 1);
+        let (shared, locations) = registration.duplicates.iter().next().unwrap();
+        let locations: Vec<_> = locations.iter().cloned().collect();
+        assert_eq!(locations, [(a.clone(), 1), (b, 4)]);
+        assert_eq!(registration.cards[shared].file_path, a);
+    }
 
-        let result = register_all_cards(&db, vec![temp_dir.clone()]).await;
+    #[tokio::test]
+    async fn register_cards_finds_missing_images_next_to_the_deck() {
+        let db = DB::new_in_memory().await.unwrap();
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::create_dir(dir.path().join("img")).unwrap();
+        std::fs::write(dir.path().join("img/heart.png"), b"png").unwrap();
+        let deck = dir.path().join("anatomy.md");
+        let contents = "Q: ![heart](img/heart.png) What is this?\nA: heart\n\nQ: And this?\nA: ![](img/lung.png)\n";
+        std::fs::write(&deck, contents).unwrap();
+
+        let registration =
+            register_cards(&db, &ScanOptions::default(), vec![dir.path().to_path_buf()])
+                .await
+                .unwrap();
+        let missing: Vec<_> = registration.missing_images.into_iter().collect();
+        assert_eq!(missing, [((deck, 4), dir.path().join("img/lung.png"))]);
+
+        // The image path is part of the card text, so pointing at another image is a new card.
+        assert_ne!(
+            get_hash("Q: ![heart](img/heart.png)\nA: heart"),
+            get_hash("Q: ![heart](img/liver.png)\nA: heart")
+        );
+    }
 
-        // Clean up
-        fs::remove_file(&test_file).unwrap();
-        fs::remove_dir(&temp_dir).unwrap();
+    #[test]
+    fn unterminated_numbered_cloze_is_rejected() {
+        let card_path = PathBuf::from("test.md");
+        let err = content_to_cards(&card_path, "C: {{c1::Paris}} and {{c2::France", "---", 0, 1)
+            .unwrap_err();
+        assert!(err.to_string().contains("{{c2::France"));
+        assert!(
+            content_to_cards(
+                &card_path,
+                "C: {{c1::Paris}} and {{c2::France}}",
+                "---",
+                0,
+                1
+            )
+            .is_ok()
+        );
 
-        // Should return error due to malformed card
-        assert!(result.is_err());
-        let err = result.unwrap_err();
-        assert!(err.to_string().contains("Failed to parse"));
+        let err = cards_from_reader(
+            &card_path,
+            "Q: ok?\nA: yes\n\nC: {{c1::open\n".as_bytes(),
+            "---",
+        )
+        .unwrap_err();
+        assert!(err.to_string().starts_with("test.md:4: Malformed cloze"));
     }
 }
@@ -0,0 +1,67 @@
+use anyhow::{Result, bail};
+
+use crate::crud::DB;
+
+pub async fn run(db: &DB, integrity_check: bool) -> Result<()> {
+    if integrity_check {
+        let problems = db.integrity_check().await?;
+        if !problems.is_empty() {
+            for problem in &problems {
+                eprintln!("  {problem}");
+            }
+            bail!(
+                "Integrity check found {} problem{}; not vacuuming. Restore a backup or re-create the database from an export.",
+                problems.len(),
+                if problems.len() == 1 { "" } else { "s" }
+            );
+        }
+        println!("Integrity check passed.");
+    }
+
+    let path = db.file_path().await?;
+    let size = || {
+        path.as_ref()
+            .and_then(|path| std::fs::metadata(path).ok())
+            .map(|m| m.len())
+    };
+    let before = size();
+    db.vacuum().await?;
+    let after = size();
+
+    match (before, after) {
+        (Some(before), Some(after)) => println!(
+            "Vacuumed database: {} -> {}",
+            format_size(before),
+            format_size(after)
+        ),
+        _ => println!("Vacuumed database."),
+    }
+    Ok(())
+}
+
+pub fn format_size(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["B", "KiB", "MiB", "GiB"];
+    let mut size = bytes as f64;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{bytes} B")
+    } else {
+        format!("{size:.1} {}", UNITS[unit])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::format_size;
+
+    #[test]
+    fn formats_sizes_with_binary_units() {
+        assert_eq!(format_size(512), "512 B");
+        assert_eq!(format_size(1536), "1.5 KiB");
+        assert_eq!(format_size(3 * 1024 * 1024), "3.0 MiB");
+    }
+}
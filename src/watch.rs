@@ -0,0 +1,125 @@
+use std::collections::HashSet;
+use std::path::PathBuf;
+use std::time::Duration;
+
+use anyhow::{Context, Result};
+use notify::{Event, EventKind, RecursiveMode, Watcher};
+use tokio::sync::mpsc;
+
+use crate::crud::DB;
+use crate::utils::{ScanOptions, is_markdown, register_cards};
+
+/// Quiet time after the last change before re-registering, so an editor's burst of
+/// writes for one save triggers a single scan.
+const DEBOUNCE: Duration = Duration::from_millis(300);
+
+/// Re-registers the cards under `paths` whenever a card file changes, until interrupted.
+/// Unchanged files come from the card cache, so each scan only re-reads what was edited.
+pub async fn run(db: &DB, scan: &ScanOptions, paths: Vec<PathBuf>) -> Result<()> {
+    let mut known = registered_hashes(db, scan, &paths).await?;
+    let (tx, mut rx) = mpsc::unbounded_channel();
+    let extensions = scan.extensions.clone();
+    let mut watcher = notify::recommended_watcher(move |event: notify::Result<Event>| {
+        if let Ok(event) = event
+            && touches_cards(&event, &extensions)
+        {
+            let _ = tx.send(());
+        }
+    })
+    .context("Failed to start the file watcher")?;
+    for path in &paths {
+        let mode = if path.is_dir() {
+            RecursiveMode::Recursive
+        } else {
+            RecursiveMode::NonRecursive
+        };
+        watcher
+            .watch(path, mode)
+            .with_context(|| format!("Failed to watch {}", path.display()))?;
+    }
+    let plural = if known.len() == 1 { "" } else { "s" };
+    println!(
+        "Watching {} card{plural} for changes. Press Ctrl+C to stop.",
+        known.len()
+    );
+
+    while rx.recv().await.is_some() {
+        while let Ok(Some(())) = tokio::time::timeout(DEBOUNCE, rx.recv()).await {}
+        let current = match registered_hashes(db, scan, &paths).await {
+            Ok(current) => current,
+            Err(err) => {
+                eprintln!("Scan failed: {err:#}");
+                continue;
+            }
+        };
+        let (added, removed) = card_changes(&known, &current);
+        let time = chrono::Local::now().format("%H:%M:%S");
+        if added > 0 || removed > 0 {
+            println!(
+                "[{time}] {added} added, {removed} removed ({} cards)",
+                current.len()
+            );
+        }
+        known = current;
+    }
+    Ok(())
+}
+
+async fn registered_hashes(
+    db: &DB,
+    scan: &ScanOptions,
+    paths: &[PathBuf],
+) -> Result<HashSet<String>> {
+    let registration = register_cards(db, scan, paths.to_vec()).await?;
+    registration.report_issues(scan.strict)?;
+    Ok(registration.cards.into_keys().collect())
+}
+
+/// Whether `event` changed a card file, as opposed to just reading one or touching
+/// something else in the tree.
+fn touches_cards(event: &Event, extensions: &[String]) -> bool {
+    !matches!(event.kind, EventKind::Access(_))
+        && event.paths.iter().any(|path| is_markdown(path, extensions))
+}
+
+/// How many cards appeared in and disappeared from `after` compared to `before`.
+fn card_changes(before: &HashSet<String>, after: &HashSet<String>) -> (usize, usize) {
+    (
+        after.difference(before).count(),
+        before.difference(after).count(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+    use std::path::PathBuf;
+
+    use notify::event::{AccessKind, CreateKind, ModifyKind};
+    use notify::{Event, EventKind};
+
+    use super::{card_changes, touches_cards};
+
+    #[test]
+    fn counts_cards_added_and_removed() {
+        let before: HashSet<String> = ["a", "b", "c"].map(String::from).into();
+        let after: HashSet<String> = ["b", "c", "d", "e"].map(String::from).into();
+        assert_eq!(card_changes(&before, &after), (2, 1));
+        assert_eq!(card_changes(&after, &after), (0, 0));
+    }
+
+    #[test]
+    fn only_card_file_writes_trigger_a_scan() {
+        let extensions = ["md".to_string(), "markdown".to_string()];
+        let touches = |kind, path: &str| {
+            touches_cards(&Event::new(kind).add_path(PathBuf::from(path)), &extensions)
+        };
+        assert!(touches(EventKind::Modify(ModifyKind::Any), "deck.md"));
+        assert!(touches(EventKind::Create(CreateKind::File), "new.markdown"));
+        assert!(!touches(EventKind::Access(AccessKind::Any), "deck.md"));
+        assert!(!touches(
+            EventKind::Modify(ModifyKind::Any),
+            "cards.db-journal"
+        ));
+    }
+}